@@ -12,7 +12,7 @@ use crate::aesni;
 
 use crate::aessafe;
 use crate::blockmodes::{PaddingProcessor, EcbEncryptor, EcbDecryptor, CbcEncryptor, CbcDecryptor, CtrMode,
-    CtrModeX8};
+    CtrModeX8, CounterPolicy};
 use crate::symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher};
 use crate::util;
 
@@ -25,7 +25,7 @@ pub enum KeySize {
 }
 
 impl KeySize {
-    fn bytes_size(self) -> usize {
+    pub(crate) fn bytes_size(self) -> usize {
         match self {
             Self::KeySize128 => 16,
             Self::KeySize192 => 24,
@@ -246,52 +246,64 @@ pub fn cbc_decryptor<X: PaddingProcessor + Send + 'static>(
 
 // aes in counter mode (ctr) (encryption and decryption are the same because of the xor operation)
 /// Get the best implementation of a Ctr
-#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 pub fn ctr(
         key_size: KeySize,
         key: &[u8],
         iv: &[u8]) -> Box<dyn SynchronousStreamCipher + 'static> {
+    ctr_with_policy(key_size, key, iv, CounterPolicy::wrapping(16))
+}
+
+/// Get the best implementation of a Ctr that increments the counter as described by `policy`.
+/// Once the counter is exhausted, `try_process()` fails with `CounterOverflow`.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn ctr_with_policy(
+        key_size: KeySize,
+        key: &[u8],
+        iv: &[u8],
+        policy: CounterPolicy) -> Box<dyn SynchronousStreamCipher + 'static> {
     if util::supports_aesni() {
         let aes_dec = aesni::AesNiEncryptor::new(key_size, key);
-        Box::new(CtrMode::new(aes_dec, iv.to_vec()))
+        Box::new(CtrMode::new_with_policy(aes_dec, iv.to_vec(), policy))
     } else {
         match key_size {
             KeySize::KeySize128 => {
                 let aes_dec = aessafe::AesSafe128EncryptorX8::new(key);
-                Box::new(CtrModeX8::new(aes_dec, iv))
+                Box::new(CtrModeX8::new_with_policy(aes_dec, iv, policy))
             }
             KeySize::KeySize192 => {
                 let aes_dec = aessafe::AesSafe192EncryptorX8::new(key);
-                Box::new(CtrModeX8::new(aes_dec, iv))
+                Box::new(CtrModeX8::new_with_policy(aes_dec, iv, policy))
             }
             KeySize::KeySize256 => {
                 let aes_dec = aessafe::AesSafe256EncryptorX8::new(key);
-                Box::new(CtrModeX8::new(aes_dec, iv))
+                Box::new(CtrModeX8::new_with_policy(aes_dec, iv, policy))
             }
         }
     }
 }
 
-/// Get the best implementation of a Ctr
+/// Get the best implementation of a Ctr that increments the counter as described by `policy`.
+/// Once the counter is exhausted, `try_process()` fails with `CounterOverflow`.
 #[cfg(all(not(target_arch = "x86"), not(target_arch = "x86_64")))]
-pub fn ctr(
+pub fn ctr_with_policy(
         key_size: KeySize,
         key: &[u8],
-        iv: &[u8]) -> Box<SynchronousStreamCipher + 'static> {
+        iv: &[u8],
+        policy: CounterPolicy) -> Box<SynchronousStreamCipher + 'static> {
     match key_size {
         KeySize::KeySize128 => {
             let aes_dec = aessafe::AesSafe128EncryptorX8::new(key);
-            let dec = Box::new(CtrModeX8::new(aes_dec, iv));
+            let dec = Box::new(CtrModeX8::new_with_policy(aes_dec, iv, policy));
             dec as Box<SynchronousStreamCipher>
         }
         KeySize::KeySize192 => {
             let aes_dec = aessafe::AesSafe192EncryptorX8::new(key);
-            let dec = Box::new(CtrModeX8::new(aes_dec, iv));
+            let dec = Box::new(CtrModeX8::new_with_policy(aes_dec, iv, policy));
             dec as Box<SynchronousStreamCipher>
         }
         KeySize::KeySize256 => {
             let aes_dec = aessafe::AesSafe256EncryptorX8::new(key);
-            let dec = Box::new(CtrModeX8::new(aes_dec, iv));
+            let dec = Box::new(CtrModeX8::new_with_policy(aes_dec, iv, policy));
             dec as Box<SynchronousStreamCipher>
         }
     }
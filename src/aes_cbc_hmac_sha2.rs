@@ -0,0 +1,320 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+This public module implements the AES_CBC_HMAC_SHA2 family of authenticated encryption algorithms
+from [RFC 7518, section 5.2](https://tools.ietf.org/html/rfc7518#section-5.2), known in JOSE as
+`A128CBC-HS256`, `A192CBC-HS384` and `A256CBC-HS512`.
+
+The plaintext is encrypted with AES in CBC mode with PKCS#7 padding, and the associated data, IV
+and ciphertext are then authenticated with HMAC-SHA2 (encrypt-then-MAC). The tag is checked before
+anything is decrypted, so unlike plain CBC decryption this can't be used as a padding oracle.
+
+The key is split in two halves: the first half is the HMAC key and the second half is the AES key.
+The tag is the HMAC output truncated to the length of the AES key.
+*/
+
+use crate::aes::{self, KeySize};
+use crate::blockmodes::PkcsPadding;
+use crate::buffer::{RefReadBuffer, RefWriteBuffer, WriteBuffer};
+use crate::buffer::BufferResult::BufferUnderflow;
+use crate::cryptoutil::write_u64_be;
+use crate::hmac::Hmac;
+use crate::mac::Mac;
+use crate::sha2::{Sha256, Sha384, Sha512};
+use crate::util::{fixed_time_eq, secure_memset};
+
+pub struct AesCbcHmacSha2 {
+    key_size: KeySize,
+    enc_key: Vec<u8>,
+    iv: [u8; 16],
+    mac: Box<dyn Mac + 'static>,
+    aad_len: u64,
+    finished: bool
+}
+
+impl AesCbcHmacSha2 {
+    /**
+     * Create a new AES_CBC_HMAC_SHA2 instance. The AES key size selects the hash function:
+     * `KeySize128` is `A128CBC-HS256`, `KeySize192` is `A192CBC-HS384` and `KeySize256` is
+     * `A256CBC-HS512`.
+     *
+     * # Arguments
+     * * key_size - The size of the AES key.
+     * * key - The combined HMAC and AES key, twice as long as the AES key.
+     * * iv - The 16 byte initialization vector. It must be unpredictable.
+     * * aad - The additional authenticated data.
+     */
+    pub fn new(key_size: KeySize, key: &[u8], iv: &[u8], aad: &[u8]) -> AesCbcHmacSha2 {
+        let enc_key_len = key_size.bytes_size();
+        assert!(key.len() == 2 * enc_key_len);
+        assert!(iv.len() == 16);
+
+        let (mac_key, enc_key) = key.split_at(enc_key_len);
+        let mut mac: Box<dyn Mac> = match key_size {
+            KeySize::KeySize128 => Box::new(Hmac::new(Sha256::new(), mac_key)),
+            KeySize::KeySize192 => Box::new(Hmac::new(Sha384::new(), mac_key)),
+            KeySize::KeySize256 => Box::new(Hmac::new(Sha512::new(), mac_key)),
+        };
+        mac.input(aad);
+        mac.input(iv);
+
+        let mut iv_copy = [0u8; 16];
+        iv_copy.copy_from_slice(iv);
+        AesCbcHmacSha2 {
+            key_size,
+            enc_key: enc_key.to_vec(),
+            iv: iv_copy,
+            mac,
+            aad_len: aad.len() as u64,
+            finished: false
+        }
+    }
+
+    /// The length of the ciphertext produced for a plaintext of `plaintext_len` bytes. PKCS#7
+    /// padding always adds between 1 and 16 bytes.
+    pub fn ciphertext_len(plaintext_len: usize) -> usize {
+        (plaintext_len / 16 + 1) * 16
+    }
+
+    /// The length of the authentication tag in bytes.
+    pub fn tag_len(&self) -> usize {
+        self.key_size.bytes_size()
+    }
+
+    // Finish the MAC over the ciphertext and write the truncated tag to `tag`.
+    fn compute_tag(&mut self, ciphertext: &[u8], tag: &mut [u8]) {
+        self.mac.input(ciphertext);
+        // AL is the length of the associated data in bits, as a 64 bit big endian integer.
+        let mut al = [0u8; 8];
+        write_u64_be(&mut al, self.aad_len * 8);
+        self.mac.input(&al);
+        let mut full_tag = [0u8; 64];
+        let mac_len = self.mac.output_bytes();
+        self.mac.raw_result(&mut full_tag[..mac_len]);
+        tag.copy_from_slice(&full_tag[..tag.len()]);
+    }
+
+    /**
+     * Encrypt and authenticate `input`.
+     *
+     * # Arguments
+     * * input - The plaintext.
+     * * output - The buffer for the ciphertext, which must be exactly `ciphertext_len(input.len())`
+     *   bytes long.
+     * * tag - The buffer for the authentication tag, which must be `tag_len()` bytes long.
+     */
+    pub fn encrypt(&mut self, input: &[u8], output: &mut [u8], tag: &mut [u8]) {
+        assert!(output.len() == AesCbcHmacSha2::ciphertext_len(input.len()));
+        assert!(tag.len() == self.tag_len());
+        assert!(!self.finished);
+        self.finished = true;
+
+        let mut encryptor = aes::cbc_encryptor(self.key_size, &self.enc_key, &self.iv, PkcsPadding);
+        {
+            let mut read_buffer = RefReadBuffer::new(input);
+            let mut write_buffer = RefWriteBuffer::new(output);
+            match encryptor.encrypt(&mut read_buffer, &mut write_buffer, true) {
+                Ok(BufferUnderflow) => assert!(write_buffer.is_full()),
+                _ => panic!("CBC encryption did not complete")
+            }
+        }
+        self.compute_tag(output, tag);
+    }
+
+    /**
+     * Verify the tag and, if it is valid, decrypt `input`. Returns the length of the plaintext
+     * written to the start of `output`, or `None` if the ciphertext or the tag is invalid, in which
+     * case nothing is written to `output`.
+     *
+     * # Arguments
+     * * input - The ciphertext.
+     * * output - The buffer for the plaintext, which must be at least `input.len()` bytes long.
+     * * tag - The authentication tag.
+     */
+    pub fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8]) -> Option<usize> {
+        assert!(output.len() >= input.len());
+        assert!(!self.finished);
+        self.finished = true;
+
+        // The length of the ciphertext is public, so there is no harm in checking it up front.
+        if input.is_empty() || !input.len().is_multiple_of(16) || tag.len() != self.tag_len() {
+            return None;
+        }
+
+        let mut calc_tag = [0u8; 32];
+        let tag_len = self.tag_len();
+        self.compute_tag(input, &mut calc_tag[..tag_len]);
+        if !fixed_time_eq(&calc_tag[..tag_len], tag) {
+            return None;
+        }
+
+        let mut decryptor = aes::cbc_decryptor(self.key_size, &self.enc_key, &self.iv, PkcsPadding);
+        let result = {
+            let mut read_buffer = RefReadBuffer::new(input);
+            let mut write_buffer = RefWriteBuffer::new(output);
+            match decryptor.decrypt(&mut read_buffer, &mut write_buffer, true) {
+                Ok(BufferUnderflow) => Some(write_buffer.position()),
+                _ => None
+            }
+        };
+        if result.is_none() {
+            // Only reachable if whoever holds the key produced badly padded data. The decryptor has
+            // already written the blocks before the padding, so erase them.
+            secure_memset(&mut output[..input.len()], 0);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aes::{self, KeySize};
+    use crate::aes_cbc_hmac_sha2::AesCbcHmacSha2;
+    use crate::blockmodes::NoPadding;
+    use crate::buffer::{RefReadBuffer, RefWriteBuffer};
+    use crate::serialize::hex::FromHex;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    struct TestVector {
+        key_size: KeySize,
+        key: Vec<u8>,
+        iv: Vec<u8>,
+        plain_text: Vec<u8>,
+        cipher_text: Vec<u8>,
+        aad: Vec<u8>,
+        tag: Vec<u8>
+    }
+
+    // Test vectors from RFC 7518, appendix B
+    fn get_test_vectors() -> Vec<TestVector> {
+        let plain_text = b"A cipher system must not be required to be secret, and it must be able to \
+            fall into the hands of the enemy without inconvenience".to_vec();
+        let aad = b"The second principle of Auguste Kerckhoffs".to_vec();
+        let iv = hex_to_bytes("1af38c2dc2b96ffdd86694092341bc04");
+        vec![
+            TestVector {
+                key_size: KeySize::KeySize128,
+                key: (0..32).collect(),
+                iv: iv.clone(),
+                plain_text: plain_text.clone(),
+                cipher_text: hex_to_bytes(
+                    "c80edfa32ddf39d5ef00c0b468834279a2e46a1b8049f792f76bfe54b903a9c9\
+                     a94ac9b47ad2655c5f10f9aef71427e2fc6f9b3f399a221489f16362c7032336\
+                     09d45ac69864e3321cf82935ac4096c86e133314c54019e8ca7980dfa4b9cf1b\
+                     384c486f3a54c51078158ee5d79de59fbd34d848b3d69550a67646344427ade5\
+                     4b8851ffb598f7f80074b9473c82e2db"),
+                aad: aad.clone(),
+                tag: hex_to_bytes("652c3fa36b0a7c5b3219fab3a30bc1c4")
+            },
+            TestVector {
+                key_size: KeySize::KeySize192,
+                key: (0..48).collect(),
+                iv: iv.clone(),
+                plain_text: plain_text.clone(),
+                cipher_text: hex_to_bytes(
+                    "ea65da6b59e61edb419be62d19712ae5d303eeb50052d0dfd6697f77224c8edb\
+                     000d279bdc14c1072654bd30944230c657bed4ca0c9f4a8466f22b226d174621\
+                     4bf8cfc2400add9f5126e479663fc90b3bed787a2f0ffcbf3904be2a641d5c21\
+                     05bfe591bae23b1d7449e532eef60a9ac8bb6c6b01d35d49787bcd57ef484927\
+                     f280adc91ac0c4e79c7b11efc60054e3"),
+                aad: aad.clone(),
+                tag: hex_to_bytes("8490ac0e58949bfe51875d733f93ac2075168039ccc733d7")
+            },
+            TestVector {
+                key_size: KeySize::KeySize256,
+                key: (0..64).collect(),
+                iv,
+                plain_text,
+                cipher_text: hex_to_bytes(
+                    "4affaaadb78c31c5da4b1b590d10ffbd3dd8d5d302423526912da037ecbcc7bd\
+                     822c301dd67c373bccb584ad3e9279c2e6d12a1374b77f077553df829410446b\
+                     36ebd97066296ae6427ea75c2e0846a11a09ccf5370dc80bfecbad28c73f09b3\
+                     a3b75e662a2594410ae496b2e2e6609e31e6e02cc837f053d21f37ff4f51950b\
+                     be2638d09dd7a4930930806d0703b1f6"),
+                aad,
+                tag: hex_to_bytes("4dd3b4c088a7f45c216839645b2012bf2e6269a8c56a816dbc1b267761955bc5")
+            },
+        ]
+    }
+
+    #[test]
+    fn aes_cbc_hmac_sha2_encrypt_test() {
+        for item in get_test_vectors().iter() {
+            let mut cipher = AesCbcHmacSha2::new(item.key_size, &item.key, &item.iv, &item.aad);
+            let mut out: Vec<u8> = vec![0; AesCbcHmacSha2::ciphertext_len(item.plain_text.len())];
+            let mut out_tag: Vec<u8> = vec![0; cipher.tag_len()];
+            cipher.encrypt(&item.plain_text, &mut out, &mut out_tag);
+            assert_eq!(out, item.cipher_text);
+            assert_eq!(out_tag, item.tag);
+        }
+    }
+
+    #[test]
+    fn aes_cbc_hmac_sha2_decrypt_test() {
+        for item in get_test_vectors().iter() {
+            let mut decipher = AesCbcHmacSha2::new(item.key_size, &item.key, &item.iv, &item.aad);
+            let mut out: Vec<u8> = vec![0; item.cipher_text.len()];
+            let result = decipher.decrypt(&item.cipher_text, &mut out, &item.tag);
+            assert_eq!(result, Some(item.plain_text.len()));
+            assert_eq!(&out[..item.plain_text.len()], &item.plain_text[..]);
+        }
+    }
+
+    #[test]
+    fn aes_cbc_hmac_sha2_decrypt_fail_test() {
+        for item in get_test_vectors().iter() {
+            let out2: Vec<u8> = vec![0; item.cipher_text.len()];
+
+            let mut bad_tag = item.tag.clone();
+            bad_tag[0] ^= 1;
+            let mut decipher = AesCbcHmacSha2::new(item.key_size, &item.key, &item.iv, &item.aad);
+            let mut out1: Vec<u8> = vec![0; item.cipher_text.len()];
+            assert_eq!(decipher.decrypt(&item.cipher_text, &mut out1, &bad_tag), None);
+            assert_eq!(out1, out2);
+
+            let mut bad_cipher_text = item.cipher_text.clone();
+            let last = bad_cipher_text.len() - 1;
+            bad_cipher_text[last] ^= 1;
+            let mut decipher = AesCbcHmacSha2::new(item.key_size, &item.key, &item.iv, &item.aad);
+            let mut out1: Vec<u8> = vec![0; item.cipher_text.len()];
+            assert_eq!(decipher.decrypt(&bad_cipher_text, &mut out1, &item.tag), None);
+            assert_eq!(out1, out2);
+
+            let mut decipher = AesCbcHmacSha2::new(item.key_size, &item.key, &item.iv, b"");
+            let mut out1: Vec<u8> = vec![0; item.cipher_text.len()];
+            assert_eq!(decipher.decrypt(&item.cipher_text, &mut out1, &item.tag), None);
+            assert_eq!(out1, out2);
+        }
+    }
+
+    #[test]
+    fn aes_cbc_hmac_sha2_decrypt_bad_padding_test() {
+        for item in get_test_vectors().iter() {
+            // Correctly authenticated, but the last plaintext byte is not a valid PKCS#7 padding
+            let plain_text = [0x42u8; 48];
+            let enc_key = &item.key[item.key.len() / 2..];
+            let mut encryptor = aes::cbc_encryptor(item.key_size, enc_key, &item.iv, NoPadding);
+            let mut cipher_text = [0u8; 48];
+            {
+                let mut read_buffer = RefReadBuffer::new(&plain_text);
+                let mut write_buffer = RefWriteBuffer::new(&mut cipher_text);
+                encryptor.encrypt(&mut read_buffer, &mut write_buffer, true).unwrap();
+            }
+            let mut tag = vec![0u8; item.key.len() / 2];
+            AesCbcHmacSha2::new(item.key_size, &item.key, &item.iv, &item.aad)
+                .compute_tag(&cipher_text, &mut tag);
+
+            let mut decipher = AesCbcHmacSha2::new(item.key_size, &item.key, &item.iv, &item.aad);
+            let mut out = [0u8; 48];
+            assert_eq!(decipher.decrypt(&cipher_text, &mut out, &tag), None);
+            assert_eq!(out[..], [0u8; 48][..]);
+        }
+    }
+}
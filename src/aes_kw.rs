@@ -0,0 +1,493 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+This public module implements the AES Key Wrap algorithm
+([RFC 3394](https://tools.ietf.org/html/rfc3394)) and the AES Key Wrap with Padding algorithm
+([RFC 5649](https://tools.ietf.org/html/rfc5649)), also known as KW and KWP in NIST SP 800-38F.
+
+Key wrapping encrypts key material under a key encryption key (KEK) and adds an integrity check,
+so that unwrapping with the wrong KEK or a modified ciphertext is detected. The functions work with
+any block cipher with a 128 bit block size, although the standards only define them for AES.
+
+# Usage
+
+```rust
+use crypto::aes_kw;
+use crypto::aessafe::{AesSafe128Encryptor, AesSafe128Decryptor};
+
+let kek = [0u8; 16];
+let key = [1u8; 32];
+
+let mut wrapped = [0u8; 40];
+aes_kw::wrap(&AesSafe128Encryptor::new(&kek), &key, &mut wrapped).unwrap();
+
+let mut unwrapped = [0u8; 32];
+aes_kw::unwrap(&AesSafe128Decryptor::new(&kek), &wrapped, &mut unwrapped).unwrap();
+assert_eq!(key, unwrapped);
+```
+*/
+
+use std;
+use std::fmt::{Display, Formatter};
+use std::error::Error;
+
+use crate::cryptoutil::{read_u32_be, read_u64v_be, write_u32_be, write_u64_be, zero};
+use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+use crate::util::fixed_time_eq;
+
+/// The default initial value from RFC 3394, section 2.2.3.1
+const IV: [u8; 8] = [0xa6; 8];
+
+/// The constant high half of the alternative initial value from RFC 5649, section 3
+const AIV_PREFIX: [u8; 4] = [0xa6, 0x59, 0x59, 0xa6];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyWrapError {
+    /// The block size of the cipher isn't 128 bits.
+    InvalidBlockSize,
+    /// The input isn't a valid length for the operation.
+    InvalidInputLength,
+    /// The output buffer doesn't have the length required for the input.
+    InvalidOutputLength,
+    /// The unwrapped data failed the integrity check, either because the KEK is wrong or because
+    /// the wrapped data was modified.
+    IntegrityCheckFailed
+}
+
+impl Display for KeyWrapError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let msg = match *self {
+            KeyWrapError::InvalidBlockSize => "key wrapping requires a 128 bit block cipher",
+            KeyWrapError::InvalidInputLength => "invalid input length for key wrapping",
+            KeyWrapError::InvalidOutputLength => "invalid output length for key wrapping",
+            KeyWrapError::IntegrityCheckFailed => "the wrapped key failed the integrity check"
+        };
+        Display::fmt(msg, f)
+    }
+}
+
+impl Error for KeyWrapError { }
+
+// The wrapping process W from RFC 3394, section 2.2.1 in its index based form. `data` holds A
+// followed by the n 64 bit blocks R[1], ..., R[n] and is transformed in place. The callers check
+// that the block size of `cipher` is 128 bits.
+fn wrap_core<E: BlockEncryptor + ?Sized>(cipher: &E, data: &mut [u8]) {
+    let n = data.len() / 8 - 1;
+    let mut block = [0u8; 16];
+    let mut out = [0u8; 16];
+    for j in 0..6 {
+        for i in 1..n + 1 {
+            block[..8].copy_from_slice(&data[..8]);
+            block[8..].copy_from_slice(&data[i * 8..i * 8 + 8]);
+            cipher.encrypt_block(&block, &mut out);
+            let t = (n * j + i) as u64;
+            let mut a = [0u64; 1];
+            read_u64v_be(&mut a, &out[..8]);
+            write_u64_be(&mut data[..8], a[0] ^ t);
+            data[i * 8..i * 8 + 8].copy_from_slice(&out[8..]);
+        }
+    }
+}
+
+// The unwrapping process W^-1 from RFC 3394, section 2.2.2 in its index based form. This is the
+// exact inverse of `wrap_core()`; checking the recovered A is left to the caller.
+fn unwrap_core<D: BlockDecryptor + ?Sized>(cipher: &D, data: &mut [u8]) {
+    let n = data.len() / 8 - 1;
+    let mut block = [0u8; 16];
+    let mut out = [0u8; 16];
+    for j in (0..6).rev() {
+        for i in (1..n + 1).rev() {
+            let t = (n * j + i) as u64;
+            let mut a = [0u64; 1];
+            read_u64v_be(&mut a, &data[..8]);
+            write_u64_be(&mut block[..8], a[0] ^ t);
+            block[8..].copy_from_slice(&data[i * 8..i * 8 + 8]);
+            cipher.decrypt_block(&block, &mut out);
+            data[..8].copy_from_slice(&out[..8]);
+            data[i * 8..i * 8 + 8].copy_from_slice(&out[8..]);
+        }
+    }
+}
+
+/**
+ * Wrap `input` under the KEK `cipher` as specified in RFC 3394.
+ *
+ * # Arguments
+ * * cipher - The block cipher keyed with the KEK. Its block size must be 16 bytes, or this fails
+ *   with `InvalidBlockSize`.
+ * * input - The key data to wrap. Its length must be a multiple of 8 and at least 16.
+ * * output - The buffer for the wrapped key, which must be exactly 8 bytes longer than `input`.
+ */
+pub fn wrap<E: BlockEncryptor + ?Sized>(cipher: &E, input: &[u8], output: &mut [u8])
+        -> Result<(), KeyWrapError> {
+    if cipher.block_size() != 16 {
+        return Err(KeyWrapError::InvalidBlockSize);
+    }
+    if input.len() < 16 || !input.len().is_multiple_of(8) {
+        return Err(KeyWrapError::InvalidInputLength);
+    }
+    if output.len() != input.len() + 8 {
+        return Err(KeyWrapError::InvalidOutputLength);
+    }
+    output[..8].copy_from_slice(&IV);
+    output[8..].copy_from_slice(input);
+    wrap_core(cipher, output);
+    Ok(())
+}
+
+/**
+ * Unwrap `input` under the KEK `cipher` and check its integrity as specified in RFC 3394. On
+ * failure, `output` is zeroed.
+ *
+ * # Arguments
+ * * cipher - The block cipher keyed with the KEK. Its block size must be 16 bytes, or this fails
+ *   with `InvalidBlockSize`.
+ * * input - The wrapped key. Its length must be a multiple of 8 and at least 24.
+ * * output - The buffer for the key data, which must be exactly 8 bytes shorter than `input`.
+ */
+pub fn unwrap<D: BlockDecryptor + ?Sized>(cipher: &D, input: &[u8], output: &mut [u8])
+        -> Result<(), KeyWrapError> {
+    if cipher.block_size() != 16 {
+        return Err(KeyWrapError::InvalidBlockSize);
+    }
+    if input.len() < 24 || !input.len().is_multiple_of(8) {
+        return Err(KeyWrapError::InvalidInputLength);
+    }
+    if output.len() != input.len() - 8 {
+        return Err(KeyWrapError::InvalidOutputLength);
+    }
+    let mut data = input.to_vec();
+    unwrap_core(cipher, &mut data);
+    let valid = fixed_time_eq(&data[..8], &IV);
+    output.copy_from_slice(&data[8..]);
+    zero(&mut data);
+    if valid {
+        Ok(())
+    } else {
+        zero(output);
+        Err(KeyWrapError::IntegrityCheckFailed)
+    }
+}
+
+/// The length of the output of `wrap_with_padding()` for an input of `input_len` bytes.
+pub fn wrapped_len_with_padding(input_len: usize) -> usize {
+    input_len.div_ceil(8) * 8 + 8
+}
+
+/**
+ * Wrap `input` under the KEK `cipher` as specified in RFC 5649. The input may have any nonzero
+ * length (up to 2^32 - 1 bytes).
+ *
+ * # Arguments
+ * * cipher - The block cipher keyed with the KEK. Its block size must be 16 bytes, or this fails
+ *   with `InvalidBlockSize`.
+ * * input - The key data to wrap.
+ * * output - The buffer for the wrapped key, which must be exactly
+ *   `wrapped_len_with_padding(input.len())` bytes long.
+ */
+pub fn wrap_with_padding<E: BlockEncryptor + ?Sized>(cipher: &E, input: &[u8], output: &mut [u8])
+        -> Result<(), KeyWrapError> {
+    if cipher.block_size() != 16 {
+        return Err(KeyWrapError::InvalidBlockSize);
+    }
+    if input.is_empty() || input.len() > u32::MAX as usize {
+        return Err(KeyWrapError::InvalidInputLength);
+    }
+    if output.len() != wrapped_len_with_padding(input.len()) {
+        return Err(KeyWrapError::InvalidOutputLength);
+    }
+    output[..4].copy_from_slice(&AIV_PREFIX);
+    write_u32_be(&mut output[4..8], input.len() as u32);
+    output[8..8 + input.len()].copy_from_slice(input);
+    zero(&mut output[8 + input.len()..]);
+    if output.len() == 16 {
+        // A single padded block is encrypted directly with the block cipher
+        let mut block = [0u8; 16];
+        block.copy_from_slice(output);
+        cipher.encrypt_block(&block, output);
+    } else {
+        wrap_core(cipher, output);
+    }
+    Ok(())
+}
+
+/**
+ * Unwrap `input` under the KEK `cipher` and check its integrity as specified in RFC 5649. Returns
+ * the length of the key data written to the start of `output`. On failure, `output` is zeroed.
+ *
+ * # Arguments
+ * * cipher - The block cipher keyed with the KEK. Its block size must be 16 bytes, or this fails
+ *   with `InvalidBlockSize`.
+ * * input - The wrapped key. Its length must be a multiple of 8 and at least 16.
+ * * output - The buffer for the key data, which must be at least 8 bytes shorter than `input`.
+ */
+pub fn unwrap_with_padding<D: BlockDecryptor + ?Sized>(cipher: &D, input: &[u8], output: &mut [u8])
+        -> Result<usize, KeyWrapError> {
+    if cipher.block_size() != 16 {
+        return Err(KeyWrapError::InvalidBlockSize);
+    }
+    if input.len() < 16 || !input.len().is_multiple_of(8) {
+        return Err(KeyWrapError::InvalidInputLength);
+    }
+    let padded_len = input.len() - 8;
+    if output.len() < padded_len {
+        return Err(KeyWrapError::InvalidOutputLength);
+    }
+    let mut data = input.to_vec();
+    if input.len() == 16 {
+        cipher.decrypt_block(input, &mut data);
+    } else {
+        unwrap_core(cipher, &mut data);
+    }
+
+    // The prefix, the message length indicator and the padding are all checked without branching
+    // on secret data, so a failure doesn't reveal which of the checks failed.
+    let mut bad = !fixed_time_eq(&data[..4], &AIV_PREFIX) as u32;
+    let mli = read_u32_be(&data[4..8]);
+    // 8 * (n - 1) < MLI <= 8 * n, that is the padding length must be between 0 and 7.
+    let pad_len = (padded_len as u32).wrapping_sub(mli);
+    bad |= (pad_len >> 31) | ((7u32.wrapping_sub(pad_len)) >> 31);
+    // The padding bytes must all be zero.
+    let mut pad_bits = 0u32;
+    for (i, &x) in data[8..].iter().rev().enumerate().take(7) {
+        let in_pad = 0u32.wrapping_sub((i as u32).wrapping_sub(pad_len) >> 31);
+        pad_bits |= in_pad & (x as u32);
+    }
+    bad |= pad_bits;
+
+    output[..padded_len].copy_from_slice(&data[8..]);
+    zero(&mut data);
+    if bad == 0 {
+        Ok(mli as usize)
+    } else {
+        zero(output);
+        Err(KeyWrapError::IntegrityCheckFailed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aes_kw::{self, KeyWrapError};
+    use crate::aessafe::{AesSafe128Encryptor, AesSafe128Decryptor, AesSafe192Encryptor,
+        AesSafe192Decryptor, AesSafe256Encryptor, AesSafe256Decryptor};
+    use crate::blowfish::Blowfish;
+    use crate::serialize::hex::FromHex;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    struct Test {
+        kek: Vec<u8>,
+        key: Vec<u8>,
+        wrapped: Vec<u8>
+    }
+
+    fn cipher_pair(kek: &[u8]) -> (Box<dyn BlockEncryptor>, Box<dyn BlockDecryptor>) {
+        match kek.len() {
+            16 => (Box::new(AesSafe128Encryptor::new(kek)), Box::new(AesSafe128Decryptor::new(kek))),
+            24 => (Box::new(AesSafe192Encryptor::new(kek)), Box::new(AesSafe192Decryptor::new(kek))),
+            32 => (Box::new(AesSafe256Encryptor::new(kek)), Box::new(AesSafe256Decryptor::new(kek))),
+            _ => unreachable!()
+        }
+    }
+
+    // Test vectors from RFC 3394, section 4
+    fn kw_tests() -> Vec<Test> {
+        vec![
+            Test {
+                kek: hex_to_bytes("000102030405060708090a0b0c0d0e0f"),
+                key: hex_to_bytes("00112233445566778899aabbccddeeff"),
+                wrapped: hex_to_bytes("1fa68b0a8112b447aef34bd8fb5a7b829d3e862371d2cfe5")
+            },
+            Test {
+                kek: hex_to_bytes("000102030405060708090a0b0c0d0e0f1011121314151617"),
+                key: hex_to_bytes("00112233445566778899aabbccddeeff"),
+                wrapped: hex_to_bytes("96778b25ae6ca435f92b5b97c050aed2468ab8a17ad84e5d")
+            },
+            Test {
+                kek: hex_to_bytes("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"),
+                key: hex_to_bytes("00112233445566778899aabbccddeeff"),
+                wrapped: hex_to_bytes("64e8c3f9ce0f5ba263e9777905818a2a93c8191e7d6e8ae7")
+            },
+            Test {
+                kek: hex_to_bytes("000102030405060708090a0b0c0d0e0f1011121314151617"),
+                key: hex_to_bytes("00112233445566778899aabbccddeeff0001020304050607"),
+                wrapped: hex_to_bytes(
+                    "031d33264e15d33268f24ec260743edce1c6c7ddee725a936ba814915c6762d2")
+            },
+            Test {
+                kek: hex_to_bytes("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"),
+                key: hex_to_bytes("00112233445566778899aabbccddeeff0001020304050607"),
+                wrapped: hex_to_bytes(
+                    "a8f9bc1612c68b3ff6e6f4fbe30e71e4769c8b80a32cb8958cd5d17d6b254da1")
+            },
+            Test {
+                kek: hex_to_bytes("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"),
+                key: hex_to_bytes("00112233445566778899aabbccddeeff000102030405060708090a0b0c0d0e0f"),
+                wrapped: hex_to_bytes(
+                    "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326cbc7f0e71a99f43bfb988b9b7a02dd21")
+            },
+        ]
+    }
+
+    // Test vectors from RFC 5649, section 6, followed by some from the NIST CAVP KWP test files
+    fn kwp_tests() -> Vec<Test> {
+        vec![
+            Test {
+                kek: hex_to_bytes("5840df6e29b02af1ab493b705bf16ea1ae8338f4dcc176a8"),
+                key: hex_to_bytes("c37b7e6492584340bed12207808941155068f738"),
+                wrapped: hex_to_bytes(
+                    "138bdeaa9b8fa7fc61f97742e72248ee5ae6ae5360d1ae6a5f54f373fa543b6a")
+            },
+            Test {
+                kek: hex_to_bytes("5840df6e29b02af1ab493b705bf16ea1ae8338f4dcc176a8"),
+                key: hex_to_bytes("466f7250617369"),
+                wrapped: hex_to_bytes("afbeb0f07dfbf5419200f2ccb50bb24f")
+            },
+            Test {
+                kek: hex_to_bytes("af83ae6624fc006da13b3c37b8a5933b"),
+                key: hex_to_bytes("13126a"),
+                wrapped: hex_to_bytes("a661f530339c9f344fa4755ad4cc3558")
+            },
+            Test {
+                kek: hex_to_bytes("d19c43011c2a0242a38bd58b8d76456d"),
+                key: hex_to_bytes("4202c90d7298cb4b"),
+                wrapped: hex_to_bytes("65befaeaacbb4620d1a5d64e7b57a760")
+            },
+            Test {
+                kek: hex_to_bytes("ebee1b9211aadefd06d258605f7134fb"),
+                key: hex_to_bytes("4029f7da4f8c29e4bb951a6f9d7f5305"),
+                wrapped: hex_to_bytes("634194eaca80d77a21d11dd3e739dc5aa3feca2ce0990507")
+            },
+        ]
+    }
+
+    #[test]
+    fn test_wrap_unwrap() {
+        for t in kw_tests().iter() {
+            let (enc, dec) = cipher_pair(&t.kek);
+            let mut wrapped = vec![0u8; t.key.len() + 8];
+            assert_eq!(aes_kw::wrap(&*enc, &t.key, &mut wrapped), Ok(()));
+            assert_eq!(wrapped, t.wrapped);
+
+            let mut unwrapped = vec![0u8; t.key.len()];
+            assert_eq!(aes_kw::unwrap(&*dec, &t.wrapped, &mut unwrapped), Ok(()));
+            assert_eq!(unwrapped, t.key);
+        }
+    }
+
+    #[test]
+    fn test_wrap_unwrap_with_padding() {
+        for t in kwp_tests().iter() {
+            let (enc, dec) = cipher_pair(&t.kek);
+            let mut wrapped = vec![0u8; aes_kw::wrapped_len_with_padding(t.key.len())];
+            assert_eq!(aes_kw::wrap_with_padding(&*enc, &t.key, &mut wrapped), Ok(()));
+            assert_eq!(wrapped, t.wrapped);
+
+            let mut unwrapped = vec![0u8; t.wrapped.len() - 8];
+            assert_eq!(aes_kw::unwrap_with_padding(&*dec, &t.wrapped, &mut unwrapped),
+                Ok(t.key.len()));
+            assert_eq!(&unwrapped[..t.key.len()], &t.key[..]);
+        }
+    }
+
+    #[test]
+    fn test_unwrap_integrity_check_failed() {
+        for t in kw_tests().iter() {
+            let (_, dec) = cipher_pair(&t.kek);
+            for i in 0..t.wrapped.len() {
+                let mut wrapped = t.wrapped.clone();
+                wrapped[i] ^= 0x80;
+                let mut unwrapped = vec![0u8; t.key.len()];
+                assert_eq!(aes_kw::unwrap(&*dec, &wrapped, &mut unwrapped),
+                    Err(KeyWrapError::IntegrityCheckFailed));
+                assert!(unwrapped.iter().all(|&x| x == 0));
+            }
+        }
+        for t in kwp_tests().iter() {
+            let (_, dec) = cipher_pair(&t.kek);
+            for i in 0..t.wrapped.len() {
+                let mut wrapped = t.wrapped.clone();
+                wrapped[i] ^= 0x80;
+                let mut unwrapped = vec![0u8; t.wrapped.len() - 8];
+                assert_eq!(aes_kw::unwrap_with_padding(&*dec, &wrapped, &mut unwrapped),
+                    Err(KeyWrapError::IntegrityCheckFailed));
+                assert!(unwrapped.iter().all(|&x| x == 0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_unwrap_with_padding_rejects_bad_length_indicator() {
+        // Wrapping with the RFC 3394 algorithm and a hand made AIV lets us produce wrapped keys
+        // with inconsistent length indicators and padding.
+        let kek = [7u8; 16];
+        let (enc, dec) = cipher_pair(&kek);
+        let cases: [(u32, [u8; 16]); 4] = [
+            // MLI too large for the data
+            (17, [1; 16]),
+            // MLI too small for the data
+            (8, [1; 16]),
+            (0, [0; 16]),
+            // Nonzero padding
+            (15, [1; 16]),
+        ];
+        for &(mli, data) in cases.iter() {
+            let mut plain = vec![0xa6, 0x59, 0x59, 0xa6];
+            plain.extend_from_slice(&mli.to_be_bytes());
+            plain.extend_from_slice(&data);
+            let mut wrapped = plain.clone();
+            super::wrap_core(&*enc, &mut wrapped);
+            let mut unwrapped = [0u8; 16];
+            assert_eq!(aes_kw::unwrap_with_padding(&*dec, &wrapped, &mut unwrapped),
+                Err(KeyWrapError::IntegrityCheckFailed));
+        }
+    }
+
+    #[test]
+    fn test_invalid_lengths() {
+        let kek = [0u8; 16];
+        let (enc, dec) = cipher_pair(&kek);
+        let mut out = [0u8; 64];
+        assert_eq!(aes_kw::wrap(&*enc, &[0; 8], &mut out[..16]),
+            Err(KeyWrapError::InvalidInputLength));
+        assert_eq!(aes_kw::wrap(&*enc, &[0; 20], &mut out[..28]),
+            Err(KeyWrapError::InvalidInputLength));
+        assert_eq!(aes_kw::wrap(&*enc, &[0; 16], &mut out[..23]),
+            Err(KeyWrapError::InvalidOutputLength));
+        assert_eq!(aes_kw::unwrap(&*dec, &[0; 16], &mut out[..8]),
+            Err(KeyWrapError::InvalidInputLength));
+        assert_eq!(aes_kw::unwrap(&*dec, &[0; 25], &mut out[..17]),
+            Err(KeyWrapError::InvalidInputLength));
+        assert_eq!(aes_kw::unwrap(&*dec, &[0; 24], &mut out[..15]),
+            Err(KeyWrapError::InvalidOutputLength));
+        assert_eq!(aes_kw::wrap_with_padding(&*enc, &[], &mut out[..16]),
+            Err(KeyWrapError::InvalidInputLength));
+        assert_eq!(aes_kw::wrap_with_padding(&*enc, &[0; 9], &mut out[..16]),
+            Err(KeyWrapError::InvalidOutputLength));
+        assert_eq!(aes_kw::unwrap_with_padding(&*dec, &[0; 8], &mut out[..8]),
+            Err(KeyWrapError::InvalidInputLength));
+        assert_eq!(aes_kw::unwrap_with_padding(&*dec, &[0; 24], &mut out[..15]),
+            Err(KeyWrapError::InvalidOutputLength));
+    }
+
+    #[test]
+    fn test_invalid_block_size() {
+        let blowfish = Blowfish::new(&[0u8; 16]);
+        let mut out = [0u8; 64];
+        assert_eq!(aes_kw::wrap(&blowfish, &[0; 16], &mut out[..24]),
+            Err(KeyWrapError::InvalidBlockSize));
+        assert_eq!(aes_kw::unwrap(&blowfish, &[0; 24], &mut out[..16]),
+            Err(KeyWrapError::InvalidBlockSize));
+        assert_eq!(aes_kw::wrap_with_padding(&blowfish, &[0; 9], &mut out[..24]),
+            Err(KeyWrapError::InvalidBlockSize));
+        assert_eq!(aes_kw::unwrap_with_padding(&blowfish, &[0; 24], &mut out[..16]),
+            Err(KeyWrapError::InvalidBlockSize));
+    }
+}
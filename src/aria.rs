@@ -0,0 +1,379 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the ARIA block cipher, as specified in
+ * [RFC 5794](https://tools.ietf.org/html/rfc5794), with 128, 192 and 256 bit keys.
+ *
+ * Like Camellia, this implementation uses secret dependent table lookups.
+ */
+
+use crate::cryptoutil::{read_u128_be, write_u128_be};
+use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+static SB1: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16];
+
+static SB2: [u8; 256] = [
+    0xe2, 0x4e, 0x54, 0xfc, 0x94, 0xc2, 0x4a, 0xcc, 0x62, 0x0d, 0x6a, 0x46, 0x3c, 0x4d, 0x8b, 0xd1,
+    0x5e, 0xfa, 0x64, 0xcb, 0xb4, 0x97, 0xbe, 0x2b, 0xbc, 0x77, 0x2e, 0x03, 0xd3, 0x19, 0x59, 0xc1,
+    0x1d, 0x06, 0x41, 0x6b, 0x55, 0xf0, 0x99, 0x69, 0xea, 0x9c, 0x18, 0xae, 0x63, 0xdf, 0xe7, 0xbb,
+    0x00, 0x73, 0x66, 0xfb, 0x96, 0x4c, 0x85, 0xe4, 0x3a, 0x09, 0x45, 0xaa, 0x0f, 0xee, 0x10, 0xeb,
+    0x2d, 0x7f, 0xf4, 0x29, 0xac, 0xcf, 0xad, 0x91, 0x8d, 0x78, 0xc8, 0x95, 0xf9, 0x2f, 0xce, 0xcd,
+    0x08, 0x7a, 0x88, 0x38, 0x5c, 0x83, 0x2a, 0x28, 0x47, 0xdb, 0xb8, 0xc7, 0x93, 0xa4, 0x12, 0x53,
+    0xff, 0x87, 0x0e, 0x31, 0x36, 0x21, 0x58, 0x48, 0x01, 0x8e, 0x37, 0x74, 0x32, 0xca, 0xe9, 0xb1,
+    0xb7, 0xab, 0x0c, 0xd7, 0xc4, 0x56, 0x42, 0x26, 0x07, 0x98, 0x60, 0xd9, 0xb6, 0xb9, 0x11, 0x40,
+    0xec, 0x20, 0x8c, 0xbd, 0xa0, 0xc9, 0x84, 0x04, 0x49, 0x23, 0xf1, 0x4f, 0x50, 0x1f, 0x13, 0xdc,
+    0xd8, 0xc0, 0x9e, 0x57, 0xe3, 0xc3, 0x7b, 0x65, 0x3b, 0x02, 0x8f, 0x3e, 0xe8, 0x25, 0x92, 0xe5,
+    0x15, 0xdd, 0xfd, 0x17, 0xa9, 0xbf, 0xd4, 0x9a, 0x7e, 0xc5, 0x39, 0x67, 0xfe, 0x76, 0x9d, 0x43,
+    0xa7, 0xe1, 0xd0, 0xf5, 0x68, 0xf2, 0x1b, 0x34, 0x70, 0x05, 0xa3, 0x8a, 0xd5, 0x79, 0x86, 0xa8,
+    0x30, 0xc6, 0x51, 0x4b, 0x1e, 0xa6, 0x27, 0xf6, 0x35, 0xd2, 0x6e, 0x24, 0x16, 0x82, 0x5f, 0xda,
+    0xe6, 0x75, 0xa2, 0xef, 0x2c, 0xb2, 0x1c, 0x9f, 0x5d, 0x6f, 0x80, 0x0a, 0x72, 0x44, 0x9b, 0x6c,
+    0x90, 0x0b, 0x5b, 0x33, 0x7d, 0x5a, 0x52, 0xf3, 0x61, 0xa1, 0xf7, 0xb0, 0xd6, 0x3f, 0x7c, 0x6d,
+    0xed, 0x14, 0xe0, 0xa5, 0x3d, 0x22, 0xb3, 0xf8, 0x89, 0xde, 0x71, 0x1a, 0xaf, 0xba, 0xb5, 0x81];
+
+static SB3: [u8; 256] = [
+    0x52, 0x09, 0x6a, 0xd5, 0x30, 0x36, 0xa5, 0x38, 0xbf, 0x40, 0xa3, 0x9e, 0x81, 0xf3, 0xd7, 0xfb,
+    0x7c, 0xe3, 0x39, 0x82, 0x9b, 0x2f, 0xff, 0x87, 0x34, 0x8e, 0x43, 0x44, 0xc4, 0xde, 0xe9, 0xcb,
+    0x54, 0x7b, 0x94, 0x32, 0xa6, 0xc2, 0x23, 0x3d, 0xee, 0x4c, 0x95, 0x0b, 0x42, 0xfa, 0xc3, 0x4e,
+    0x08, 0x2e, 0xa1, 0x66, 0x28, 0xd9, 0x24, 0xb2, 0x76, 0x5b, 0xa2, 0x49, 0x6d, 0x8b, 0xd1, 0x25,
+    0x72, 0xf8, 0xf6, 0x64, 0x86, 0x68, 0x98, 0x16, 0xd4, 0xa4, 0x5c, 0xcc, 0x5d, 0x65, 0xb6, 0x92,
+    0x6c, 0x70, 0x48, 0x50, 0xfd, 0xed, 0xb9, 0xda, 0x5e, 0x15, 0x46, 0x57, 0xa7, 0x8d, 0x9d, 0x84,
+    0x90, 0xd8, 0xab, 0x00, 0x8c, 0xbc, 0xd3, 0x0a, 0xf7, 0xe4, 0x58, 0x05, 0xb8, 0xb3, 0x45, 0x06,
+    0xd0, 0x2c, 0x1e, 0x8f, 0xca, 0x3f, 0x0f, 0x02, 0xc1, 0xaf, 0xbd, 0x03, 0x01, 0x13, 0x8a, 0x6b,
+    0x3a, 0x91, 0x11, 0x41, 0x4f, 0x67, 0xdc, 0xea, 0x97, 0xf2, 0xcf, 0xce, 0xf0, 0xb4, 0xe6, 0x73,
+    0x96, 0xac, 0x74, 0x22, 0xe7, 0xad, 0x35, 0x85, 0xe2, 0xf9, 0x37, 0xe8, 0x1c, 0x75, 0xdf, 0x6e,
+    0x47, 0xf1, 0x1a, 0x71, 0x1d, 0x29, 0xc5, 0x89, 0x6f, 0xb7, 0x62, 0x0e, 0xaa, 0x18, 0xbe, 0x1b,
+    0xfc, 0x56, 0x3e, 0x4b, 0xc6, 0xd2, 0x79, 0x20, 0x9a, 0xdb, 0xc0, 0xfe, 0x78, 0xcd, 0x5a, 0xf4,
+    0x1f, 0xdd, 0xa8, 0x33, 0x88, 0x07, 0xc7, 0x31, 0xb1, 0x12, 0x10, 0x59, 0x27, 0x80, 0xec, 0x5f,
+    0x60, 0x51, 0x7f, 0xa9, 0x19, 0xb5, 0x4a, 0x0d, 0x2d, 0xe5, 0x7a, 0x9f, 0x93, 0xc9, 0x9c, 0xef,
+    0xa0, 0xe0, 0x3b, 0x4d, 0xae, 0x2a, 0xf5, 0xb0, 0xc8, 0xeb, 0xbb, 0x3c, 0x83, 0x53, 0x99, 0x61,
+    0x17, 0x2b, 0x04, 0x7e, 0xba, 0x77, 0xd6, 0x26, 0xe1, 0x69, 0x14, 0x63, 0x55, 0x21, 0x0c, 0x7d];
+
+static SB4: [u8; 256] = [
+    0x30, 0x68, 0x99, 0x1b, 0x87, 0xb9, 0x21, 0x78, 0x50, 0x39, 0xdb, 0xe1, 0x72, 0x09, 0x62, 0x3c,
+    0x3e, 0x7e, 0x5e, 0x8e, 0xf1, 0xa0, 0xcc, 0xa3, 0x2a, 0x1d, 0xfb, 0xb6, 0xd6, 0x20, 0xc4, 0x8d,
+    0x81, 0x65, 0xf5, 0x89, 0xcb, 0x9d, 0x77, 0xc6, 0x57, 0x43, 0x56, 0x17, 0xd4, 0x40, 0x1a, 0x4d,
+    0xc0, 0x63, 0x6c, 0xe3, 0xb7, 0xc8, 0x64, 0x6a, 0x53, 0xaa, 0x38, 0x98, 0x0c, 0xf4, 0x9b, 0xed,
+    0x7f, 0x22, 0x76, 0xaf, 0xdd, 0x3a, 0x0b, 0x58, 0x67, 0x88, 0x06, 0xc3, 0x35, 0x0d, 0x01, 0x8b,
+    0x8c, 0xc2, 0xe6, 0x5f, 0x02, 0x24, 0x75, 0x93, 0x66, 0x1e, 0xe5, 0xe2, 0x54, 0xd8, 0x10, 0xce,
+    0x7a, 0xe8, 0x08, 0x2c, 0x12, 0x97, 0x32, 0xab, 0xb4, 0x27, 0x0a, 0x23, 0xdf, 0xef, 0xca, 0xd9,
+    0xb8, 0xfa, 0xdc, 0x31, 0x6b, 0xd1, 0xad, 0x19, 0x49, 0xbd, 0x51, 0x96, 0xee, 0xe4, 0xa8, 0x41,
+    0xda, 0xff, 0xcd, 0x55, 0x86, 0x36, 0xbe, 0x61, 0x52, 0xf8, 0xbb, 0x0e, 0x82, 0x48, 0x69, 0x9a,
+    0xe0, 0x47, 0x9e, 0x5c, 0x04, 0x4b, 0x34, 0x15, 0x79, 0x26, 0xa7, 0xde, 0x29, 0xae, 0x92, 0xd7,
+    0x84, 0xe9, 0xd2, 0xba, 0x5d, 0xf3, 0xc5, 0xb0, 0xbf, 0xa4, 0x3b, 0x71, 0x44, 0x46, 0x2b, 0xfc,
+    0xeb, 0x6f, 0xd5, 0xf6, 0x14, 0xfe, 0x7c, 0x70, 0x5a, 0x7d, 0xfd, 0x2f, 0x18, 0x83, 0x16, 0xa5,
+    0x91, 0x1f, 0x05, 0x95, 0x74, 0xa9, 0xc1, 0x5b, 0x4a, 0x85, 0x6d, 0x13, 0x07, 0x4f, 0x4e, 0x45,
+    0xb2, 0x0f, 0xc9, 0x1c, 0xa6, 0xbc, 0xec, 0x73, 0x90, 0x7b, 0xcf, 0x59, 0x8f, 0xa1, 0xf9, 0x2d,
+    0xf2, 0xb1, 0x00, 0x94, 0x37, 0x9f, 0xd0, 0x2e, 0x9c, 0x6e, 0x28, 0x3f, 0x80, 0xf0, 0x3d, 0xd3,
+    0x25, 0x8a, 0xb5, 0xe7, 0x42, 0xb3, 0xc7, 0xea, 0xf7, 0x4c, 0x11, 0x33, 0x03, 0xa2, 0xac, 0x60];
+
+static C: [u128; 3] = [
+    0x517cc1b727220a94fe13abe8fa9a6ee0,
+    0x6db14acc9e21c820ff28b1d5ef5de2b0,
+    0xdb92371d2126e9700324977504e8c90e];
+
+// Substitution layer of the odd rounds
+fn sl1(x: u128) -> u128 {
+    let mut b = x.to_be_bytes();
+    for i in (0..16).step_by(4) {
+        b[i] = SB1[b[i] as usize];
+        b[i + 1] = SB2[b[i + 1] as usize];
+        b[i + 2] = SB3[b[i + 2] as usize];
+        b[i + 3] = SB4[b[i + 3] as usize];
+    }
+    u128::from_be_bytes(b)
+}
+
+// Substitution layer of the even rounds, the inverse of sl1
+fn sl2(x: u128) -> u128 {
+    let mut b = x.to_be_bytes();
+    for i in (0..16).step_by(4) {
+        b[i] = SB3[b[i] as usize];
+        b[i + 1] = SB4[b[i + 1] as usize];
+        b[i + 2] = SB1[b[i + 2] as usize];
+        b[i + 3] = SB2[b[i + 3] as usize];
+    }
+    u128::from_be_bytes(b)
+}
+
+// The involutional diffusion layer
+fn a(x: u128) -> u128 {
+    let x = x.to_be_bytes();
+    let y = [
+        x[3] ^ x[4] ^ x[6] ^ x[8] ^ x[9] ^ x[13] ^ x[14],
+        x[2] ^ x[5] ^ x[7] ^ x[8] ^ x[9] ^ x[12] ^ x[15],
+        x[1] ^ x[4] ^ x[6] ^ x[10] ^ x[11] ^ x[12] ^ x[15],
+        x[0] ^ x[5] ^ x[7] ^ x[10] ^ x[11] ^ x[13] ^ x[14],
+        x[0] ^ x[2] ^ x[5] ^ x[8] ^ x[11] ^ x[14] ^ x[15],
+        x[1] ^ x[3] ^ x[4] ^ x[9] ^ x[10] ^ x[14] ^ x[15],
+        x[0] ^ x[2] ^ x[7] ^ x[9] ^ x[10] ^ x[12] ^ x[13],
+        x[1] ^ x[3] ^ x[6] ^ x[8] ^ x[11] ^ x[12] ^ x[13],
+        x[0] ^ x[1] ^ x[4] ^ x[7] ^ x[10] ^ x[13] ^ x[15],
+        x[0] ^ x[1] ^ x[5] ^ x[6] ^ x[11] ^ x[12] ^ x[14],
+        x[2] ^ x[3] ^ x[5] ^ x[6] ^ x[8] ^ x[13] ^ x[15],
+        x[2] ^ x[3] ^ x[4] ^ x[7] ^ x[9] ^ x[12] ^ x[14],
+        x[1] ^ x[2] ^ x[6] ^ x[7] ^ x[9] ^ x[11] ^ x[12],
+        x[0] ^ x[3] ^ x[6] ^ x[7] ^ x[8] ^ x[10] ^ x[13],
+        x[0] ^ x[3] ^ x[4] ^ x[5] ^ x[9] ^ x[11] ^ x[14],
+        x[1] ^ x[2] ^ x[4] ^ x[5] ^ x[8] ^ x[10] ^ x[15]];
+    u128::from_be_bytes(y)
+}
+
+fn fo(x: u128, key: u128) -> u128 {
+    a(sl1(x ^ key))
+}
+
+fn fe(x: u128, key: u128) -> u128 {
+    a(sl2(x ^ key))
+}
+
+#[derive(Clone, Copy)]
+pub struct Aria {
+    rounds: usize,
+    ek: [u128; 17],
+    dk: [u128; 17]
+}
+
+impl Aria {
+    /**
+     * Create a new Aria instance.
+     *
+     * # Arguments
+     * * key - The key, which must be 16, 24 or 32 bytes long.
+     */
+    pub fn new(key: &[u8]) -> Aria {
+        let (rounds, ck) = match key.len() {
+            16 => (12, [C[0], C[1], C[2]]),
+            24 => (14, [C[1], C[2], C[0]]),
+            32 => (16, [C[2], C[0], C[1]]),
+            _ => panic!("Invalid ARIA key length")
+        };
+        let mut kr = [0u8; 16];
+        kr[..key.len() - 16].copy_from_slice(&key[16..]);
+
+        let w0 = read_u128_be(&key[0..16]);
+        let w1 = fo(w0, ck[0]) ^ read_u128_be(&kr);
+        let w2 = fe(w1, ck[1]) ^ w0;
+        let w3 = fo(w2, ck[2]) ^ w1;
+        let w = [w0, w1, w2, w3];
+
+        let mut ek = [0u128; 17];
+        for (i, k) in ek.iter_mut().enumerate() {
+            let x = w[i % 4];
+            let y = w[(i + 1) % 4];
+            *k = x ^ match i / 4 {
+                0 => y.rotate_right(19),
+                1 => y.rotate_right(31),
+                2 => y.rotate_left(61),
+                3 => y.rotate_left(31),
+                _ => y.rotate_left(19)
+            };
+        }
+
+        let mut dk = [0u128; 17];
+        dk[0] = ek[rounds];
+        for i in 1..rounds {
+            dk[i] = a(ek[rounds - i]);
+        }
+        dk[rounds] = ek[0];
+
+        Aria {
+            rounds,
+            ek,
+            dk
+        }
+    }
+
+    fn crypt(&self, keys: &[u128; 17], input: &[u8], output: &mut [u8]) {
+        let mut p = read_u128_be(input);
+        for (i, &k) in keys[..self.rounds - 1].iter().enumerate() {
+            p = if i % 2 == 0 { fo(p, k) } else { fe(p, k) };
+        }
+        p = sl2(p ^ keys[self.rounds - 1]) ^ keys[self.rounds];
+        write_u128_be(output, p);
+    }
+}
+
+impl BlockEncryptor for Aria {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        self.crypt(&self.ek, input, output);
+    }
+}
+
+impl BlockDecryptor for Aria {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        self.crypt(&self.dk, input, output);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aead::{AeadEncryptor, AeadDecryptor};
+    use crate::aria::Aria;
+    use crate::blockmodes::{CbcEncryptor, CbcDecryptor, CtrMode, NoPadding};
+    use crate::buffer::{RefReadBuffer, RefWriteBuffer};
+    use crate::gcm::Gcm;
+    use crate::serialize::hex::FromHex;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor, Encryptor, Decryptor,
+                                 SynchronousStreamCipher};
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    // Test vectors from RFC 5794, Appendix A
+    #[test]
+    fn test_rfc5794() {
+        let plaintext = hex_to_bytes("00112233445566778899aabbccddeeff");
+        let tests = [
+            ("000102030405060708090a0b0c0d0e0f",
+             "d718fbd6ab644c739da95f3be6451778"),
+            ("000102030405060708090a0b0c0d0e0f1011121314151617",
+             "26449c1805dbe7aa25a468ce263a9e79"),
+            ("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+             "f92bd7c79fb72e2f2b8f80c1972d24fc"),
+        ];
+        for &(key, ciphertext) in tests.iter() {
+            let aria = Aria::new(&hex_to_bytes(key));
+            let ciphertext = hex_to_bytes(ciphertext);
+            let mut output = [0u8; 16];
+            aria.encrypt_block(&plaintext, &mut output);
+            assert_eq!(&output[..], &ciphertext[..]);
+            aria.decrypt_block(&ciphertext, &mut output);
+            assert_eq!(&output[..], &plaintext[..]);
+        }
+    }
+
+    fn sp800_38a_plaintext() -> Vec<u8> {
+        hex_to_bytes(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52ef")
+    }
+
+    #[test]
+    fn test_aria_cbc() {
+        let key = hex_to_bytes("2b7e151628aed2a6abf7158809cf4f3c");
+        let iv = hex_to_bytes("000102030405060708090a0b0c0d0e0f");
+        let plaintext = sp800_38a_plaintext();
+        let expected = hex_to_bytes(
+            "a9f7b5dccaddf6b43a508169291dd5fb103934265c51b36f2701313975bc27d4\
+             4741691dc6d50e03cd1feba01c65c103");
+
+        let mut enc = CbcEncryptor::new(Aria::new(&key), NoPadding, iv.clone());
+        let mut output = vec![0u8; plaintext.len()];
+        enc.encrypt(
+            &mut RefReadBuffer::new(&plaintext),
+            &mut RefWriteBuffer::new(&mut output),
+            true).unwrap();
+        assert_eq!(output, expected);
+
+        let mut dec = CbcDecryptor::new(Aria::new(&key), NoPadding, iv);
+        let mut output = vec![0u8; expected.len()];
+        dec.decrypt(
+            &mut RefReadBuffer::new(&expected),
+            &mut RefWriteBuffer::new(&mut output),
+            true).unwrap();
+        assert_eq!(output, plaintext);
+    }
+
+    #[test]
+    fn test_aria_ctr() {
+        let key = hex_to_bytes("2b7e151628aed2a6abf7158809cf4f3c");
+        let ctr = hex_to_bytes("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let plaintext = sp800_38a_plaintext();
+        let expected = hex_to_bytes(
+            "ba9a1252804da940019074e10c15a8b9501cc374311793ed2ab0f813b593bc65\
+             72c8b2bbc5a00f6c38c85b2092c2e4a7");
+
+        let mut ctr = CtrMode::new(Aria::new(&key), ctr);
+        let mut output = vec![0u8; plaintext.len()];
+        ctr.process(&plaintext, &mut output);
+        assert_eq!(output, expected);
+    }
+
+    // The inputs of test case 4 of the GCM specification, with ARIA instead of AES
+    #[test]
+    fn test_aria_gcm() {
+        let key = hex_to_bytes("feffe9928665731c6d6a8f9467308308");
+        let nonce = hex_to_bytes("cafebabefacedbaddecaf888");
+        let aad = hex_to_bytes("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let plaintext = hex_to_bytes(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+             1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39");
+        let expected = hex_to_bytes(
+            "2f0d6bb68cb6efdec154221b734467e012b09b88ec8d703c6e8f929f793d3224\
+             9256be7b0fbd963f90e8f1d85e18725c1cc8f1f3ac505634e5318905");
+        let expected_tag = hex_to_bytes("554dbe8e1d62c3a4d25a9971108e535c");
+
+        let mut ciphertext = vec![0u8; plaintext.len()];
+        let mut tag = [0u8; 16];
+        Gcm::new(Aria::new(&key), &nonce, &aad).encrypt(&plaintext, &mut ciphertext, &mut tag);
+        assert_eq!(ciphertext, expected);
+        assert_eq!(&tag[..], &expected_tag[..]);
+
+        let mut output = vec![0u8; ciphertext.len()];
+        assert!(Gcm::new(Aria::new(&key), &nonce, &aad).decrypt(&ciphertext, &mut output, &tag));
+        assert_eq!(output, plaintext);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::aria::Aria;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+    #[bench]
+    pub fn aria128_encrypt(bh: &mut Bencher) {
+        let aria = Aria::new(&[1u8; 16]);
+        let input = [1u8; 16];
+        let mut output = [0u8; 16];
+        bh.iter( || {
+            aria.encrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn aria256_decrypt(bh: &mut Bencher) {
+        let aria = Aria::new(&[1u8; 32]);
+        let input = [1u8; 16];
+        let mut output = [0u8; 16];
+        bh.iter( || {
+            aria.decrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
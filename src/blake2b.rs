@@ -321,7 +321,6 @@ impl Blake2b {
             *b = 0;
         }
         self.buflen = 0;
-        self.last_node = 0;
         self.computed = false;
         self.apply_param();
         if self.key_length > 0 {
@@ -337,6 +336,137 @@ impl Blake2b {
     }
 }
 
+/// Builds a `Blake2b` instance with a custom parameter block: a key, a salt, a personalization
+/// string, and the fields that place a node in a hash tree.
+#[derive(Copy, Clone)]
+pub struct Blake2bBuilder {
+    param: Blake2bParam,
+    key: [u8; BLAKE2B_KEYBYTES],
+    // The number of bytes of `key` to hash, which is the key length of the parameter block except
+    // for the root of BLAKE2bp
+    key_length: usize,
+    last_node: bool
+}
+
+impl Blake2bBuilder {
+    /// Start building an instance producing `outlen` bytes, with the parameters of sequential
+    /// hashing.
+    pub fn new(outlen: usize) -> Blake2bBuilder {
+        assert!(outlen > 0 && outlen <= BLAKE2B_OUTBYTES);
+        Blake2bBuilder {
+            param: Blake2b::default_param(outlen as u8),
+            key: [0; BLAKE2B_KEYBYTES],
+            key_length: 0,
+            last_node: false
+        }
+    }
+
+    /// Set the key, of at most 64 bytes.
+    pub fn key(mut self, key: &[u8]) -> Blake2bBuilder {
+        assert!(key.len() <= BLAKE2B_KEYBYTES);
+        self.key = [0; BLAKE2B_KEYBYTES];
+        copy_memory(key, &mut self.key);
+        self.key_length = key.len();
+        self.param.key_length = key.len() as u8;
+        self
+    }
+
+    // Sets the key length of the parameter block without hashing a key, as the root of BLAKE2bp
+    // does
+    pub(crate) fn param_key_length(mut self, key_length: usize) -> Blake2bBuilder {
+        assert!(key_length <= BLAKE2B_KEYBYTES);
+        self.param.key_length = key_length as u8;
+        self
+    }
+
+    /// Set the salt, of at most 16 bytes. Shorter salts are padded with zeros.
+    pub fn salt(mut self, salt: &[u8]) -> Blake2bBuilder {
+        assert!(salt.len() <= BLAKE2B_SALTBYTES);
+        self.param.salt = [0; BLAKE2B_SALTBYTES];
+        copy_memory(salt, &mut self.param.salt);
+        self
+    }
+
+    /// Set the personalization string, of at most 16 bytes. Shorter strings are padded with
+    /// zeros.
+    pub fn personal(mut self, personal: &[u8]) -> Blake2bBuilder {
+        assert!(personal.len() <= BLAKE2B_PERSONALBYTES);
+        self.param.personal = [0; BLAKE2B_PERSONALBYTES];
+        copy_memory(personal, &mut self.param.personal);
+        self
+    }
+
+    /// Set the fanout of the tree, 0 for unlimited.
+    pub fn fanout(mut self, fanout: u8) -> Blake2bBuilder {
+        self.param.fanout = fanout;
+        self
+    }
+
+    /// Set the maximal depth of the tree, 255 for unlimited.
+    pub fn depth(mut self, depth: u8) -> Blake2bBuilder {
+        assert!(depth > 0);
+        self.param.depth = depth;
+        self
+    }
+
+    /// Set the maximal byte length of the leaves, 0 for unlimited.
+    pub fn leaf_length(mut self, leaf_length: u32) -> Blake2bBuilder {
+        self.param.leaf_length = leaf_length;
+        self
+    }
+
+    /// Set the offset of the node within its level of the tree.
+    pub fn node_offset(mut self, node_offset: u64) -> Blake2bBuilder {
+        self.param.node_offset = node_offset;
+        self
+    }
+
+    // Sets the output length of BLAKE2Xb, which takes the upper half of the node offset
+    pub(crate) fn xof_length(mut self, xof_length: u32) -> Blake2bBuilder {
+        self.param.node_offset = self.param.node_offset & 0xFFFFFFFF | (xof_length as u64) << 32;
+        self
+    }
+
+    // Sets the parameters of the nodes expanding the root hash of BLAKE2Xb, which are the only
+    // ones with a depth of 0
+    pub(crate) fn xof_node(mut self, node_offset: u32, xof_length: u32) -> Blake2bBuilder {
+        self.param.fanout = 0;
+        self.param.depth = 0;
+        self.param.leaf_length = BLAKE2B_OUTBYTES as u32;
+        self.param.inner_length = BLAKE2B_OUTBYTES as u8;
+        self.node_offset(node_offset as u64).xof_length(xof_length)
+    }
+
+    /// Set the depth of the node, 0 for the leaves.
+    pub fn node_depth(mut self, node_depth: u8) -> Blake2bBuilder {
+        self.param.node_depth = node_depth;
+        self
+    }
+
+    /// Set the byte length of the inner hashes, at most 64.
+    pub fn inner_length(mut self, inner_length: usize) -> Blake2bBuilder {
+        assert!(inner_length <= BLAKE2B_OUTBYTES);
+        self.param.inner_length = inner_length as u8;
+        self
+    }
+
+    /// Mark the node as the last one of its level of the tree.
+    pub fn last_node(mut self, last_node: bool) -> Blake2bBuilder {
+        self.last_node = last_node;
+        self
+    }
+
+    /// Create the `Blake2b` instance.
+    pub fn build(&self) -> Blake2b {
+        let mut b = Blake2b::init_param(self.param, &self.key[..self.key_length]);
+        b.last_node = self.last_node as u8;
+        if self.key_length > 0 {
+            b.apply_key();
+        }
+        b
+    }
+}
+
 impl Digest for Blake2b {
     fn reset(&mut self) { Blake2b::reset(self); }
     fn input(&mut self, msg: &[u8]) { self.update(msg); }
@@ -523,6 +653,93 @@ mod mac_tests {
     }
 }
 
+#[cfg(test)]
+mod builder_tests {
+    use crate::blake2b::Blake2bBuilder;
+    use crate::digest::Digest;
+    use crate::serialize::hex::FromHex;
+
+    fn check(builder: Blake2bBuilder, input: &[u8], expected_hex: &str) {
+        let expected = expected_hex.from_hex().unwrap();
+        let mut sh = builder.build();
+        sh.input(input);
+        let mut out = vec![0u8; expected.len()];
+        sh.result(&mut out);
+        assert_eq!(out, expected);
+
+        // The parameters survive a reset
+        sh.reset();
+        sh.input(input);
+        let mut out = vec![0u8; expected.len()];
+        sh.result(&mut out);
+        assert_eq!(out, expected);
+    }
+
+    // From blake2b-kat.txt of the BLAKE2 reference code
+    #[test]
+    fn test_blake2b_builder_keyed() {
+        let input: Vec<u8> = (0..255).collect();
+        let key: Vec<u8> = (0..64).collect();
+        check(Blake2bBuilder::new(64).key(&key), &input,
+              "142709d62e28fcccd0af97fad0f8465b971e82201dc51070faa0372aa43e9248\
+               4be1c1e73ba10906d5d1853db6a4106e0a7bf9800d373d6dee2d46d62ef2a461");
+    }
+
+    // The remaining vectors were computed with the BLAKE2 reference code, as used by Python's
+    // hashlib
+
+    #[test]
+    fn test_blake2b_builder_salt_personal() {
+        check(Blake2bBuilder::new(32).salt(b"0123456789abcdef").personal(b"MyPersonalString"),
+              b"abc", "576e2b3aa294089f13b8eaf0b23103d15e7d9489ddeb4331fd278e99a57ff201");
+    }
+
+    #[test]
+    fn test_blake2b_builder_all_parameters() {
+        let builder = Blake2bBuilder::new(20)
+            .key(b"key")
+            .salt(b"01234")
+            .personal(b"MyP")
+            .fanout(3)
+            .depth(4)
+            .leaf_length(1234)
+            .node_offset(0x010203040506)
+            .node_depth(5)
+            .inner_length(33)
+            .last_node(true);
+        check(builder, b"abc", "e82e08efaacccfbddc44c6ce3517393f2bf6d2f3");
+    }
+
+    // A tree of two leaves of at most 4096 bytes, and a root
+    #[test]
+    fn test_blake2b_builder_tree() {
+        let input: Vec<u8> = (0..6144).map(|i| i as u8).collect();
+        let node = |offset: u64, depth: u8, last: bool, outlen: usize| {
+            Blake2bBuilder::new(outlen)
+                .fanout(2)
+                .depth(2)
+                .leaf_length(4096)
+                .inner_length(64)
+                .node_offset(offset)
+                .node_depth(depth)
+                .last_node(last)
+                .build()
+        };
+
+        let mut leaves = [[0u8; 64]; 2];
+        for (i, (leaf, data)) in leaves.iter_mut().zip(input.chunks(4096)).enumerate() {
+            let mut sh = node(i as u64, 0, i == 1, 64);
+            sh.input(data);
+            sh.result(leaf);
+        }
+
+        let mut root = node(0, 1, true, 32);
+        root.input(&leaves[0]);
+        root.input(&leaves[1]);
+        assert_eq!(root.result_str(), "c365e2e2a2f67f7ed0455bce73a780d432ad0638347f56e47ec9a8fb6c6671dc");
+    }
+}
+
 #[cfg(all(test, feature = "with-bench"))]
 mod bench {
     use test::Bencher;
@@ -0,0 +1,354 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements BLAKE2bp, the 4-way parallel variant of BLAKE2b [1]. The message is split
+ * into 128 byte blocks, which are distributed round robin over 4 leaves; the root of the tree
+ * hashes the 4 leaf hashes. The nodes are `Blake2b` instances with the tree parameters set.
+ *
+ * The leaves can optionally be hashed on separate threads, see `Blake2bp::threaded`.
+ *
+ * # Usage
+ *
+ * ```rust
+ * use self::crypto::blake2bp::Blake2bp;
+ * use self::crypto::digest::Digest;
+ *
+ * let mut hasher = Blake2bp::new(64).threaded(true);
+ * hasher.input(&[0u8; 1 << 20]);
+ * let mut output = [0u8; 64];
+ * hasher.result(&mut output);
+ * ```
+ *
+ * # References
+ * [1] - J.-P. Aumasson, S. Neves, Z. Wilcox-O'Hearn, C. Winnerlein. BLAKE2: simpler, smaller, fast
+ *       as MD5. <https://blake2.net/blake2.pdf>
+ */
+
+use std::array;
+use std::cmp;
+use std::thread;
+
+use crate::blake2b::{Blake2b, Blake2bBuilder};
+use crate::digest::Digest;
+use crate::mac::{Mac, MacResult};
+
+const PARALLELISM_DEGREE: usize = 4;
+const BLAKE2B_BLOCKBYTES: usize = 128;
+const BLAKE2B_OUTBYTES: usize = 64;
+const BLAKE2B_KEYBYTES: usize = 64;
+
+// One block for each of the leaves
+const STRIPE_BYTES: usize = PARALLELISM_DEGREE * BLAKE2B_BLOCKBYTES;
+
+// Calls to `input` with less data than this hash the leaves on the calling thread, even if
+// threading is enabled
+const THREADED_MIN_BYTES: usize = 1 << 16;
+
+#[derive(Copy, Clone)]
+pub struct Blake2bp {
+    leaves: [Blake2b; PARALLELISM_DEGREE],
+    // The root before it has absorbed the leaf hashes
+    root: Blake2b,
+    // The position of the next byte of input within the current stripe of blocks
+    pos: usize,
+    digest_length: usize,
+    threaded: bool
+}
+
+impl Blake2bp {
+    fn init(outlen: usize, key: &[u8]) -> Blake2bp {
+        assert!(outlen > 0 && outlen <= BLAKE2B_OUTBYTES);
+        assert!(key.len() <= BLAKE2B_KEYBYTES);
+
+        let node = |outlen: usize| {
+            Blake2bBuilder::new(outlen)
+                .fanout(PARALLELISM_DEGREE as u8)
+                .depth(2)
+                .inner_length(BLAKE2B_OUTBYTES)
+        };
+
+        let leaves = array::from_fn(|i| {
+            node(BLAKE2B_OUTBYTES)
+                .key(key)
+                .node_offset(i as u64)
+                .last_node(i == PARALLELISM_DEGREE - 1)
+                .build()
+        });
+        let root = node(outlen)
+            .param_key_length(key.len())
+            .node_depth(1)
+            .last_node(true)
+            .build();
+
+        Blake2bp { leaves, root, pos: 0, digest_length: outlen, threaded: false }
+    }
+
+    pub fn new(outlen: usize) -> Blake2bp {
+        Blake2bp::init(outlen, &[])
+    }
+
+    pub fn new_keyed(outlen: usize, key: &[u8]) -> Blake2bp {
+        assert!(!key.is_empty());
+        Blake2bp::init(outlen, key)
+    }
+
+    /// Enable or disable hashing the leaves on separate threads, for calls to `input` with at
+    /// least 64 KiB of data.
+    pub fn threaded(mut self, threaded: bool) -> Blake2bp {
+        self.threaded = threaded;
+        self
+    }
+
+    // Hands `input` to the leaves block by block, on the calling thread
+    fn update_sequential(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            let leaf = self.pos / BLAKE2B_BLOCKBYTES;
+            let n = cmp::min(BLAKE2B_BLOCKBYTES - self.pos % BLAKE2B_BLOCKBYTES, input.len());
+            Digest::input(&mut self.leaves[leaf], &input[..n]);
+            self.pos = (self.pos + n) % STRIPE_BYTES;
+            input = &input[n..];
+        }
+    }
+
+    fn update(&mut self, input: &[u8]) {
+        if !self.threaded || input.len() < THREADED_MIN_BYTES {
+            self.update_sequential(input);
+            return;
+        }
+
+        // Complete the current stripe, so that the leaves are at the start of a block
+        let head = cmp::min((STRIPE_BYTES - self.pos) % STRIPE_BYTES, input.len());
+        self.update_sequential(&input[..head]);
+        let input = &input[head..];
+
+        let stripes_len = input.len() - input.len() % STRIPE_BYTES;
+        let stripes = &input[..stripes_len];
+        thread::scope(|s| {
+            for (i, leaf) in self.leaves.iter_mut().enumerate() {
+                s.spawn(move || {
+                    for stripe in stripes.chunks(STRIPE_BYTES) {
+                        Digest::input(leaf, &stripe[i * BLAKE2B_BLOCKBYTES..(i + 1) * BLAKE2B_BLOCKBYTES]);
+                    }
+                });
+            }
+        });
+
+        self.update_sequential(&input[stripes_len..]);
+    }
+
+    fn finalize(&self, out: &mut [u8]) {
+        assert!(out.len() == self.digest_length);
+        let mut root = self.root;
+        for leaf in self.leaves.iter() {
+            let mut leaf = *leaf;
+            let mut leaf_hash = [0u8; BLAKE2B_OUTBYTES];
+            Digest::result(&mut leaf, &mut leaf_hash);
+            Digest::input(&mut root, &leaf_hash);
+        }
+        Digest::result(&mut root, out);
+    }
+
+    pub fn reset(&mut self) {
+        for leaf in self.leaves.iter_mut() {
+            Blake2b::reset(leaf);
+        }
+        self.pos = 0;
+    }
+
+    pub fn blake2bp(out: &mut[u8], input: &[u8], key: &[u8]) {
+        let mut hasher : Blake2bp = if !key.is_empty() { Blake2bp::new_keyed(out.len(), key) } else { Blake2bp::new(out.len()) };
+
+        hasher.update(input);
+        hasher.finalize(out);
+    }
+}
+
+impl Digest for Blake2bp {
+    fn reset(&mut self) { Blake2bp::reset(self); }
+    fn input(&mut self, msg: &[u8]) { self.update(msg); }
+    fn result(&mut self, out: &mut [u8]) { self.finalize(out); }
+    fn output_bits(&self) -> usize { 8 * self.digest_length }
+    fn block_size(&self) -> usize { BLAKE2B_BLOCKBYTES }
+}
+
+impl Mac for Blake2bp {
+    /**
+     * Process input data.
+     *
+     * # Arguments
+     * * data - The input data to process.
+     *
+     */
+    fn input(&mut self, data: &[u8]) {
+        self.update(data);
+    }
+
+    /**
+     * Reset the Mac state to begin processing another input stream.
+     */
+    fn reset(&mut self) {
+        Blake2bp::reset(self);
+    }
+
+    /**
+     * Obtain the result of a Mac computation as a MacResult.
+     */
+    fn result(&mut self) -> MacResult {
+        let mut mac = vec![0u8; self.digest_length];
+        self.raw_result(&mut mac);
+        MacResult::new_from_owned(mac)
+    }
+
+    /**
+     * Obtain the result of a Mac computation as [u8]. This method should be used very carefully
+     * since incorrect use of the Mac code could result in permitting a timing attack which defeats
+     * the security provided by a Mac function.
+     */
+    fn raw_result(&mut self, output: &mut [u8]) {
+        self.finalize(output);
+    }
+
+    /**
+     * Get the size of the Mac code, in bytes.
+     */
+    fn output_bytes(&self) -> usize { self.digest_length }
+}
+
+#[cfg(test)]
+mod digest_tests {
+    use crate::blake2bp::Blake2bp;
+    use crate::digest::Digest;
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_blake2bp_empty() {
+        let mut sh = Blake2bp::new(64);
+        assert_eq!(sh.result_str(),
+                   "b5ef811a8038f70b628fa8b294daae7492b1ebe343a80eaabbf1f6ae664dd67b\
+                    9d90b0120791eab81dc96985f28849f6a305186a85501b405114bfa678df9380");
+    }
+
+    // Computed with a separate implementation of BLAKE2bp on top of the BLAKE2b reference code
+    #[test]
+    fn test_blake2bp_long() {
+        let input = test_data(300000);
+        let expected = "950592404e33d4a7325148a3270726849ca19feb83b2f0c196180fce16564890\
+                        b3dd898105086ccdf55b5edf8e42fa5bf096f5f156fc50a3a4eddb41de2dc688";
+
+        for &threaded in [false, true].iter() {
+            let mut sh = Blake2bp::new(64).threaded(threaded);
+            sh.input(&input);
+            assert_eq!(sh.result_str(), expected);
+
+            // The same message, in pieces that don't line up with the blocks
+            sh.reset();
+            for piece in input.chunks(70001) {
+                sh.input(piece);
+            }
+            assert_eq!(sh.result_str(), expected);
+        }
+    }
+}
+
+#[cfg(test)]
+mod mac_tests {
+    use crate::blake2bp::Blake2bp;
+    use crate::mac::Mac;
+    use crate::serialize::hex::FromHex;
+
+    fn check(outlen: usize, key: &[u8], input_len: usize, expected_hex: &str) {
+        let input: Vec<u8> = (0..input_len).map(|i| i as u8).collect();
+        let expected = expected_hex.from_hex().unwrap();
+
+        let mut m = Blake2bp::new_keyed(outlen, key);
+        m.input(&input);
+        assert_eq!(m.result().code(), &expected[..]);
+
+        // Byte by byte, after a reset
+        m.reset();
+        for b in input.iter() {
+            m.input(&[*b]);
+        }
+        assert_eq!(m.result().code(), &expected[..]);
+    }
+
+    // From blake2bp-kat.txt of the BLAKE2 reference code: the key is 00 01 .. 3f, and the input
+    // 00 01 .. of the given length
+    #[test]
+    fn test_blake2bp_kat() {
+        let key: Vec<u8> = (0..64).collect();
+        check(64, &key, 0,
+              "9d9461073e4eb640a255357b839f394b838c6ff57c9b686a3f76107c1066728f\
+               3c9956bd785cbc3bf79dc2ab578c5a0c063b9d9c405848de1dbe821cd05c940a");
+        check(64, &key, 1,
+              "ff8e90a37b94623932c59f7559f26035029c376732cb14d41602001cbb73adb7\
+               9293a2dbda5f60703025144d158e2735529596251c73c0345ca6fccb1fb1e97e");
+        check(64, &key, 64,
+              "22b8249eaf722964ce424f71a74d038ff9b615fba5c7c22cb62797f5398224c3\
+               f072ebc1dacba32fc6f66360b3e1658d0fa0da1ed1c1da662a2037da823a3383");
+        check(64, &key, 127,
+              "7926708859e6e2ab68f604da69a9fb5087bb33f4e8d895730e301ab2d7df748b\
+               67df0b6b8622e52dd57d8d3ad87d5820d4ecfd24178b2d2b78d64f4fbd387582");
+        check(64, &key, 128,
+              "9280f4d1157032ab315c100d636283fbf4fba2fbad0f8bc020721d76bc1c8973\
+               ced28871cc907dab60e59756987b0e0f867fa2fe9d9041f2c9618074e44fe5e9");
+        check(64, &key, 129,
+              "5530c2d59f144872e987e4e258a7d8c38ce844e2cc2eed940ffc683b498815e5\
+               3adb1faaf568946122805ac3b8e2fed435fed6162e76f564e586ba464424e885");
+        check(64, &key, 255,
+              "96fbcbb60bd313b8845033e5bc058a38027438572d7e7957f3684f6268aadd3a\
+               d08d21767ed6878685331ba98571487e12470aad669326716e46667f69f8d7e8");
+    }
+
+    #[test]
+    fn test_blake2bp_short_key_and_output() {
+        let key: Vec<u8> = (0..10).collect();
+        check(20, &key, 200, "d72d9dab48cfe3b9a584797ec8ef84890b1d4f02");
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::digest::Digest;
+    use crate::blake2bp::Blake2bp;
+
+
+    #[bench]
+    pub fn blake2bp_1k(bh: & mut Bencher) {
+        let mut sh = Blake2bp::new(64);
+        let bytes = [1u8; 1024];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn blake2bp_64k(bh: & mut Bencher) {
+        let mut sh = Blake2bp::new(64);
+        let bytes = [1u8; 65536];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn blake2bp_1m_threaded(bh: & mut Bencher) {
+        let mut sh = Blake2bp::new(64).threaded(true);
+        let bytes = vec![1u8; 1 << 20];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+}
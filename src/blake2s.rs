@@ -311,7 +311,6 @@ impl Blake2s {
             *b = 0;
         }
         self.buflen = 0;
-        self.last_node = 0;
         self.computed = false;
         self.apply_param();
         if self.key_length > 0 {
@@ -327,6 +326,137 @@ impl Blake2s {
     }
 }
 
+/// Builds a `Blake2s` instance with a custom parameter block: a key, a salt, a personalization
+/// string, and the fields that place a node in a hash tree.
+#[derive(Copy, Clone)]
+pub struct Blake2sBuilder {
+    param: Blake2sParam,
+    key: [u8; BLAKE2S_KEYBYTES],
+    // The number of bytes of `key` to hash, which is the key length of the parameter block except
+    // for the root of BLAKE2sp
+    key_length: usize,
+    last_node: bool
+}
+
+impl Blake2sBuilder {
+    /// Start building an instance producing `outlen` bytes, with the parameters of sequential
+    /// hashing.
+    pub fn new(outlen: usize) -> Blake2sBuilder {
+        assert!(outlen > 0 && outlen <= BLAKE2S_OUTBYTES);
+        Blake2sBuilder {
+            param: Blake2s::default_param(outlen as u8),
+            key: [0; BLAKE2S_KEYBYTES],
+            key_length: 0,
+            last_node: false
+        }
+    }
+
+    /// Set the key, of at most 32 bytes.
+    pub fn key(mut self, key: &[u8]) -> Blake2sBuilder {
+        assert!(key.len() <= BLAKE2S_KEYBYTES);
+        self.key = [0; BLAKE2S_KEYBYTES];
+        copy_memory(key, &mut self.key);
+        self.key_length = key.len();
+        self.param.key_length = key.len() as u8;
+        self
+    }
+
+    // Sets the key length of the parameter block without hashing a key, as the root of BLAKE2sp
+    // does
+    pub(crate) fn param_key_length(mut self, key_length: usize) -> Blake2sBuilder {
+        assert!(key_length <= BLAKE2S_KEYBYTES);
+        self.param.key_length = key_length as u8;
+        self
+    }
+
+    /// Set the salt, of at most 8 bytes. Shorter salts are padded with zeros.
+    pub fn salt(mut self, salt: &[u8]) -> Blake2sBuilder {
+        assert!(salt.len() <= BLAKE2S_SALTBYTES);
+        self.param.salt = [0; BLAKE2S_SALTBYTES];
+        copy_memory(salt, &mut self.param.salt);
+        self
+    }
+
+    /// Set the personalization string, of at most 8 bytes. Shorter strings are padded with zeros.
+    pub fn personal(mut self, personal: &[u8]) -> Blake2sBuilder {
+        assert!(personal.len() <= BLAKE2S_PERSONALBYTES);
+        self.param.personal = [0; BLAKE2S_PERSONALBYTES];
+        copy_memory(personal, &mut self.param.personal);
+        self
+    }
+
+    /// Set the fanout of the tree, 0 for unlimited.
+    pub fn fanout(mut self, fanout: u8) -> Blake2sBuilder {
+        self.param.fanout = fanout;
+        self
+    }
+
+    /// Set the maximal depth of the tree, 255 for unlimited.
+    pub fn depth(mut self, depth: u8) -> Blake2sBuilder {
+        assert!(depth > 0);
+        self.param.depth = depth;
+        self
+    }
+
+    /// Set the maximal byte length of the leaves, 0 for unlimited.
+    pub fn leaf_length(mut self, leaf_length: u32) -> Blake2sBuilder {
+        self.param.leaf_length = leaf_length;
+        self
+    }
+
+    /// Set the offset of the node within its level of the tree, less than 2^48.
+    pub fn node_offset(mut self, node_offset: u64) -> Blake2sBuilder {
+        assert!(node_offset < 1 << 48);
+        self.param.node_offset.copy_from_slice(&node_offset.to_le_bytes()[..6]);
+        self
+    }
+
+    // Sets the output length of BLAKE2Xs, which takes the upper 2 bytes of the node offset
+    pub(crate) fn xof_length(mut self, xof_length: u16) -> Blake2sBuilder {
+        self.param.node_offset[4..].copy_from_slice(&xof_length.to_le_bytes());
+        self
+    }
+
+    // Sets the parameters of the nodes expanding the root hash of BLAKE2Xs, which are the only
+    // ones with a depth of 0
+    pub(crate) fn xof_node(mut self, node_offset: u32, xof_length: u16) -> Blake2sBuilder {
+        self.param.fanout = 0;
+        self.param.depth = 0;
+        self.param.leaf_length = BLAKE2S_OUTBYTES as u32;
+        self.param.inner_length = BLAKE2S_OUTBYTES as u8;
+        self.node_offset(node_offset as u64).xof_length(xof_length)
+    }
+
+    /// Set the depth of the node, 0 for the leaves.
+    pub fn node_depth(mut self, node_depth: u8) -> Blake2sBuilder {
+        self.param.node_depth = node_depth;
+        self
+    }
+
+    /// Set the byte length of the inner hashes, at most 32.
+    pub fn inner_length(mut self, inner_length: usize) -> Blake2sBuilder {
+        assert!(inner_length <= BLAKE2S_OUTBYTES);
+        self.param.inner_length = inner_length as u8;
+        self
+    }
+
+    /// Mark the node as the last one of its level of the tree.
+    pub fn last_node(mut self, last_node: bool) -> Blake2sBuilder {
+        self.last_node = last_node;
+        self
+    }
+
+    /// Create the `Blake2s` instance.
+    pub fn build(&self) -> Blake2s {
+        let mut b = Blake2s::init_param(self.param, &self.key[..self.key_length]);
+        b.last_node = self.last_node as u8;
+        if self.key_length > 0 {
+            b.apply_key();
+        }
+        b
+    }
+}
+
 impl Digest for Blake2s {
     fn reset(&mut self) { Blake2s::reset(self); }
     fn input(&mut self, msg: &[u8]) { self.update(msg); }
@@ -485,6 +615,92 @@ mod mac_tests {
     }
 }
 
+#[cfg(test)]
+mod builder_tests {
+    use crate::blake2s::Blake2sBuilder;
+    use crate::digest::Digest;
+    use crate::serialize::hex::FromHex;
+
+    fn check(builder: Blake2sBuilder, input: &[u8], expected_hex: &str) {
+        let expected = expected_hex.from_hex().unwrap();
+        let mut sh = builder.build();
+        sh.input(input);
+        let mut out = vec![0u8; expected.len()];
+        sh.result(&mut out);
+        assert_eq!(out, expected);
+
+        // The parameters survive a reset
+        sh.reset();
+        sh.input(input);
+        let mut out = vec![0u8; expected.len()];
+        sh.result(&mut out);
+        assert_eq!(out, expected);
+    }
+
+    // From blake2s-kat.txt of the BLAKE2 reference code
+    #[test]
+    fn test_blake2s_builder_keyed() {
+        let input: Vec<u8> = (0..255).collect();
+        let key: Vec<u8> = (0..32).collect();
+        check(Blake2sBuilder::new(32).key(&key), &input,
+              "3fb735061abc519dfe979e54c1ee5bfad0a9d858b3315bad34bde999efd724dd");
+    }
+
+    // The remaining vectors were computed with the BLAKE2 reference code, as used by Python's
+    // hashlib
+
+    #[test]
+    fn test_blake2s_builder_salt_personal() {
+        check(Blake2sBuilder::new(16).salt(b"01234567").personal(b"Personal"),
+              b"abc", "771ec97514b7681d93aeac5d6b3415ec");
+    }
+
+    #[test]
+    fn test_blake2s_builder_all_parameters() {
+        let builder = Blake2sBuilder::new(20)
+            .key(b"key")
+            .salt(b"01234")
+            .personal(b"Per")
+            .fanout(3)
+            .depth(4)
+            .leaf_length(1234)
+            .node_offset(0x010203040506)
+            .node_depth(5)
+            .inner_length(17)
+            .last_node(true);
+        check(builder, b"abc", "1a89781af7da296ed3d007f2f0a13cb02c43e453");
+    }
+
+    // A tree of two leaves of at most 4096 bytes, and a root
+    #[test]
+    fn test_blake2s_builder_tree() {
+        let input: Vec<u8> = (0..6144).map(|i| i as u8).collect();
+        let node = |offset: u64, depth: u8, last: bool, outlen: usize| {
+            Blake2sBuilder::new(outlen)
+                .fanout(2)
+                .depth(2)
+                .leaf_length(4096)
+                .inner_length(32)
+                .node_offset(offset)
+                .node_depth(depth)
+                .last_node(last)
+                .build()
+        };
+
+        let mut leaves = [[0u8; 32]; 2];
+        for (i, (leaf, data)) in leaves.iter_mut().zip(input.chunks(4096)).enumerate() {
+            let mut sh = node(i as u64, 0, i == 1, 32);
+            sh.input(data);
+            sh.result(leaf);
+        }
+
+        let mut root = node(0, 1, true, 16);
+        root.input(&leaves[0]);
+        root.input(&leaves[1]);
+        assert_eq!(root.result_str(), "d7bc930313eac3ba6e84cc4bbc119196");
+    }
+}
+
 #[cfg(all(test, feature = "with-bench"))]
 mod bench {
     use test::Bencher;
@@ -0,0 +1,337 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements BLAKE2sp, the 8-way parallel variant of BLAKE2s [1]. The message is split
+ * into 64 byte blocks, which are distributed round robin over 8 leaves; the root of the tree
+ * hashes the 8 leaf hashes. The nodes are `Blake2s` instances with the tree parameters set.
+ *
+ * The leaves can optionally be hashed on separate threads, see `Blake2sp::threaded`.
+ *
+ * # Usage
+ *
+ * ```rust
+ * use self::crypto::blake2sp::Blake2sp;
+ * use self::crypto::digest::Digest;
+ *
+ * let mut hasher = Blake2sp::new(32).threaded(true);
+ * hasher.input(&[0u8; 1 << 20]);
+ * let mut output = [0u8; 32];
+ * hasher.result(&mut output);
+ * ```
+ *
+ * # References
+ * [1] - J.-P. Aumasson, S. Neves, Z. Wilcox-O'Hearn, C. Winnerlein. BLAKE2: simpler, smaller, fast
+ *       as MD5. <https://blake2.net/blake2.pdf>
+ */
+
+use std::array;
+use std::cmp;
+use std::thread;
+
+use crate::blake2s::{Blake2s, Blake2sBuilder};
+use crate::digest::Digest;
+use crate::mac::{Mac, MacResult};
+
+const PARALLELISM_DEGREE: usize = 8;
+const BLAKE2S_BLOCKBYTES: usize = 64;
+const BLAKE2S_OUTBYTES: usize = 32;
+const BLAKE2S_KEYBYTES: usize = 32;
+
+// One block for each of the leaves
+const STRIPE_BYTES: usize = PARALLELISM_DEGREE * BLAKE2S_BLOCKBYTES;
+
+// Calls to `input` with less data than this hash the leaves on the calling thread, even if
+// threading is enabled
+const THREADED_MIN_BYTES: usize = 1 << 16;
+
+#[derive(Copy, Clone)]
+pub struct Blake2sp {
+    leaves: [Blake2s; PARALLELISM_DEGREE],
+    // The root before it has absorbed the leaf hashes
+    root: Blake2s,
+    // The position of the next byte of input within the current stripe of blocks
+    pos: usize,
+    digest_length: usize,
+    threaded: bool
+}
+
+impl Blake2sp {
+    fn init(outlen: usize, key: &[u8]) -> Blake2sp {
+        assert!(outlen > 0 && outlen <= BLAKE2S_OUTBYTES);
+        assert!(key.len() <= BLAKE2S_KEYBYTES);
+
+        let node = |outlen: usize| {
+            Blake2sBuilder::new(outlen)
+                .fanout(PARALLELISM_DEGREE as u8)
+                .depth(2)
+                .inner_length(BLAKE2S_OUTBYTES)
+        };
+
+        let leaves = array::from_fn(|i| {
+            node(BLAKE2S_OUTBYTES)
+                .key(key)
+                .node_offset(i as u64)
+                .last_node(i == PARALLELISM_DEGREE - 1)
+                .build()
+        });
+        let root = node(outlen)
+            .param_key_length(key.len())
+            .node_depth(1)
+            .last_node(true)
+            .build();
+
+        Blake2sp { leaves, root, pos: 0, digest_length: outlen, threaded: false }
+    }
+
+    pub fn new(outlen: usize) -> Blake2sp {
+        Blake2sp::init(outlen, &[])
+    }
+
+    pub fn new_keyed(outlen: usize, key: &[u8]) -> Blake2sp {
+        assert!(!key.is_empty());
+        Blake2sp::init(outlen, key)
+    }
+
+    /// Enable or disable hashing the leaves on separate threads, for calls to `input` with at
+    /// least 64 KiB of data.
+    pub fn threaded(mut self, threaded: bool) -> Blake2sp {
+        self.threaded = threaded;
+        self
+    }
+
+    // Hands `input` to the leaves block by block, on the calling thread
+    fn update_sequential(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            let leaf = self.pos / BLAKE2S_BLOCKBYTES;
+            let n = cmp::min(BLAKE2S_BLOCKBYTES - self.pos % BLAKE2S_BLOCKBYTES, input.len());
+            Digest::input(&mut self.leaves[leaf], &input[..n]);
+            self.pos = (self.pos + n) % STRIPE_BYTES;
+            input = &input[n..];
+        }
+    }
+
+    fn update(&mut self, input: &[u8]) {
+        if !self.threaded || input.len() < THREADED_MIN_BYTES {
+            self.update_sequential(input);
+            return;
+        }
+
+        // Complete the current stripe, so that the leaves are at the start of a block
+        let head = cmp::min((STRIPE_BYTES - self.pos) % STRIPE_BYTES, input.len());
+        self.update_sequential(&input[..head]);
+        let input = &input[head..];
+
+        let stripes_len = input.len() - input.len() % STRIPE_BYTES;
+        let stripes = &input[..stripes_len];
+        thread::scope(|s| {
+            for (i, leaf) in self.leaves.iter_mut().enumerate() {
+                s.spawn(move || {
+                    for stripe in stripes.chunks(STRIPE_BYTES) {
+                        Digest::input(leaf, &stripe[i * BLAKE2S_BLOCKBYTES..(i + 1) * BLAKE2S_BLOCKBYTES]);
+                    }
+                });
+            }
+        });
+
+        self.update_sequential(&input[stripes_len..]);
+    }
+
+    fn finalize(&self, out: &mut [u8]) {
+        assert!(out.len() == self.digest_length);
+        let mut root = self.root;
+        for leaf in self.leaves.iter() {
+            let mut leaf = *leaf;
+            let mut leaf_hash = [0u8; BLAKE2S_OUTBYTES];
+            Digest::result(&mut leaf, &mut leaf_hash);
+            Digest::input(&mut root, &leaf_hash);
+        }
+        Digest::result(&mut root, out);
+    }
+
+    pub fn reset(&mut self) {
+        for leaf in self.leaves.iter_mut() {
+            Blake2s::reset(leaf);
+        }
+        self.pos = 0;
+    }
+
+    pub fn blake2sp(out: &mut[u8], input: &[u8], key: &[u8]) {
+        let mut hasher : Blake2sp = if !key.is_empty() { Blake2sp::new_keyed(out.len(), key) } else { Blake2sp::new(out.len()) };
+
+        hasher.update(input);
+        hasher.finalize(out);
+    }
+}
+
+impl Digest for Blake2sp {
+    fn reset(&mut self) { Blake2sp::reset(self); }
+    fn input(&mut self, msg: &[u8]) { self.update(msg); }
+    fn result(&mut self, out: &mut [u8]) { self.finalize(out); }
+    fn output_bits(&self) -> usize { 8 * self.digest_length }
+    fn block_size(&self) -> usize { BLAKE2S_BLOCKBYTES }
+}
+
+impl Mac for Blake2sp {
+    /**
+     * Process input data.
+     *
+     * # Arguments
+     * * data - The input data to process.
+     *
+     */
+    fn input(&mut self, data: &[u8]) {
+        self.update(data);
+    }
+
+    /**
+     * Reset the Mac state to begin processing another input stream.
+     */
+    fn reset(&mut self) {
+        Blake2sp::reset(self);
+    }
+
+    /**
+     * Obtain the result of a Mac computation as a MacResult.
+     */
+    fn result(&mut self) -> MacResult {
+        let mut mac = vec![0u8; self.digest_length];
+        self.raw_result(&mut mac);
+        MacResult::new_from_owned(mac)
+    }
+
+    /**
+     * Obtain the result of a Mac computation as [u8]. This method should be used very carefully
+     * since incorrect use of the Mac code could result in permitting a timing attack which defeats
+     * the security provided by a Mac function.
+     */
+    fn raw_result(&mut self, output: &mut [u8]) {
+        self.finalize(output);
+    }
+
+    /**
+     * Get the size of the Mac code, in bytes.
+     */
+    fn output_bytes(&self) -> usize { self.digest_length }
+}
+
+#[cfg(test)]
+mod digest_tests {
+    use crate::blake2sp::Blake2sp;
+    use crate::digest::Digest;
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_blake2sp_empty() {
+        let mut sh = Blake2sp::new(32);
+        assert_eq!(sh.result_str(), "dd0e891776933f43c7d032b08a917e25741f8aa9a12c12e1cac8801500f2ca4f");
+    }
+
+    // Computed with a separate implementation of BLAKE2sp on top of the BLAKE2s reference code
+    #[test]
+    fn test_blake2sp_long() {
+        let input = test_data(300000);
+        let expected = "f450d44e56c042f24dd2f15bc1e6f2223480a423ac4bab97c8af4f97c14d5635";
+
+        for &threaded in [false, true].iter() {
+            let mut sh = Blake2sp::new(32).threaded(threaded);
+            sh.input(&input);
+            assert_eq!(sh.result_str(), expected);
+
+            // The same message, in pieces that don't line up with the blocks
+            sh.reset();
+            for piece in input.chunks(70001) {
+                sh.input(piece);
+            }
+            assert_eq!(sh.result_str(), expected);
+        }
+    }
+}
+
+#[cfg(test)]
+mod mac_tests {
+    use crate::blake2sp::Blake2sp;
+    use crate::mac::Mac;
+    use crate::serialize::hex::FromHex;
+
+    fn check(outlen: usize, key: &[u8], input_len: usize, expected_hex: &str) {
+        let input: Vec<u8> = (0..input_len).map(|i| i as u8).collect();
+        let expected = expected_hex.from_hex().unwrap();
+
+        let mut m = Blake2sp::new_keyed(outlen, key);
+        m.input(&input);
+        assert_eq!(m.result().code(), &expected[..]);
+
+        // Byte by byte, after a reset
+        m.reset();
+        for b in input.iter() {
+            m.input(&[*b]);
+        }
+        assert_eq!(m.result().code(), &expected[..]);
+    }
+
+    // From blake2sp-kat.txt of the BLAKE2 reference code: the key is 00 01 .. 1f, and the input
+    // 00 01 .. of the given length
+    #[test]
+    fn test_blake2sp_kat() {
+        let key: Vec<u8> = (0..32).collect();
+        check(32, &key, 0, "715cb13895aeb678f6124160bff21465b30f4f6874193fc851b4621043f09cc6");
+        check(32, &key, 1, "40578ffa52bf51ae1866f4284d3a157fc1bcd36ac13cbdcb0377e4d0cd0b6603");
+        check(32, &key, 64, "1d3701a5661bd31ab20562bd07b74dd19ac8f3524b73ce7bc996b788afd2f317");
+        check(32, &key, 127, "44cb6311d0750b7e33f7333aa78aaca9c34ad5f79c1b1591ec33951e69c4c461");
+        check(32, &key, 128, "0c6ce32a3ea05612c5f8090f6a7e87f5ab30e41b707dcbe54155620ad770a340");
+        check(32, &key, 129, "c65938dd3a053c729cf5b7c89f390bfebb5112766bb00aa5fa3164dfdf3b5647");
+        check(32, &key, 255, "0c8a36597d7461c63a94732821c941856c668376606c86a52de0ee4104c615db");
+    }
+
+    #[test]
+    fn test_blake2sp_short_key_and_output() {
+        let key: Vec<u8> = (0..10).collect();
+        check(20, &key, 200, "49959f6e2628a5a9729aecb255da60e21cee97bc");
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::digest::Digest;
+    use crate::blake2sp::Blake2sp;
+
+
+    #[bench]
+    pub fn blake2sp_1k(bh: & mut Bencher) {
+        let mut sh = Blake2sp::new(32);
+        let bytes = [1u8; 1024];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn blake2sp_64k(bh: & mut Bencher) {
+        let mut sh = Blake2sp::new(32);
+        let bytes = [1u8; 65536];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn blake2sp_1m_threaded(bh: & mut Bencher) {
+        let mut sh = Blake2sp::new(32).threaded(true);
+        let bytes = vec![1u8; 1 << 20];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+}
@@ -0,0 +1,259 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements BLAKE2Xb, the extendable output function built on BLAKE2b [1]. A root
+ * `Blake2b` instance hashes the message, with the length of the output in its parameter block. The
+ * output is then produced in blocks of 64 bytes, each of them a hash of the root hash with the
+ * index of the block as its node offset.
+ *
+ * As the output length is a parameter of the root, outputs of different lengths are unrelated. If
+ * the length isn't known in advance, pass `BLAKE2XB_UNKNOWN_LENGTH`: up to 256 GiB of output can
+ * then be read.
+ *
+ * # Usage
+ *
+ * ```rust
+ * use self::crypto::blake2xb::Blake2xb;
+ * use self::crypto::digest::{ExtendableOutput, XofReader};
+ *
+ * let mut xof = Blake2xb::new(100);
+ * xof.input(b"hello world");
+ * let mut reader = xof.finalize_xof();
+ * let mut output = [0u8; 100];
+ * reader.read(&mut output);
+ * ```
+ *
+ * # References
+ * [1] - J.-P. Aumasson, S. Neves, Z. Wilcox-O'Hearn, C. Winnerlein. BLAKE2X.
+ *       <https://blake2.net/blake2x.pdf>
+ */
+
+use std::cmp;
+
+use crate::blake2b::{Blake2b, Blake2bBuilder};
+use crate::digest::{Digest, ExtendableOutput, XofReader};
+
+const BLAKE2B_OUTBYTES: usize = 64;
+
+/// The output length to pass when the length of the output isn't known in advance
+pub const BLAKE2XB_UNKNOWN_LENGTH: u32 = 0xFFFFFFFF;
+
+/// The BLAKE2Xb extendable output function. `finalize_xof` returns a `Blake2xbReader`.
+#[derive(Copy, Clone)]
+pub struct Blake2xb {
+    root: Blake2b,
+    xof_length: u32
+}
+
+impl Blake2xb {
+    /// Create a BLAKE2Xb instance producing `outlen` bytes, or `BLAKE2XB_UNKNOWN_LENGTH`.
+    pub fn new(outlen: u32) -> Blake2xb {
+        assert!(outlen > 0);
+        Blake2xb {
+            root: Blake2bBuilder::new(BLAKE2B_OUTBYTES).xof_length(outlen).build(),
+            xof_length: outlen
+        }
+    }
+
+    /// Create a BLAKE2Xb instance producing `outlen` bytes, or `BLAKE2XB_UNKNOWN_LENGTH`, with a
+    /// key of at most 64 bytes.
+    pub fn new_keyed(outlen: u32, key: &[u8]) -> Blake2xb {
+        assert!(outlen > 0);
+        assert!(!key.is_empty());
+        Blake2xb {
+            root: Blake2bBuilder::new(BLAKE2B_OUTBYTES).key(key).xof_length(outlen).build(),
+            xof_length: outlen
+        }
+    }
+
+    /// Hash `input` with an optional key of at most 64 bytes (none if `key` is empty), filling
+    /// `out`, which must be shorter than `BLAKE2XB_UNKNOWN_LENGTH` bytes.
+    pub fn blake2xb(out: &mut [u8], input: &[u8], key: &[u8]) {
+        assert!(out.len() < BLAKE2XB_UNKNOWN_LENGTH as usize);
+        if out.is_empty() {
+            return;
+        }
+        let mut xof = if !key.is_empty() {
+            Blake2xb::new_keyed(out.len() as u32, key)
+        } else {
+            Blake2xb::new(out.len() as u32)
+        };
+
+        xof.input(input);
+        xof.finalize_xof().read(out);
+    }
+}
+
+impl ExtendableOutput for Blake2xb {
+    type Reader = Blake2xbReader;
+
+    fn input(&mut self, data: &[u8]) {
+        Digest::input(&mut self.root, data);
+    }
+
+    fn finalize_xof(mut self) -> Blake2xbReader {
+        let mut root_hash = [0u8; BLAKE2B_OUTBYTES];
+        Digest::result(&mut self.root, &mut root_hash);
+
+        let available = if self.xof_length == BLAKE2XB_UNKNOWN_LENGTH {
+            (BLAKE2B_OUTBYTES as u64) << 32
+        } else {
+            self.xof_length as u64
+        };
+        Blake2xbReader {
+            root_hash,
+            xof_length: self.xof_length,
+            available,
+            pos: 0,
+            block: [0; BLAKE2B_OUTBYTES]
+        }
+    }
+
+    fn reset(&mut self) {
+        Blake2b::reset(&mut self.root);
+    }
+}
+
+/// Reads the output of BLAKE2Xb
+#[derive(Copy, Clone)]
+pub struct Blake2xbReader {
+    root_hash: [u8; BLAKE2B_OUTBYTES],
+    xof_length: u32,
+    // The number of bytes of output that can be read in total
+    available: u64,
+    // The number of bytes read so far
+    pos: u64,
+    // The current block of output
+    block: [u8; BLAKE2B_OUTBYTES]
+}
+
+impl Blake2xbReader {
+    fn next_block(&mut self) {
+        let node_offset = (self.pos / BLAKE2B_OUTBYTES as u64) as u32;
+        let len = cmp::min(self.available - self.pos, BLAKE2B_OUTBYTES as u64) as usize;
+        let mut node = Blake2bBuilder::new(len).xof_node(node_offset, self.xof_length).build();
+        Digest::input(&mut node, &self.root_hash);
+        Digest::result(&mut node, &mut self.block[..len]);
+    }
+}
+
+impl XofReader for Blake2xbReader {
+    fn read(&mut self, out: &mut [u8]) {
+        assert!(out.len() as u64 <= self.available - self.pos,
+                "read past the end of the BLAKE2Xb output");
+
+        let mut written = 0;
+        while written < out.len() {
+            let offset = (self.pos % BLAKE2B_OUTBYTES as u64) as usize;
+            if offset == 0 {
+                self.next_block();
+            }
+            let n = cmp::min(BLAKE2B_OUTBYTES - offset, out.len() - written);
+            out[written..written + n].copy_from_slice(&self.block[offset..offset + n]);
+            written += n;
+            self.pos += n as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blake2xb::{Blake2xb, BLAKE2XB_UNKNOWN_LENGTH};
+    use crate::digest::{ExtendableOutput, XofReader};
+    use crate::serialize::hex::FromHex;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    // The layout of the known answer tests of the reference implementation: the key is 00 01 .. 3F
+    // and the message 00 01 .. FF, for every output length
+    fn check_kat(outlen: usize, expected_hex: &str) {
+        let key: Vec<u8> = (0..64).collect();
+        let input: Vec<u8> = (0..=255).collect();
+        let mut out = vec![0u8; outlen];
+        Blake2xb::blake2xb(&mut out, &input, &key);
+        assert_eq!(out, hex_to_bytes(expected_hex));
+    }
+
+    #[test]
+    fn test_blake2xb_kat() {
+        check_kat(1, "64");
+        check_kat(2, "f457");
+        check_kat(63,
+            "e101f43179d8e8546e5ce6a96d7556b7e6b9d4a7d00e7aade5579d085d527ce34a9329551ebcaf6ba946949bbe38e3\
+             0a62ae344c1950b4bde55306b3bac432");
+        check_kat(64,
+            "4324561d76c370ef35ac36a4adf8f3773a50d86504bd284f71f7ce9e2bc4c1f1d34a7fb2d67561d101955d448b6757\
+             7eb30dfee96a95c7f921ef53e20be8bc44");
+        check_kat(65,
+            "78f0ed6e220b3da3cc9381563b2f72c8dc830cb0f39a48c6ae479a6a78dcfa94002631dec467e9e9b47cc8f0887eb6\
+             80e340aec3ec009d4a33d241533c76c8ca8c");
+        check_kat(256,
+            "1e9b2c454e9de3a2d723d850331037dbf54133dbe27488ff757dd255833a27d8eb8a128ad12d0978b6884e25737086\
+             a704fb289aaaccf930d5b582ab4df1f55f0c429b6875edec3fe45464fa74164be056a55e243c4222c586bec5b18f39\
+             036aa903d98180f24f83d09a454dfa1e03a60e6a3ba4613e99c35f874d790174ee48a557f4f021ade4d1b278d7997e\
+             f094569b37b3db0505951e9ee8400adaea275c6db51b325ee730c69df97745b556ae41cd98741e28aa3a49544541ee\
+             b3da1b1e8fa4e8e9100d66dd0c7f5e2c271b1ecc077de79c462b9fe4c273543ecd82a5bea63c5acc01eca5fb780c7d\
+             7c8c9fe208ae8bd50cad1769693d92c6c8649d20d8");
+    }
+
+    #[test]
+    fn test_blake2xb_unkeyed() {
+        let mut out = [0u8; 128];
+        Blake2xb::blake2xb(&mut out, b"", b"");
+        assert_eq!(out[..], hex_to_bytes(
+            "c4a522632c214104372ed2a05d7a9c0423153094f569e06c75a9989f530ce51efb837f468cb95ad94755e4ced2093c\
+             7edffa59dc89331f17a87fe01c22ff4ddc68e4d158946acf99a7590f142e0b6b08667fcb735ddcf7997693b11647e0\
+             3da59781606ce4c7e38e98f290319fd093efc3ed3c68db238080f5d6c9565b6f6998")[..]);
+    }
+
+    #[test]
+    fn test_blake2xb_unknown_length() {
+        let mut xof = Blake2xb::new(BLAKE2XB_UNKNOWN_LENGTH);
+        xof.input(b"abc");
+        let mut reader = xof.finalize_xof();
+
+        // The output doesn't depend on how it is split across reads
+        let mut out = [0u8; 100];
+        reader.read(&mut out[..1]);
+        reader.read(&mut out[1..70]);
+        reader.read(&mut out[70..]);
+        assert_eq!(out[..], hex_to_bytes(
+            "ae080c1efbcf7f60ed52a04161d02b7ee63bed362534f0661da02c6e40cd208946d066b86b3dff620e57acea9cd72d\
+             3056cf6cb0c18341452a17ce2cced67b702669bf0bed358c1b708e97de2533b294cdd5e9e229678be36399b5b28d65\
+             41c4bc4e3079")[..]);
+    }
+
+    #[test]
+    fn test_blake2xb_reset() {
+        let mut xof = Blake2xb::new_keyed(200, b"key");
+        xof.input(b"some data");
+        xof.reset();
+        xof.input(b"other data");
+        let mut out1 = [0u8; 200];
+        xof.finalize_xof().read(&mut out1);
+
+        let mut out2 = [0u8; 200];
+        Blake2xb::blake2xb(&mut out2, b"other data", b"key");
+        assert_eq!(out1[..], out2[..]);
+    }
+
+    #[test]
+    fn test_blake2xb_empty_output() {
+        Blake2xb::blake2xb(&mut [], b"abc", b"");
+        Blake2xb::blake2xb(&mut [], b"abc", b"key");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_blake2xb_read_past_end() {
+        let mut reader = Blake2xb::new(10).finalize_xof();
+        let mut out = [0u8; 11];
+        reader.read(&mut out);
+    }
+}
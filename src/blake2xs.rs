@@ -0,0 +1,252 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements BLAKE2Xs, the extendable output function built on BLAKE2s [1]. A root
+ * `Blake2s` instance hashes the message, with the length of the output in its parameter block. The
+ * output is then produced in blocks of 32 bytes, each of them a hash of the root hash with the
+ * index of the block as its node offset.
+ *
+ * As the output length is a parameter of the root, outputs of different lengths are unrelated. If
+ * the length isn't known in advance, pass `BLAKE2XS_UNKNOWN_LENGTH`: up to 128 GiB of output can
+ * then be read.
+ *
+ * # Usage
+ *
+ * ```rust
+ * use self::crypto::blake2xs::Blake2xs;
+ * use self::crypto::digest::{ExtendableOutput, XofReader};
+ *
+ * let mut xof = Blake2xs::new(100);
+ * xof.input(b"hello world");
+ * let mut reader = xof.finalize_xof();
+ * let mut output = [0u8; 100];
+ * reader.read(&mut output);
+ * ```
+ *
+ * # References
+ * [1] - J.-P. Aumasson, S. Neves, Z. Wilcox-O'Hearn, C. Winnerlein. BLAKE2X.
+ *       <https://blake2.net/blake2x.pdf>
+ */
+
+use std::cmp;
+
+use crate::blake2s::{Blake2s, Blake2sBuilder};
+use crate::digest::{Digest, ExtendableOutput, XofReader};
+
+const BLAKE2S_OUTBYTES: usize = 32;
+
+/// The output length to pass when the length of the output isn't known in advance
+pub const BLAKE2XS_UNKNOWN_LENGTH: u16 = 0xFFFF;
+
+/// The BLAKE2Xs extendable output function. `finalize_xof` returns a `Blake2xsReader`.
+#[derive(Copy, Clone)]
+pub struct Blake2xs {
+    root: Blake2s,
+    xof_length: u16
+}
+
+impl Blake2xs {
+    /// Create a BLAKE2Xs instance producing `outlen` bytes, or `BLAKE2XS_UNKNOWN_LENGTH`.
+    pub fn new(outlen: u16) -> Blake2xs {
+        assert!(outlen > 0);
+        Blake2xs {
+            root: Blake2sBuilder::new(BLAKE2S_OUTBYTES).xof_length(outlen).build(),
+            xof_length: outlen
+        }
+    }
+
+    /// Create a BLAKE2Xs instance producing `outlen` bytes, or `BLAKE2XS_UNKNOWN_LENGTH`, with a
+    /// key of at most 32 bytes.
+    pub fn new_keyed(outlen: u16, key: &[u8]) -> Blake2xs {
+        assert!(outlen > 0);
+        assert!(!key.is_empty());
+        Blake2xs {
+            root: Blake2sBuilder::new(BLAKE2S_OUTBYTES).key(key).xof_length(outlen).build(),
+            xof_length: outlen
+        }
+    }
+
+    /// Hash `input` with an optional key of at most 32 bytes (none if `key` is empty), filling
+    /// `out`, which must be shorter than `BLAKE2XS_UNKNOWN_LENGTH` bytes.
+    pub fn blake2xs(out: &mut [u8], input: &[u8], key: &[u8]) {
+        assert!(out.len() < BLAKE2XS_UNKNOWN_LENGTH as usize);
+        if out.is_empty() {
+            return;
+        }
+        let mut xof = if !key.is_empty() {
+            Blake2xs::new_keyed(out.len() as u16, key)
+        } else {
+            Blake2xs::new(out.len() as u16)
+        };
+
+        xof.input(input);
+        xof.finalize_xof().read(out);
+    }
+}
+
+impl ExtendableOutput for Blake2xs {
+    type Reader = Blake2xsReader;
+
+    fn input(&mut self, data: &[u8]) {
+        Digest::input(&mut self.root, data);
+    }
+
+    fn finalize_xof(mut self) -> Blake2xsReader {
+        let mut root_hash = [0u8; BLAKE2S_OUTBYTES];
+        Digest::result(&mut self.root, &mut root_hash);
+
+        let available = if self.xof_length == BLAKE2XS_UNKNOWN_LENGTH {
+            (BLAKE2S_OUTBYTES as u64) << 32
+        } else {
+            self.xof_length as u64
+        };
+        Blake2xsReader {
+            root_hash,
+            xof_length: self.xof_length,
+            available,
+            pos: 0,
+            block: [0; BLAKE2S_OUTBYTES]
+        }
+    }
+
+    fn reset(&mut self) {
+        Blake2s::reset(&mut self.root);
+    }
+}
+
+/// Reads the output of BLAKE2Xs
+#[derive(Copy, Clone)]
+pub struct Blake2xsReader {
+    root_hash: [u8; BLAKE2S_OUTBYTES],
+    xof_length: u16,
+    // The number of bytes of output that can be read in total
+    available: u64,
+    // The number of bytes read so far
+    pos: u64,
+    // The current block of output
+    block: [u8; BLAKE2S_OUTBYTES]
+}
+
+impl Blake2xsReader {
+    fn next_block(&mut self) {
+        let node_offset = (self.pos / BLAKE2S_OUTBYTES as u64) as u32;
+        let len = cmp::min(self.available - self.pos, BLAKE2S_OUTBYTES as u64) as usize;
+        let mut node = Blake2sBuilder::new(len).xof_node(node_offset, self.xof_length).build();
+        Digest::input(&mut node, &self.root_hash);
+        Digest::result(&mut node, &mut self.block[..len]);
+    }
+}
+
+impl XofReader for Blake2xsReader {
+    fn read(&mut self, out: &mut [u8]) {
+        assert!(out.len() as u64 <= self.available - self.pos,
+                "read past the end of the BLAKE2Xs output");
+
+        let mut written = 0;
+        while written < out.len() {
+            let offset = (self.pos % BLAKE2S_OUTBYTES as u64) as usize;
+            if offset == 0 {
+                self.next_block();
+            }
+            let n = cmp::min(BLAKE2S_OUTBYTES - offset, out.len() - written);
+            out[written..written + n].copy_from_slice(&self.block[offset..offset + n]);
+            written += n;
+            self.pos += n as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blake2xs::{Blake2xs, BLAKE2XS_UNKNOWN_LENGTH};
+    use crate::digest::{ExtendableOutput, XofReader};
+    use crate::serialize::hex::FromHex;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    // The layout of the known answer tests of the reference implementation: the key is 00 01 .. 1F
+    // and the message 00 01 .. FF, for every output length
+    fn check_kat(outlen: usize, expected_hex: &str) {
+        let key: Vec<u8> = (0..32).collect();
+        let input: Vec<u8> = (0..=255).collect();
+        let mut out = vec![0u8; outlen];
+        Blake2xs::blake2xs(&mut out, &input, &key);
+        assert_eq!(out, hex_to_bytes(expected_hex));
+    }
+
+    #[test]
+    fn test_blake2xs_kat() {
+        check_kat(1, "0e");
+        check_kat(2, "5196");
+        check_kat(31, "02dd758fa23113a14fd94830e50e0f6b86faec4e551e808b0ca8d00fef2a15");
+        check_kat(32, "a4fe2bd0f96a215fa7164ae1a405f4030a586c12b0c29806a099d7d7fdd8dd72");
+        check_kat(33, "7dce710a20f42ab687ec6ea83b53faaa418229ce0d5a2ff2a5e66defb0b65c03c9");
+        check_kat(256,
+            "5784e614d538f7f26c803191deb464a884817002988c36448dcbecfad1997fe51ab0b3853c51ed49ce9f4e477522fb\
+             3f32cc50515b753c18fb89a8d965afcf1ed5e099b22c4225732baeb986f5c5bc88e4582d27915e2a19126d3d4555fa\
+             b4f6516a6a156dbfeed9e982fc589e33ce2b9e1ba2b416e11852ddeab93025974267ac82c84f071c3d07f215f47e35\
+             65fd1d962c76e0d635892ea71488273765887d31f250a26c4ddc377ed89b17326e259f6cc1de0e63158e83aebb7f5a\
+             7c08c63c767876c8203639958a407acca096d1f606c04b4f4b3fd771781a5901b1c3cee7c04c3b6870226eee309b74\
+             f51edbf70a3817cc8da87875301e04d0416a65dc5d");
+    }
+
+    #[test]
+    fn test_blake2xs_unkeyed() {
+        let mut out = [0u8; 64];
+        Blake2xs::blake2xs(&mut out, b"", b"");
+        assert_eq!(out[..], hex_to_bytes(
+            "196bee41a64218a34de21aac89f3340cedc6dba67dd2ff572881e4306f2bb9f28d62f5181a2f67a6cf3f6f881789bb\
+             af01e630391fa80e01df3689ad32ecdb3f")[..]);
+    }
+
+    #[test]
+    fn test_blake2xs_unknown_length() {
+        let mut xof = Blake2xs::new(BLAKE2XS_UNKNOWN_LENGTH);
+        xof.input(b"abc");
+        let mut reader = xof.finalize_xof();
+
+        // The output doesn't depend on how it is split across reads
+        let mut out = [0u8; 100];
+        reader.read(&mut out[..1]);
+        reader.read(&mut out[1..70]);
+        reader.read(&mut out[70..]);
+        assert_eq!(out[..], hex_to_bytes(
+            "bf5c4f309fde8a62195bc8364ceea81e84eb9330579270c5737b9300085b61495576fef12a5cfa717343bff2bb2461\
+             d733fc71c0c51a60392e4d2f84218b1351e28d85cc8981eeffb4c8b952f91563f50ff8a4927a771832fe94208d0952\
+             0bd6b6b3fd31")[..]);
+    }
+
+    #[test]
+    fn test_blake2xs_reset() {
+        let mut xof = Blake2xs::new_keyed(200, b"key");
+        xof.input(b"some data");
+        xof.reset();
+        xof.input(b"other data");
+        let mut out1 = [0u8; 200];
+        xof.finalize_xof().read(&mut out1);
+
+        let mut out2 = [0u8; 200];
+        Blake2xs::blake2xs(&mut out2, b"other data", b"key");
+        assert_eq!(out1[..], out2[..]);
+    }
+
+    #[test]
+    fn test_blake2xs_empty_output() {
+        Blake2xs::blake2xs(&mut [], b"abc", b"");
+        Blake2xs::blake2xs(&mut [], b"abc", b"key");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_blake2xs_read_past_end() {
+        let mut reader = Blake2xs::new(10).finalize_xof();
+        let mut out = [0u8; 11];
+        reader.read(&mut out);
+    }
+}
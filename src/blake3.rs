@@ -0,0 +1,658 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the BLAKE3 hash function [1], in its three modes:
+ *
+ * * `Blake3::new`, the regular hash function
+ * * `Blake3::new_keyed`, a keyed hash with a 32 byte key, usable as a `Mac`
+ * * `Blake3::new_derive_key`, key derivation with an application specific context string
+ *
+ * The message is split into chunks of 1024 bytes, which are the leaves of a binary Merkle tree.
+ * The hasher keeps a stack of the chaining values of the completed subtrees, so that it handles
+ * inputs of any length, up to 2^64 bytes, in constant memory. When an input spans several whole
+ * chunks, they are compressed four at a time with one chunk in each lane of the `u32x4` vectors of
+ * the `simd` module.
+ *
+ * `Blake3` is a `Digest` with an output of 32 bytes, and also an extendable output function. As
+ * those traits and `Mac` all have `input` and `reset` methods, the type has inherent versions of
+ * them too.
+ *
+ * # Usage
+ *
+ * ```rust
+ * use self::crypto::blake3::Blake3;
+ * use self::crypto::digest::{Digest, ExtendableOutput, XofReader};
+ *
+ * let mut hasher = Blake3::new();
+ * hasher.input(b"hello world");
+ * let hash = hasher.result_str();
+ *
+ * let mut kdf = Blake3::new_derive_key("example.com 2024-01-01 session keys");
+ * kdf.input(b"input key material");
+ * let mut keys = [0u8; 64];
+ * kdf.finalize_xof().read(&mut keys);
+ * ```
+ *
+ * # References
+ * [1] - J. O'Connor, J.-P. Aumasson, S. Neves, Z. Wilcox-O'Hearn. BLAKE3: one function, fast
+ *       everywhere. <https://github.com/BLAKE3-team/BLAKE3-specs/blob/master/blake3.pdf>
+ */
+
+use std::cmp;
+
+use crate::cryptoutil::{read_u32_le, read_u32v_le, write_u32v_le};
+use crate::digest::{Digest, ExtendableOutput, XofReader};
+use crate::mac::{Mac, MacResult};
+use crate::simd::u32x4;
+
+const OUT_LEN: usize = 32;
+const KEY_LEN: usize = 32;
+const BLOCK_LEN: usize = 64;
+const CHUNK_LEN: usize = 1024;
+
+// The number of chunks compressed together, one in each lane of a `u32x4`
+const SIMD_DEGREE: usize = 4;
+
+// A chaining value for each level of a tree of 2^54 chunks, which is 2^64 bytes of input
+const MAX_DEPTH: usize = 54;
+
+const CHUNK_START: u32 = 1 << 0;
+const CHUNK_END: u32 = 1 << 1;
+const PARENT: u32 = 1 << 2;
+const ROOT: u32 = 1 << 3;
+const KEYED_HASH: u32 = 1 << 4;
+const DERIVE_KEY_CONTEXT: u32 = 1 << 5;
+const DERIVE_KEY_MATERIAL: u32 = 1 << 6;
+
+static IV : [u32; 8] = [
+  0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A,
+  0x510E527F, 0x9B05688C, 0x1F83D9AB, 0x5BE0CD19,
+];
+
+// The message permutation, applied once per round
+static MSG_SCHEDULE : [[usize; 16]; 7] = [
+  [  0,  1,  2,  3,  4,  5,  6,  7,  8,  9, 10, 11, 12, 13, 14, 15 ],
+  [  2,  6,  3, 10,  7,  0,  4, 13,  1, 11, 12,  5,  9, 14, 15,  8 ],
+  [  3,  4, 10, 12, 13,  2,  7, 14,  6,  5,  9,  0, 11, 15,  8,  1 ],
+  [ 10,  7, 12,  9, 14,  3, 13, 15,  4,  0, 11,  2,  5,  8,  1,  6 ],
+  [ 12, 13,  9, 11, 15, 10, 14,  8,  7,  2,  5,  3,  0,  1,  6,  4 ],
+  [  9, 14, 11,  5,  8, 12, 15,  1, 13,  3,  0, 10,  2,  6,  4,  7 ],
+  [ 11, 15,  5,  0,  1,  9,  8,  6, 14, 10,  2, 12,  3,  4,  7, 13 ],
+];
+
+macro_rules! round( ($g:ident, $s:expr, $v:expr, $m:expr) => ( {
+    let s = $s;
+    $g($v, 0, 4,  8, 12, $m[s[ 0]], $m[s[ 1]]);
+    $g($v, 1, 5,  9, 13, $m[s[ 2]], $m[s[ 3]]);
+    $g($v, 2, 6, 10, 14, $m[s[ 4]], $m[s[ 5]]);
+    $g($v, 3, 7, 11, 15, $m[s[ 6]], $m[s[ 7]]);
+    $g($v, 0, 5, 10, 15, $m[s[ 8]], $m[s[ 9]]);
+    $g($v, 1, 6, 11, 12, $m[s[10]], $m[s[11]]);
+    $g($v, 2, 7,  8, 13, $m[s[12]], $m[s[13]]);
+    $g($v, 3, 4,  9, 14, $m[s[14]], $m[s[15]]);
+  }
+));
+
+fn g(v: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize, x: u32, y: u32) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(12);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(8);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(7);
+}
+
+fn compress(cv: &[u32; 8], block: &[u32; 16], counter: u64, block_len: u32, flags: u32)
+        -> [u32; 16] {
+    let mut v = [
+        cv[0], cv[1], cv[2], cv[3], cv[4], cv[5], cv[6], cv[7],
+        IV[0], IV[1], IV[2], IV[3], counter as u32, (counter >> 32) as u32, block_len, flags,
+    ];
+    for s in MSG_SCHEDULE.iter() {
+        round!(g, s, &mut v, block);
+    }
+    for (i, cv_word) in cv.iter().enumerate() {
+        v[i] ^= v[i + 8];
+        v[i + 8] ^= cv_word;
+    }
+    v
+}
+
+fn first_8_words(words: &[u32; 16]) -> [u32; 8] {
+    let mut cv = [0u32; 8];
+    cv.copy_from_slice(&words[..8]);
+    cv
+}
+
+fn rotr4(x: u32x4, n: usize) -> u32x4 {
+    (x >> n) | (x << (32 - n))
+}
+
+fn splat(x: u32) -> u32x4 {
+    u32x4(x, x, x, x)
+}
+
+fn g4(v: &mut [u32x4; 16], a: usize, b: usize, c: usize, d: usize, x: u32x4, y: u32x4) {
+    v[a] = v[a] + v[b] + x;
+    v[d] = rotr4(v[d] ^ v[a], 16);
+    v[c] = v[c] + v[d];
+    v[b] = rotr4(v[b] ^ v[c], 12);
+    v[a] = v[a] + v[b] + y;
+    v[d] = rotr4(v[d] ^ v[a], 8);
+    v[c] = v[c] + v[d];
+    v[b] = rotr4(v[b] ^ v[c], 7);
+}
+
+// Compresses the 4 whole chunks of `chunks` at once, the first of them with the counter `counter`,
+// and returns their chaining values. None of them may be the root of the tree.
+fn compress_chunks4(chunks: &[u8], key: &[u32; 8], counter: u64, flags: u32)
+        -> [[u32; 8]; SIMD_DEGREE] {
+    assert!(chunks.len() == SIMD_DEGREE * CHUNK_LEN);
+
+    let lane_word = |lane: usize, offset: usize| {
+        read_u32_le(&chunks[lane * CHUNK_LEN + offset..lane * CHUNK_LEN + offset + 4])
+    };
+    let counter_lanes = |shift: u32| {
+        let lane = |i: u64| (counter.wrapping_add(i) >> shift) as u32;
+        u32x4(lane(0), lane(1), lane(2), lane(3))
+    };
+
+    let mut cv = [splat(0); 8];
+    for (cv_word, key_word) in cv.iter_mut().zip(key.iter()) {
+        *cv_word = splat(*key_word);
+    }
+
+    for block in 0..CHUNK_LEN / BLOCK_LEN {
+        let mut m = [splat(0); 16];
+        for (i, word) in m.iter_mut().enumerate() {
+            let offset = block * BLOCK_LEN + 4 * i;
+            *word = u32x4(lane_word(0, offset), lane_word(1, offset), lane_word(2, offset),
+                          lane_word(3, offset));
+        }
+
+        let mut block_flags = flags;
+        if block == 0 {
+            block_flags |= CHUNK_START;
+        }
+        if block == CHUNK_LEN / BLOCK_LEN - 1 {
+            block_flags |= CHUNK_END;
+        }
+
+        let mut v = [
+            cv[0], cv[1], cv[2], cv[3], cv[4], cv[5], cv[6], cv[7],
+            splat(IV[0]), splat(IV[1]), splat(IV[2]), splat(IV[3]),
+            counter_lanes(0), counter_lanes(32), splat(BLOCK_LEN as u32), splat(block_flags),
+        ];
+        for s in MSG_SCHEDULE.iter() {
+            round!(g4, s, &mut v, m);
+        }
+        for (i, cv_word) in cv.iter_mut().enumerate() {
+            *cv_word = v[i] ^ v[i + 8];
+        }
+    }
+
+    let mut cvs = [[0u32; 8]; SIMD_DEGREE];
+    for (i, &u32x4(w0, w1, w2, w3)) in cv.iter().enumerate() {
+        cvs[0][i] = w0;
+        cvs[1][i] = w1;
+        cvs[2][i] = w2;
+        cvs[3][i] = w3;
+    }
+    cvs
+}
+
+// The inputs of the compression of a node of the tree, which is either the chaining value of the
+// node or, for the root, the output of the hash
+#[derive(Copy, Clone)]
+struct Output {
+    input_cv: [u32; 8],
+    block: [u32; 16],
+    counter: u64,
+    block_len: u32,
+    flags: u32
+}
+
+impl Output {
+    fn chaining_value(&self) -> [u32; 8] {
+        let words = compress(&self.input_cv, &self.block, self.counter, self.block_len, self.flags);
+        first_8_words(&words)
+    }
+
+    fn root_output_block(&self, output_block_counter: u64, out: &mut [u8; BLOCK_LEN]) {
+        let words = compress(&self.input_cv, &self.block, output_block_counter, self.block_len,
+                             self.flags | ROOT);
+        write_u32v_le(out, &words);
+    }
+}
+
+fn parent_output(left_cv: &[u32; 8], right_cv: &[u32; 8], key: &[u32; 8], flags: u32) -> Output {
+    let mut block = [0u32; 16];
+    block[..8].copy_from_slice(left_cv);
+    block[8..].copy_from_slice(right_cv);
+    Output {
+        input_cv: *key,
+        block,
+        counter: 0,
+        block_len: BLOCK_LEN as u32,
+        flags: flags | PARENT
+    }
+}
+
+// The state of the chunk being hashed
+#[derive(Copy, Clone)]
+struct ChunkState {
+    cv: [u32; 8],
+    chunk_counter: u64,
+    block: [u8; BLOCK_LEN],
+    block_len: usize,
+    blocks_compressed: usize,
+    flags: u32
+}
+
+impl ChunkState {
+    fn new(key: &[u32; 8], chunk_counter: u64, flags: u32) -> ChunkState {
+        ChunkState {
+            cv: *key,
+            chunk_counter,
+            block: [0; BLOCK_LEN],
+            block_len: 0,
+            blocks_compressed: 0,
+            flags
+        }
+    }
+
+    fn len(&self) -> usize {
+        BLOCK_LEN * self.blocks_compressed + self.block_len
+    }
+
+    fn start_flag(&self) -> u32 {
+        if self.blocks_compressed == 0 { CHUNK_START } else { 0 }
+    }
+
+    fn update(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            // The last block of the chunk is compressed by `output`, as it has more flags
+            if self.block_len == BLOCK_LEN {
+                let mut block = [0u32; 16];
+                read_u32v_le(&mut block, &self.block);
+                let flags = self.flags | self.start_flag();
+                let words = compress(&self.cv, &block, self.chunk_counter, BLOCK_LEN as u32, flags);
+                self.cv = first_8_words(&words);
+                self.blocks_compressed += 1;
+                self.block = [0; BLOCK_LEN];
+                self.block_len = 0;
+            }
+
+            let n = cmp::min(BLOCK_LEN - self.block_len, input.len());
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&input[..n]);
+            self.block_len += n;
+            input = &input[n..];
+        }
+    }
+
+    fn output(&self) -> Output {
+        let mut block = [0u32; 16];
+        read_u32v_le(&mut block, &self.block);
+        Output {
+            input_cv: self.cv,
+            block,
+            counter: self.chunk_counter,
+            block_len: self.block_len as u32,
+            flags: self.flags | self.start_flag() | CHUNK_END
+        }
+    }
+}
+
+/// BLAKE3 state, in one of the regular hash, keyed hash or key derivation modes. It is both a
+/// `Digest` with a 32 byte output and an `ExtendableOutput`.
+#[derive(Copy, Clone)]
+pub struct Blake3 {
+    key: [u32; 8],
+    chunk_state: ChunkState,
+    // The chaining values of the completed subtrees, largest first
+    cv_stack: [[u32; 8]; MAX_DEPTH],
+    cv_stack_len: usize,
+    flags: u32
+}
+
+impl Blake3 {
+    fn init(key: [u32; 8], flags: u32) -> Blake3 {
+        Blake3 {
+            key,
+            chunk_state: ChunkState::new(&key, 0, flags),
+            cv_stack: [[0; 8]; MAX_DEPTH],
+            cv_stack_len: 0,
+            flags
+        }
+    }
+
+    /// Create a BLAKE3 instance in the regular hash mode.
+    pub fn new() -> Blake3 {
+        Blake3::init(IV, 0)
+    }
+
+    /// Create a BLAKE3 instance in the keyed hash mode, with a key of 32 bytes.
+    pub fn new_keyed(key: &[u8]) -> Blake3 {
+        assert!(key.len() == KEY_LEN, "BLAKE3 keys are 32 bytes");
+        let mut key_words = [0u32; 8];
+        read_u32v_le(&mut key_words, key);
+        Blake3::init(key_words, KEYED_HASH)
+    }
+
+    /// Create a BLAKE3 instance in the key derivation mode. The input is the key material, and
+    /// `context` should be a hardcoded string, unique to the application and to the purpose of
+    /// the derived key.
+    pub fn new_derive_key(context: &str) -> Blake3 {
+        let mut context_hasher = Blake3::init(IV, DERIVE_KEY_CONTEXT);
+        context_hasher.input(context.as_bytes());
+        let mut context_key = [0u8; KEY_LEN];
+        context_hasher.finalize_xof().read(&mut context_key);
+
+        let mut key_words = [0u32; 8];
+        read_u32v_le(&mut key_words, &context_key);
+        Blake3::init(key_words, DERIVE_KEY_MATERIAL)
+    }
+
+    /// Absorb `input`, which may be split across any number of calls.
+    pub fn input(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            // A full chunk is only completed once more input follows, as it is the root of the
+            // tree if the input ends there
+            if self.chunk_state.len() == CHUNK_LEN {
+                let chunk_cv = self.chunk_state.output().chaining_value();
+                let total_chunks = self.chunk_state.chunk_counter + 1;
+                self.add_chunk_chaining_value(chunk_cv, total_chunks);
+                self.chunk_state = ChunkState::new(&self.key, total_chunks, self.flags);
+            }
+
+            // For the same reason, whole chunks are only compressed together if more input
+            // follows them
+            if self.chunk_state.len() == 0 && input.len() > SIMD_DEGREE * CHUNK_LEN {
+                let counter = self.chunk_state.chunk_counter;
+                let cvs = compress_chunks4(&input[..SIMD_DEGREE * CHUNK_LEN], &self.key, counter,
+                                           self.flags);
+                for (i, cv) in cvs.iter().enumerate() {
+                    self.add_chunk_chaining_value(*cv, counter + i as u64 + 1);
+                }
+                self.chunk_state = ChunkState::new(&self.key, counter + SIMD_DEGREE as u64,
+                                                   self.flags);
+                input = &input[SIMD_DEGREE * CHUNK_LEN..];
+                continue;
+            }
+
+            let n = cmp::min(CHUNK_LEN - self.chunk_state.len(), input.len());
+            self.chunk_state.update(&input[..n]);
+            input = &input[n..];
+        }
+    }
+
+    /// Discard the input, keeping the mode and the key.
+    pub fn reset(&mut self) {
+        self.chunk_state = ChunkState::new(&self.key, 0, self.flags);
+        self.cv_stack_len = 0;
+    }
+
+    // Pushes the chaining value of a chunk, after merging it with the completed subtrees of the
+    // same size: one for each trailing zero bit of the number of chunks
+    fn add_chunk_chaining_value(&mut self, mut new_cv: [u32; 8], mut total_chunks: u64) {
+        while total_chunks & 1 == 0 {
+            self.cv_stack_len -= 1;
+            new_cv = parent_output(&self.cv_stack[self.cv_stack_len], &new_cv, &self.key,
+                                   self.flags).chaining_value();
+            total_chunks >>= 1;
+        }
+        self.cv_stack[self.cv_stack_len] = new_cv;
+        self.cv_stack_len += 1;
+    }
+
+    fn final_output(&self) -> Output {
+        let mut output = self.chunk_state.output();
+        for cv in self.cv_stack[..self.cv_stack_len].iter().rev() {
+            output = parent_output(cv, &output.chaining_value(), &self.key, self.flags);
+        }
+        output
+    }
+
+    /// Hash `input` in the regular mode, with an output of any length.
+    pub fn blake3(out: &mut [u8], input: &[u8]) {
+        let mut hasher = Blake3::new();
+        hasher.input(input);
+        hasher.finalize_xof().read(out);
+    }
+
+    /// Derive a key of any length from `key_material`, with the context string `context`.
+    pub fn derive_key(context: &str, key_material: &[u8], out: &mut [u8]) {
+        let mut hasher = Blake3::new_derive_key(context);
+        hasher.input(key_material);
+        hasher.finalize_xof().read(out);
+    }
+}
+
+impl Default for Blake3 {
+    fn default() -> Blake3 {
+        Blake3::new()
+    }
+}
+
+impl ExtendableOutput for Blake3 {
+    type Reader = Blake3Reader;
+
+    fn input(&mut self, data: &[u8]) {
+        Blake3::input(self, data);
+    }
+
+    fn finalize_xof(self) -> Blake3Reader {
+        Blake3Reader {
+            output: self.final_output(),
+            pos: 0,
+            block: [0; BLOCK_LEN]
+        }
+    }
+
+    fn reset(&mut self) {
+        Blake3::reset(self);
+    }
+}
+
+impl Digest for Blake3 {
+    fn input(&mut self, data: &[u8]) {
+        Blake3::input(self, data);
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        self.finalize_xof().read(&mut out[..OUT_LEN]);
+    }
+
+    fn reset(&mut self) {
+        Blake3::reset(self);
+    }
+
+    fn output_bits(&self) -> usize {
+        8 * OUT_LEN
+    }
+
+    fn block_size(&self) -> usize {
+        BLOCK_LEN
+    }
+}
+
+impl Mac for Blake3 {
+    fn input(&mut self, data: &[u8]) {
+        Blake3::input(self, data);
+    }
+
+    fn reset(&mut self) {
+        Blake3::reset(self);
+    }
+
+    fn result(&mut self) -> MacResult {
+        let mut mac = vec![0u8; OUT_LEN];
+        self.raw_result(&mut mac);
+        MacResult::new_from_owned(mac)
+    }
+
+    fn raw_result(&mut self, output: &mut [u8]) {
+        self.finalize_xof().read(&mut output[..OUT_LEN]);
+    }
+
+    fn output_bytes(&self) -> usize {
+        OUT_LEN
+    }
+}
+
+/// Reads the output of BLAKE3
+#[derive(Copy, Clone)]
+pub struct Blake3Reader {
+    output: Output,
+    // The number of bytes read so far
+    pos: u64,
+    // The current block of output
+    block: [u8; BLOCK_LEN]
+}
+
+impl XofReader for Blake3Reader {
+    fn read(&mut self, out: &mut [u8]) {
+        let mut written = 0;
+        while written < out.len() {
+            let offset = (self.pos % BLOCK_LEN as u64) as usize;
+            if offset == 0 {
+                self.output.root_output_block(self.pos / BLOCK_LEN as u64, &mut self.block);
+            }
+            let n = cmp::min(BLOCK_LEN - offset, out.len() - written);
+            out[written..written + n].copy_from_slice(&self.block[offset..offset + n]);
+            written += n;
+            self.pos += n as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blake3::Blake3;
+    use crate::digest::{Digest, ExtendableOutput, XofReader};
+    use crate::mac::Mac;
+    use crate::serialize::hex::FromHex;
+    use crate::serialize::json::Json;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    fn test_input(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn check(mut hasher: Blake3, input: &[u8], expected: &[u8]) {
+        // The whole input at once, which compresses whole chunks together
+        let mut all_at_once = hasher;
+        all_at_once.input(input);
+
+        // One chunk at a time, which doesn't
+        for chunk in input.chunks(1024) {
+            hasher.input(chunk);
+        }
+
+        for h in [all_at_once, hasher].iter_mut() {
+            let mut out = vec![0u8; expected.len()];
+            h.finalize_xof().read(&mut out);
+            assert_eq!(out, expected);
+
+            let mut digest = [0u8; 32];
+            Digest::result(h, &mut digest);
+            assert_eq!(digest[..], expected[..32]);
+        }
+    }
+
+    // The test vectors of the reference implementation, with the output of each mode extended to
+    // 131 bytes
+    #[test]
+    fn test_vectors() {
+        let vectors = Json::from_str(include_str!("blake3_test_vectors.json")).unwrap();
+        let key = vectors["key"].as_string().unwrap().as_bytes();
+        let context = vectors["context_string"].as_string().unwrap();
+
+        for case in vectors["cases"].as_array().unwrap() {
+            let input = test_input(case["input_len"].as_u64().unwrap() as usize);
+            check(Blake3::new(), &input, &hex_to_bytes(case["hash"].as_string().unwrap()));
+            check(Blake3::new_keyed(key), &input,
+                  &hex_to_bytes(case["keyed_hash"].as_string().unwrap()));
+            check(Blake3::new_derive_key(context), &input,
+                  &hex_to_bytes(case["derive_key"].as_string().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_split_reads() {
+        let mut reader = Blake3::new().finalize_xof();
+        let mut expected = [0u8; 200];
+        reader.clone().read(&mut expected);
+
+        let mut out = [0u8; 200];
+        reader.read(&mut out[..1]);
+        reader.read(&mut out[1..64]);
+        reader.read(&mut out[64..130]);
+        reader.read(&mut out[130..]);
+        assert_eq!(out[..], expected[..]);
+    }
+
+    #[test]
+    fn test_reset() {
+        let input = test_input(10000);
+        let mut hasher = Blake3::new_keyed(&[7u8; 32]);
+        hasher.input(&input);
+        let expected = Mac::result(&mut hasher);
+
+        hasher.reset();
+        hasher.input(b"some other input");
+        hasher.reset();
+        hasher.input(&input);
+        assert!(Mac::result(&mut hasher) == expected);
+    }
+
+    #[test]
+    fn test_derive_key() {
+        let mut out = [0u8; 32];
+        Blake3::derive_key("BLAKE3 2019-12-27 16:29:52 test vectors context", b"", &mut out);
+        assert_eq!(out[..], hex_to_bytes(
+            "2cc39783c223154fea8dfb7c1b1660f2ac2dcbd1c1de8277b0b0dd39b7e50d7d")[..]);
+    }
+
+    #[test]
+    #[should_panic(expected = "BLAKE3 keys are 32 bytes")]
+    fn test_short_key() {
+        Blake3::new_keyed(&[7u8; 16]);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::blake3::Blake3;
+
+    #[bench]
+    pub fn blake3_1k(bh: & mut Bencher) {
+        let mut sh = Blake3::new();
+        let bytes = [1u8; 1024];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn blake3_64k(bh: & mut Bencher) {
+        let mut sh = Blake3::new();
+        let bytes = [1u8; 65536];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+}
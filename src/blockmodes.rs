@@ -16,8 +16,9 @@ use crate::buffer::{ReadBuffer, WriteBuffer, OwnedReadBuffer, OwnedWriteBuffer,
 use crate::buffer::BufferResult::{BufferUnderflow, BufferOverflow};
 use crate::cryptoutil::{self, symm_enc_or_dec};
 use crate::symmetriccipher::{BlockEncryptor, BlockEncryptorX8, Encryptor, BlockDecryptor, Decryptor,
-    SynchronousStreamCipher, SymmetricCipherError};
-use crate::symmetriccipher::SymmetricCipherError::{InvalidPadding, InvalidLength};
+    SeekableStreamCipher, SynchronousStreamCipher, SymmetricCipherError, TweakableBlockEncryptor,
+    TweakableBlockDecryptor};
+use crate::symmetriccipher::SymmetricCipherError::{CounterOverflow, InvalidPadding, InvalidLength};
 
 /// The `BlockProcessor` trait is used to implement modes that require processing complete blocks of
 /// data. The methods of this trait are called by the `BlockEngine` which is in charge of properly
@@ -328,11 +329,7 @@ impl <P: BlockProcessor, X: PaddingProcessor> BlockEngine<P, X> {
                         self.padding.pad_input(&mut self.in_scratch);
                         if self.in_scratch.is_full() {
                             process_scratch(self);
-                            if self.padding.strip_output(self.out_read_scratch.as_mut().unwrap()) {
-                                self.state = BlockEngineState::Finished;
-                            } else {
-                                self.state = BlockEngineState::Error(InvalidPadding);
-                            }
+                            self.state = self.strip_padding();
                         } else if self.in_scratch.is_empty() {
                             self.state = BlockEngineState::Finished;
                         } else {
@@ -344,11 +341,7 @@ impl <P: BlockProcessor, X: PaddingProcessor> BlockEngine<P, X> {
                         if self.in_scratch.is_full() {
                             self.state = BlockEngineState::LastInput2;
                         } else if self.in_scratch.is_empty() {
-                            if self.padding.strip_output(self.out_read_scratch.as_mut().unwrap()) {
-                                self.state = BlockEngineState::Finished;
-                            } else {
-                                self.state = BlockEngineState::Error(InvalidPadding);
-                            }
+                            self.state = self.strip_padding();
                         } else {
                             self.state = BlockEngineState::Error(InvalidLength);
                         }
@@ -364,11 +357,7 @@ impl <P: BlockProcessor, X: PaddingProcessor> BlockEngine<P, X> {
                     if rout.is_empty() {
                         self.out_write_scratch = Some(rout.into_write_buffer());
                         process_scratch(self);
-                        if self.padding.strip_output(self.out_read_scratch.as_mut().unwrap()) {
-                            self.state = BlockEngineState::Finished;
-                        } else {
-                            self.state = BlockEngineState::Error(InvalidPadding);
-                        }
+                        self.state = self.strip_padding();
                     } else {
                         self.out_read_scratch = Some(rout);
                         return Ok(BufferOverflow);
@@ -398,6 +387,21 @@ impl <P: BlockProcessor, X: PaddingProcessor> BlockEngine<P, X> {
             }
         }
     }
+    /// Strip the padding from the final block, which must be in `out_read_scratch`, and return the
+    /// state to transition to. If the padding is invalid, the decrypted block is wiped before the
+    /// error is reported so that none of it can be observed by the caller.
+    fn strip_padding(&mut self) -> BlockEngineState {
+        if self.padding.strip_output(self.out_read_scratch.as_mut().unwrap()) {
+            BlockEngineState::Finished
+        } else {
+            let mut wout = self.out_read_scratch.take().unwrap().into_write_buffer();
+            cryptoutil::zero(wout.take_remaining());
+            wout.reset();
+            self.out_write_scratch = Some(wout);
+            BlockEngineState::Error(InvalidPadding)
+        }
+    }
+
     fn reset(&mut self) {
         self.state = BlockEngineState::FastMode;
         self.in_scratch.reset();
@@ -426,9 +430,19 @@ impl PaddingProcessor for NoPadding {
 }
 
 /// PKCS padding mode for ECB and CBC encryption
+///
+/// The padding is validated in constant time, so the time taken to strip it doesn't depend on
+/// where (or whether) it is malformed. Note that unauthenticated CBC decryption is still malleable;
+/// if the ciphertext may come from an attacker, authenticate it first, for example with
+/// `aes_cbc_hmac_sha2`.
 #[derive(Clone, Copy)]
 pub struct PkcsPadding;
 
+/// Returns 0xff if `a < b` and 0 otherwise, without branching. Both values must be less than 2^31.
+fn ct_lt_mask(a: u32, b: u32) -> u8 {
+    0u8.wrapping_sub((a.wrapping_sub(b) >> 31) as u8)
+}
+
 // This class implements both encryption padding, where padding is added, and decryption padding,
 // where padding is stripped. Since BlockEngine doesn't know if its an Encryption or Decryption
 // operation, it will call both methods if given a chance. So, this class can't be passed directly
@@ -445,16 +459,23 @@ impl PaddingProcessor for PkcsPadding {
         }
     }
     fn strip_output<R: ReadBuffer>(&mut self, output_buffer: &mut R) -> bool {
+        // Every byte of the block is examined, whatever the value of the last byte, and failures
+        // are accumulated into a mask instead of returning early.
         let last_byte: u8;
+        let mut bad: u8;
         {
             let data = output_buffer.peek_remaining();
             last_byte = *data.last().unwrap();
-            for &x in data.iter().rev().take(last_byte as usize) {
-                if x != last_byte {
-                    return false;
-                }
+            let pad_len = last_byte as u32;
+            // The padding length must be in the range [1, block size].
+            bad = ct_lt_mask(pad_len, 1) | ct_lt_mask(data.len() as u32, pad_len);
+            for (i, &x) in data.iter().rev().enumerate() {
+                bad |= ct_lt_mask(i as u32, pad_len) & (x ^ last_byte);
             }
         }
+        if bad != 0 {
+            return false;
+        }
         output_buffer.truncate(last_byte as usize);
         true
     }
@@ -661,48 +682,146 @@ impl <T: BlockDecryptor, X: PaddingProcessor> Decryptor for CbcDecryptor<T, X> {
     }
 }
 
-fn add_ctr(ctr: &mut [u8], mut ammount: u8) {
-    for i in ctr.iter_mut().rev() {
-        let prev = *i;
-        *i = i.wrapping_add(ammount);
-        if *i >= prev {
-            break;
+/// Byte order of the counter in a CTR mode counter block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterEndianness {
+    /// The counter is stored big endian in the last bytes of the block, as in GCM and SRTP
+    BigEndian,
+    /// The counter is stored little endian in the first bytes of the block
+    LittleEndian
+}
+
+/// Describes which part of the counter block CTR mode increments. The rest of the block stays
+/// fixed. Once the counter has taken all of its values, processing fails with `CounterOverflow`
+/// instead of wrapping around and reusing keystream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CounterPolicy {
+    width: usize,
+    endianness: CounterEndianness,
+    wrapping: bool
+}
+
+impl CounterPolicy {
+    /// Create a counter policy for a counter of `bits` bits, which must be 32, 64 or 128
+    pub fn new(bits: usize, endianness: CounterEndianness) -> CounterPolicy {
+        assert!(bits == 32 || bits == 64 || bits == 128, "Counter width must be 32, 64 or 128 bits!");
+        CounterPolicy {
+            width: bits / 8,
+            endianness,
+            wrapping: false
+        }
+    }
+
+    // The whole block is a big endian counter that wraps around to 0
+    pub(crate) fn wrapping(block_size: usize) -> CounterPolicy {
+        CounterPolicy {
+            width: block_size,
+            endianness: CounterEndianness::BigEndian,
+            wrapping: true
+        }
+    }
+
+    // Index in the counter block of the byte of the counter with significance k
+    fn index(&self, ctr: &[u8], k: usize) -> usize {
+        match self.endianness {
+            CounterEndianness::BigEndian => ctr.len() - 1 - k,
+            CounterEndianness::LittleEndian => k
+        }
+    }
+
+    // Add a number of blocks to the counter. Returns true if the counter ran past its maximum,
+    // which never happens for a wrapping counter.
+    fn add(&self, ctr: &mut [u8], blocks: u64) -> bool {
+        let mut carry = blocks;
+        for k in 0..self.width {
+            if carry == 0 {
+                break;
+            }
+            let i = self.index(ctr, k);
+            let sum = ctr[i] as u64 + (carry & 0xff);
+            ctr[i] = sum as u8;
+            carry = (carry >> 8) + (sum >> 8);
+        }
+        carry != 0 && !self.wrapping
+    }
+
+    // The number of blocks that can still be generated starting at the counter, saturated to the
+    // range of a u64
+    fn blocks_left(&self, ctr: &[u8]) -> u64 {
+        if self.wrapping {
+            return u64::MAX;
+        }
+        // The maximum minus the counter is the bitwise complement of the counter
+        let mut left = 0u64;
+        for k in 0..self.width {
+            let b = !ctr[self.index(ctr, k)];
+            if k >= 8 {
+                if b != 0 {
+                    return u64::MAX;
+                }
+            } else {
+                left |= (b as u64) << (8 * k);
+            }
         }
-        ammount = 1;
+        left.saturating_add(1)
+    }
+
+    fn check_block_size(&self, block_size: usize) {
+        assert!(self.width <= block_size, "Counter is wider than the blocksize!");
     }
 }
 
 /// CTR Mode
 pub struct CtrMode<A> {
     algo: A,
+    policy: CounterPolicy,
+    initial_ctr: Vec<u8>,
     ctr: Vec<u8>,
-    bytes: OwnedReadBuffer
+    exhausted: bool,
+    bytes: OwnedReadBuffer,
+    position: u64
 }
 
 impl <A: BlockEncryptor> CtrMode<A> {
-    /// Create a new CTR object
+    /// Create a new CTR object. The whole counter block is incremented as a big endian integer,
+    /// wrapping around to 0.
     pub fn new(algo: A, ctr: Vec<u8>) -> CtrMode<A> {
+        let policy = CounterPolicy::wrapping(algo.block_size());
+        CtrMode::new_with_policy(algo, ctr, policy)
+    }
+    /// Create a new CTR object that increments the counter as described by `policy`
+    pub fn new_with_policy(algo: A, ctr: Vec<u8>, policy: CounterPolicy) -> CtrMode<A> {
         let block_size = algo.block_size();
         assert!(ctr.len() == block_size, "Ctr length isn't equal to the blocksize!");
+        policy.check_block_size(block_size);
         CtrMode {
             algo: algo,
+            policy,
+            initial_ctr: ctr.clone(),
             ctr: ctr,
-            bytes: OwnedReadBuffer::new_with_len(repeat(0).take(block_size).collect(), 0)
+            exhausted: false,
+            bytes: OwnedReadBuffer::new_with_len(repeat(0).take(block_size).collect(), 0),
+            position: 0
         }
     }
     pub fn reset(&mut self, ctr: &[u8]) {
+        cryptoutil::copy_memory(ctr, &mut self.initial_ctr);
         cryptoutil::copy_memory(ctr, &mut self.ctr);
-        self.bytes.reset();
+        self.exhausted = false;
+        // Drop the keystream of the old counter
+        self.bytes.borrow_write_buffer();
+        self.position = 0;
     }
-    fn process(&mut self, input: &[u8], output: &mut [u8]) {
+    fn try_process(&mut self, input: &[u8], output: &mut [u8]) -> Result<(), SymmetricCipherError> {
         assert!(input.len() == output.len());
         let len = input.len();
+        if len as u64 > self.keystream_left() {
+            return Err(CounterOverflow);
+        }
         let mut i = 0;
         while i < len {
             if self.bytes.is_empty() {
-                let mut wb = self.bytes.borrow_write_buffer();
-                self.algo.encrypt_block(&self.ctr[..], wb.take_remaining());
-                add_ctr(&mut self.ctr, 1);
+                self.next_block();
             }
             let count = cmp::min(self.bytes.remaining(), len - i);
             let bytes_it = self.bytes.take_next(count).iter();
@@ -713,12 +832,52 @@ impl <A: BlockEncryptor> CtrMode<A> {
             }
             i += count;
         }
+        self.position = self.position.wrapping_add(len as u64);
+        Ok(())
+    }
+    fn keystream_left(&self) -> u64 {
+        let blocks = if self.exhausted { 0 } else { self.policy.blocks_left(&self.ctr) };
+        blocks.saturating_mul(self.algo.block_size() as u64).saturating_add(self.bytes.remaining() as u64)
+    }
+    fn next_block(&mut self) {
+        let mut wb = self.bytes.borrow_write_buffer();
+        self.algo.encrypt_block(&self.ctr[..], wb.take_remaining());
+        self.exhausted = self.policy.add(&mut self.ctr, 1);
+    }
+}
+
+impl <A: BlockEncryptor> SeekableStreamCipher for CtrMode<A> {
+    fn seek(&mut self, byte_offset: u64) -> Result<(), SymmetricCipherError> {
+        let block_size = self.algo.block_size() as u64;
+        let blocks = byte_offset / block_size;
+        let partial = (byte_offset % block_size) as usize;
+        let left = self.policy.blocks_left(&self.initial_ctr);
+        if blocks > left || (blocks == left && partial != 0) {
+            return Err(CounterOverflow);
+        }
+        cryptoutil::copy_memory(&self.initial_ctr, &mut self.ctr);
+        self.exhausted = self.policy.add(&mut self.ctr, blocks);
+        // Drop the buffered keystream, then refill it if the offset is within a block
+        self.bytes.borrow_write_buffer();
+        if partial != 0 {
+            self.next_block();
+            self.bytes.take_next(partial);
+        }
+        self.position = byte_offset;
+        Ok(())
+    }
+
+    fn position(&self) -> u64 {
+        self.position
     }
 }
 
 impl <A: BlockEncryptor> SynchronousStreamCipher for CtrMode<A> {
     fn process(&mut self, input: &[u8], output: &mut [u8]) {
-        self.process(input, output);
+        self.try_process(input, output).expect("counter is exhausted");
+    }
+    fn try_process(&mut self, input: &[u8], output: &mut [u8]) -> Result<(), SymmetricCipherError> {
+        self.try_process(input, output)
     }
 }
 
@@ -739,45 +898,58 @@ impl <A: BlockEncryptor> Decryptor for CtrMode<A> {
 /// CTR Mode that operates on 8 blocks at a time
 pub struct CtrModeX8<A> {
     algo: A,
+    policy: CounterPolicy,
+    initial_ctr: Vec<u8>,
+    ctr: Vec<u8>,
+    exhausted: bool,
     ctr_x8: Vec<u8>,
-    bytes: OwnedReadBuffer
-}
-
-fn construct_ctr_x8(in_ctr: &[u8], out_ctr_x8: &mut [u8]) {
-    for (i, ctr_i) in out_ctr_x8.chunks_mut(in_ctr.len()).enumerate() {
-        cryptoutil::copy_memory(in_ctr, ctr_i);
-        add_ctr(ctr_i, i as u8);
-    }
+    bytes: OwnedReadBuffer,
+    position: u64
 }
 
 impl <A: BlockEncryptorX8> CtrModeX8<A> {
-    /// Create a new CTR object that operates on 8 blocks at a time
+    /// Create a new CTR object that operates on 8 blocks at a time. The whole counter block is
+    /// incremented as a big endian integer, wrapping around to 0.
     pub fn new(algo: A, ctr: &[u8]) -> CtrModeX8<A> {
+        let policy = CounterPolicy::wrapping(algo.block_size());
+        CtrModeX8::new_with_policy(algo, ctr, policy)
+    }
+    /// Create a new CTR object that operates on 8 blocks at a time and increments the counter as
+    /// described by `policy`
+    pub fn new_with_policy(algo: A, ctr: &[u8], policy: CounterPolicy) -> CtrModeX8<A> {
         let block_size = algo.block_size();
-        let mut ctr_x8: Vec<u8> = repeat(0).take(block_size * 8).collect();
-        construct_ctr_x8(ctr, &mut ctr_x8);
+        assert!(ctr.len() == block_size, "Ctr length isn't equal to the blocksize!");
+        policy.check_block_size(block_size);
         CtrModeX8 {
             algo: algo,
-            ctr_x8: ctr_x8,
-            bytes: OwnedReadBuffer::new_with_len(repeat(0).take(block_size * 8).collect(), 0)
+            policy,
+            initial_ctr: ctr.to_vec(),
+            ctr: ctr.to_vec(),
+            exhausted: false,
+            ctr_x8: repeat(0).take(block_size * 8).collect(),
+            bytes: OwnedReadBuffer::new_with_len(repeat(0).take(block_size * 8).collect(), 0),
+            position: 0
         }
     }
     pub fn reset(&mut self, ctr: &[u8]) {
-        construct_ctr_x8(ctr, &mut self.ctr_x8);
-        self.bytes.reset();
+        cryptoutil::copy_memory(ctr, &mut self.initial_ctr);
+        cryptoutil::copy_memory(ctr, &mut self.ctr);
+        self.exhausted = false;
+        // Drop the keystream of the old counter
+        self.bytes.borrow_write_buffer();
+        self.position = 0;
     }
-    fn process(&mut self, input: &[u8], output: &mut [u8]) {
+    fn try_process(&mut self, input: &[u8], output: &mut [u8]) -> Result<(), SymmetricCipherError> {
         // TODO - Can some of this be combined with regular CtrMode?
         assert!(input.len() == output.len());
         let len = input.len();
+        if len as u64 > self.keystream_left() {
+            return Err(CounterOverflow);
+        }
         let mut i = 0;
         while i < len {
             if self.bytes.is_empty() {
-                let mut wb = self.bytes.borrow_write_buffer();
-                self.algo.encrypt_block_x8(&self.ctr_x8[..], wb.take_remaining());
-                for ctr_i in &mut self.ctr_x8.chunks_mut(self.algo.block_size()) {
-                    add_ctr(ctr_i, 8);
-                }
+                self.next_blocks();
             }
             let count = cmp::min(self.bytes.remaining(), len - i);
             let bytes_it = self.bytes.take_next(count).iter();
@@ -788,12 +960,64 @@ impl <A: BlockEncryptorX8> CtrModeX8<A> {
             }
             i += count;
         }
+        self.position = self.position.wrapping_add(len as u64);
+        Ok(())
+    }
+    fn keystream_left(&self) -> u64 {
+        let blocks = if self.exhausted { 0 } else { self.policy.blocks_left(&self.ctr) };
+        blocks.saturating_mul(self.algo.block_size() as u64).saturating_add(self.bytes.remaining() as u64)
+    }
+    fn next_blocks(&mut self) {
+        let block_size = self.algo.block_size();
+        for (i, ctr_i) in self.ctr_x8.chunks_mut(block_size).enumerate() {
+            cryptoutil::copy_memory(&self.ctr, ctr_i);
+            self.policy.add(ctr_i, i as u64);
+        }
+        // Blocks past the end of the counter are computed, but never used
+        let valid = cmp::min(self.policy.blocks_left(&self.ctr), 8) as usize;
+        {
+            let mut wb = self.bytes.borrow_write_buffer();
+            self.algo.encrypt_block_x8(&self.ctr_x8[..], wb.take_remaining());
+        }
+        self.bytes.truncate((8 - valid) * block_size);
+        self.exhausted = self.policy.add(&mut self.ctr, 8);
+    }
+}
+
+impl <A: BlockEncryptorX8> SeekableStreamCipher for CtrModeX8<A> {
+    fn seek(&mut self, byte_offset: u64) -> Result<(), SymmetricCipherError> {
+        let block_size = self.algo.block_size() as u64;
+        let blocks = byte_offset / block_size;
+        let left = self.policy.blocks_left(&self.initial_ctr);
+        let partial = (byte_offset % block_size) as usize;
+        if blocks > left || (blocks == left && partial != 0) {
+            return Err(CounterOverflow);
+        }
+        let chunk_size = block_size * 8;
+        cryptoutil::copy_memory(&self.initial_ctr, &mut self.ctr);
+        self.exhausted = self.policy.add(&mut self.ctr, byte_offset / chunk_size * 8);
+        // Drop the buffered keystream, then refill it if the offset is within the 8 blocks
+        self.bytes.borrow_write_buffer();
+        let chunk_partial = (byte_offset % chunk_size) as usize;
+        if chunk_partial != 0 {
+            self.next_blocks();
+            self.bytes.take_next(chunk_partial);
+        }
+        self.position = byte_offset;
+        Ok(())
+    }
+
+    fn position(&self) -> u64 {
+        self.position
     }
 }
 
 impl <A: BlockEncryptorX8> SynchronousStreamCipher for CtrModeX8<A> {
     fn process(&mut self, input: &[u8], output: &mut [u8]) {
-        self.process(input, output);
+        self.try_process(input, output).expect("counter is exhausted");
+    }
+    fn try_process(&mut self, input: &[u8], output: &mut [u8]) -> Result<(), SymmetricCipherError> {
+        self.try_process(input, output)
     }
 }
 
@@ -811,17 +1035,75 @@ impl <A: BlockEncryptorX8> Decryptor for CtrModeX8<A> {
     }
 }
 
+/// Sector mode for storage encryption with a tweakable block cipher that takes the tweak natively,
+/// such as Threefish. Every block is encrypted independently, with the raw block position as the
+/// tweak: the block index within the sector, as a little endian u64, followed by the sector number,
+/// as a little endian u64. Any remaining bytes of the tweak are zero. Unlike LRW or XEX, which turn
+/// an ordinary block cipher into a tweakable one, no masks are derived or applied.
+pub struct NativeTweakMode<A> {
+    algo: A,
+}
+
+impl <A> NativeTweakMode<A> {
+    /// Create a new native tweak mode object
+    pub fn new(algo: A) -> NativeTweakMode<A> {
+        NativeTweakMode { algo }
+    }
+}
+
+// A zeroed tweak buffer, with the sector number set
+fn sector_tweak(tweak_size: usize, sector: u64) -> Vec<u8> {
+    assert!(tweak_size >= 16, "Tweak size should be at least 16 bytes!");
+    let mut tweak = vec![0u8; tweak_size];
+    cryptoutil::write_u64_le(&mut tweak[8..16], sector);
+    tweak
+}
+
+impl <A: TweakableBlockEncryptor> NativeTweakMode<A> {
+    /// Encrypt the data of one sector. The length has to be a multiple of the block size.
+    pub fn encrypt_sector(&self, sector: u64, input: &[u8], output: &mut [u8]) {
+        let block_size = self.algo.block_size();
+        assert!(input.len() == output.len());
+        assert_eq!(input.len() % block_size, 0, "Input isn't a multiple of the blocksize!");
+        let mut tweak = sector_tweak(self.algo.tweak_size(), sector);
+        for (block, (in_block, out_block)) in
+                input.chunks(block_size).zip(output.chunks_mut(block_size)).enumerate() {
+            cryptoutil::write_u64_le(&mut tweak[..8], block as u64);
+            self.algo.encrypt_block_with_tweak(&tweak, in_block, out_block);
+        }
+    }
+}
+
+impl <A: TweakableBlockDecryptor> NativeTweakMode<A> {
+    /// Decrypt the data of one sector. The length has to be a multiple of the block size.
+    pub fn decrypt_sector(&self, sector: u64, input: &[u8], output: &mut [u8]) {
+        let block_size = self.algo.block_size();
+        assert!(input.len() == output.len());
+        assert_eq!(input.len() % block_size, 0, "Input isn't a multiple of the blocksize!");
+        let mut tweak = sector_tweak(self.algo.tweak_size(), sector);
+        for (block, (in_block, out_block)) in
+                input.chunks(block_size).zip(output.chunks_mut(block_size)).enumerate() {
+            cryptoutil::write_u64_le(&mut tweak[..8], block as u64);
+            self.algo.decrypt_block_with_tweak(&tweak, in_block, out_block);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::iter::repeat;
 
+    use crate::aes;
     use crate::aessafe;
     use crate::blockmodes::{EcbEncryptor, EcbDecryptor, CbcEncryptor, CbcDecryptor, CtrMode, CtrModeX8,
-        NoPadding, PkcsPadding};
+        NoPadding, PkcsPadding, NativeTweakMode, CounterPolicy, CounterEndianness};
     use crate::buffer::{ReadBuffer, WriteBuffer, RefReadBuffer, RefWriteBuffer, BufferResult};
     use crate::buffer::BufferResult::{BufferUnderflow, BufferOverflow};
-    use crate::symmetriccipher::{Encryptor, Decryptor};
-    use crate::symmetriccipher::SymmetricCipherError::{self, InvalidLength, InvalidPadding};
+    use crate::symmetriccipher::{BlockEncryptor, Encryptor, Decryptor, SeekableStreamCipher,
+        SynchronousStreamCipher};
+    use crate::symmetriccipher::SymmetricCipherError::{self, CounterOverflow, InvalidLength,
+        InvalidPadding};
+    use crate::threefish::Threefish256;
 
     use std::cmp;
 
@@ -1091,7 +1373,8 @@ mod test {
                     out_pos += tmp_out.position();
                 }
                 Err(InvalidPadding) => panic!("Invalid Padding"),
-                Err(InvalidLength) => panic!("Invalid Length")
+                Err(InvalidLength) => panic!("Invalid Length"),
+                Err(CounterOverflow) => panic!("Counter Overflow")
             }
         }
 
@@ -1115,7 +1398,8 @@ mod test {
                     out_pos += tmp_out.position();
                 }
                 Err(InvalidPadding) => panic!("Invalid Padding"),
-                Err(InvalidLength) => panic!("Invalid Length")
+                Err(InvalidLength) => panic!("Invalid Length"),
+                Err(CounterOverflow) => panic!("Counter Overflow")
             }
         }
     }
@@ -1288,6 +1572,51 @@ mod test {
         }
     }
 
+    #[test]
+    fn aes_cbc_pkcs_padding_invalid() {
+        let key = [1u8; 16];
+        let iv = [3u8; 16];
+        let mut bad_last_blocks: Vec<Vec<u8>> = Vec::new();
+        // A zero padding length
+        bad_last_blocks.push(vec![0; 16]);
+        // A padding length longer than the block
+        bad_last_blocks.push(vec![17; 16]);
+        bad_last_blocks.push(vec![0xff; 16]);
+        // Padding bytes that don't match the padding length
+        let mut block: Vec<u8> = vec![4; 16];
+        block[12] = 5;
+        bad_last_blocks.push(block);
+        let mut block: Vec<u8> = vec![16; 16];
+        block[0] = 0;
+        bad_last_blocks.push(block);
+
+        for last_block in bad_last_blocks.iter() {
+            let mut plain: Vec<u8> = vec![2; 16];
+            plain.extend_from_slice(&last_block[..]);
+
+            let mut cipher: Vec<u8> = vec![0; 32];
+            {
+                let aes_enc = aessafe::AesSafe128Encryptor::new(&key);
+                let mut enc = CbcEncryptor::new(aes_enc, NoPadding, iv.to_vec());
+                let mut buff_in = RefReadBuffer::new(&plain);
+                let mut buff_out = RefWriteBuffer::new(&mut cipher);
+                assert!(enc.encrypt(&mut buff_in, &mut buff_out, true).is_ok());
+            }
+
+            let mut out: Vec<u8> = vec![0; 32];
+            let aes_dec = aessafe::AesSafe128Decryptor::new(&key);
+            let mut dec = CbcDecryptor::new(aes_dec, PkcsPadding, iv.to_vec());
+            let mut buff_in = RefReadBuffer::new(&cipher);
+            let mut buff_out = RefWriteBuffer::new(&mut out);
+            match dec.decrypt(&mut buff_in, &mut buff_out, true) {
+                Err(InvalidPadding) => {}
+                _ => panic!("Invalid padding not detected")
+            }
+            // The badly padded block must not be written to the output
+            assert!(buff_out.position() <= 16);
+        }
+    }
+
     #[test]
     fn aes_ctr() {
         let tests = aes_ctr_tests();
@@ -1321,6 +1650,218 @@ mod test {
                 });
         }
     }
+
+    fn run_seek_test<S, NewFunc>(new: NewFunc) where
+            S: SeekableStreamCipher,
+            NewFunc: Fn() -> S {
+        let input = [0u8; 500];
+        let mut expected = [0u8; 500];
+        new().process(&input, &mut expected);
+
+        for &offset in [0usize, 1, 15, 16, 17, 127, 128, 129, 300, 499].iter() {
+            let mut c = new();
+            c.process(&input[..37], &mut [0u8; 37]);
+            assert_eq!(c.position(), 37);
+            c.seek(offset as u64).unwrap();
+            assert_eq!(c.position(), offset as u64);
+            let mut output = vec![0u8; 500 - offset];
+            c.process(&input[offset..], &mut output);
+            assert_eq!(&output[..], &expected[offset..]);
+            assert_eq!(c.position(), 500);
+        }
+    }
+
+    #[test]
+    fn aes_ctr_seek() {
+        let key = [0x2bu8; 16];
+        // A counter close to the maximum, so seeking has to carry and wrap around
+        let mut ctr = vec![0xffu8; 16];
+        ctr[14] = 0xfe;
+        ctr[15] = 0xfa;
+        run_seek_test(|| CtrMode::new(aessafe::AesSafe128Encryptor::new(&key), ctr.clone()));
+        run_seek_test(|| CtrModeX8::new(aessafe::AesSafe128EncryptorX8::new(&key), &ctr));
+    }
+
+    #[test]
+    fn aes_ctr_reset_after_seek() {
+        let key = [0x2bu8; 16];
+        let ctr = [0u8; 16];
+        let mut expected = [0u8; 40];
+        CtrMode::new(aessafe::AesSafe128Encryptor::new(&key), ctr.to_vec())
+            .process(&[0u8; 40], &mut expected);
+
+        let mut c = CtrMode::new(aessafe::AesSafe128Encryptor::new(&key), vec![0xaa; 16]);
+        c.seek(1000).unwrap();
+        c.process(&[0u8; 7], &mut [0u8; 7]);
+        c.reset(&ctr);
+        assert_eq!(c.position(), 0);
+        let mut output = [0u8; 40];
+        c.process(&[0u8; 40], &mut output);
+        assert_eq!(&output[..], &expected[..]);
+
+        let mut c = CtrModeX8::new(aessafe::AesSafe128EncryptorX8::new(&key), &[0xaa; 16]);
+        c.seek(1000).unwrap();
+        c.process(&[0u8; 7], &mut [0u8; 7]);
+        c.reset(&ctr);
+        assert_eq!(c.position(), 0);
+        let mut output = [0u8; 40];
+        c.process(&[0u8; 40], &mut output);
+        assert_eq!(&output[..], &expected[..]);
+    }
+
+    // Keystream of the given counter blocks, computed block by block
+    fn ctr_keystream(key: &[u8], blocks: &[Vec<u8>]) -> Vec<u8> {
+        let aes_enc = aessafe::AesSafe128Encryptor::new(key);
+        let mut out = vec![0u8; blocks.len() * 16];
+        for (block, o) in blocks.iter().zip(out.chunks_mut(16)) {
+            aes_enc.encrypt_block(block, o);
+        }
+        out
+    }
+
+    fn run_policy_test<S, NewFunc>(new: NewFunc, expected: &[u8]) where
+            S: SeekableStreamCipher + Encryptor,
+            NewFunc: Fn() -> S {
+        // The whole keystream can be used, in pieces of any size
+        let mut c = new();
+        let mut output = vec![0u8; expected.len()];
+        c.try_process(&[0u8; 5], &mut output[..5]).unwrap();
+        c.try_process(&vec![0u8; expected.len() - 5], &mut output[5..]).unwrap();
+        assert_eq!(&output[..], expected);
+
+        // After that it fails, without consuming anything
+        let mut byte = [0u8; 1];
+        match c.try_process(&[0u8], &mut byte) {
+            Err(CounterOverflow) => {},
+            _ => panic!("processing past the end of the counter must fail"),
+        }
+        assert_eq!(c.position(), expected.len() as u64);
+        let mut c = new();
+        let input = vec![0u8; expected.len() + 1];
+        let mut output = vec![0u8; expected.len() + 1];
+        match c.encrypt(&mut RefReadBuffer::new(&input), &mut RefWriteBuffer::new(&mut output), false) {
+            Err(CounterOverflow) => {},
+            _ => panic!("encrypting past the end of the counter must fail"),
+        }
+
+        // Seeking to the end is fine, but not past it
+        let mut c = new();
+        c.seek(expected.len() as u64).unwrap();
+        assert!(c.try_process(&[0u8], &mut byte).is_err());
+        assert!(c.seek(expected.len() as u64 + 1).is_err());
+        assert!(c.seek(expected.len() as u64 + 16).is_err());
+        assert_eq!(c.position(), expected.len() as u64);
+        c.seek(20).unwrap();
+        let mut output = vec![0u8; expected.len() - 20];
+        c.try_process(&vec![0u8; expected.len() - 20], &mut output).unwrap();
+        assert_eq!(&output[..], &expected[20..]);
+    }
+
+    #[test]
+    fn aes_ctr_policy_32_bit_big_endian() {
+        let key = [0x2bu8; 16];
+        let mut ctr = vec![0x5au8; 16];
+        ctr[12..].copy_from_slice(&[0xff, 0xff, 0xff, 0xfd]);
+        // Only the last 4 bytes are counted, so there are 3 blocks left
+        let blocks: Vec<Vec<u8>> = [0xfdu8, 0xfe, 0xff].iter().map(|&last| {
+            let mut block = ctr.clone();
+            block[15] = last;
+            block
+        }).collect();
+        let expected = ctr_keystream(&key, &blocks);
+        let policy = CounterPolicy::new(32, CounterEndianness::BigEndian);
+        run_policy_test(
+            || CtrMode::new_with_policy(aessafe::AesSafe128Encryptor::new(&key), ctr.clone(), policy),
+            &expected);
+        run_policy_test(
+            || CtrModeX8::new_with_policy(aessafe::AesSafe128EncryptorX8::new(&key), &ctr, policy),
+            &expected);
+    }
+
+    #[test]
+    fn aes_ctr_policy_64_bit_little_endian() {
+        let key = [0x2bu8; 16];
+        let mut ctr = vec![0x5au8; 16];
+        ctr[..8].copy_from_slice(&[0xf4, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        // The first 8 bytes are counted with carries, leaving 0x10c blocks
+        let blocks: Vec<Vec<u8>> = (0..0x10cu64).map(|i| {
+            let mut block = ctr.clone();
+            block[..8].copy_from_slice(&(0xfffffffffffffef4u64 + i).to_le_bytes());
+            block
+        }).collect();
+        let expected = ctr_keystream(&key, &blocks);
+        let policy = CounterPolicy::new(64, CounterEndianness::LittleEndian);
+        run_policy_test(
+            || CtrMode::new_with_policy(aessafe::AesSafe128Encryptor::new(&key), ctr.clone(), policy),
+            &expected);
+        run_policy_test(
+            || CtrModeX8::new_with_policy(aessafe::AesSafe128EncryptorX8::new(&key), &ctr, policy),
+            &expected);
+    }
+
+    #[test]
+    fn aes_ctr_policy_128_bit() {
+        let key = [0x2bu8; 16];
+        let mut ctr = vec![0xffu8; 16];
+        ctr[15] = 0xf0;
+        let blocks: Vec<Vec<u8>> = (0xf0..=0xffu8).map(|last| {
+            let mut block = ctr.clone();
+            block[15] = last;
+            block
+        }).collect();
+        let expected = ctr_keystream(&key, &blocks);
+        let policy = CounterPolicy::new(128, CounterEndianness::BigEndian);
+        run_policy_test(
+            || CtrMode::new_with_policy(aessafe::AesSafe128Encryptor::new(&key), ctr.clone(), policy),
+            &expected);
+        run_policy_test(
+            || CtrModeX8::new_with_policy(aessafe::AesSafe128EncryptorX8::new(&key), &ctr, policy),
+            &expected);
+
+        let mut c = aes::ctr_with_policy(aes::KeySize::KeySize128, &key, &ctr, policy);
+        let mut output = vec![0u8; expected.len()];
+        c.try_process(&vec![0u8; expected.len()], &mut output).unwrap();
+        assert_eq!(output, expected);
+        assert!(c.try_process(&[0u8], &mut [0u8]).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn aes_ctr_policy_exhausted_process() {
+        let policy = CounterPolicy::new(32, CounterEndianness::LittleEndian);
+        let mut c = CtrMode::new_with_policy(aessafe::AesSafe128Encryptor::new(&[0u8; 16]), vec![0xff; 16], policy);
+        let mut output = [0u8; 17];
+        c.process(&[0u8; 17], &mut output);
+    }
+
+    #[test]
+    fn threefish_native_tweak_mode() {
+        let key: Vec<u8> = (0..32).collect();
+        let plain: Vec<u8> = (0..96).map(|i| (i * 7) as u8).collect();
+        let sector = 0x0123456789abcdef;
+
+        let mode = NativeTweakMode::new(Threefish256::new(&key, &[0; 16]));
+        let mut cipher = vec![0u8; plain.len()];
+        mode.encrypt_sector(sector, &plain, &mut cipher);
+
+        // Every block uses its index and the sector number as tweak
+        for (i, block) in cipher.chunks(32).enumerate() {
+            let mut tweak = vec![i as u8, 0, 0, 0, 0, 0, 0, 0];
+            tweak.extend_from_slice(&[0xef, 0xcd, 0xab, 0x89, 0x67, 0x45, 0x23, 0x01]);
+            let mut expected = [0u8; 32];
+            Threefish256::new(&key, &tweak).encrypt_block(&plain[i * 32..(i + 1) * 32], &mut expected);
+            assert_eq!(block, &expected[..]);
+        }
+
+        // Equal plaintext blocks in other sectors encrypt differently
+        let mut other = vec![0u8; plain.len()];
+        mode.encrypt_sector(sector + 1, &plain, &mut other);
+        assert!(other[..32] != cipher[..32]);
+
+        let mut decrypted = vec![0u8; plain.len()];
+        mode.decrypt_sector(sector, &cipher, &mut decrypted);
+        assert_eq!(decrypted, plain);
+    }
 }
 
 #[cfg(all(test, feature = "with-bench"))]
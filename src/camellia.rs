@@ -0,0 +1,350 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the Camellia block cipher, as specified in
+ * [RFC 3713](https://tools.ietf.org/html/rfc3713), with 128, 192 and 256 bit keys.
+ *
+ * Like Blowfish and Twofish, this implementation uses secret dependent table lookups.
+ */
+
+use crate::cryptoutil::{read_u64_be, read_u128_be, write_u64_be};
+use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+static SBOX1: [u8; 256] = [
+    0x70, 0x82, 0x2c, 0xec, 0xb3, 0x27, 0xc0, 0xe5, 0xe4, 0x85, 0x57, 0x35, 0xea, 0x0c, 0xae, 0x41,
+    0x23, 0xef, 0x6b, 0x93, 0x45, 0x19, 0xa5, 0x21, 0xed, 0x0e, 0x4f, 0x4e, 0x1d, 0x65, 0x92, 0xbd,
+    0x86, 0xb8, 0xaf, 0x8f, 0x7c, 0xeb, 0x1f, 0xce, 0x3e, 0x30, 0xdc, 0x5f, 0x5e, 0xc5, 0x0b, 0x1a,
+    0xa6, 0xe1, 0x39, 0xca, 0xd5, 0x47, 0x5d, 0x3d, 0xd9, 0x01, 0x5a, 0xd6, 0x51, 0x56, 0x6c, 0x4d,
+    0x8b, 0x0d, 0x9a, 0x66, 0xfb, 0xcc, 0xb0, 0x2d, 0x74, 0x12, 0x2b, 0x20, 0xf0, 0xb1, 0x84, 0x99,
+    0xdf, 0x4c, 0xcb, 0xc2, 0x34, 0x7e, 0x76, 0x05, 0x6d, 0xb7, 0xa9, 0x31, 0xd1, 0x17, 0x04, 0xd7,
+    0x14, 0x58, 0x3a, 0x61, 0xde, 0x1b, 0x11, 0x1c, 0x32, 0x0f, 0x9c, 0x16, 0x53, 0x18, 0xf2, 0x22,
+    0xfe, 0x44, 0xcf, 0xb2, 0xc3, 0xb5, 0x7a, 0x91, 0x24, 0x08, 0xe8, 0xa8, 0x60, 0xfc, 0x69, 0x50,
+    0xaa, 0xd0, 0xa0, 0x7d, 0xa1, 0x89, 0x62, 0x97, 0x54, 0x5b, 0x1e, 0x95, 0xe0, 0xff, 0x64, 0xd2,
+    0x10, 0xc4, 0x00, 0x48, 0xa3, 0xf7, 0x75, 0xdb, 0x8a, 0x03, 0xe6, 0xda, 0x09, 0x3f, 0xdd, 0x94,
+    0x87, 0x5c, 0x83, 0x02, 0xcd, 0x4a, 0x90, 0x33, 0x73, 0x67, 0xf6, 0xf3, 0x9d, 0x7f, 0xbf, 0xe2,
+    0x52, 0x9b, 0xd8, 0x26, 0xc8, 0x37, 0xc6, 0x3b, 0x81, 0x96, 0x6f, 0x4b, 0x13, 0xbe, 0x63, 0x2e,
+    0xe9, 0x79, 0xa7, 0x8c, 0x9f, 0x6e, 0xbc, 0x8e, 0x29, 0xf5, 0xf9, 0xb6, 0x2f, 0xfd, 0xb4, 0x59,
+    0x78, 0x98, 0x06, 0x6a, 0xe7, 0x46, 0x71, 0xba, 0xd4, 0x25, 0xab, 0x42, 0x88, 0xa2, 0x8d, 0xfa,
+    0x72, 0x07, 0xb9, 0x55, 0xf8, 0xee, 0xac, 0x0a, 0x36, 0x49, 0x2a, 0x68, 0x3c, 0x38, 0xf1, 0xa4,
+    0x40, 0x28, 0xd3, 0x7b, 0xbb, 0xc9, 0x43, 0xc1, 0x15, 0xe3, 0xad, 0xf4, 0x77, 0xc7, 0x80, 0x9e];
+
+static SIGMA: [u64; 6] = [
+    0xa09e667f3bcc908b, 0xb67ae8584caa73b2, 0xc6ef372fe94f82be,
+    0x54ff53a5f1d36f1c, 0x10e527fade682d1d, 0xb05688c2b3e6c1fd];
+
+fn sbox1(x: u8) -> u8 { SBOX1[x as usize] }
+fn sbox2(x: u8) -> u8 { SBOX1[x as usize].rotate_left(1) }
+fn sbox3(x: u8) -> u8 { SBOX1[x as usize].rotate_left(7) }
+fn sbox4(x: u8) -> u8 { SBOX1[x.rotate_left(1) as usize] }
+
+fn f(input: u64, key: u64) -> u64 {
+    let x = (input ^ key).to_be_bytes();
+    let t = [sbox1(x[0]), sbox2(x[1]), sbox3(x[2]), sbox4(x[3]),
+             sbox2(x[4]), sbox3(x[5]), sbox4(x[6]), sbox1(x[7])];
+    u64::from_be_bytes([
+        t[0] ^ t[2] ^ t[3] ^ t[5] ^ t[6] ^ t[7],
+        t[0] ^ t[1] ^ t[3] ^ t[4] ^ t[6] ^ t[7],
+        t[0] ^ t[1] ^ t[2] ^ t[4] ^ t[5] ^ t[7],
+        t[1] ^ t[2] ^ t[3] ^ t[4] ^ t[5] ^ t[6],
+        t[0] ^ t[1] ^ t[5] ^ t[6] ^ t[7],
+        t[1] ^ t[2] ^ t[4] ^ t[6] ^ t[7],
+        t[2] ^ t[3] ^ t[4] ^ t[5] ^ t[7],
+        t[0] ^ t[3] ^ t[4] ^ t[5] ^ t[6]])
+}
+
+fn fl(x: u64, key: u64) -> u64 {
+    let (mut x1, mut x2) = ((x >> 32) as u32, x as u32);
+    let (k1, k2) = ((key >> 32) as u32, key as u32);
+    x2 ^= (x1 & k1).rotate_left(1);
+    x1 ^= x2 | k2;
+    ((x1 as u64) << 32) | x2 as u64
+}
+
+fn fl_inv(y: u64, key: u64) -> u64 {
+    let (mut y1, mut y2) = ((y >> 32) as u32, y as u32);
+    let (k1, k2) = ((key >> 32) as u32, key as u32);
+    y1 ^= y2 | k2;
+    y2 ^= (y1 & k1).rotate_left(1);
+    ((y1 as u64) << 32) | y2 as u64
+}
+
+// The left and right halves of x rotated left by n bits
+fn halves(x: u128, n: u32) -> [u64; 2] {
+    let r = x.rotate_left(n);
+    [(r >> 64) as u64, r as u64]
+}
+
+#[derive(Clone, Copy)]
+struct Subkeys {
+    kw: [u64; 4],
+    k: [u64; 24],
+    ke: [u64; 6]
+}
+
+impl Subkeys {
+    fn new(kw: &[[u64; 2]], k: &[[u64; 2]], ke: &[[u64; 2]]) -> Subkeys {
+        let mut subkeys = Subkeys {
+            kw: [0; 4],
+            k: [0; 24],
+            ke: [0; 6]
+        };
+        for (dst, src) in [(&mut subkeys.kw[..], kw), (&mut subkeys.k[..], k), (&mut subkeys.ke[..], ke)].iter_mut() {
+            for (d, s) in dst.chunks_mut(2).zip(src.iter()) {
+                d.copy_from_slice(s);
+            }
+        }
+        subkeys
+    }
+
+    // The subkeys for decryption, which are those for encryption in reverse order
+    fn reverse(&self, rounds: usize) -> Subkeys {
+        let mut subkeys = Subkeys {
+            kw: [self.kw[2], self.kw[3], self.kw[0], self.kw[1]],
+            k: self.k,
+            ke: self.ke
+        };
+        subkeys.k[..rounds].reverse();
+        subkeys.ke[..rounds / 3 - 2].reverse();
+        subkeys
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Camellia {
+    enc: Subkeys,
+    dec: Subkeys,
+    rounds: usize
+}
+
+impl Camellia {
+    /**
+     * Create a new Camellia instance.
+     *
+     * # Arguments
+     * * key - The 16, 24 or 32 byte key.
+     */
+    pub fn new(key: &[u8]) -> Camellia {
+        let kl = read_u128_be(&key[..16]);
+        let kr = match key.len() {
+            16 => 0,
+            24 => {
+                let r = read_u64_be(&key[16..24]);
+                ((r as u128) << 64) | (!r) as u128
+            }
+            32 => read_u128_be(&key[16..32]),
+            _ => panic!("Invalid Camellia key length")
+        };
+
+        let [mut d1, mut d2] = halves(kl ^ kr, 0);
+        d2 ^= f(d1, SIGMA[0]);
+        d1 ^= f(d2, SIGMA[1]);
+        d1 ^= (kl >> 64) as u64;
+        d2 ^= kl as u64;
+        d2 ^= f(d1, SIGMA[2]);
+        d1 ^= f(d2, SIGMA[3]);
+        let ka = ((d1 as u128) << 64) | d2 as u128;
+        let [mut d1, mut d2] = halves(ka ^ kr, 0);
+        d2 ^= f(d1, SIGMA[4]);
+        d1 ^= f(d2, SIGMA[5]);
+        let kb = ((d1 as u128) << 64) | d2 as u128;
+
+        let (enc, rounds) = if key.len() == 16 {
+            let k9_k10 = [halves(ka, 45)[0], halves(kl, 60)[1]];
+            (Subkeys::new(
+                &[halves(kl, 0), halves(ka, 111)],
+                &[halves(ka, 0), halves(kl, 15), halves(ka, 15), halves(kl, 45), k9_k10,
+                  halves(ka, 60), halves(kl, 94), halves(ka, 94), halves(kl, 111)],
+                &[halves(ka, 30), halves(kl, 77)]), 18)
+        } else {
+            (Subkeys::new(
+                &[halves(kl, 0), halves(kb, 111)],
+                &[halves(kb, 0), halves(kr, 15), halves(ka, 15), halves(kb, 30), halves(kl, 45),
+                  halves(ka, 45), halves(kr, 60), halves(kb, 60), halves(kl, 77), halves(kr, 94),
+                  halves(ka, 94), halves(kl, 111)],
+                &[halves(kr, 30), halves(kl, 60), halves(ka, 77)]), 24)
+        };
+
+        Camellia {
+            enc,
+            dec: enc.reverse(rounds),
+            rounds
+        }
+    }
+
+    fn crypt(&self, keys: &Subkeys, input: &[u8], output: &mut [u8]) {
+        let mut d1 = read_u64_be(&input[0..8]) ^ keys.kw[0];
+        let mut d2 = read_u64_be(&input[8..16]) ^ keys.kw[1];
+        for (i, k) in keys.k[..self.rounds].chunks(2).enumerate() {
+            // The FL layer after every 6 rounds
+            if i > 0 && i % 3 == 0 {
+                let j = 2 * (i / 3 - 1);
+                d1 = fl(d1, keys.ke[j]);
+                d2 = fl_inv(d2, keys.ke[j + 1]);
+            }
+            d2 ^= f(d1, k[0]);
+            d1 ^= f(d2, k[1]);
+        }
+        write_u64_be(&mut output[0..8], d2 ^ keys.kw[2]);
+        write_u64_be(&mut output[8..16], d1 ^ keys.kw[3]);
+    }
+}
+
+impl BlockEncryptor for Camellia {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        self.crypt(&self.enc, input, output);
+    }
+}
+
+impl BlockDecryptor for Camellia {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        self.crypt(&self.dec, input, output);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aead::{AeadEncryptor, AeadDecryptor};
+    use crate::blockmodes::{CbcEncryptor, CtrMode, NoPadding};
+    use crate::buffer::{RefReadBuffer, RefWriteBuffer};
+    use crate::camellia::Camellia;
+    use crate::gcm::Gcm;
+    use crate::serialize::hex::FromHex;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor, Encryptor, SynchronousStreamCipher};
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    // Test vectors from RFC 3713, Appendix A
+    #[test]
+    fn test_rfc3713() {
+        let plaintext = hex_to_bytes("0123456789abcdeffedcba9876543210");
+        let tests = [
+            ("0123456789abcdeffedcba9876543210",
+             "67673138549669730857065648eabe43"),
+            ("0123456789abcdeffedcba98765432100011223344556677",
+             "b4993401b3e996f84ee5cee7d79b09b9"),
+            ("0123456789abcdeffedcba987654321000112233445566778899aabbccddeeff",
+             "9acc237dff16d76c20ef7c919e3a7509"),
+        ];
+        for &(key, ciphertext) in tests.iter() {
+            let camellia = Camellia::new(&hex_to_bytes(key));
+            let ciphertext = hex_to_bytes(ciphertext);
+            let mut output = [0u8; 16];
+            camellia.encrypt_block(&plaintext, &mut output);
+            assert_eq!(&output[..], &ciphertext[..]);
+            camellia.decrypt_block(&ciphertext, &mut output);
+            assert_eq!(&output[..], &plaintext[..]);
+        }
+    }
+
+    fn sp800_38a_plaintext() -> Vec<u8> {
+        hex_to_bytes(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52ef")
+    }
+
+    #[test]
+    fn test_camellia_cbc() {
+        let key = hex_to_bytes("2b7e151628aed2a6abf7158809cf4f3c");
+        let iv = hex_to_bytes("000102030405060708090a0b0c0d0e0f");
+        let plaintext = sp800_38a_plaintext();
+        let expected = hex_to_bytes(
+            "1607cf494b36bbf00daeb0b503c831aba2f2cf671629ef7840c5a5dfb5074887\
+             0f06165008cf8b8b5a63586362543e54");
+
+        let mut enc = CbcEncryptor::new(Camellia::new(&key), NoPadding, iv);
+        let mut output = vec![0u8; plaintext.len()];
+        enc.encrypt(
+            &mut RefReadBuffer::new(&plaintext),
+            &mut RefWriteBuffer::new(&mut output),
+            true).unwrap();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_camellia_ctr() {
+        let key = hex_to_bytes("2b7e151628aed2a6abf7158809cf4f3c");
+        let ctr = hex_to_bytes("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let plaintext = sp800_38a_plaintext();
+        let expected = hex_to_bytes(
+            "b809140877dd16c076780904f83ded11bb41e64e9bf176ce05d4186b2586d4c9\
+             49e82fdc5d6e78ab781363e51781fc9b");
+
+        let mut ctr = CtrMode::new(Camellia::new(&key), ctr);
+        let mut output = vec![0u8; plaintext.len()];
+        ctr.process(&plaintext, &mut output);
+        assert_eq!(output, expected);
+    }
+
+    // The inputs of test case 4 of the GCM specification, with Camellia instead of AES
+    #[test]
+    fn test_camellia_gcm() {
+        let key = hex_to_bytes("feffe9928665731c6d6a8f9467308308");
+        let nonce = hex_to_bytes("cafebabefacedbaddecaf888");
+        let aad = hex_to_bytes("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let plaintext = hex_to_bytes(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+             1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39");
+        let expected = hex_to_bytes(
+            "d0d94a13b632f337a0cc9955b94fa020c815f903aab12f1efaf2fe9d90f729a6\
+             cccbfa986ef2ff2c33de418d9a2529091cf18fe652c1cfde13f82606");
+        let expected_tag = hex_to_bytes("9f458869431576ea6a095456ec6b8101");
+
+        let mut ciphertext = vec![0u8; plaintext.len()];
+        let mut tag = [0u8; 16];
+        Gcm::new(Camellia::new(&key), &nonce, &aad).encrypt(&plaintext, &mut ciphertext, &mut tag);
+        assert_eq!(ciphertext, expected);
+        assert_eq!(&tag[..], &expected_tag[..]);
+
+        let mut output = vec![0u8; ciphertext.len()];
+        assert!(Gcm::new(Camellia::new(&key), &nonce, &aad).decrypt(&ciphertext, &mut output, &tag));
+        assert_eq!(output, plaintext);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::camellia::Camellia;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+    #[bench]
+    pub fn camellia128_encrypt(bh: &mut Bencher) {
+        let camellia = Camellia::new(&[1u8; 16]);
+        let input = [1u8; 16];
+        let mut output = [0u8; 16];
+        bh.iter( || {
+            camellia.encrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn camellia256_decrypt(bh: &mut Bencher) {
+        let camellia = Camellia::new(&[1u8; 32]);
+        let input = [1u8; 16];
+        let mut output = [0u8; 16];
+        bh.iter( || {
+            camellia.decrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
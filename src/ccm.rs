@@ -0,0 +1,228 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+This public module implements Counter with CBC-MAC (CCM) mode, as specified in NIST SP 800-38C and
+RFC 3610, for any block cipher with a 128 bit block size, such as SM4 or Camellia.
+
+The nonce is 7 to 13 bytes long; the shorter the nonce, the longer a message may be, as the
+remaining bytes of the counter block hold the message length and the block counter. The tag is 4,
+6, 8, 10, 12, 14 or 16 bytes long. As the length of the message is authenticated first, the
+additional authenticated data is kept until the message is encrypted or decrypted.
+*/
+
+use crate::aead::{AeadEncryptor, AeadDecryptor};
+use crate::symmetriccipher::BlockEncryptor;
+use crate::util::{fixed_time_eq, secure_memset};
+
+pub struct Ccm<E> {
+    cipher: E,
+    nonce: Vec<u8>,
+    aad: Vec<u8>,
+    tag_len: usize,
+    finished: bool
+}
+
+impl <E: BlockEncryptor> Ccm<E> {
+    /**
+     * Create a new Ccm instance.
+     *
+     * # Arguments
+     * * cipher - The block cipher, keyed with the encryption key. Its block size must be 16 bytes.
+     * * nonce - The nonce, 7 to 13 bytes long, which must never be reused with the same key.
+     * * aad - The additional authenticated data.
+     * * tag_len - The length of the tag: 4, 6, 8, 10, 12, 14 or 16 bytes.
+     */
+    pub fn new(cipher: E, nonce: &[u8], aad: &[u8], tag_len: usize) -> Ccm<E> {
+        assert!(cipher.block_size() == 16);
+        assert!((7..=13).contains(&nonce.len()));
+        assert!((4..=16).contains(&tag_len) && tag_len & 1 == 0);
+        Ccm {
+            cipher,
+            nonce: nonce.to_vec(),
+            aad: aad.to_vec(),
+            tag_len,
+            finished: false
+        }
+    }
+
+    // The size in bytes of the length and counter fields
+    fn l(&self) -> usize {
+        15 - self.nonce.len()
+    }
+
+    // The counter block A_i
+    fn counter_block(&self, i: u64) -> [u8; 16] {
+        let mut block = [0u8; 16];
+        block[0] = (self.l() - 1) as u8;
+        block[1..1 + self.nonce.len()].copy_from_slice(&self.nonce);
+        let l = self.l();
+        block[16 - l..].copy_from_slice(&i.to_be_bytes()[8 - l..]);
+        block
+    }
+
+    // The CBC-MAC of the formatted nonce, additional data and payload, encrypted with S_0
+    fn tag(&self, payload: &[u8], tag: &mut [u8]) {
+        let l = self.l();
+        let len = payload.len() as u64;
+        assert!(l >= 8 || len >> (8 * l) == 0, "message too long for the nonce length");
+
+        let mut b0 = self.counter_block(len);
+        b0[0] |= (((self.tag_len - 2) / 2) << 3) as u8;
+        if !self.aad.is_empty() {
+            b0[0] |= 0x40;
+        }
+        let mut mac = [0u8; 16];
+        self.cipher.encrypt_block(&b0, &mut mac);
+
+        // The length of the additional data is encoded in 2, 6 or 10 bytes
+        let mut aad = Vec::with_capacity(self.aad.len() + 10);
+        let aad_len = self.aad.len() as u64;
+        if aad_len == 0 {
+            // Nothing to authenticate
+        } else if aad_len < 0xff00 {
+            aad.extend_from_slice(&(aad_len as u16).to_be_bytes());
+        } else if aad_len >> 32 == 0 {
+            aad.extend_from_slice(&[0xff, 0xfe]);
+            aad.extend_from_slice(&(aad_len as u32).to_be_bytes());
+        } else {
+            aad.extend_from_slice(&[0xff, 0xff]);
+            aad.extend_from_slice(&aad_len.to_be_bytes());
+        }
+        aad.extend_from_slice(&self.aad);
+
+        for data in [&aad[..], payload].iter() {
+            for chunk in data.chunks(16) {
+                for (m, &d) in mac.iter_mut().zip(chunk.iter()) {
+                    *m ^= d;
+                }
+                let input = mac;
+                self.cipher.encrypt_block(&input, &mut mac);
+            }
+        }
+
+        let mut s0 = [0u8; 16];
+        self.cipher.encrypt_block(&self.counter_block(0), &mut s0);
+        for ((t, &m), &s) in tag.iter_mut().zip(mac.iter()).zip(s0.iter()) {
+            *t = m ^ s;
+        }
+    }
+
+    fn process(&self, input: &[u8], output: &mut [u8]) {
+        let mut keystream = [0u8; 16];
+        for (i, (inp, out)) in input.chunks(16).zip(output.chunks_mut(16)).enumerate() {
+            self.cipher.encrypt_block(&self.counter_block(i as u64 + 1), &mut keystream);
+            for ((o, &i), &k) in out.iter_mut().zip(inp.iter()).zip(keystream.iter()) {
+                *o = i ^ k;
+            }
+        }
+    }
+}
+
+impl <E: BlockEncryptor> AeadEncryptor for Ccm<E> {
+    fn encrypt(&mut self, input: &[u8], output: &mut [u8], tag: &mut [u8]) {
+        assert!(input.len() == output.len());
+        assert!(tag.len() == self.tag_len);
+        assert!(!self.finished);
+        self.finished = true;
+        self.tag(input, tag);
+        self.process(input, output);
+    }
+}
+
+impl <E: BlockEncryptor> AeadDecryptor for Ccm<E> {
+    fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8]) -> bool {
+        assert!(input.len() == output.len());
+        assert!(!self.finished);
+        self.finished = true;
+        self.process(input, output);
+        let mut calc_tag = [0u8; 16];
+        self.tag(output, &mut calc_tag[..self.tag_len]);
+        if fixed_time_eq(&calc_tag[..self.tag_len], tag) {
+            true
+        } else {
+            secure_memset(output, 0);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aead::{AeadEncryptor, AeadDecryptor};
+    use crate::aessafe::AesSafe128Encryptor;
+    use crate::ccm::Ccm;
+    use crate::serialize::hex::FromHex;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    struct Test {
+        nonce: Vec<u8>,
+        aad: Vec<u8>,
+        plaintext: Vec<u8>,
+        ciphertext: Vec<u8>,
+        tag: Vec<u8>
+    }
+
+    // Examples 1 to 3 from NIST SP 800-38C, Appendix C
+    fn tests() -> Vec<Test> {
+        vec![
+            Test {
+                nonce: hex_to_bytes("10111213141516"),
+                aad: hex_to_bytes("0001020304050607"),
+                plaintext: hex_to_bytes("20212223"),
+                ciphertext: hex_to_bytes("7162015b"),
+                tag: hex_to_bytes("4dac255d")
+            },
+            Test {
+                nonce: hex_to_bytes("1011121314151617"),
+                aad: hex_to_bytes("000102030405060708090a0b0c0d0e0f"),
+                plaintext: hex_to_bytes("202122232425262728292a2b2c2d2e2f"),
+                ciphertext: hex_to_bytes("d2a1f0e051ea5f62081a7792073d593d"),
+                tag: hex_to_bytes("1fc64fbfaccd")
+            },
+            Test {
+                nonce: hex_to_bytes("101112131415161718191a1b"),
+                aad: hex_to_bytes("000102030405060708090a0b0c0d0e0f10111213"),
+                plaintext: hex_to_bytes("202122232425262728292a2b2c2d2e2f3031323334353637"),
+                ciphertext: hex_to_bytes("e3b201a9f5b71a7a9b1ceaeccd97e70b6176aad9a4428aa5"),
+                tag: hex_to_bytes("484392fbc1b09951")
+            },
+        ]
+    }
+
+    fn new_ccm(t: &Test) -> Ccm<AesSafe128Encryptor> {
+        let key = hex_to_bytes("404142434445464748494a4b4c4d4e4f");
+        Ccm::new(AesSafe128Encryptor::new(&key), &t.nonce, &t.aad, t.tag.len())
+    }
+
+    #[test]
+    fn test_ccm_aes() {
+        for t in tests().iter() {
+            let mut ciphertext = vec![0u8; t.plaintext.len()];
+            let mut tag = vec![0u8; t.tag.len()];
+            new_ccm(t).encrypt(&t.plaintext, &mut ciphertext, &mut tag);
+            assert_eq!(ciphertext, t.ciphertext);
+            assert_eq!(tag, t.tag);
+        }
+    }
+
+    #[test]
+    fn test_ccm_aes_decrypt() {
+        for t in tests().iter() {
+            let mut plaintext = vec![0u8; t.ciphertext.len()];
+            assert!(new_ccm(t).decrypt(&t.ciphertext, &mut plaintext, &t.tag));
+            assert_eq!(plaintext, t.plaintext);
+
+            let mut bad_tag = t.tag.clone();
+            bad_tag[0] ^= 1;
+            assert!(!new_ccm(t).decrypt(&t.ciphertext, &mut plaintext, &bad_tag));
+            assert!(plaintext.iter().all(|&b| b == 0));
+        }
+    }
+}
@@ -6,9 +6,12 @@
 use std::cmp;
 
 use crate::buffer::{BufferResult, RefReadBuffer, RefWriteBuffer};
-use crate::symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher, SymmetricCipherError};
+use crate::symmetriccipher::{Encryptor, Decryptor, SeekableStreamCipher, SynchronousStreamCipher,
+                             SymmetricCipherError};
 use crate::cryptoutil::{read_u32_le, symm_enc_or_dec, write_u32_le, xor_keystream};
 use crate::simd::u32x4;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::stream_x86::{self, Backend};
 
 #[derive(Clone,Copy)]
 struct ChaChaState {
@@ -18,14 +21,42 @@ struct ChaChaState {
   d: u32x4
 }
 
+impl ChaChaState {
+    fn from_words(w: &[u32; 16]) -> ChaChaState {
+        ChaChaState {
+            a: u32x4(w[0], w[1], w[2], w[3]),
+            b: u32x4(w[4], w[5], w[6], w[7]),
+            c: u32x4(w[8], w[9], w[10], w[11]),
+            d: u32x4(w[12], w[13], w[14], w[15])
+        }
+    }
+
+    // The 16 words of the state in the natural order
+    fn words(&self) -> [u32; 16] {
+        let u32x4(a0, a1, a2, a3) = self.a;
+        let u32x4(b0, b1, b2, b3) = self.b;
+        let u32x4(c0, c1, c2, c3) = self.c;
+        let u32x4(d0, d1, d2, d3) = self.d;
+        [a0, a1, a2, a3, b0, b1, b2, b3, c0, c1, c2, c3, d0, d1, d2, d3]
+    }
+}
+
+/// The ChaCha stream cipher with `ROUNDS` rounds, which must be even
 #[derive(Copy)]
-pub struct ChaCha20 {
+pub struct ChaCha<const ROUNDS: usize> {
     state  : ChaChaState,
     output : [u8; 64],
     offset : usize,
 }
 
-impl Clone for ChaCha20 { fn clone(&self) -> ChaCha20 { *self } }
+/// ChaCha with 20 rounds
+pub type ChaCha20 = ChaCha<20>;
+/// ChaCha with 12 rounds
+pub type ChaCha12 = ChaCha<12>;
+/// ChaCha with 8 rounds
+pub type ChaCha8 = ChaCha<8>;
+
+impl <const ROUNDS: usize> Clone for ChaCha<ROUNDS> { fn clone(&self) -> ChaCha<ROUNDS> { *self } }
 
 macro_rules! swizzle{
     ($b: expr, $c: expr, $d: expr) => {{
@@ -78,21 +109,28 @@ macro_rules! rotate {
     }}
 }
 
+// The length in bytes of the keystream for one key and nonce
+const KEYSTREAM_END: u64 = u32::MAX as u64 * 64;
+
 static S32:u32x4 = u32x4(32, 32, 32, 32);
 static S16:u32x4 = u32x4(16, 16, 16, 16);
 static S12:u32x4 = u32x4(12, 12, 12, 12);
 static S8:u32x4 = u32x4(8, 8, 8, 8);
 static S7:u32x4 = u32x4(7, 7, 7, 7);
 
-impl ChaCha20 {
-    pub fn new(key: &[u8], nonce: &[u8]) -> ChaCha20 {
+impl <const ROUNDS: usize> ChaCha<ROUNDS> {
+    pub fn new(key: &[u8], nonce: &[u8]) -> ChaCha<ROUNDS> {
+        assert!(ROUNDS > 0 && ROUNDS & 1 == 0);
         assert!(key.len() == 16 || key.len() == 32);
         assert!(nonce.len() == 8 || nonce.len() == 12);
 
-        ChaCha20{ state: ChaCha20::expand(key, nonce), output: [0u8; 64], offset: 64 }
+        ChaCha{ state: Self::expand(key, nonce), output: [0u8; 64], offset: 64 }
     }
 
-    pub fn new_xchacha20(key: &[u8], nonce: &[u8]) -> ChaCha20 {
+    /// Create an XChaCha instance. The subkey is derived with HChaCha using the same number of
+    /// rounds.
+    pub fn new_xchacha20(key: &[u8], nonce: &[u8]) -> ChaCha<ROUNDS> {
+        assert!(ROUNDS > 0 && ROUNDS & 1 == 0);
         assert!(key.len() == 32);
         assert!(nonce.len() == 24);
 
@@ -102,13 +140,13 @@ impl ChaCha20 {
         //  * (x0, x1, x2, x3) is the ChaCha20 constant.
         //  * (x4, x5, ... x11) is a 256 bit key.
         //  * (x12, x13, x14, x15) is a 128 bit nonce.
-        let mut xchacha20 = ChaCha20{ state: ChaCha20::expand(key, &nonce[0..16]), output: [0u8; 64], offset: 64 };
+        let mut xchacha20 = ChaCha{ state: Self::expand(key, &nonce[0..16]), output: [0u8; 64], offset: 64 };
 
         // Use HChaCha to derive the subkey, and initialize a ChaCha20 instance
         // with the subkey and the remaining 8 bytes of the nonce.
         let mut new_key = [0; 32];
         xchacha20.hchacha20(&mut new_key);
-        xchacha20.state = ChaCha20::expand(&new_key, &nonce[16..24]);
+        xchacha20.state = Self::expand(&new_key, &nonce[16..24]);
 
         xchacha20
     }
@@ -178,7 +216,7 @@ impl ChaCha20 {
 
         // Apply r/2 iterations of the same "double-round" function,
         // obtaining (z0, z1, ... z15) = doubleround r/2 (x0, x1, ... x15).
-        for _ in 0..10 {
+        for _ in 0..ROUNDS / 2 {
             round!(state);
             let u32x4(b10, b11, b12, b13) = state.b;
             state.b = u32x4(b11, b12, b13, b10);
@@ -213,7 +251,7 @@ impl ChaCha20 {
     fn update(&mut self) {
         let mut state = self.state;
 
-        for _ in 0..10 {
+        for _ in 0..ROUNDS / 2 {
             round!(state);
             swizzle!(state.b, state.c, state.d);
             round!(state);
@@ -237,9 +275,59 @@ impl ChaCha20 {
 
         self.offset = 0;
     }
+
+    // Process as many whole blocks as the SIMD backend generates at once, and return the number of
+    // bytes processed
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn process_blocks(&mut self, input: &[u8], output: &mut [u8]) -> usize {
+        let backend = match Backend::detect() {
+            Some(backend) => backend,
+            None => return 0,
+        };
+        let size = backend.blocks() * 64;
+        let mut keystream = [0u8; 512];
+        let mut done = 0;
+        while input.len() - done >= size {
+            let u32x4(counter, d1, d2, d3) = self.state.d;
+            // Leave the last blocks of the counter to update(), which checks for exhaustion
+            if counter as u64 + backend.blocks() as u64 > u32::MAX as u64 {
+                break;
+            }
+            stream_x86::chacha_blocks(backend, &self.state.words(), ROUNDS, &mut keystream[..size]);
+            xor_keystream(&mut output[done..done + size], &input[done..done + size], &keystream[..size]);
+            self.state.d = u32x4(counter + backend.blocks() as u32, d1, d2, d3);
+            done += size;
+        }
+        done
+    }
+
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    fn process_blocks(&mut self, _: &[u8], _: &mut [u8]) -> usize {
+        0
+    }
+}
+
+/// The ChaCha block function with `rounds` rounds: computes the keystream block for the state
+/// `input`, given as its 16 words in the natural order. Nothing is incremented.
+pub(crate) fn chacha_block(rounds: usize, input: &[u32; 16], output: &mut [u32; 16]) {
+    let initial = ChaChaState::from_words(input);
+    let mut state = initial;
+
+    for _ in 0..rounds / 2 {
+        round!(state);
+        swizzle!(state.b, state.c, state.d);
+        round!(state);
+        swizzle!(state.d, state.c, state.b);
+    }
+    state.a = state.a + initial.a;
+    state.b = state.b + initial.b;
+    state.c = state.c + initial.c;
+    state.d = state.d + initial.d;
+
+    *output = state.words();
 }
 
-impl SynchronousStreamCipher for ChaCha20 {
+impl <const ROUNDS: usize> SynchronousStreamCipher for ChaCha<ROUNDS> {
     fn process(&mut self, input: &[u8], output: &mut [u8]) {
         assert!(input.len() == output.len());
         let len = input.len();
@@ -248,6 +336,11 @@ impl SynchronousStreamCipher for ChaCha20 {
             // If there is no keystream available in the output buffer,
             // generate the next block.
             if self.offset == 64 {
+                // Whole blocks can be generated several at a time
+                i += self.process_blocks(&input[i..], &mut output[i..]);
+                if i == len {
+                    break;
+                }
                 self.update();
             }
 
@@ -258,16 +351,52 @@ impl SynchronousStreamCipher for ChaCha20 {
             self.offset += count;
         }
     }
+
+    fn try_process(&mut self, input: &[u8], output: &mut [u8]) -> Result<(), SymmetricCipherError> {
+        assert!(input.len() == output.len());
+        if input.len() as u64 > KEYSTREAM_END - self.position() {
+            return Err(SymmetricCipherError::CounterOverflow);
+        }
+        self.process(input, output);
+        Ok(())
+    }
+}
+
+impl <const ROUNDS: usize> SeekableStreamCipher for ChaCha<ROUNDS> {
+    fn seek(&mut self, byte_offset: u64) -> Result<(), SymmetricCipherError> {
+        // The block with the last counter value can't be used, since update() refuses to let the
+        // counter wrap around after it, so the keystream ends where that block starts.
+        if byte_offset > KEYSTREAM_END {
+            return Err(SymmetricCipherError::CounterOverflow);
+        }
+        let block = byte_offset / 64;
+
+        let u32x4(_, d1, d2, d3) = self.state.d;
+        self.state.d = u32x4(block as u32, d1, d2, d3);
+        self.offset = 64;
+        let partial = (byte_offset % 64) as usize;
+        if partial != 0 {
+            self.update();
+            self.offset = partial;
+        }
+        Ok(())
+    }
+
+    fn position(&self) -> u64 {
+        // The counter is the number of the next block to generate
+        let u32x4(counter, _, _, _) = self.state.d;
+        counter as u64 * 64 - (64 - self.offset) as u64
+    }
 }
 
-impl Encryptor for ChaCha20 {
+impl <const ROUNDS: usize> Encryptor for ChaCha<ROUNDS> {
     fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
             -> Result<BufferResult, SymmetricCipherError> {
         symm_enc_or_dec(self, input, output)
     }
 }
 
-impl Decryptor for ChaCha20 {
+impl <const ROUNDS: usize> Decryptor for ChaCha<ROUNDS> {
     fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
             -> Result<BufferResult, SymmetricCipherError> {
         symm_enc_or_dec(self, input, output)
@@ -278,8 +407,15 @@ impl Decryptor for ChaCha20 {
 mod test {
     use std::iter::repeat;
 
-    use crate::chacha20::ChaCha20;
-    use crate::symmetriccipher::SynchronousStreamCipher;
+    use crate::chacha20::{ChaCha, ChaCha8, ChaCha12, ChaCha20};
+    use crate::buffer::{RefReadBuffer, RefWriteBuffer};
+    use crate::symmetriccipher::{Encryptor, SeekableStreamCipher, SynchronousStreamCipher};
+    use crate::symmetriccipher::SymmetricCipherError::CounterOverflow;
+    use crate::serialize::hex::FromHex;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
 
     #[test]
     fn test_chacha20_256_tls_vectors() {
@@ -591,6 +727,176 @@ mod test {
             assert_eq!(output, tv.keystream);
         }
     }
+
+    #[test]
+    fn test_chacha20_seek() {
+        let key: Vec<u8> = (0..32).collect();
+        let nonce = [0u8; 8];
+        let input = [0u8; 1000];
+        let mut expected = [0u8; 1000];
+        ChaCha20::new(&key, &nonce).process(&input, &mut expected);
+
+        for &offset in [0usize, 1, 63, 64, 65, 500, 999].iter() {
+            let mut c = ChaCha20::new(&key, &nonce);
+            c.process(&input[..7], &mut [0u8; 7]);
+            assert_eq!(c.position(), 7);
+            c.seek(offset as u64).unwrap();
+            assert_eq!(c.position(), offset as u64);
+            let mut output = vec![0u8; 1000 - offset];
+            c.process(&input[offset..], &mut output);
+            assert_eq!(&output[..], &expected[offset..]);
+            assert_eq!(c.position(), 1000);
+        }
+    }
+
+    #[test]
+    fn test_chacha20_seek_counter() {
+        let key: Vec<u8> = (0..32).collect();
+        let nonce = hex_to_bytes("000000090000004a00000000");
+        // Generated with OpenSSL, starting at block counter 0x12345678
+        let expected = hex_to_bytes("22a90f58a1d34516ad0eada5e152813b723bfc1cee6ee5df2380bbde05b8b1ab\
+            07649a0ea0d962f441f7062bd65b0ab6c85d9dd7bb1329b368242de4260b3340\
+            5cad32c122f33b8d386a7d5a7031a24fe76912ea662e31dd151f0d556773eedd\
+            f5ad070b60592ab201ccddfc872eb15b6ff318b706a6d7f3501829eeb7d55909");
+        let mut c = ChaCha20::new(&key, &nonce);
+        c.seek(0x12345678 * 64 + 10).unwrap();
+        let mut output = [0u8; 118];
+        c.process(&[0u8; 118], &mut output);
+        assert_eq!(&output[..], &expected[10..]);
+
+        // The last usable block of the keystream
+        let expected = hex_to_bytes("bed7e9fb1985cf676d5b3eee7cdd741282a8e6703109423c0c15c551a64d374f\
+            8a897645cf2688709dc061487d111682e4be93a05f18df4a1d292f8ca8b5f460");
+        let mut c = ChaCha20::new(&key, &nonce);
+        c.seek(0xfffffffe * 64).unwrap();
+        let mut output = [0u8; 64];
+        c.process(&[0u8; 64], &mut output);
+        assert_eq!(&output[..], &expected[..]);
+        assert_eq!(c.position(), 0xffffffff * 64);
+    }
+
+    #[test]
+    fn test_chacha20_seek_end() {
+        let mut c = ChaCha20::new(&[0u8; 32], &[0u8; 12]);
+        c.seek(0xfffffffe * 64).unwrap();
+        c.process(&[0u8; 64], &mut [0u8; 64]);
+
+        // The keystream is used up, but its end is still a valid position
+        let end = c.position();
+        assert_eq!(end, 0xffffffff * 64);
+        c.seek(end).unwrap();
+        assert_eq!(c.position(), end);
+        c.process(&[], &mut []);
+
+        match c.seek(end + 1) {
+            Err(CounterOverflow) => {},
+            _ => panic!("seeking past the end of the keystream must fail"),
+        }
+        assert_eq!(c.position(), end);
+    }
+
+    #[test]
+    fn test_chacha20_try_process_end() {
+        let mut c = ChaCha20::new(&[0u8; 32], &[0u8; 12]);
+        let end = 0xffffffff * 64;
+        c.seek(end - 10).unwrap();
+
+        let mut output = [0u8; 11];
+        match c.try_process(&[0u8; 11], &mut output) {
+            Err(CounterOverflow) => {},
+            _ => panic!("processing past the end of the keystream must fail"),
+        }
+        assert_eq!(output, [0u8; 11]);
+        assert_eq!(c.position(), end - 10);
+
+        c.try_process(&[0u8; 10], &mut output[..10]).unwrap();
+        assert_eq!(c.position(), end);
+
+        // Encryptor goes through try_process too, so it fails instead of panicking
+        let mut out = [0u8; 1];
+        match c.encrypt(&mut RefReadBuffer::new(&[0u8; 1]), &mut RefWriteBuffer::new(&mut out), true) {
+            Err(CounterOverflow) => {},
+            _ => panic!("encrypting past the end of the keystream must fail"),
+        }
+    }
+
+    #[test]
+    fn test_chacha_reduced_rounds() {
+        // TC1 of draft-strombergson-chacha-test-vectors: all zero 256 bit key and IV
+        let key = [0u8; 32];
+        let nonce = [0u8; 8];
+        let mut output = [0u8; 64];
+
+        let expected = hex_to_bytes("3e00ef2f895f40d67f5bb8e81f09a5a12c840ec3ce9a7f3b181be188ef711a1e\
+            984ce172b9216f419f445367456d5619314a42a3da86b001387bfdb80e0cfe42");
+        ChaCha8::new(&key, &nonce).process(&[0u8; 64], &mut output);
+        assert_eq!(&output[..], &expected[..]);
+
+        let expected = hex_to_bytes("9bf49a6a0755f953811fce125f2683d50429c3bb49e074147e0089a52eae155f\
+            0564f879d27ae3c02ce82834acfa8c793a629f2ca0de6919610be82f411326be");
+        ChaCha12::new(&key, &nonce).process(&[0u8; 64], &mut output);
+        assert_eq!(&output[..], &expected[..]);
+    }
+
+    // Compare the blocks of each SIMD backend with the portable implementation
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn check_simd_backends<const ROUNDS: usize>() {
+        use crate::stream_x86::{self, Backend};
+
+        let key: Vec<u8> = (0..32).collect();
+        let nonce: Vec<u8> = (0..12).collect();
+        for &counter in [0u32, 1, 0x12345678, 0xfffffff0].iter() {
+            let mut c = ChaCha::<ROUNDS>::new(&key, &nonce);
+            c.seek(counter as u64 * 64).unwrap();
+            let mut portable = c;
+            let mut expected = [0u8; 512];
+            for block in expected.chunks_mut(64) {
+                portable.update();
+                block.copy_from_slice(&portable.output);
+            }
+
+            for &backend in [Backend::Sse2, Backend::Ssse3, Backend::Avx2].iter() {
+                if !backend.supported() {
+                    continue;
+                }
+                let size = backend.blocks() * 64;
+                let mut output = [0u8; 512];
+                stream_x86::chacha_blocks(backend, &c.state.words(), ROUNDS, &mut output[..size]);
+                assert_eq!(&output[..size], &expected[..size]);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn test_chacha_simd_backends() {
+        check_simd_backends::<8>();
+        check_simd_backends::<12>();
+        check_simd_backends::<20>();
+    }
+
+    #[test]
+    fn test_chacha20_bulk_matches_blockwise() {
+        // Large inputs take the SIMD path where available, single blocks never do. The end of the
+        // counter has to be left to the portable path.
+        let key: Vec<u8> = (0..32).collect();
+        let nonce = [7u8; 12];
+        for &start in [3u64, 0xffffffea * 64 + 3].iter() {
+            let input: Vec<u8> = (0..21 * 64 - 3).map(|i| i as u8).collect();
+            let mut c = ChaCha20::new(&key, &nonce);
+            c.seek(start).unwrap();
+            let mut expected = vec![0u8; input.len()];
+            for (i, o) in input.chunks(64).zip(expected.chunks_mut(64)) {
+                c.process(i, o);
+            }
+
+            let mut c = ChaCha20::new(&key, &nonce);
+            c.seek(start).unwrap();
+            let mut output = vec![0u8; input.len()];
+            c.process(&input, &mut output);
+            assert_eq!(output, expected);
+        }
+    }
 }
 
 #[cfg(all(test, feature = "with-bench"))]
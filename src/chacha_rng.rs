@@ -0,0 +1,381 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * A cryptographically secure random number generator based on the ChaCha stream cipher.
+ *
+ * The 32 byte seed is used as the ChaCha key. Words 12 and 13 of the ChaCha state hold a 64 bit
+ * block counter and words 14 and 15 a 64 bit stream number, so that a single seed provides 2^64
+ * independent streams of 2^68 words each. The output is the ChaCha keystream, consumed as little
+ * endian 32 bit words, which makes it reproducible against the `ChaChaRng`, `ChaCha12Rng` and
+ * `ChaCha8Rng` generators of the `rand_chacha` crate:
+ *
+ * * `next_u32` consumes one word.
+ * * `next_u64` consumes two words, the first being the low half.
+ * * `fill_bytes` consumes whole words, discarding the unused bytes of the last one.
+ *
+ * Unlike `Fortuna`, the generator is ready for use as soon as it is created and never reseeds
+ * itself; seed it from `OsRng` with `from_rng` where fresh randomness is needed.
+ */
+
+use rand::{Rng, SeedableRng};
+
+use crate::chacha20::chacha_block;
+use crate::cryptoutil::{read_u32v_le, write_u32_le, write_u32v_le};
+
+
+// "expand 32-byte k"
+const CONSTANTS: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+
+// The word position wraps around after 2^64 blocks of 16 words
+const WORD_POS_MASK: u128 = (1 << 68) - 1;
+
+/// A random number generator using ChaCha with `ROUNDS` rounds, which must be even
+#[derive(Clone)]
+pub struct ChaChaRng<const ROUNDS: usize> {
+    // The input of the next block to generate
+    state: [u32; 16],
+    results: [u32; 16],
+    index: usize
+}
+
+/// The random number generator using ChaCha with 20 rounds
+pub type ChaCha20Rng = ChaChaRng<20>;
+/// The random number generator using ChaCha with 12 rounds
+pub type ChaCha12Rng = ChaChaRng<12>;
+/// The random number generator using ChaCha with 8 rounds
+pub type ChaCha8Rng = ChaChaRng<8>;
+
+impl <const ROUNDS: usize> ChaChaRng<ROUNDS> {
+    /// Creates a generator seeded with 32 bytes taken from `rng`, for example `OsRng`.
+    pub fn from_rng<R: Rng>(rng: &mut R) -> ChaChaRng<ROUNDS> {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        ChaChaRng::from_seed(seed)
+    }
+
+    /// Returns the seed the generator was created with.
+    pub fn get_seed(&self) -> [u8; 32] {
+        let mut seed = [0u8; 32];
+        write_u32v_le(&mut seed, &self.state[4..12]);
+        seed
+    }
+
+    /// Selects one of the 2^64 streams of the seed, keeping the word position.
+    pub fn set_stream(&mut self, stream: u64) {
+        self.state[14] = stream as u32;
+        self.state[15] = (stream >> 32) as u32;
+        if self.index < 16 {
+            // Regenerate the buffered block from the new stream
+            let word_pos = self.get_word_pos();
+            self.set_word_pos(word_pos);
+        }
+    }
+
+    /// Returns the stream the generator currently reads from.
+    pub fn get_stream(&self) -> u64 {
+        (self.state[15] as u64) << 32 | self.state[14] as u64
+    }
+
+    /// Moves to the given position in the stream, counted in 32 bit words. Only the lowest 68 bits
+    /// of `word_offset` are used.
+    pub fn set_word_pos(&mut self, word_offset: u128) {
+        self.set_block_counter((word_offset >> 4) as u64);
+        self.generate();
+        self.index = (word_offset & 15) as usize;
+    }
+
+    /// Returns the position in the stream, counted in 32 bit words.
+    pub fn get_word_pos(&self) -> u128 {
+        // The block counter is one past the block in the buffer
+        let buffer_block = self.block_counter().wrapping_sub(1);
+        ((buffer_block as u128) * 16 + self.index as u128) & WORD_POS_MASK
+    }
+
+    fn block_counter(&self) -> u64 {
+        (self.state[13] as u64) << 32 | self.state[12] as u64
+    }
+
+    fn set_block_counter(&mut self, counter: u64) {
+        self.state[12] = counter as u32;
+        self.state[13] = (counter >> 32) as u32;
+    }
+
+    // Puts the next block into self.results
+    fn generate(&mut self) {
+        chacha_block(ROUNDS, &self.state, &mut self.results);
+        let counter = self.block_counter().wrapping_add(1);
+        self.set_block_counter(counter);
+        self.index = 0;
+    }
+
+    fn next_word(&mut self) -> u32 {
+        if self.index == 16 {
+            self.generate();
+        }
+        let word = self.results[self.index];
+        self.index += 1;
+        word
+    }
+}
+
+impl <const ROUNDS: usize> Rng for ChaChaRng<ROUNDS> {
+    fn next_u32(&mut self) -> u32 {
+        self.next_word()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = self.next_word() as u64;
+        let high = self.next_word() as u64;
+        high << 32 | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let word = self.next_word();
+            if chunk.len() == 4 {
+                write_u32_le(chunk, word);
+            } else {
+                let mut bytes = [0u8; 4];
+                write_u32_le(&mut bytes, word);
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+    }
+}
+
+impl <const ROUNDS: usize> SeedableRng<[u8; 32]> for ChaChaRng<ROUNDS> {
+    /// Creates a generator at the start of stream 0 of `seed`.
+    fn from_seed(seed: [u8; 32]) -> ChaChaRng<ROUNDS> {
+        assert!(ROUNDS > 0 && ROUNDS & 1 == 0);
+        let mut state = [0u32; 16];
+        state[..4].copy_from_slice(&CONSTANTS);
+        read_u32v_le(&mut state[4..12], &seed);
+
+        ChaChaRng { state, results: [0; 16], index: 16 }
+    }
+
+    /// Replaces the seed, and starts over at the start of stream 0.
+    fn reseed(&mut self, seed: [u8; 32]) {
+        *self = ChaChaRng::from_seed(seed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{Rng, SeedableRng};
+
+    use crate::chacha_rng::{ChaCha8Rng, ChaCha12Rng, ChaCha20Rng};
+    use crate::fortuna::Fortuna;
+
+    // The expected outputs are those of the rand_chacha crate, whose tests take them from the
+    // ChaCha20 test vectors in draft-nir-cfrg-chacha20-poly1305-04.
+
+    fn next_words<R: Rng>(rng: &mut R, n: usize) -> Vec<u32> {
+        (0..n).map(|_| rng.next_u32()).collect()
+    }
+
+    #[test]
+    fn test_chacha_rng_true_values_a() {
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        let expected = [
+            0xade0b876, 0x903df1a0, 0xe56a5d40, 0x28bd8653,
+            0xb819d2bd, 0x1aed8da0, 0xccef36a8, 0xc70d778b,
+            0x7c5941da, 0x8d485751, 0x3fe02477, 0x374ad8b8,
+            0xf4b8436a, 0x1ca11815, 0x69b687c3, 0x8665eeb2,
+            0xbee7079f, 0x7a385155, 0x7c97ba98, 0x0d082d73,
+            0xa0290fcb, 0x6965e348, 0x3e53c612, 0xed7aee32,
+            0x7621b729, 0x434ee69c, 0xb03371d5, 0xd539d874,
+            0x281fed31, 0x45fb0a51, 0x1f0ae1ac, 0x6f4d794b];
+        assert_eq!(next_words(&mut rng, 32), &expected[..]);
+    }
+
+    #[test]
+    fn test_chacha_rng_true_values_c() {
+        let mut seed = [0u8; 32];
+        seed[1] = 0xff;
+        let expected = [
+            0xfb4dd572, 0x4bc42ef1, 0xdf922636, 0x327f1394,
+            0xa78dea8f, 0x5e269039, 0xa1bebbc1, 0xcaf09aae,
+            0xa25ab213, 0x48a6b46c, 0x1b9d9bcb, 0x092c5be6,
+            0x546ca624, 0x1bec45d5, 0x87f47473, 0x96f0992e];
+
+        // Block 2, by skipping blocks 0 and 1
+        let mut rng1 = ChaCha20Rng::from_seed(seed);
+        next_words(&mut rng1, 32);
+        assert_eq!(next_words(&mut rng1, 16), &expected[..]);
+        assert_eq!(rng1.get_word_pos(), 48);
+
+        // Block 2, by setting the word position
+        let mut rng2 = ChaCha20Rng::from_seed(seed);
+        rng2.set_word_pos(32);
+        assert_eq!(next_words(&mut rng2, 16), &expected[..]);
+        assert_eq!(rng2.get_word_pos(), 48);
+
+        // Part of block 2, starting in the middle of it
+        let mut rng3 = ChaCha20Rng::from_seed(seed);
+        rng3.set_word_pos(37);
+        assert_eq!(next_words(&mut rng3, 11), &expected[5..]);
+    }
+
+    #[test]
+    fn test_chacha_rng_multiple_blocks() {
+        let seed = [
+            0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0,
+            4, 0, 0, 0, 5, 0, 0, 0, 6, 0, 0, 0, 7, 0, 0, 0];
+        let mut rng = ChaCha20Rng::from_seed(seed);
+
+        // Take every 5th word, so that the blocks are crossed at various points
+        let mut results = Vec::new();
+        for _ in 0..16 {
+            results.push(rng.next_u32());
+            next_words(&mut rng, 4);
+        }
+        let expected = [
+            0xf225c81a, 0x315f05b9, 0x86478f6d, 0x2d6b20df,
+            0xebaf14fc, 0x6148cb5f, 0x93b59a73, 0x518da149,
+            0xdfd65b68, 0x4bb161c3, 0x1d7279cf, 0x388ea1dd,
+            0xe6d6d1e6, 0xcee8e2b7, 0xf412a9d0, 0x07d12249];
+        assert_eq!(results, &expected[..]);
+    }
+
+    #[test]
+    fn test_chacha_rng_true_bytes() {
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        let mut results = [0u8; 32];
+        rng.fill_bytes(&mut results);
+        let expected = [
+            118, 184, 224, 173, 160, 241, 61, 144, 64, 93, 106, 229, 83, 134, 189, 40,
+            189, 210, 25, 184, 160, 141, 237, 26, 168, 54, 239, 204, 139, 119, 13, 199];
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_chacha_rng_partial_words() {
+        // fill_bytes discards the rest of a partially used word, and next_u64 takes the low half
+        // first
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        let mut bytes = [0u8; 3];
+        rng.fill_bytes(&mut bytes);
+        assert_eq!(bytes, [0x76, 0xb8, 0xe0]);
+        assert_eq!(rng.get_word_pos(), 1);
+        assert_eq!(rng.next_u64(), 0xe56a5d40903df1a0);
+
+        // A u64 spanning two blocks
+        rng.set_word_pos(15);
+        assert_eq!(rng.next_u64(), 0xbee7079f8665eeb2);
+    }
+
+    #[test]
+    fn test_chacha_rng_stream() {
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        rng.set_stream(2u64 << (24 + 32));
+        assert_eq!(rng.get_stream(), 2u64 << (24 + 32));
+        let expected = [
+            0x374dc6c2, 0x3736d58c, 0xb904e24a, 0xcd3f93ef,
+            0x88228b1a, 0x96a4dfb3, 0x5b76ab72, 0xc727ee54,
+            0x0e0e978a, 0xf3145c95, 0x1b748ea8, 0xf786c297,
+            0x99c28f5f, 0x628314e8, 0x398a19fa, 0x6ded1b53];
+        assert_eq!(next_words(&mut rng, 16), &expected[..]);
+
+        // Changing the stream in the middle of a block keeps the position
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        next_words(&mut rng, 5);
+        rng.set_stream(2u64 << (24 + 32));
+        assert_eq!(rng.get_word_pos(), 5);
+        assert_eq!(next_words(&mut rng, 11), &expected[5..]);
+    }
+
+    #[test]
+    fn test_chacha_rng_counter_crosses_32_bits() {
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        rng.set_word_pos(0xffffffff * 16 + 14);
+        let expected = [0x70939345, 0x1d8bb523, 0x3a1db43d, 0x2829d3a0];
+        assert_eq!(next_words(&mut rng, 4), &expected[..]);
+        assert_eq!(rng.get_word_pos(), 0x100000000 * 16 + 2);
+    }
+
+    #[test]
+    fn test_chacha_rng_word_pos_wraps() {
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        rng.set_word_pos((1 << 68) - 1);
+        assert_eq!(rng.get_word_pos(), (1 << 68) - 1);
+        rng.next_u32();
+        assert_eq!(rng.get_word_pos(), 0);
+        assert_eq!(rng.next_u32(), 0xade0b876);
+    }
+
+    #[test]
+    fn test_chacha_rng_reduced_rounds() {
+        let mut rng = ChaCha8Rng::from_seed([0; 32]);
+        let expected = [
+            0x2fef003e, 0xd6405f89, 0xe8b85b7f, 0xa1a5091f,
+            0xc30e842c, 0x3b7f9ace, 0x88e11b18, 0x1e1a71ef];
+        assert_eq!(next_words(&mut rng, 8), &expected[..]);
+
+        let mut rng = ChaCha12Rng::from_seed([0; 32]);
+        let expected = [
+            0x6a9af49b, 0x53f95507, 0x12ce1f81, 0xd583265f,
+            0xbbc32904, 0x1474e049, 0xa589007e, 0x5f15ae2e];
+        assert_eq!(next_words(&mut rng, 8), &expected[..]);
+    }
+
+    #[test]
+    fn test_chacha_rng_seed() {
+        let mut seed = [0u8; 32];
+        for (i, s) in seed.iter_mut().enumerate() {
+            *s = i as u8;
+        }
+        let mut rng = ChaCha20Rng::from_seed(seed);
+        assert_eq!(rng.get_seed(), seed);
+
+        let first = next_words(&mut rng, 20);
+        rng.set_stream(7);
+        rng.reseed(seed);
+        assert_eq!(rng.get_stream(), 0);
+        assert_eq!(next_words(&mut rng, 20), first);
+    }
+
+    #[test]
+    fn test_chacha_rng_from_rng() {
+        let mut source = Fortuna::from_seed(&[1, 2, 3, 4][..]);
+        let mut source_copy = Fortuna::from_seed(&[1, 2, 3, 4][..]);
+        let mut seed = [0u8; 32];
+        source_copy.fill_bytes(&mut seed);
+
+        let mut rng1 = ChaCha20Rng::from_rng(&mut source);
+        let mut rng2 = ChaCha20Rng::from_seed(seed);
+        assert_eq!(next_words(&mut rng1, 20), next_words(&mut rng2, 20));
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+    use rand::{Rng, SeedableRng};
+    use crate::chacha_rng::ChaCha20Rng;
+
+    #[bench]
+    pub fn chacha20_rng_u32(bh: & mut Bencher) {
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        bh.iter( || {
+            for _ in 0..256 {
+                rng.next_u32();
+            }
+        });
+        bh.bytes = 1024;
+    }
+
+    #[bench]
+    pub fn chacha20_rng_fill_64k(bh: & mut Bencher) {
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        let mut output = [0u8; 65536];
+        bh.iter( || {
+            rng.fill_bytes(&mut output);
+        });
+        bh.bytes = output.len() as u64;
+    }
+}
@@ -0,0 +1,317 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the CMAC function - a Message Authentication Code using a block cipher,
+ * as specified in NIST SP 800-38B and, for AES, in [RFC 4493](https://tools.ietf.org/html/rfc4493).
+ *
+ * AES-CMAC-96 ([RFC 4494](https://tools.ietf.org/html/rfc4494)) is AES-CMAC with the tag
+ * truncated to 96 bits, which is what `Mac::raw_result()` produces when given a 12 byte output.
+ * AES-CMAC-PRF-128 ([RFC 4615](https://tools.ietf.org/html/rfc4615)) is provided by
+ * `aes_cmac_prf_128()`.
+ */
+
+use std::cmp::min;
+
+use crate::aessafe::AesSafe128Encryptor;
+use crate::cryptoutil::copy_memory;
+use crate::mac::{Mac, MacResult};
+use crate::symmetriccipher::BlockEncryptor;
+
+/// Multiply `block`, interpreted as a big endian element of GF(2^64) or GF(2^128), by x. This is
+/// the subkey derivation step shared by CMAC and PMAC. The reduction is done without branching on
+/// the (secret) top bit.
+pub(crate) fn dbl(block: &mut [u8]) {
+    let r: u8 = match block.len() {
+        8 => 0x1b,
+        16 => 0x87,
+        _ => panic!("Only 64 and 128 bit block ciphers are supported")
+    };
+    let mask = 0u8.wrapping_sub(block[0] >> 7);
+    let mut carry = 0;
+    for b in block.iter_mut().rev() {
+        let next_carry = *b >> 7;
+        *b = (*b << 1) | carry;
+        carry = next_carry;
+    }
+    let last = block.len() - 1;
+    block[last] ^= mask & r;
+}
+
+/**
+ * The Cmac struct represents a CMAC function - a Message Authentication Code using a block cipher
+ * with a 64 or 128 bit block size.
+ */
+pub struct Cmac<E> {
+    cipher: E,
+    k1: Vec<u8>,
+    k2: Vec<u8>,
+    // The running CBC-MAC state; once finished, the tag.
+    state: Vec<u8>,
+    // The last (possibly partial) block of input. It is only processed once more input arrives,
+    // since the final block is treated differently.
+    buffer: Vec<u8>,
+    buffer_len: usize,
+    finished: bool
+}
+
+impl <E: BlockEncryptor> Cmac<E> {
+    /**
+     * Create a new Cmac instance.
+     *
+     * # Arguments
+     * * cipher - The block cipher, keyed with the MAC key.
+     */
+    pub fn new(cipher: E) -> Cmac<E> {
+        let block_size = cipher.block_size();
+        let zeros = vec![0u8; block_size];
+        let mut k1 = vec![0u8; block_size];
+        cipher.encrypt_block(&zeros, &mut k1);
+        dbl(&mut k1);
+        let mut k2 = k1.clone();
+        dbl(&mut k2);
+        Cmac {
+            cipher,
+            k1,
+            k2,
+            state: zeros.clone(),
+            buffer: zeros,
+            buffer_len: 0,
+            finished: false
+        }
+    }
+
+    fn process_buffer(&mut self) {
+        for (s, &b) in self.state.iter_mut().zip(self.buffer.iter()) {
+            *s ^= b;
+        }
+        let input = self.state.clone();
+        self.cipher.encrypt_block(&input, &mut self.state);
+        self.buffer_len = 0;
+    }
+
+    fn finish(&mut self) {
+        let block_size = self.buffer.len();
+        if self.buffer_len == block_size {
+            for (b, &k) in self.buffer.iter_mut().zip(self.k1.iter()) {
+                *b ^= k;
+            }
+        } else {
+            self.buffer[self.buffer_len] = 0x80;
+            for b in self.buffer[self.buffer_len + 1..].iter_mut() {
+                *b = 0;
+            }
+            for (b, &k) in self.buffer.iter_mut().zip(self.k2.iter()) {
+                *b ^= k;
+            }
+        }
+        self.process_buffer();
+        self.finished = true;
+    }
+}
+
+impl <E: BlockEncryptor> Mac for Cmac<E> {
+    fn input(&mut self, data: &[u8]) {
+        assert!(!self.finished);
+        let block_size = self.buffer.len();
+        let mut m = data;
+        while !m.is_empty() {
+            if self.buffer_len == block_size {
+                self.process_buffer();
+            }
+            let count = min(block_size - self.buffer_len, m.len());
+            copy_memory(&m[..count], &mut self.buffer[self.buffer_len..]);
+            self.buffer_len += count;
+            m = &m[count..];
+        }
+    }
+
+    fn reset(&mut self) {
+        for s in self.state.iter_mut() {
+            *s = 0;
+        }
+        self.buffer_len = 0;
+        self.finished = false;
+    }
+
+    fn result(&mut self) -> MacResult {
+        let mut code = vec![0u8; self.output_bytes()];
+        self.raw_result(&mut code);
+        MacResult::new_from_owned(code)
+    }
+
+    /// Write the tag to `output`. If `output` is shorter than the block size, the tag is truncated
+    /// to its length, keeping the most significant bytes.
+    fn raw_result(&mut self, output: &mut [u8]) {
+        assert!(output.len() <= self.state.len());
+        if !self.finished {
+            self.finish();
+        }
+        let len = output.len();
+        copy_memory(&self.state[..len], output);
+    }
+
+    fn output_bytes(&self) -> usize { self.state.len() }
+}
+
+/**
+ * Compute AES-CMAC-PRF-128 as specified in RFC 4615. Unlike AES-CMAC, the key may have any length:
+ * keys that aren't 16 bytes long are first hashed to 16 bytes with AES-CMAC under the zero key.
+ *
+ * # Arguments
+ * * key - The variable length key.
+ * * message - The input message.
+ * * output - The 16 byte buffer for the pseudo random output.
+ */
+pub fn aes_cmac_prf_128(key: &[u8], message: &[u8], output: &mut [u8]) {
+    assert!(output.len() == 16);
+    let mut derived_key = [0u8; 16];
+    if key.len() == 16 {
+        derived_key.copy_from_slice(key);
+    } else {
+        let mut cmac = Cmac::new(AesSafe128Encryptor::new(&[0u8; 16]));
+        cmac.input(key);
+        cmac.raw_result(&mut derived_key);
+    }
+    let mut cmac = Cmac::new(AesSafe128Encryptor::new(&derived_key));
+    cmac.input(message);
+    cmac.raw_result(output);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aessafe::AesSafe128Encryptor;
+    use crate::cmac::{Cmac, aes_cmac_prf_128};
+    use crate::mac::{Mac, MacResult};
+    use crate::serialize::hex::FromHex;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    struct Test {
+        key: Vec<u8>,
+        data: Vec<u8>,
+        expected: Vec<u8>
+    }
+
+    // Test vectors from RFC 4493, section 4
+    fn tests() -> Vec<Test> {
+        let key = hex_to_bytes("2b7e151628aed2a6abf7158809cf4f3c");
+        let data = hex_to_bytes(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710");
+        vec![
+            Test {
+                key: key.clone(),
+                data: Vec::new(),
+                expected: hex_to_bytes("bb1d6929e95937287fa37d129b756746")
+            },
+            Test {
+                key: key.clone(),
+                data: data[..16].to_vec(),
+                expected: hex_to_bytes("070a16b46b4d4144f79bdd9dd04a287c")
+            },
+            Test {
+                key: key.clone(),
+                data: data[..40].to_vec(),
+                expected: hex_to_bytes("dfa66747de9ae63030ca32611497c827")
+            },
+            Test {
+                key,
+                data,
+                expected: hex_to_bytes("51f0bebf7e3b9d92fc49741779363cfe")
+            },
+        ]
+    }
+
+    #[test]
+    fn test_cmac_aes() {
+        for t in tests().iter() {
+            let mut cmac = Cmac::new(AesSafe128Encryptor::new(&t.key));
+
+            cmac.input(&t.data);
+            let result = cmac.result();
+            let expected = MacResult::new(&t.expected);
+            assert!(result == expected);
+
+            cmac.reset();
+
+            cmac.input(&t.data);
+            let result2 = cmac.result();
+            assert!(result2 == expected);
+        }
+    }
+
+    #[test]
+    fn test_cmac_aes_incremental() {
+        for t in tests().iter() {
+            let mut cmac = Cmac::new(AesSafe128Encryptor::new(&t.key));
+            for chunk in t.data.chunks(7) {
+                cmac.input(chunk);
+            }
+            let result = cmac.result();
+            assert!(result == MacResult::new(&t.expected));
+        }
+    }
+
+    // RFC 4494 uses the same messages as RFC 4493, with the tags truncated to 96 bits
+    #[test]
+    fn test_cmac_aes_96() {
+        for t in tests().iter() {
+            let mut cmac = Cmac::new(AesSafe128Encryptor::new(&t.key));
+            cmac.input(&t.data);
+            let mut output = [0u8; 12];
+            cmac.raw_result(&mut output);
+            assert_eq!(&output[..], &t.expected[..12]);
+        }
+    }
+
+    // Test vectors from RFC 4615, section 4
+    #[test]
+    fn test_aes_cmac_prf_128() {
+        let message = hex_to_bytes("000102030405060708090a0b0c0d0e0f10111213");
+        let tests = [
+            ("000102030405060708090a0b0c0d0e0fedcb", "84a348a4a45d235babfffc0d2b4da09a"),
+            ("000102030405060708090a0b0c0d0e0f", "980ae87b5f4c9c5214f5b6a8455e4c2d"),
+            ("00010203040506070809", "290d9e112edb09ee141fcf64c0b72f3d"),
+        ];
+        for &(key, expected) in tests.iter() {
+            let mut output = [0u8; 16];
+            aes_cmac_prf_128(&hex_to_bytes(key), &message, &mut output);
+            assert_eq!(&output[..], &hex_to_bytes(expected)[..]);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::aessafe::AesSafe128Encryptor;
+    use crate::cmac::Cmac;
+    use crate::mac::Mac;
+
+    #[bench]
+    pub fn cmac_aes_10(bh: & mut Bencher) {
+        let mut mac = Cmac::new(AesSafe128Encryptor::new(&[0; 16]));
+        let bytes = [1u8; 10];
+        bh.iter( || {
+            mac.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn cmac_aes_1k(bh: & mut Bencher) {
+        let mut mac = Cmac::new(AesSafe128Encryptor::new(&[0; 16]));
+        let bytes = [1u8; 1024];
+        bh.iter( || {
+            mac.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+}
@@ -24,6 +24,13 @@ pub fn write_u64_be(dst: &mut[u8], input: u64) {
     BigEndian::write_u64(dst, input);
 }
 
+/// Write a u128 into a vector, which must be 16 bytes long. The value is written in big-endian
+/// format.
+pub fn write_u128_be(dst: &mut[u8], input: u128) {
+    assert!(dst.len() == 16);
+    BigEndian::write_u128(dst, input);
+}
+
 /// Write a u64 into a vector, which must be 8 bytes long. The value is written in little-endian
 /// format.
 pub fn write_u64_le(dst: &mut[u8], input: u64) {
@@ -87,6 +94,24 @@ pub fn read_u32_be(input: &[u8]) -> u32 {
     BigEndian::read_u32(input)
 }
 
+/// Read the value of a vector of bytes as a u64 value in little-endian format.
+pub fn read_u64_le(input: &[u8]) -> u64 {
+    assert!(input.len() == 8);
+    LittleEndian::read_u64(input)
+}
+
+/// Read the value of a vector of bytes as a u64 value in big-endian format.
+pub fn read_u64_be(input: &[u8]) -> u64 {
+    assert!(input.len() == 8);
+    BigEndian::read_u64(input)
+}
+
+/// Read the value of a vector of bytes as a u128 value in big-endian format.
+pub fn read_u128_be(input: &[u8]) -> u128 {
+    assert!(input.len() == 16);
+    BigEndian::read_u128(input)
+}
+
 /// XOR plaintext and keystream, storing the result in dst.
 pub fn xor_keystream(dst: &mut[u8], plaintext: &[u8], keystream: &[u8]) {
     assert!(dst.len() == plaintext.len());
@@ -128,7 +153,7 @@ pub fn symm_enc_or_dec<S: SynchronousStreamCipher, R: ReadBuffer, W: WriteBuffer
         output: &mut W) ->
         Result<BufferResult, SymmetricCipherError> {
     let count = std::cmp::min(input.remaining(), output.remaining());
-    c.process(input.take_next(count), output.take_next(count));
+    c.try_process(input.take_next(count), output.take_next(count))?;
     if input.is_empty() {
         Ok(BufferUnderflow)
     } else {
@@ -394,6 +419,8 @@ pub mod test {
 
     use crate::cryptoutil::{add_bytes_to_bits, add_bytes_to_bits_tuple};
     use crate::digest::Digest;
+    use crate::serialize::hex::FromHex;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
 
     /// Feed 1,000,000 'a's into the digest with varying input sizes and check that the result is
     /// correct.
@@ -419,6 +446,49 @@ pub mod test {
         assert!(expected == &result_str[..]);
     }
 
+    /// Run the first outer iterations of the Monte Carlo tests of the AES candidate submissions
+    /// (ecb_e_m.txt and ecb_d_m.txt): for each of the outer iterations, the block is encrypted (or
+    /// decrypted) 10000 times and the key is then updated with the last output blocks. The keys and
+    /// blocks start out as zero, and `expected` holds the final block of each outer iteration.
+    ///
+    /// If `byte_reversed` is set, the submission writes keys and blocks with their bytes in the
+    /// reverse of the order taken by the cipher, so they are reversed on the way in and out.
+    pub fn test_aes_candidate_monte_carlo<C, F>(new_cipher: F, key_size: usize, decrypt: bool,
+            byte_reversed: bool, expected: &[&str])
+            where C: BlockEncryptor + BlockDecryptor, F: Fn(&[u8]) -> C {
+        let mut key = vec![0u8; key_size];
+        let mut block = [0u8; 16];
+        for ct in expected.iter() {
+            let mut cipher_key = key.clone();
+            if byte_reversed {
+                cipher_key.reverse();
+            }
+            let cipher = new_cipher(&cipher_key);
+
+            let mut last = [0u8; 32];
+            for _ in 0..10000 {
+                last.copy_within(16.., 0);
+                let mut input = block;
+                if byte_reversed {
+                    input.reverse();
+                }
+                if decrypt {
+                    cipher.decrypt_block(&input, &mut block);
+                } else {
+                    cipher.encrypt_block(&input, &mut block);
+                }
+                if byte_reversed {
+                    block.reverse();
+                }
+                last[16..].copy_from_slice(&block);
+            }
+            assert_eq!(&block[..], &ct.from_hex().unwrap()[..]);
+            for (k, &l) in key.iter_mut().zip(last[32 - key_size..].iter()) {
+                *k ^= l;
+            }
+        }
+    }
+
     // A normal addition - no overflow occurs
     #[test]
     fn test_add_bytes_to_bits_ok() {
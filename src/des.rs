@@ -0,0 +1,428 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the Data Encryption Standard (DES) and the Triple Data Encryption
+ * Algorithm (TDEA, or 3DES) in its EDE form, as specified in NIST SP 800-67.
+ *
+ * DES has a 56 bit key and should only be used to interoperate with legacy systems. `TdesEde3`
+ * uses three independent keys (keying option 1) and `TdesEde2` uses two keys, with K3 = K1
+ * (keying option 2). The least significant bit of every key byte is a parity bit and is ignored.
+ *
+ * The implementation doesn't use any secret dependent table lookups or branches: each S-box row is
+ * packed into a 64 bit word, the row is selected with masks and the column with a shift.
+ */
+
+use crate::cryptoutil::{read_u64_be, write_u64_be};
+use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+use crate::util::fixed_time_eq;
+
+// The bit permutations below use the numbering of FIPS 46-3: bit 1 is the most significant bit.
+
+static IP: [u8; 64] = [
+    58, 50, 42, 34, 26, 18, 10, 2, 60, 52, 44, 36, 28, 20, 12, 4,
+    62, 54, 46, 38, 30, 22, 14, 6, 64, 56, 48, 40, 32, 24, 16, 8,
+    57, 49, 41, 33, 25, 17, 9, 1, 59, 51, 43, 35, 27, 19, 11, 3,
+    61, 53, 45, 37, 29, 21, 13, 5, 63, 55, 47, 39, 31, 23, 15, 7];
+
+static FP: [u8; 64] = [
+    40, 8, 48, 16, 56, 24, 64, 32, 39, 7, 47, 15, 55, 23, 63, 31,
+    38, 6, 46, 14, 54, 22, 62, 30, 37, 5, 45, 13, 53, 21, 61, 29,
+    36, 4, 44, 12, 52, 20, 60, 28, 35, 3, 43, 11, 51, 19, 59, 27,
+    34, 2, 42, 10, 50, 18, 58, 26, 33, 1, 41, 9, 49, 17, 57, 25];
+
+static E: [u8; 48] = [
+    32, 1, 2, 3, 4, 5, 4, 5, 6, 7, 8, 9, 8, 9, 10, 11,
+    12, 13, 12, 13, 14, 15, 16, 17, 16, 17, 18, 19, 20, 21, 20, 21,
+    22, 23, 24, 25, 24, 25, 26, 27, 28, 29, 28, 29, 30, 31, 32, 1];
+
+static P: [u8; 32] = [
+    16, 7, 20, 21, 29, 12, 28, 17, 1, 15, 23, 26, 5, 18, 31, 10,
+    2, 8, 24, 14, 32, 27, 3, 9, 19, 13, 30, 6, 22, 11, 4, 25];
+
+static PC1: [u8; 56] = [
+    57, 49, 41, 33, 25, 17, 9, 1, 58, 50, 42, 34, 26, 18,
+    10, 2, 59, 51, 43, 35, 27, 19, 11, 3, 60, 52, 44, 36,
+    63, 55, 47, 39, 31, 23, 15, 7, 62, 54, 46, 38, 30, 22,
+    14, 6, 61, 53, 45, 37, 29, 21, 13, 5, 28, 20, 12, 4];
+
+static PC2: [u8; 48] = [
+    14, 17, 11, 24, 1, 5, 3, 28, 15, 6, 21, 10,
+    23, 19, 12, 4, 26, 8, 16, 7, 27, 20, 13, 2,
+    41, 52, 31, 37, 47, 55, 30, 40, 51, 45, 33, 48,
+    44, 49, 39, 56, 34, 53, 46, 42, 50, 36, 29, 32];
+
+static SHIFTS: [u32; 16] = [1, 1, 2, 2, 2, 2, 2, 2, 1, 2, 2, 2, 2, 2, 2, 1];
+
+// The S-boxes S1 to S8. Every row is packed into a u64, with column c in bits 4c to 4c + 3.
+static SBOXES: [[u64; 4]; 8] = [
+    [0x7095c6a38bf21d4e, 0x8359bc6a1d2e47f0, 0x05a379cfb26d8e14, 0xd60ae3b5719428cf],
+    [0xa50cd27943b6e81f, 0x5b96a10ce82f74d3, 0xf2396c851d4ab7e0, 0x9e50c76b24f31a8d],
+    [0x824b7cd15f36e90a, 0x1fbce582a643907d, 0x7ea5c21b03f8946d, 0xc25b3ef478960da1],
+    [0xf4cb5821a9603ed7, 0x9ea1c27430f65b8d, 0x4825e31fd7bc096a, 0xe27cb5498d1a60f3],
+    [0x9e0df3586ba714c2, 0x6893af051d74c2be, 0xe0365c9f87dab124, 0x354a90f6d2e17c8b],
+    [0xb57e43d08629fa1c, 0x83b0ed1659c724fa, 0x6bd1a4073c825fe9, 0xd80671ebaf59c234],
+    [0x16a579c3d80fe2b4, 0x68f2c53ea1947b0d, 0x295086fae73cdb41, 0xc32ef0597a418db6],
+    [0x7c05e39a1bf6482d, 0x29e0b65c473a8df1, 0x853fda602ec914b7, 0xb65309cfd8a47e12]];
+
+// The weak and semi-weak keys from NIST SP 800-67, section 3.3.2, with the parity bits cleared
+static WEAK_KEYS: [[u8; 8]; 16] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0xfe, 0xfe, 0xfe, 0xfe, 0xfe, 0xfe, 0xfe, 0xfe],
+    [0xe0, 0xe0, 0xe0, 0xe0, 0xf0, 0xf0, 0xf0, 0xf0],
+    [0x1e, 0x1e, 0x1e, 0x1e, 0x0e, 0x0e, 0x0e, 0x0e],
+    [0x00, 0x1e, 0x00, 0x1e, 0x00, 0x0e, 0x00, 0x0e],
+    [0x1e, 0x00, 0x1e, 0x00, 0x0e, 0x00, 0x0e, 0x00],
+    [0x00, 0xe0, 0x00, 0xe0, 0x00, 0xf0, 0x00, 0xf0],
+    [0xe0, 0x00, 0xe0, 0x00, 0xf0, 0x00, 0xf0, 0x00],
+    [0x00, 0xfe, 0x00, 0xfe, 0x00, 0xfe, 0x00, 0xfe],
+    [0xfe, 0x00, 0xfe, 0x00, 0xfe, 0x00, 0xfe, 0x00],
+    [0x1e, 0xe0, 0x1e, 0xe0, 0x0e, 0xf0, 0x0e, 0xf0],
+    [0xe0, 0x1e, 0xe0, 0x1e, 0xf0, 0x0e, 0xf0, 0x0e],
+    [0x1e, 0xfe, 0x1e, 0xfe, 0x0e, 0xfe, 0x0e, 0xfe],
+    [0xfe, 0x1e, 0xfe, 0x1e, 0xfe, 0x0e, 0xfe, 0x0e],
+    [0xe0, 0xfe, 0xe0, 0xfe, 0xf0, 0xfe, 0xf0, 0xfe],
+    [0xfe, 0xe0, 0xfe, 0xe0, 0xfe, 0xf0, 0xfe, 0xf0]];
+
+/**
+ * Check whether `key` is one of the 4 weak or 12 semi-weak DES keys. Encryption under a weak key is
+ * an involution, and each semi-weak key decrypts what its partner encrypts. The parity bits are
+ * ignored and the check runs in constant time.
+ */
+pub fn is_weak_key(key: &[u8]) -> bool {
+    assert!(key.len() == 8);
+    let mut masked = [0u8; 8];
+    for (m, &k) in masked.iter_mut().zip(key.iter()) {
+        *m = k & 0xfe;
+    }
+    let mut weak = false;
+    for weak_key in WEAK_KEYS.iter() {
+        weak |= fixed_time_eq(&masked, weak_key);
+    }
+    weak
+}
+
+// Apply the permutation `table` to the low `input_bits` bits of `input`. The positions are public,
+// so this doesn't leak anything about `input`.
+fn permute(input: u64, input_bits: u32, table: &[u8]) -> u64 {
+    let mut output = 0;
+    for &pos in table.iter() {
+        output = (output << 1) | ((input >> (input_bits - pos as u32)) & 1);
+    }
+    output
+}
+
+fn sbox(sbox: &[u64; 4], input: u64) -> u64 {
+    let row = ((input >> 4) & 2) | (input & 1);
+    let col = (input >> 1) & 0xf;
+    let mut bits = 0;
+    for (r, &packed) in sbox.iter().enumerate() {
+        // All ones if r == row, without branching
+        let mask = 0u64.wrapping_sub(((r as u64 ^ row).wrapping_sub(1)) >> 63);
+        bits |= packed & mask;
+    }
+    (bits >> (col * 4)) & 0xf
+}
+
+fn feistel(r: u32, subkey: u64) -> u32 {
+    let x = permute(r as u64, 32, &E) ^ subkey;
+    let mut s = 0;
+    for (i, sb) in SBOXES.iter().enumerate() {
+        s = (s << 4) | sbox(sb, (x >> (42 - 6 * i)) & 0x3f);
+    }
+    permute(s, 32, &P) as u32
+}
+
+/// The DES block cipher with a 64 bit key (56 effective bits)
+#[derive(Clone, Copy)]
+pub struct Des {
+    subkeys: [u64; 16]
+}
+
+impl Des {
+    pub fn new(key: &[u8]) -> Des {
+        assert!(key.len() == 8);
+        let cd = permute(read_u64_be(key), 64, &PC1);
+        let mut c = cd >> 28;
+        let mut d = cd & 0x0fffffff;
+        let mut subkeys = [0u64; 16];
+        for (subkey, &shift) in subkeys.iter_mut().zip(SHIFTS.iter()) {
+            c = ((c << shift) | (c >> (28 - shift))) & 0x0fffffff;
+            d = ((d << shift) | (d >> (28 - shift))) & 0x0fffffff;
+            *subkey = permute((c << 28) | d, 56, &PC2);
+        }
+        Des {
+            subkeys
+        }
+    }
+
+    fn crypt(&self, block: u64, decrypt: bool) -> u64 {
+        let block = permute(block, 64, &IP);
+        let mut l = (block >> 32) as u32;
+        let mut r = block as u32;
+        for i in 0..16 {
+            let subkey = if decrypt { self.subkeys[15 - i] } else { self.subkeys[i] };
+            let t = r;
+            r = l ^ feistel(r, subkey);
+            l = t;
+        }
+        permute(((r as u64) << 32) | l as u64, 64, &FP)
+    }
+
+    fn encrypt(&self, block: u64) -> u64 {
+        self.crypt(block, false)
+    }
+
+    fn decrypt(&self, block: u64) -> u64 {
+        self.crypt(block, true)
+    }
+}
+
+/// Triple DES in EDE mode with three independent keys (24 byte key K1 || K2 || K3)
+#[derive(Clone, Copy)]
+pub struct TdesEde3 {
+    k1: Des,
+    k2: Des,
+    k3: Des
+}
+
+impl TdesEde3 {
+    pub fn new(key: &[u8]) -> TdesEde3 {
+        assert!(key.len() == 24);
+        TdesEde3 {
+            k1: Des::new(&key[0..8]),
+            k2: Des::new(&key[8..16]),
+            k3: Des::new(&key[16..24])
+        }
+    }
+}
+
+/// Triple DES in EDE mode with two keys (16 byte key K1 || K2, with K3 = K1)
+#[derive(Clone, Copy)]
+pub struct TdesEde2 {
+    k1: Des,
+    k2: Des
+}
+
+impl TdesEde2 {
+    pub fn new(key: &[u8]) -> TdesEde2 {
+        assert!(key.len() == 16);
+        TdesEde2 {
+            k1: Des::new(&key[0..8]),
+            k2: Des::new(&key[8..16])
+        }
+    }
+}
+
+fn encrypt_with(input: &[u8], output: &mut [u8], f: impl Fn(u64) -> u64) {
+    assert!(input.len() == 8);
+    assert!(output.len() == 8);
+    write_u64_be(output, f(read_u64_be(input)));
+}
+
+impl BlockEncryptor for Des {
+    fn block_size(&self) -> usize { 8 }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        encrypt_with(input, output, |x| self.encrypt(x));
+    }
+}
+
+impl BlockDecryptor for Des {
+    fn block_size(&self) -> usize { 8 }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        encrypt_with(input, output, |x| self.decrypt(x));
+    }
+}
+
+impl BlockEncryptor for TdesEde3 {
+    fn block_size(&self) -> usize { 8 }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        encrypt_with(input, output, |x| self.k3.encrypt(self.k2.decrypt(self.k1.encrypt(x))));
+    }
+}
+
+impl BlockDecryptor for TdesEde3 {
+    fn block_size(&self) -> usize { 8 }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        encrypt_with(input, output, |x| self.k1.decrypt(self.k2.encrypt(self.k3.decrypt(x))));
+    }
+}
+
+impl BlockEncryptor for TdesEde2 {
+    fn block_size(&self) -> usize { 8 }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        encrypt_with(input, output, |x| self.k1.encrypt(self.k2.decrypt(self.k1.encrypt(x))));
+    }
+}
+
+impl BlockDecryptor for TdesEde2 {
+    fn block_size(&self) -> usize { 8 }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        encrypt_with(input, output, |x| self.k1.decrypt(self.k2.encrypt(self.k1.decrypt(x))));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blockmodes::{CbcDecryptor, CbcEncryptor, NoPadding};
+    use crate::buffer::{RefReadBuffer, RefWriteBuffer};
+    use crate::des::{Des, TdesEde2, TdesEde3, is_weak_key, WEAK_KEYS};
+    use crate::serialize::hex::FromHex;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor, Decryptor, Encryptor};
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    fn check_block<E: BlockEncryptor + BlockDecryptor>(cipher: &E, plaintext: &[u8], ciphertext: &[u8]) {
+        let mut output = vec![0u8; plaintext.len()];
+        for (p, o) in plaintext.chunks(8).zip(output.chunks_mut(8)) {
+            cipher.encrypt_block(p, o);
+        }
+        assert_eq!(&output[..], ciphertext);
+        for (c, o) in ciphertext.chunks(8).zip(output.chunks_mut(8)) {
+            cipher.decrypt_block(c, o);
+        }
+        assert_eq!(&output[..], plaintext);
+    }
+
+    // Known answer tests from NIST SP 800-20 (the CAVP TECBvartext, TECBvarkey, TECBpermop and
+    // TECBsubtab files), which use K1 = K2 = K3, and J. Orlin Grabbe's worked DES example.
+    fn des_tests() -> Vec<(&'static str, &'static str, &'static str)> {
+        vec![
+            ("0101010101010101", "8000000000000000", "95f8a5e5dd31d900"),
+            ("0101010101010101", "4000000000000000", "dd7f121ca5015619"),
+            ("0101010101010101", "0000000000000001", "166b40b44aba4bd6"),
+            ("8001010101010101", "0000000000000000", "95a8d72813daa94d"),
+            ("0101010101010180", "0000000000000000", "9cc62df43b6eed74"),
+            ("1046913489980131", "0000000000000000", "88d55e54f54c97b4"),
+            ("7ca110454a1a6e57", "01a1d6d039776742", "690f5b0d9a26939b"),
+            ("0131d9619dc1376e", "5cd54ca83def57da", "7a389d10354bd271"),
+            ("133457799bbcdff1", "0123456789abcdef", "85e813540f0ab405"),
+        ]
+    }
+
+    #[test]
+    fn test_des() {
+        for &(key, plaintext, ciphertext) in des_tests().iter() {
+            let key = hex_to_bytes(key);
+            let plaintext = hex_to_bytes(plaintext);
+            let ciphertext = hex_to_bytes(ciphertext);
+            check_block(&Des::new(&key), &plaintext, &ciphertext);
+
+            // With K1 = K2 = K3, TDEA reduces to DES
+            let mut key3 = key.clone();
+            key3.extend_from_slice(&key);
+            check_block(&TdesEde2::new(&key3), &plaintext, &ciphertext);
+            key3.extend_from_slice(&key);
+            check_block(&TdesEde3::new(&key3), &plaintext, &ciphertext);
+        }
+    }
+
+    // The TDEA example from NIST SP 800-67, appendix B
+    #[test]
+    fn test_tdes_ede3_sp800_67() {
+        let key = hex_to_bytes("0123456789abcdef23456789abcdef01456789abcdef0123");
+        let plaintext = b"The qufck brown fox jump";
+        let ciphertext = hex_to_bytes("a826fd8ce53b855fcce21c8112256fe668d5c05dd9b6b900");
+        check_block(&TdesEde3::new(&key), plaintext, &ciphertext);
+    }
+
+    #[test]
+    fn test_tdes_ede2() {
+        let key = hex_to_bytes("0123456789abcdef23456789abcdef01");
+        check_block(&TdesEde2::new(&key), b"The qufc", &hex_to_bytes("c44862f70cf2fbdc"));
+    }
+
+    #[test]
+    fn test_tdes_ede3_cbc() {
+        let key = hex_to_bytes("0123456789abcdef23456789abcdef01456789abcdef0123");
+        let iv = hex_to_bytes("f69f2445df4f9b17");
+        let plaintext = b"The qufck brown fox jump";
+        let expected = hex_to_bytes("a5c282bad0de3774becd2e04386b589fb5057d8552fc4336");
+
+        let mut ciphertext = vec![0u8; plaintext.len()];
+        {
+            let mut enc = CbcEncryptor::new(TdesEde3::new(&key), NoPadding, iv.clone());
+            let mut buff_in = RefReadBuffer::new(plaintext);
+            let mut buff_out = RefWriteBuffer::new(&mut ciphertext);
+            assert!(enc.encrypt(&mut buff_in, &mut buff_out, true).is_ok());
+        }
+        assert_eq!(ciphertext, expected);
+
+        let mut decrypted = vec![0u8; plaintext.len()];
+        {
+            let mut dec = CbcDecryptor::new(TdesEde3::new(&key), NoPadding, iv);
+            let mut buff_in = RefReadBuffer::new(&ciphertext);
+            let mut buff_out = RefWriteBuffer::new(&mut decrypted);
+            assert!(dec.decrypt(&mut buff_in, &mut buff_out, true).is_ok());
+        }
+        assert_eq!(&decrypted[..], &plaintext[..]);
+    }
+
+    fn encrypt_twice(k1: &[u8], k2: &[u8], input: &[u8]) -> Vec<u8> {
+        let mut tmp = [0u8; 8];
+        let mut output = vec![0u8; 8];
+        Des::new(k1).encrypt_block(input, &mut tmp);
+        Des::new(k2).encrypt_block(&tmp, &mut output);
+        output
+    }
+
+    #[test]
+    fn test_weak_keys() {
+        let plaintext = hex_to_bytes("0123456789abcdef");
+        // Set the parity bits, which must be ignored
+        let keys: Vec<Vec<u8>> = WEAK_KEYS.iter().map(|k| k.iter().map(|&b| b | 1).collect()).collect();
+        for key in keys.iter() {
+            assert!(is_weak_key(key));
+        }
+        for key in keys[..4].iter() {
+            assert_eq!(encrypt_twice(key, key, &plaintext), plaintext);
+        }
+        for pair in keys[4..].chunks(2) {
+            assert_eq!(encrypt_twice(&pair[0], &pair[1], &plaintext), plaintext);
+            assert_eq!(encrypt_twice(&pair[1], &pair[0], &plaintext), plaintext);
+        }
+        assert!(!is_weak_key(&hex_to_bytes("0123456789abcdef")));
+        assert!(!is_weak_key(&hex_to_bytes("0101010101010102")));
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::des::{Des, TdesEde3};
+    use crate::symmetriccipher::BlockEncryptor;
+
+    #[bench]
+    pub fn des(bh: &mut Bencher) {
+        let des = Des::new(&[1u8; 8]);
+        let input = [1u8; 8];
+        let mut output = [0u8; 8];
+        bh.iter( || {
+            des.encrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn tdes_ede3(bh: &mut Bencher) {
+        let tdes = TdesEde3::new(&[1u8; 24]);
+        let input = [1u8; 8];
+        let mut output = [0u8; 8];
+        bh.iter( || {
+            tdes.encrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
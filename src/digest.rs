@@ -79,3 +79,59 @@ pub trait Digest {
         buf[..].to_hex()
     }
 }
+
+/**
+ * The ExtendableOutput trait specifies an interface common to extendable output functions (XOFs),
+ * such as SHAKE128 and SHAKE256, which produce output of any length. Absorbing the input and
+ * squeezing the output are done by separate types: finalizing the XOF turns it into an
+ * `XofReader`.
+ */
+pub trait ExtendableOutput {
+    /// The type squeezing the output of the XOF.
+    type Reader: XofReader;
+
+    /**
+     * Provide message data.
+     *
+     * # Arguments
+     *
+     * * input - A vector of message data
+     */
+    fn input(&mut self, input: &[u8]);
+
+    /**
+     * Finish absorbing the message, and return the reader for the output.
+     */
+    fn finalize_xof(self) -> Self::Reader;
+
+    /**
+     * Reset the XOF, so that it can absorb a new message.
+     */
+    fn reset(&mut self);
+
+    /**
+     * Convenience function that feeds a string into an XOF.
+     *
+     * # Arguments
+     *
+     * * `input` The string to feed into the XOF
+     */
+    fn input_str(&mut self, input: &str) {
+        self.input(input.as_bytes());
+    }
+}
+
+/**
+ * The XofReader trait squeezes the output of an extendable output function.
+ */
+pub trait XofReader {
+    /**
+     * Read the next `out.len()` bytes of output. This method may be called any number of times,
+     * and the output doesn't depend on how it is split across calls.
+     *
+     * # Arguments
+     *
+     * * out - the vector to hold the output
+     */
+    fn read(&mut self, out: &mut [u8]);
+}
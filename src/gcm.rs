@@ -0,0 +1,264 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+This public module implements Galois/Counter Mode (GCM), as specified in NIST SP 800-38D, for any
+block cipher with a 128 bit block size, such as Camellia or ARIA. For AES, `aes_gcm::AesGcm`
+can be used as well.
+
+Unlike `AesGcm`, nonces of any length are supported: 12 byte nonces are used directly, other
+nonces are hashed with GHASH first. Only the right most 32 bits of the counter block are
+incremented, as the specification requires. As the counter would then repeat, a message may be at
+most 2^32 - 2 blocks long; longer messages panic.
+*/
+
+use crate::aead::{AeadEncryptor, AeadDecryptor};
+use crate::cryptoutil::copy_memory;
+use crate::ghash::Ghash;
+use crate::symmetriccipher::BlockEncryptor;
+use crate::util::fixed_time_eq;
+
+// The maximum number of blocks of a message, from the limit of 2^39 - 256 bits in SP 800-38D
+const MAX_BLOCKS: u64 = (1 << 32) - 2;
+
+pub struct Gcm<E> {
+    cipher: E,
+    counter: [u8; 16],
+    // The number of blocks processed so far
+    blocks: u64,
+    mac: Ghash,
+    finished: bool,
+    end_tag: [u8; 16]
+}
+
+// Increment the right most 32 bits of the counter block
+fn inc32(counter: &mut [u8; 16]) {
+    for b in counter[12..].iter_mut().rev() {
+        *b = b.wrapping_add(1);
+        if *b != 0 {
+            break;
+        }
+    }
+}
+
+impl <E: BlockEncryptor> Gcm<E> {
+    /**
+     * Create a new Gcm instance.
+     *
+     * # Arguments
+     * * cipher - The block cipher, keyed with the encryption key. Its block size must be 16 bytes.
+     * * nonce - The nonce, which must never be reused with the same key. 12 bytes is recommended.
+     * * aad - The additional authenticated data.
+     */
+    pub fn new(cipher: E, nonce: &[u8], aad: &[u8]) -> Gcm<E> {
+        assert!(cipher.block_size() == 16);
+        assert!(!nonce.is_empty());
+
+        let zeros = [0u8; 16];
+        let mut hash_key = [0u8; 16];
+        cipher.encrypt_block(&zeros, &mut hash_key);
+
+        let mut counter = [0u8; 16];
+        if nonce.len() == 12 {
+            copy_memory(nonce, &mut counter);
+            counter[15] = 1;
+        } else {
+            // GHASH of the nonce with an empty A
+            counter = Ghash::new(&hash_key).input_c(nonce).result();
+        }
+        let mut end_tag = [0u8; 16];
+        cipher.encrypt_block(&counter, &mut end_tag);
+        inc32(&mut counter);
+
+        Gcm {
+            cipher,
+            counter,
+            blocks: 0,
+            mac: Ghash::new(&hash_key).input_a(aad),
+            finished: false,
+            end_tag
+        }
+    }
+
+    fn process(&mut self, input: &[u8], output: &mut [u8]) {
+        let blocks = input.len().div_ceil(16) as u64;
+        assert!(blocks <= MAX_BLOCKS - self.blocks, "GCM messages are limited to 2^32 - 2 blocks");
+        self.blocks += blocks;
+
+        let mut keystream = [0u8; 16];
+        for (i, o) in input.chunks(16).zip(output.chunks_mut(16)) {
+            self.cipher.encrypt_block(&self.counter, &mut keystream);
+            inc32(&mut self.counter);
+            for ((o, &i), &k) in o.iter_mut().zip(i.iter()).zip(keystream.iter()) {
+                *o = i ^ k;
+            }
+        }
+    }
+}
+
+impl <E: BlockEncryptor> AeadEncryptor for Gcm<E> {
+    fn encrypt(&mut self, input: &[u8], output: &mut [u8], tag: &mut [u8]) {
+        assert!(input.len() == output.len());
+        assert!(tag.len() == 16);
+        assert!(!self.finished);
+        self.finished = true;
+        self.process(input, output);
+        let result = self.mac.input_c(output).result();
+        for ((t, &r), &e) in tag.iter_mut().zip(result.iter()).zip(self.end_tag.iter()) {
+            *t = r ^ e;
+        }
+    }
+}
+
+impl <E: BlockEncryptor> AeadDecryptor for Gcm<E> {
+    fn decrypt(&mut self, input: &[u8], output: &mut [u8], tag: &[u8]) -> bool {
+        assert!(input.len() == output.len());
+        assert!(!self.finished);
+        self.finished = true;
+        let mut calc_tag = self.mac.input_c(input).result();
+        for (c, &e) in calc_tag.iter_mut().zip(self.end_tag.iter()) {
+            *c ^= e;
+        }
+        if fixed_time_eq(&calc_tag, tag) {
+            self.process(input, output);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aead::{AeadEncryptor, AeadDecryptor};
+    use crate::aessafe::AesSafe128Encryptor;
+    use crate::gcm::{Gcm, MAX_BLOCKS, inc32};
+    use crate::serialize::hex::FromHex;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    struct Test {
+        key: Vec<u8>,
+        nonce: Vec<u8>,
+        aad: Vec<u8>,
+        plaintext: Vec<u8>,
+        ciphertext: Vec<u8>,
+        tag: Vec<u8>
+    }
+
+    // Test cases 2, 4, 5 and 6 from the GCM specification, which include nonces of 8 and 60 bytes
+    fn tests() -> Vec<Test> {
+        let plaintext = hex_to_bytes(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+             1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39");
+        let aad = hex_to_bytes("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        vec![
+            Test {
+                key: hex_to_bytes("00000000000000000000000000000000"),
+                nonce: hex_to_bytes("000000000000000000000000"),
+                aad: Vec::new(),
+                plaintext: hex_to_bytes("00000000000000000000000000000000"),
+                ciphertext: hex_to_bytes("0388dace60b6a392f328c2b971b2fe78"),
+                tag: hex_to_bytes("ab6e47d42cec13bdf53a67b21257bddf")
+            },
+            Test {
+                key: hex_to_bytes("feffe9928665731c6d6a8f9467308308"),
+                nonce: hex_to_bytes("cafebabefacedbaddecaf888"),
+                aad: aad.clone(),
+                plaintext: plaintext.clone(),
+                ciphertext: hex_to_bytes(
+                    "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+                     21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091"),
+                tag: hex_to_bytes("5bc94fbc3221a5db94fae95ae7121a47")
+            },
+            Test {
+                key: hex_to_bytes("feffe9928665731c6d6a8f9467308308"),
+                nonce: hex_to_bytes("cafebabefacedbad"),
+                aad: aad.clone(),
+                plaintext: plaintext.clone(),
+                ciphertext: hex_to_bytes(
+                    "61353b4c2806934a777ff51fa22a4755699b2a714fcdc6f83766e5f97b6c7423\
+                     73806900e49f24b22b097544d4896b424989b5e1ebac0f07c23f4598"),
+                tag: hex_to_bytes("3612d2e79e3b0785561be14aaca2fccb")
+            },
+            Test {
+                key: hex_to_bytes("feffe9928665731c6d6a8f9467308308"),
+                nonce: hex_to_bytes(
+                    "9313225df88406e555909c5aff5269aa6a7a9538534f7da1e4c303d2a318a728\
+                     c3c0c95156809539fcf0e2429a6b525416aedbf5a0de6a57a637b39b"),
+                aad,
+                plaintext,
+                ciphertext: hex_to_bytes(
+                    "8ce24998625615b603a033aca13fb894be9112a5c3a211a8ba262a3cca7e2ca7\
+                     01e4a9a4fba43c90ccdcb281d48c7c6fd62875d2aca417034c34aee5"),
+                tag: hex_to_bytes("619cc5aefffe0bfa462af43c1699d050")
+            },
+        ]
+    }
+
+    fn new_gcm(t: &Test) -> Gcm<AesSafe128Encryptor> {
+        Gcm::new(AesSafe128Encryptor::new(&t.key), &t.nonce, &t.aad)
+    }
+
+    #[test]
+    fn test_gcm_aes() {
+        for t in tests().iter() {
+            let mut ciphertext = vec![0u8; t.plaintext.len()];
+            let mut tag = [0u8; 16];
+            new_gcm(t).encrypt(&t.plaintext, &mut ciphertext, &mut tag);
+            assert_eq!(ciphertext, t.ciphertext);
+            assert_eq!(&tag[..], &t.tag[..]);
+        }
+    }
+
+    #[test]
+    fn test_gcm_aes_decrypt() {
+        for t in tests().iter() {
+            let mut plaintext = vec![0u8; t.ciphertext.len()];
+            assert!(new_gcm(t).decrypt(&t.ciphertext, &mut plaintext, &t.tag));
+            assert_eq!(plaintext, t.plaintext);
+
+            let mut bad_tag = t.tag.clone();
+            bad_tag[0] ^= 1;
+            assert!(!new_gcm(t).decrypt(&t.ciphertext, &mut plaintext, &bad_tag));
+        }
+    }
+
+    // A message ending on the last block before the counter comes back to its initial value
+    #[test]
+    fn test_gcm_max_blocks() {
+        let t = &tests()[1];
+        let mut gcm = new_gcm(t);
+        gcm.blocks = MAX_BLOCKS - 4;
+        let mut ciphertext = vec![0u8; t.plaintext.len()];
+        let mut tag = [0u8; 16];
+        gcm.encrypt(&t.plaintext, &mut ciphertext, &mut tag);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_gcm_too_many_blocks() {
+        let t = &tests()[1];
+        let mut gcm = new_gcm(t);
+        gcm.blocks = MAX_BLOCKS - 3;
+        let mut ciphertext = vec![0u8; t.plaintext.len()];
+        let mut tag = [0u8; 16];
+        gcm.encrypt(&t.plaintext, &mut ciphertext, &mut tag);
+    }
+
+    #[test]
+    fn test_inc32() {
+        let mut counter = [0xffu8; 16];
+        inc32(&mut counter);
+        let mut expected = [0xffu8; 16];
+        for b in expected[12..].iter_mut() {
+            *b = 0;
+        }
+        assert_eq!(counter, expected);
+    }
+}
@@ -0,0 +1,150 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+This public module implements GMAC, the Galois Message Authentication Code from NIST SP 800-38D.
+GMAC is GCM with an empty plaintext: all of the input is authenticated data, and the tag is
+GHASH(H, A, {}) encrypted with the counter block derived from the nonce.
+
+Like GCM, GMAC must never be used with the same key and nonce for two different messages.
+*/
+
+use crate::aes::{ctr, KeySize};
+use crate::cryptoutil::copy_memory;
+use crate::ghash::Ghash;
+use crate::mac::{Mac, MacResult};
+
+#[derive(Clone, Copy)]
+pub struct Gmac {
+    mac: Ghash,
+    end_tag: [u8; 16]
+}
+
+impl Gmac {
+    /**
+     * Create a new Gmac instance.
+     *
+     * # Arguments
+     * * key_size - The size of the AES key.
+     * * key - The AES key.
+     * * nonce - The 12 byte nonce. It must be unique for every message authenticated under `key`.
+     */
+    pub fn new(key_size: KeySize, key: &[u8], nonce: &[u8]) -> Gmac {
+        assert!(key.len() == key_size.bytes_size());
+        assert!(nonce.len() == 12);
+
+        // See AesGcm::new() for why generic CTR mode can be used to compute the counter blocks.
+        let mut iv = [0u8; 16];
+        copy_memory(nonce, &mut iv);
+        iv[15] = 1u8;
+        let zeros = [0u8; 16];
+        let mut end_tag = [0u8; 16];
+        ctr(key_size, key, &iv).process(&zeros, &mut end_tag);
+        let mut hash_key = [0u8; 16];
+        ctr(key_size, key, &zeros).process(&zeros, &mut hash_key);
+        Gmac {
+            mac: Ghash::new(&hash_key),
+            end_tag
+        }
+    }
+}
+
+impl Mac for Gmac {
+    fn input(&mut self, data: &[u8]) {
+        self.mac.input(data);
+    }
+
+    fn reset(&mut self) {
+        self.mac.reset();
+    }
+
+    fn result(&mut self) -> MacResult {
+        let mut mac = [0u8; 16];
+        self.raw_result(&mut mac);
+        MacResult::new(&mac)
+    }
+
+    fn raw_result(&mut self, output: &mut [u8]) {
+        assert!(output.len() >= 16);
+        self.mac.raw_result(output);
+        for (o, &t) in output.iter_mut().zip(self.end_tag.iter()) {
+            *o ^= t;
+        }
+    }
+
+    fn output_bytes(&self) -> usize { 16 }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aes::KeySize;
+    use crate::gmac::Gmac;
+    use crate::mac::{Mac, MacResult};
+    use crate::serialize::hex::FromHex;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    struct Test {
+        key_size: KeySize,
+        key: Vec<u8>,
+        nonce: Vec<u8>,
+        aad: Vec<u8>,
+        tag: Vec<u8>
+    }
+
+    // Test cases 1, 7 and 13 from the GCM specification and a GMAC test vector (a test with an
+    // empty plaintext) from the NIST CAVP gcmEncryptExtIV files
+    fn tests() -> Vec<Test> {
+        vec![
+            Test {
+                key_size: KeySize::KeySize128,
+                key: hex_to_bytes("00000000000000000000000000000000"),
+                nonce: hex_to_bytes("000000000000000000000000"),
+                aad: Vec::new(),
+                tag: hex_to_bytes("58e2fccefa7e3061367f1d57a4e7455a")
+            },
+            Test {
+                key_size: KeySize::KeySize192,
+                key: hex_to_bytes("000000000000000000000000000000000000000000000000"),
+                nonce: hex_to_bytes("000000000000000000000000"),
+                aad: Vec::new(),
+                tag: hex_to_bytes("cd33b28ac773f74ba00ed1f312572435")
+            },
+            Test {
+                key_size: KeySize::KeySize256,
+                key: hex_to_bytes("0000000000000000000000000000000000000000000000000000000000000000"),
+                nonce: hex_to_bytes("000000000000000000000000"),
+                aad: Vec::new(),
+                tag: hex_to_bytes("530f8afbc74536b9a963b4f1c4cb738b")
+            },
+            Test {
+                key_size: KeySize::KeySize128,
+                key: hex_to_bytes("77be63708971c4e240d1cb79e8d77feb"),
+                nonce: hex_to_bytes("e0e00f19fed7ba0136a797f3"),
+                aad: hex_to_bytes("7a43ec1d9c0a5a78a0b16533a6213cab"),
+                tag: hex_to_bytes("209fcc8d3675ed938e9c7166709dd946")
+            },
+        ]
+    }
+
+    #[test]
+    fn test_gmac() {
+        for t in tests().iter() {
+            let mut gmac = Gmac::new(t.key_size, &t.key, &t.nonce);
+            gmac.input(&t.aad);
+            let expected = MacResult::new(&t.tag);
+            assert!(gmac.result() == expected);
+
+            gmac.reset();
+            for chunk in t.aad.chunks(5) {
+                gmac.input(chunk);
+            }
+            assert!(gmac.result() == expected);
+        }
+    }
+}
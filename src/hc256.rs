@@ -0,0 +1,265 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the HC-256 stream cipher, the 256 bit sibling of HC-128, as specified
+ * in [1]. It takes a 256 bit key and a 256 bit IV.
+ *
+ * # References
+ * [1] - H. Wu. A New Stream Cipher HC-256. Fast Software Encryption 2004.
+ *       <https://www.ecrypt.eu.org/stream/p3ciphers/hc/hc256_p3.pdf>
+ */
+
+use crate::buffer::{BufferResult, RefReadBuffer, RefWriteBuffer};
+use crate::symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher, SymmetricCipherError};
+use crate::cryptoutil::{read_u32_le, read_u32v_le, symm_enc_or_dec, write_u32_le};
+
+
+#[derive(Copy)]
+pub struct Hc256 {
+    p: [u32; 1024],
+    q: [u32; 1024],
+    cnt: usize,
+    output: [u8; 4],
+    output_index: usize
+}
+
+impl Clone for Hc256 { fn clone(&self) -> Hc256 { *self } }
+
+impl Hc256 {
+    pub fn new(key: &[u8], nonce: &[u8]) -> Hc256 {
+        assert!(key.len() == 32);
+        assert!(nonce.len() == 32);
+        let mut hc256 = Hc256 { p: [0; 1024], q: [0; 1024], cnt: 0, output: [0; 4], output_index: 0 };
+        hc256.init(key, nonce);
+
+        hc256
+    }
+
+    fn init(&mut self, key : &[u8], nonce : &[u8]) {
+        let mut w : [u32; 2560] = [0; 2560];
+
+        read_u32v_le(&mut w[0..8], key);
+        read_u32v_le(&mut w[8..16], nonce);
+
+        for i in 16..2560 {
+            w[i] = f2(w[i - 2]).wrapping_add(w[i - 7]).wrapping_add(f1(w[i - 15])).wrapping_add(w[i - 16]).wrapping_add(i as u32);
+        }
+
+        self.p.copy_from_slice(&w[512..1536]);
+        self.q.copy_from_slice(&w[1536..2560]);
+
+        // Run the cipher 4096 steps without generating output
+        self.cnt = 0;
+        for _ in 0..4096 {
+            self.step();
+        }
+
+        self.cnt = 0;
+    }
+
+    fn step(&mut self) -> u32 {
+        let j : usize = self.cnt & 0x3FF;
+
+        // Precompute resources
+        let dim_j3 : usize = (j.wrapping_sub(3)) & 0x3FF;
+        let dim_j10 : usize = (j.wrapping_sub(10)) & 0x3FF;
+        let dim_j1023 : usize = (j.wrapping_sub(1023)) & 0x3FF;
+        let dim_j12 : usize = (j.wrapping_sub(12)) & 0x3FF;
+
+        let ret : u32;
+
+        if self.cnt < 1024 {
+            let x = self.p[dim_j3];
+            let y = self.p[dim_j1023];
+            let g1 = (x.rotate_right(10) ^ y.rotate_right(23)).wrapping_add(self.q[((x ^ y) & 0x3FF) as usize]);
+            self.p[j] = self.p[j].wrapping_add(self.p[dim_j10]).wrapping_add(g1);
+            ret = h(&self.q, self.p[dim_j12]) ^ self.p[j];
+        } else {
+            let x = self.q[dim_j3];
+            let y = self.q[dim_j1023];
+            let g2 = (x.rotate_right(10) ^ y.rotate_right(23)).wrapping_add(self.p[((x ^ y) & 0x3FF) as usize]);
+            self.q[j] = self.q[j].wrapping_add(self.q[dim_j10]).wrapping_add(g2);
+            ret = h(&self.p, self.q[dim_j12]) ^ self.q[j];
+        }
+
+        self.cnt = (self.cnt + 1) & 0x7FF;
+        ret
+    }
+
+    fn next(&mut self) -> u8 {
+        if self.output_index == 0 {
+            let step = self.step();
+            write_u32_le(&mut self.output, step);
+        }
+        let ret = self.output[self.output_index];
+        self.output_index = (self.output_index + 1) & 0x3;
+
+        ret
+    }
+}
+
+fn f1(x: u32) -> u32 {
+    x.rotate_right(7) ^ x.rotate_right(18) ^ (x >> 3)
+}
+
+fn f2(x: u32) -> u32 {
+    x.rotate_right(17) ^ x.rotate_right(19) ^ (x >> 10)
+}
+
+// The functions h1 and h2: each byte of x selects a word from one quarter of the other table
+fn h(table: &[u32; 1024], x: u32) -> u32 {
+    table[(x & 0xFF) as usize]
+        .wrapping_add(table[256 + ((x >> 8) & 0xFF) as usize])
+        .wrapping_add(table[512 + ((x >> 16) & 0xFF) as usize])
+        .wrapping_add(table[768 + (x >> 24) as usize])
+}
+
+impl SynchronousStreamCipher for Hc256 {
+    fn process(&mut self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == output.len());
+
+        let mut data_index = 0;
+        let data_index_end = input.len();
+
+        // Process any unused keystream remaining from previous operations
+        while self.output_index > 0 && data_index < data_index_end {
+            output[data_index] = input[data_index] ^ self.next();
+            data_index += 1;
+        }
+
+        // Process whole keystream words
+        while data_index + 4 <= data_index_end {
+            let data_index_inc = data_index + 4;
+            let xored = read_u32_le(&input[data_index..data_index_inc]) ^ self.step();
+            write_u32_le(&mut output[data_index..data_index_inc], xored);
+            data_index = data_index_inc;
+        }
+
+        // Process the remaining data, if any
+        while data_index < data_index_end {
+            output[data_index] = input[data_index] ^ self.next();
+            data_index += 1;
+        }
+    }
+}
+
+impl Encryptor for Hc256 {
+    fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        symm_enc_or_dec(self, input, output)
+    }
+}
+
+impl Decryptor for Hc256 {
+    fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        symm_enc_or_dec(self, input, output)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::hc256::Hc256;
+    use crate::symmetriccipher::SynchronousStreamCipher;
+    use crate::serialize::hex::{FromHex};
+
+    fn check(key_hex: &str, nonce_hex: &str, expected_output_hex: &str) {
+        let key = key_hex.from_hex().unwrap();
+        let nonce = nonce_hex.from_hex().unwrap();
+        let expected_output = expected_output_hex.from_hex().unwrap();
+
+        let input = [0u8; 64];
+        let mut output = [0u8; 64];
+        let mut hc256 = Hc256::new(&key, &nonce);
+        hc256.process(&input, &mut output);
+        assert_eq!(output[..], expected_output[..]);
+
+        // The same keystream, processed in pieces that don't line up with the keystream words
+        let mut output = [0u8; 64];
+        let mut hc256 = Hc256::new(&key, &nonce);
+        for (i, o) in input.chunks(7).zip(output.chunks_mut(7)) {
+            hc256.process(i, o);
+        }
+        assert_eq!(output[..], expected_output[..]);
+    }
+
+    // Vectors from the HC-256 specification, where the key and IV words are given little endian
+
+    #[test]
+    fn test_hc256_spec_vector_1() {
+        check("0000000000000000000000000000000000000000000000000000000000000000",
+              "0000000000000000000000000000000000000000000000000000000000000000",
+              "5B078985D8F6F30D42C5C02FA6B6795153F06534801F89F24E74248B720B4818\
+               CD9227ECEBCF4DBF8DBF6977E4AE14FAE8504C7BC8A9F3EA6C0106F5327E6981");
+    }
+
+    #[test]
+    fn test_hc256_spec_vector_2() {
+        check("0000000000000000000000000000000000000000000000000000000000000000",
+              "0100000000000000000000000000000000000000000000000000000000000000",
+              "AFE2A2BF4F17CEE9FEC2058BD1B18BB15FC042EE712B3101DD501FC60B082A50\
+               06C7FEED41923D6348C4DAA6FF6185AF5A13045E34C44894F3E9E72DDF0B5237");
+    }
+
+    #[test]
+    fn test_hc256_spec_vector_3() {
+        check("5500000000000000000000000000000000000000000000000000000000000000",
+              "0000000000000000000000000000000000000000000000000000000000000000",
+              "1C404AFE4FE25FED958F9AD1AE36C06F88A65A3CC0ABE223AEB3902F420ED3A8\
+               6C3AF05944EB396EFB79758F5E7A1370D8B7106DCDF7D0ADDA233472E6DD75F5");
+    }
+
+    // eSTREAM Set 1, vector 0
+    #[test]
+    fn test_hc256_ecrypt_set_1_vector_0() {
+        check("8000000000000000000000000000000000000000000000000000000000000000",
+              "0000000000000000000000000000000000000000000000000000000000000000",
+              "240146C5EA6C72A8DFC93E54E8811C32A85E0BF7291BDDC0DBEAE086D051D5B0\
+               5CC9DD5C311ED2F7E8484CC477C68BC8C5D3F3450553F5327253768E958C0C55");
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+    use crate::symmetriccipher::SynchronousStreamCipher;
+    use crate::hc256::Hc256;
+
+    #[bench]
+    pub fn hc256_10(bh: & mut Bencher) {
+        let mut hc256 = Hc256::new(&[0; 32], &[0; 32]);
+        let input = [1u8; 10];
+        let mut output = [0u8; 10];
+        bh.iter( || {
+            hc256.process(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn hc256_1k(bh: & mut Bencher) {
+        let mut hc256 = Hc256::new(&[0; 32], &[0; 32]);
+        let input = [1u8; 1024];
+        let mut output = [0u8; 1024];
+        bh.iter( || {
+            hc256.process(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn hc256_64k(bh: & mut Bencher) {
+        let mut hc256 = Hc256::new(&[0; 32], &[0; 32]);
+        let input = [1u8; 65536];
+        let mut output = [0u8; 65536];
+        bh.iter( || {
+            hc256.process(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
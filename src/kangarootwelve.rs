@@ -0,0 +1,456 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements TurboSHAKE and KangarooTwelve as specified in RFC 9861 [1]:
+ *
+ * * `TurboShake128` and `TurboShake256`, SHAKE with the number of rounds of the permutation
+ *   reduced from 24 to 12, and a domain separation byte chosen by the caller
+ * * `Kt128` and `Kt256`, KangarooTwelve, which hashes messages longer than 8 KiB as a tree of
+ *   TurboSHAKE calls over independent chunks, and takes an optional customization string
+ *
+ * The `SECURITY` parameter of the types is the security strength in bits, either 128 or 256. All of
+ * them are extendable output functions, and are also usable as a `Digest` with an output of
+ * `2 * SECURITY` bits. As both traits have `input` and `reset` methods, the types have inherent
+ * versions of those too.
+ *
+ * # Usage
+ *
+ * ```rust
+ * use self::crypto::digest::{ExtendableOutput, XofReader};
+ * use self::crypto::kangarootwelve::Kt128;
+ *
+ * let mut kt = Kt128::new(b"My Application");
+ * kt.input(b"hello world");
+ * let mut reader = kt.finalize_xof();
+ * let mut output = [0u8; 100];
+ * reader.read(&mut output);
+ * ```
+ *
+ * # References
+ * [1] - B. Viguier, D. Wong, G. Van Assche, Q. Dang, J. Daemen. KangarooTwelve and TurboSHAKE.
+ *       RFC 9861. <https://www.rfc-editor.org/rfc/rfc9861>
+ */
+
+use std::cmp;
+
+use crate::digest::{Digest, ExtendableOutput, XofReader};
+use crate::keccak::{capacity, Sponge};
+use crate::sha3::ShakeReader;
+
+const ROUNDS: usize = 12;
+
+// The size of the chunks of a KangarooTwelve message
+const CHUNK_SIZE: usize = 8192;
+
+// Domain separation bytes of KangarooTwelve, for a single node, the leaves, and the final node of a
+// tree
+const KT_SINGLE_DS: u8 = 0x07;
+const KT_LEAF_DS: u8 = 0x0b;
+const KT_FINAL_DS: u8 = 0x06;
+
+// Follows the first chunk of a message when more chunks are hashed as leaves
+const KT_MARKER: [u8; 8] = [0x03, 0, 0, 0, 0, 0, 0, 0];
+
+// Encodes `x` as its big endian bytes without leading zeros, followed by their number
+fn length_encode(x: u64) -> Vec<u8> {
+    let bytes = x.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count();
+    let mut out = bytes[skip..].to_vec();
+    out.push((8 - skip) as u8);
+    out
+}
+
+/// TurboSHAKE with `SECURITY` bits of security, 128 or 256
+#[derive(Copy, Clone)]
+pub struct TurboShake<const SECURITY: usize> {
+    sponge: Sponge,
+    domain: u8
+}
+
+/// TurboSHAKE128
+pub type TurboShake128 = TurboShake<128>;
+/// TurboSHAKE256
+pub type TurboShake256 = TurboShake<256>;
+
+impl <const SECURITY: usize> TurboShake<SECURITY> {
+    /// Create a TurboSHAKE instance with the domain separation byte `domain`, in the range 0x01 to
+    /// 0x7F. Use 0x1F if the application has no need for separate domains.
+    pub fn new(domain: u8) -> TurboShake<SECURITY> {
+        assert!((0x01..=0x7f).contains(&domain));
+        TurboShake { sponge: Sponge::new_with_rounds(capacity(SECURITY), ROUNDS), domain }
+    }
+
+    /// Absorb `data` into the sponge.
+    pub fn input(&mut self, data: &[u8]) {
+        self.sponge.absorb(data);
+    }
+
+    /// Reset to the empty message, keeping the domain separation byte.
+    pub fn reset(&mut self) {
+        *self = TurboShake::new(self.domain);
+    }
+}
+
+impl <const SECURITY: usize> ExtendableOutput for TurboShake<SECURITY> {
+    type Reader = ShakeReader;
+
+    fn input(&mut self, data: &[u8]) {
+        TurboShake::input(self, data);
+    }
+
+    fn finalize_xof(self) -> ShakeReader {
+        ShakeReader::new(self.sponge, self.domain)
+    }
+
+    fn reset(&mut self) {
+        TurboShake::reset(self);
+    }
+}
+
+impl <const SECURITY: usize> Digest for TurboShake<SECURITY> {
+    fn input(&mut self, data: &[u8]) {
+        TurboShake::input(self, data);
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        self.finalize_xof().read(&mut out[..2 * SECURITY / 8]);
+    }
+
+    fn reset(&mut self) {
+        TurboShake::reset(self);
+    }
+
+    fn output_bits(&self) -> usize {
+        2 * SECURITY
+    }
+
+    fn block_size(&self) -> usize {
+        self.sponge.rate()
+    }
+}
+
+/// KangarooTwelve with `SECURITY` bits of security, 128 or 256
+#[derive(Clone)]
+pub struct KangarooTwelve<const SECURITY: usize> {
+    customization: Vec<u8>,
+    // Absorbs the first chunk of the input, then the chaining values of the other chunks
+    final_node: Sponge,
+    // TurboSHAKE over the current chunk, after the first one
+    leaf: Sponge,
+    // Bytes of the message absorbed so far
+    len: u64
+}
+
+/// KT128, KangarooTwelve based on TurboSHAKE128
+pub type Kt128 = KangarooTwelve<128>;
+/// KT256, KangarooTwelve based on TurboSHAKE256
+pub type Kt256 = KangarooTwelve<256>;
+
+impl <const SECURITY: usize> KangarooTwelve<SECURITY> {
+    /// Create a KangarooTwelve instance with the customization string `customization`, which may
+    /// be empty.
+    pub fn new(customization: &[u8]) -> KangarooTwelve<SECURITY> {
+        KangarooTwelve {
+            customization: customization.to_vec(),
+            final_node: Sponge::new_with_rounds(capacity(SECURITY), ROUNDS),
+            leaf: Sponge::new_with_rounds(capacity(SECURITY), ROUNDS),
+            len: 0
+        }
+    }
+
+    /// Absorb `data` into the tree.
+    pub fn input(&mut self, data: &[u8]) {
+        let mut data = data;
+        while !data.is_empty() {
+            if self.len < CHUNK_SIZE as u64 {
+                let n = cmp::min(CHUNK_SIZE - self.len as usize, data.len());
+                self.final_node.absorb(&data[..n]);
+                self.len += n as u64;
+                data = &data[n..];
+                continue;
+            }
+
+            if self.len == CHUNK_SIZE as u64 {
+                self.final_node.absorb(&KT_MARKER);
+            }
+
+            let n = cmp::min(CHUNK_SIZE - self.leaf_offset(), data.len());
+            self.leaf.absorb(&data[..n]);
+            self.len += n as u64;
+            data = &data[n..];
+
+            if self.leaf_offset() == 0 {
+                self.finish_leaf();
+            }
+        }
+    }
+
+    /// Reset to the empty message, keeping the customization string.
+    pub fn reset(&mut self) {
+        self.final_node = Sponge::new_with_rounds(capacity(SECURITY), ROUNDS);
+        self.leaf = Sponge::new_with_rounds(capacity(SECURITY), ROUNDS);
+        self.len = 0;
+    }
+
+    // The number of bytes absorbed into the current leaf
+    fn leaf_offset(&self) -> usize {
+        ((self.len - CHUNK_SIZE as u64) % CHUNK_SIZE as u64) as usize
+    }
+
+    // Absorbs the chaining value of the current leaf into the final node, and starts the next one
+    fn finish_leaf(&mut self) {
+        let mut cv = [0u8; 64];
+        let cv = &mut cv[..2 * SECURITY / 8];
+        ShakeReader::new(self.leaf, KT_LEAF_DS).read(cv);
+        self.final_node.absorb(cv);
+
+        self.leaf = Sponge::new_with_rounds(capacity(SECURITY), ROUNDS);
+    }
+}
+
+impl <const SECURITY: usize> ExtendableOutput for KangarooTwelve<SECURITY> {
+    type Reader = ShakeReader;
+
+    fn input(&mut self, data: &[u8]) {
+        KangarooTwelve::input(self, data);
+    }
+
+    fn finalize_xof(mut self) -> ShakeReader {
+        // The customization string is hashed as part of the message
+        let customization = ::std::mem::take(&mut self.customization);
+        self.input(&customization);
+        self.input(&length_encode(customization.len() as u64));
+
+        if self.len <= CHUNK_SIZE as u64 {
+            return ShakeReader::new(self.final_node, KT_SINGLE_DS);
+        }
+
+        if self.leaf_offset() != 0 {
+            self.finish_leaf();
+        }
+        let leaves = (self.len - 1) / CHUNK_SIZE as u64;
+        self.final_node.absorb(&length_encode(leaves));
+        self.final_node.absorb(&[0xff, 0xff]);
+        ShakeReader::new(self.final_node, KT_FINAL_DS)
+    }
+
+    fn reset(&mut self) {
+        KangarooTwelve::reset(self);
+    }
+}
+
+impl <const SECURITY: usize> Digest for KangarooTwelve<SECURITY> {
+    fn input(&mut self, data: &[u8]) {
+        KangarooTwelve::input(self, data);
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        self.clone().finalize_xof().read(&mut out[..2 * SECURITY / 8]);
+    }
+
+    fn reset(&mut self) {
+        KangarooTwelve::reset(self);
+    }
+
+    fn output_bits(&self) -> usize {
+        2 * SECURITY
+    }
+
+    fn block_size(&self) -> usize {
+        self.final_node.rate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::digest::{Digest, ExtendableOutput, XofReader};
+    use crate::kangarootwelve::{length_encode, Kt128, Kt256, TurboShake128, TurboShake256};
+    use crate::serialize::hex::FromHex;
+
+    // The test vectors are from RFC 9861, section 5
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    // The repeating pattern 00 01 .. FA, truncated to `len` bytes
+    fn ptn(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn check_xof<X: ExtendableOutput>(mut xof: X, data: &[u8], expected_hex: &str) {
+        let expected = hex_to_bytes(expected_hex);
+        xof.input(data);
+        let mut reader = xof.finalize_xof();
+        let mut out = vec![0u8; expected.len()];
+        let (first, second) = out.split_at_mut(5);
+        reader.read(first);
+        reader.read(second);
+        assert_eq!(out, expected);
+    }
+
+    // Checks the last bytes of a long output
+    fn check_xof_tail<X: ExtendableOutput>(mut xof: X, output_len: usize, expected_hex: &str) {
+        let expected = hex_to_bytes(expected_hex);
+        xof.input(b"");
+        let mut out = vec![0u8; output_len];
+        xof.finalize_xof().read(&mut out);
+        assert_eq!(&out[output_len - expected.len()..], &expected[..]);
+    }
+
+    fn check_kt128(data: &[u8], customization: &[u8], expected_hex: &str) {
+        let mut kt = Kt128::new(customization);
+        kt.input(data);
+        let mut out = [0u8; 32];
+        kt.result(&mut out);
+        assert_eq!(out[..], hex_to_bytes(expected_hex)[..]);
+
+        // The same message, in pieces that don't line up with the chunks
+        let mut kt = Kt128::new(customization);
+        for piece in data.chunks(1000) {
+            kt.input(piece);
+        }
+        check_xof(kt, b"", expected_hex);
+    }
+
+    #[test]
+    fn test_length_encode() {
+        assert_eq!(length_encode(0), [0]);
+        assert_eq!(length_encode(12), [12, 1]);
+        assert_eq!(length_encode(65538), [1, 0, 2, 3]);
+    }
+
+    #[test]
+    fn test_turboshake128() {
+        check_xof(TurboShake128::new(0x1f), b"",
+            "1E415F1C5983AFF2169217277D17BB538CD945A397DDEC541F1CE41AF2C1B74C\
+             3E8CCAE2A4DAE56C84A04C2385C03C15E8193BDF58737363321691C05462C8DF");
+        check_xof_tail(TurboShake128::new(0x1f), 10032,
+            "A3B9B0385900CE761F22AED548E754DA10A5242D62E8C658E3F3A923A7555607");
+        check_xof(TurboShake128::new(0x1f), &ptn(17),
+            "9C97D036A3BAC819DB70EDE0CA554EC6E4C2A1A4FFBFD9EC269CA6A111161233");
+        check_xof(TurboShake128::new(0x1f), &ptn(289),
+            "96C77C279E0126F7FC07C9B07F5CDAE1E0BE60BDBE10620040E75D7223A624D2");
+        check_xof(TurboShake128::new(0x01), &[0xff; 3],
+            "BF323F940494E88EE1C540FE660BE8A0C93F43D15EC006998462FA994EED5DAB");
+        check_xof(TurboShake128::new(0x06), &[0xff],
+            "8EC9C66465ED0D4A6C35D13506718D687A25CB05C74CCA1E42501ABD83874A67");
+        check_xof(TurboShake128::new(0x07), &[0xff; 3],
+            "B658576001CAD9B1E5F399A9F77723BBA05458042D68206F7252682DBA3663ED");
+        check_xof(TurboShake128::new(0x0b), &[0xff; 7],
+            "8DEEAA1AEC47CCEE569F659C21DFA8E112DB3CEE37B18178B2ACD805B799CC37");
+        check_xof(TurboShake128::new(0x30), &[0xff],
+            "553122E2135E363C3292BED2C6421FA232BAB03DAA07C7D6636603286506325B");
+        check_xof(TurboShake128::new(0x7f), &[0xff; 3],
+            "16274CC656D44CEFD422395D0F9053BDA6D28E122ABA15C765E5AD0E6EAF26F9");
+    }
+
+    #[test]
+    fn test_turboshake256() {
+        check_xof(TurboShake256::new(0x1f), b"",
+            "367A329DAFEA871C7802EC67F905AE13C57695DC2C6663C61035F59A18F8E7DB\
+             11EDC0E12E91EA60EB6B32DF06DD7F002FBAFABB6E13EC1CC20D995547600DB0");
+        check_xof(TurboShake256::new(0x1f), &ptn(17),
+            "B3BAB0300E6A191FBE6137939835923578794EA54843F5011090FA2F3780A9E5\
+             CB22C59D78B40A0FBFF9E672C0FBE0970BD2C845091C6044D687054DA5D8E9C7");
+        check_xof(TurboShake256::new(0x01), &[0xff; 3],
+            "D21C6FBBF587FA2282F29AEA620175FB0257413AF78A0B1B2A87419CE031D933\
+             AE7A4D383327A8A17641A34F8A1D1003AD7DA6B72DBA84BB62FEF28F62F12424");
+        check_xof(TurboShake256::new(0x06), &[0xff],
+            "738D7B4E37D18B7F22AD1B5313E357E3DD7D07056A26A303C433FA3533455280\
+             F4F5A7D4F700EFB437FE6D281405E07BE32A0A972E22E63ADC1B090DAEFE004B");
+    }
+
+    #[test]
+    fn test_turboshake_digest() {
+        let mut ts = TurboShake256::new(0x1f);
+        ts.input(&ptn(17));
+        assert_eq!(ts.output_bits(), 512);
+        assert_eq!(ts.result_str(),
+            "b3bab0300e6a191fbe6137939835923578794ea54843f5011090fa2f3780a9e5\
+             cb22c59d78b40a0fbff9e672c0fbe0970bd2c845091c6044d687054da5d8e9c7");
+    }
+
+    #[test]
+    fn test_kt128() {
+        check_xof(Kt128::new(b""), b"",
+            "1AC2D450FC3B4205D19DA7BFCA1B37513C0803577AC7167F06FE2CE1F0EF39E5\
+             4269C056B8C82E48276038B6D292966CC07A3D4645272E31FF38508139EB0A71");
+        check_xof_tail(Kt128::new(b""), 10032,
+            "E8DC563642F7228C84684C898405D3A834799158C079B12880277A1D28E2FF6D");
+        check_kt128(b"", b"",
+            "1AC2D450FC3B4205D19DA7BFCA1B37513C0803577AC7167F06FE2CE1F0EF39E5");
+        check_kt128(&ptn(17), b"",
+            "6BF75FA2239198DB4772E36478F8E19B0F371205F6A9A93A273F51DF37122888");
+        check_kt128(&ptn(289), b"",
+            "0C315EBCDEDBF61426DE7DCF8FB725D1E74675D7F5327A5067F367B108ECB67C");
+        check_kt128(&ptn(4913), b"",
+            "CB552E2EC77D9910701D578B457DDF772C12E322E4EE7FE417F92C758F0D59D0");
+        check_kt128(&ptn(83521), b"",
+            "8701045E22205345FF4DDA05555CBB5C3AF1A771C2B89BAEF37DB43D9998B9FE");
+        check_kt128(b"", &ptn(1),
+            "FAB658DB63E94A246188BF7AF69A133045F46EE984C56E3C3328CAAF1AA1A583");
+        check_kt128(&[0xff], &ptn(41),
+            "D848C5068CED736F4462159B9867FD4C20B808ACC3D5BC48E0B06BA0A3762EC4");
+        check_kt128(&[0xff; 3], &ptn(1681),
+            "C389E5009AE57120854C2E8C64670AC01358CF4C1BAF89447A724234DC7CED74");
+        check_kt128(&[0xff; 7], &ptn(68921),
+            "75D2F86A2E644566726B4FBCFC5657B9DBCF070C7B0DCA06450AB291D7443BCF");
+        check_kt128(&ptn(8191), b"",
+            "1B577636F723643E990CC7D6A659837436FD6A103626600EB8301CD1DBE553D6");
+        check_kt128(&ptn(8192), b"",
+            "48F256F6772F9EDFB6A8B661EC92DC93B95EBD05A08A17B39AE3490870C926C3");
+        check_kt128(&ptn(8192), &ptn(8189),
+            "3ED12F70FB05DDB58689510AB3E4D23C6C6033849AA01E1D8C220A297FEDCD0B");
+        check_kt128(&ptn(8192), &ptn(8190),
+            "6A7C1B6A5CD0D8C9CA943A4A216CC64604559A2EA45F78570A15253D67BA00AE");
+    }
+
+    #[test]
+    fn test_kt256() {
+        check_xof(Kt256::new(b""), b"",
+            "B23D2E9CEA9F4904E02BEC06817FC10CE38CE8E93EF4C89E6537076AF8646404\
+             E3E8B68107B8833A5D30490AA33482353FD4ADC7148ECB782855003AAEBDE4A9");
+        check_xof(Kt256::new(b""), &ptn(17),
+            "1BA3C02B1FC514474F06C8979978A9056C8483F4A1B63D0DCCEFE3A28A2F323E\
+             1CDCCA40EBF006AC76EF0397152346837B1277D3E7FAA9C9653B19075098527B");
+        check_xof(Kt256::new(b""), &ptn(289),
+            "DE8CCBC63E0F133EBB4416814D4C66F691BBF8B6A61EC0A7700F836B086CB029\
+             D54F12AC7159472C72DB118C35B4E6AA213C6562CAAA9DCC518959E69B10F3BA");
+        check_xof(Kt256::new(b""), &ptn(4913),
+            "647EFB49FE9D717500171B41E7F11BD491544443209997CE1C2530D15EB1FFBB\
+             598935EF954528FFC152B1E4D731EE2683680674365CD191D562BAE753B84AA5");
+        check_xof(Kt256::new(&ptn(1)), b"",
+            "9280F5CC39B54A5A594EC63DE0BB99371E4609D44BF845C2F5B8C316D72B1598\
+             11F748F23E3FABBE5C3226EC96C62186DF2D33E9DF74C5069CEECBB4DD10EFF6");
+        check_xof(Kt256::new(&ptn(41)), &[0xff],
+            "47EF96DD616F200937AA7847E34EC2FEAE8087E3761DC0F8C1A154F51DC9CCF8\
+             45D7ADBCE57FF64B639722C6A1672E3BF5372D87E00AFF89BE97240756998853");
+        check_xof(Kt256::new(&ptn(8189)), &ptn(8192),
+            "74E47879F10A9C5D11BD2DA7E194FE57E86378BF3C3F7448EFF3C576A0F18C5C\
+             AAE0999979512090A7F348AF4260D4DE3C37F1ECAF8D2C2C96C1D16C64B12496");
+    }
+
+    #[test]
+    fn test_kt_digest_reset() {
+        let mut kt = Kt256::new(&ptn(41));
+        kt.input(&ptn(20000));
+        kt.reset();
+        kt.input(&[0xff]);
+        assert_eq!(kt.output_bits(), 512);
+
+        // The result can be retrieved more than once
+        let mut first = [0u8; 64];
+        let mut second = [0u8; 64];
+        kt.result(&mut first);
+        kt.result(&mut second);
+        assert_eq!(first, second);
+        assert_eq!(first[..], hex_to_bytes(
+            "47EF96DD616F200937AA7847E34EC2FEAE8087E3761DC0F8C1A154F51DC9CCF8\
+             45D7ADBCE57FF64B639722C6A1672E3BF5372D87E00AFF89BE97240756998853")[..]);
+    }
+}
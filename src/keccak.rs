@@ -0,0 +1,434 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module exposes the Keccak-f[1600] permutation that SHA-3 and its derived functions are
+ * built on, for use in other constructions:
+ *
+ * * `keccak_f1600` and `keccak_p1600`, the permutation and its round reduced variants, on a state
+ *   of 25 little endian 64 bit lanes
+ * * `Sponge`, the sponge construction with a configurable capacity and padding
+ * * `Duplex`, a duplex object which absorbs, overwrites, squeezes and encrypts a byte at a time,
+ *   as used by protocol frameworks such as STROBE
+ *
+ * Capacities and rates are given in bytes.
+ *
+ * # Usage
+ *
+ * ```rust
+ * use self::crypto::keccak::Sponge;
+ *
+ * // SHAKE128 is the sponge with a 256 bit capacity and the padding suffix 0x1F
+ * let mut sponge = Sponge::new(32);
+ * sponge.absorb(b"");
+ * sponge.pad(0x1f);
+ *
+ * let mut output = [0u8; 16];
+ * sponge.squeeze(&mut output);
+ * assert_eq!(output, [0x7f, 0x9c, 0x2b, 0xa4, 0xe8, 0x8f, 0x82, 0x7d,
+ *                     0x61, 0x60, 0x45, 0x50, 0x76, 0x05, 0x85, 0x3e]);
+ * ```
+ *
+ * # References
+ * [1] - G. Bertoni, J. Daemen, M. Peeters, G. Van Assche. The Keccak reference, version 3.0.
+ *       <https://keccak.team/files/Keccak-reference-3.0.pdf>
+ * [2] - G. Bertoni, J. Daemen, M. Peeters, G. Van Assche. Duplexing the sponge: single-pass
+ *       authenticated encryption and other applications. SAC 2011.
+ *       <https://keccak.team/files/SpongeDuplex.pdf>
+ */
+
+use std::cmp;
+
+use crate::cryptoutil::{write_u64v_le, read_u64v_le};
+
+pub(crate) const B: usize = 200;
+pub(crate) const NROUNDS: usize = 24;
+const RC: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008
+];
+const ROTC: [usize; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36,
+    45, 55, 2, 14, 27, 41, 56, 8,
+    25, 43, 62, 18, 39, 61, 20, 44
+];
+const PIL: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16,
+    8, 21, 24, 4, 15, 23, 19, 13,
+    12, 2, 20, 14, 22, 9, 6, 1
+];
+const M5: [usize; 10] = [
+    0, 1, 2, 3, 4, 0, 1, 2, 3, 4
+];
+
+#[inline]
+fn rotl64(v: u64, n: usize) -> u64 {
+    ((v << (n % 64)) & 0xffffffffffffffff) ^ (v >> (64 - (n % 64)))
+}
+
+/// Applies the Keccak-f[1600] permutation to `state`.
+pub fn keccak_f1600(state: &mut [u64; 25]) {
+    keccak_p1600(state, NROUNDS);
+}
+
+/// Applies Keccak-p[1600, `rounds`], the last `rounds` rounds of Keccak-f[1600], to `state`. The
+/// round reduced functions such as TurboSHAKE use this with 12 rounds.
+// Code based on Keccak-compact64.c from ref implementation.
+pub fn keccak_p1600(state: &mut [u64; 25], rounds: usize) {
+    assert!(rounds <= NROUNDS);
+
+    let s = state;
+    let mut t: [u64; 1] = [0; 1];
+    let mut c: [u64; 5] = [0; 5];
+
+    for rc in &RC[NROUNDS - rounds..] {
+        // Theta
+        for x in 0..5 {
+            c[x] = s[x] ^ s[5 + x] ^ s[10 + x] ^ s[15 + x] ^ s[20 + x];
+        }
+        for x in 0..5 {
+            t[0] = c[M5[x + 4]] ^ rotl64(c[M5[x + 1]], 1);
+            for y in 0..5 {
+                s[y * 5 + x] = s[y * 5 + x] ^ t[0];
+            }
+        }
+
+        // Rho Pi
+        t[0] = s[1];
+        for x in 0..24 {
+            c[0] = s[PIL[x]];
+            s[PIL[x]] = rotl64(t[0], ROTC[x]);
+            t[0] = c[0];
+        }
+
+        // Chi
+        for y in 0..5 {
+            for x in 0..5 {
+                c[x] = s[y * 5 + x];
+            }
+            for x in 0..5 {
+                s[y * 5 + x] = c[x] ^ (!c[M5[x + 1]] & c[M5[x + 2]]);
+            }
+        }
+
+        // Iota
+        s[0] = s[0] ^ *rc;
+    }
+}
+
+// Applies Keccak-p[1600, rounds] to a state stored as 200 bytes
+pub(crate) fn keccak_f(state: &mut [u8], rounds: usize) {
+    assert!(state.len() == B);
+
+    let mut s: [u64; 25] = [0; 25];
+    read_u64v_le(&mut s, state);
+    keccak_p1600(&mut s, rounds);
+    write_u64v_le(state, &s);
+}
+
+// The capacity in bytes for a security strength of `security` bits, either 128 or 256
+pub(crate) fn capacity(security: usize) -> usize {
+    assert!(security == 128 || security == 256);
+    security / 4
+}
+
+/// The sponge construction over Keccak-p[1600]. Input is absorbed until the sponge is padded with
+/// `pad`, after which any amount of output can be squeezed.
+#[derive(Copy, Clone)]
+pub struct Sponge {
+    state: [u8; B],
+    rate: usize,
+    rounds: usize,
+    // Bytes absorbed into the current block while absorbing, or bytes of the current block read
+    // while squeezing
+    offset: usize,
+    squeezing: bool
+}
+
+impl Sponge {
+    /// Create a sponge over Keccak-f[1600] with a capacity of `capacity` bytes.
+    pub fn new(capacity: usize) -> Sponge {
+        Sponge::new_with_rounds(capacity, NROUNDS)
+    }
+
+    /// Create a sponge over Keccak-p[1600, `rounds`] with a capacity of `capacity` bytes.
+    pub fn new_with_rounds(capacity: usize, rounds: usize) -> Sponge {
+        assert!(capacity > 0 && capacity < B);
+        assert!(rounds <= NROUNDS);
+        Sponge { state: [0; B], rate: B - capacity, rounds, offset: 0, squeezing: false }
+    }
+
+    /// Get the rate, the number of bytes processed per call of the permutation.
+    pub fn rate(&self) -> usize {
+        self.rate
+    }
+
+    /// Absorb `data`. Panics if the sponge has been padded.
+    pub fn absorb(&mut self, data: &[u8]) {
+        assert!(!self.squeezing);
+        let mut data = data;
+        while !data.is_empty() {
+            let n = cmp::min(self.rate - self.offset, data.len());
+            for (s, d) in self.state[self.offset..self.offset + n].iter_mut().zip(data) {
+                *s ^= *d;
+            }
+            self.offset += n;
+            data = &data[n..];
+
+            if self.offset == self.rate {
+                keccak_f(&mut self.state, self.rounds);
+                self.offset = 0;
+            }
+        }
+    }
+
+    /// Pad the input and switch to squeezing. `suffix` holds the domain separation bits followed
+    /// by the first bit of the pad10*1 padding, from the least significant bit on: 0x01 for the
+    /// original Keccak, 0x06 for SHA-3 and 0x1F for SHAKE.
+    pub fn pad(&mut self, suffix: u8) {
+        assert!(!self.squeezing);
+        assert!(suffix != 0);
+        self.state[self.offset] ^= suffix;
+        self.state[self.rate - 1] ^= 0x80;
+        keccak_f(&mut self.state, self.rounds);
+        self.offset = 0;
+        self.squeezing = true;
+    }
+
+    /// Squeeze the next `out.len()` bytes of output. Panics if the sponge hasn't been padded.
+    pub fn squeeze(&mut self, out: &mut [u8]) {
+        assert!(self.squeezing);
+        let mut pos = 0;
+        while pos < out.len() {
+            if self.offset == self.rate {
+                keccak_f(&mut self.state, self.rounds);
+                self.offset = 0;
+            }
+            let n = cmp::min(self.rate - self.offset, out.len() - pos);
+            out[pos..pos + n].copy_from_slice(&self.state[self.offset..self.offset + n]);
+            self.offset += n;
+            pos += n;
+        }
+    }
+}
+
+/// A duplex object over Keccak-p[1600]. All operations act on the outer part of the state a byte
+/// at a time, from the current position on. A block holds up to `rate - 1` bytes; when it is full,
+/// or when `permute` is called, the block is padded with the suffix given at construction followed
+/// by pad10*1, and the permutation is applied.
+#[derive(Copy, Clone)]
+pub struct Duplex {
+    state: [u8; B],
+    rate: usize,
+    rounds: usize,
+    suffix: u8,
+    pos: usize
+}
+
+impl Duplex {
+    /// Create a duplex object over Keccak-f[1600] with a capacity of `capacity` bytes, padding its
+    /// blocks with `suffix`.
+    pub fn new(capacity: usize, suffix: u8) -> Duplex {
+        Duplex::new_with_rounds(capacity, suffix, NROUNDS)
+    }
+
+    /// Create a duplex object over Keccak-p[1600, `rounds`] with a capacity of `capacity` bytes,
+    /// padding its blocks with `suffix`.
+    pub fn new_with_rounds(capacity: usize, suffix: u8, rounds: usize) -> Duplex {
+        Duplex::from_state([0; 25], capacity, suffix, rounds)
+    }
+
+    /// Create a duplex object starting from `state` instead of the all zero state, with the
+    /// position at the start of a block.
+    pub fn from_state(state: [u64; 25], capacity: usize, suffix: u8, rounds: usize) -> Duplex {
+        assert!(capacity > 0 && capacity < B - 1);
+        assert!(suffix != 0);
+        assert!(rounds <= NROUNDS);
+        let mut duplex = Duplex { state: [0; B], rate: B - capacity, rounds, suffix, pos: 0 };
+        write_u64v_le(&mut duplex.state, &state);
+        duplex
+    }
+
+    /// Get the rate of the underlying sponge in bytes.
+    pub fn rate(&self) -> usize {
+        self.rate
+    }
+
+    /// Get the position within the current block.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    // Applies `f` to the next `len` bytes of the state, along with their index
+    fn process<F: FnMut(&mut u8, usize)>(&mut self, len: usize, mut f: F) {
+        for i in 0..len {
+            f(&mut self.state[self.pos], i);
+            self.pos += 1;
+            if self.pos == self.rate - 1 {
+                self.permute();
+            }
+        }
+    }
+
+    /// XOR `data` into the state.
+    pub fn absorb(&mut self, data: &[u8]) {
+        self.process(data.len(), |s, i| *s ^= data[i]);
+    }
+
+    /// Replace the state with `data`.
+    pub fn overwrite(&mut self, data: &[u8]) {
+        self.process(data.len(), |s, i| *s = data[i]);
+    }
+
+    /// Read the state into `out`.
+    pub fn squeeze(&mut self, out: &mut [u8]) {
+        self.process(out.len(), |s, i| out[i] = *s);
+    }
+
+    /// Duplex `data`: XOR the state into it, and replace the state with the result. Used to
+    /// encrypt, as the state then depends on the ciphertext.
+    pub fn encrypt(&mut self, data: &mut [u8]) {
+        self.process(data.len(), |s, i| {
+            data[i] ^= *s;
+            *s = data[i];
+        });
+    }
+
+    /// The inverse of `encrypt`: XOR the state into `data`, and replace the state with the
+    /// original `data`.
+    pub fn decrypt(&mut self, data: &mut [u8]) {
+        self.process(data.len(), |s, i| {
+            let c = data[i];
+            data[i] ^= *s;
+            *s = c;
+        });
+    }
+
+    /// Forget the next `len` bytes of the state by overwriting them with zeros. After a call to
+    /// `permute` this prevents the state from being rolled back.
+    pub fn ratchet(&mut self, len: usize) {
+        self.process(len, |s, _| *s = 0);
+    }
+
+    /// Pad the current block and apply the permutation, starting a new block.
+    pub fn permute(&mut self) {
+        self.state[self.pos] ^= self.suffix;
+        self.state[self.rate - 1] ^= 0x80;
+        keccak_f(&mut self.state, self.rounds);
+        self.pos = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::keccak::{keccak_f1600, keccak_p1600, Duplex, Sponge};
+    use crate::serialize::hex::FromHex;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    #[test]
+    fn test_keccak_f1600_zero_state() {
+        let mut state = [0u64; 25];
+        keccak_f1600(&mut state);
+        assert_eq!(state[0], 0xf1258f7940e1dde7);
+        assert_eq!(state[1], 0x84d5ccf933c0478a);
+
+        let mut full_rounds = [0u64; 25];
+        keccak_p1600(&mut full_rounds, 24);
+        assert_eq!(full_rounds, state);
+    }
+
+    #[test]
+    fn test_sponge_sha3_256() {
+        let mut sponge = Sponge::new(64);
+        assert_eq!(sponge.rate(), 136);
+        sponge.absorb(b"a");
+        sponge.absorb(b"bc");
+        sponge.pad(0x06);
+        let mut out = [0u8; 32];
+        sponge.squeeze(&mut out);
+        assert_eq!(out[..], hex_to_bytes(
+            "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532")[..]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sponge_absorb_after_pad() {
+        let mut sponge = Sponge::new(32);
+        sponge.pad(0x1f);
+        sponge.absorb(b"abc");
+    }
+
+    #[test]
+    fn test_duplex_encrypt_decrypt() {
+        let mut alice = Duplex::new(32, 0x04);
+        let mut bob = Duplex::new(32, 0x04);
+        alice.absorb(b"shared key");
+        bob.absorb(b"shared key");
+        alice.permute();
+        bob.permute();
+
+        let message: Vec<u8> = (0..500).map(|i| i as u8).collect();
+        let mut data = message.clone();
+        alice.encrypt(&mut data);
+        assert!(data != message);
+        bob.decrypt(&mut data);
+        assert_eq!(data, message);
+        assert_eq!(alice.position(), bob.position());
+
+        let mut tag_alice = [0u8; 16];
+        let mut tag_bob = [0u8; 16];
+        alice.squeeze(&mut tag_alice);
+        bob.squeeze(&mut tag_bob);
+        assert_eq!(tag_alice, tag_bob);
+    }
+
+    #[test]
+    fn test_duplex_ratchet() {
+        let mut duplex = Duplex::new(32, 0x04);
+        duplex.absorb(b"secret");
+        duplex.permute();
+        let mut forgetful = duplex;
+
+        forgetful.ratchet(16);
+        duplex.absorb(&[0; 16]);
+        let mut out = [0u8; 16];
+        let mut out_forgetful = [0u8; 16];
+        duplex.squeeze(&mut out);
+        forgetful.squeeze(&mut out_forgetful);
+        assert_eq!(out, out_forgetful);
+
+        forgetful.permute();
+        duplex.permute();
+        duplex.squeeze(&mut out);
+        forgetful.squeeze(&mut out_forgetful);
+        assert!(out != out_forgetful);
+    }
+}
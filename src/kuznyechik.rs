@@ -0,0 +1,284 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the Kuznyechik block cipher, as specified in GOST R 34.12-2015 and
+ * [RFC 7801](https://tools.ietf.org/html/rfc7801). It has a 128 bit block and a 256 bit key.
+ *
+ * The linear transformation L is applied with masks over its 128 precomputed columns, but the
+ * S-box uses secret dependent table lookups.
+ */
+
+use crate::cryptoutil::{read_u128_be, write_u128_be};
+use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+// The substitution Pi, which is shared with the Streebog hash function
+pub(crate) const PI: [u8; 256] = [
+    0xfc, 0xee, 0xdd, 0x11, 0xcf, 0x6e, 0x31, 0x16, 0xfb, 0xc4, 0xfa, 0xda, 0x23, 0xc5, 0x04, 0x4d,
+    0xe9, 0x77, 0xf0, 0xdb, 0x93, 0x2e, 0x99, 0xba, 0x17, 0x36, 0xf1, 0xbb, 0x14, 0xcd, 0x5f, 0xc1,
+    0xf9, 0x18, 0x65, 0x5a, 0xe2, 0x5c, 0xef, 0x21, 0x81, 0x1c, 0x3c, 0x42, 0x8b, 0x01, 0x8e, 0x4f,
+    0x05, 0x84, 0x02, 0xae, 0xe3, 0x6a, 0x8f, 0xa0, 0x06, 0x0b, 0xed, 0x98, 0x7f, 0xd4, 0xd3, 0x1f,
+    0xeb, 0x34, 0x2c, 0x51, 0xea, 0xc8, 0x48, 0xab, 0xf2, 0x2a, 0x68, 0xa2, 0xfd, 0x3a, 0xce, 0xcc,
+    0xb5, 0x70, 0x0e, 0x56, 0x08, 0x0c, 0x76, 0x12, 0xbf, 0x72, 0x13, 0x47, 0x9c, 0xb7, 0x5d, 0x87,
+    0x15, 0xa1, 0x96, 0x29, 0x10, 0x7b, 0x9a, 0xc7, 0xf3, 0x91, 0x78, 0x6f, 0x9d, 0x9e, 0xb2, 0xb1,
+    0x32, 0x75, 0x19, 0x3d, 0xff, 0x35, 0x8a, 0x7e, 0x6d, 0x54, 0xc6, 0x80, 0xc3, 0xbd, 0x0d, 0x57,
+    0xdf, 0xf5, 0x24, 0xa9, 0x3e, 0xa8, 0x43, 0xc9, 0xd7, 0x79, 0xd6, 0xf6, 0x7c, 0x22, 0xb9, 0x03,
+    0xe0, 0x0f, 0xec, 0xde, 0x7a, 0x94, 0xb0, 0xbc, 0xdc, 0xe8, 0x28, 0x50, 0x4e, 0x33, 0x0a, 0x4a,
+    0xa7, 0x97, 0x60, 0x73, 0x1e, 0x00, 0x62, 0x44, 0x1a, 0xb8, 0x38, 0x82, 0x64, 0x9f, 0x26, 0x41,
+    0xad, 0x45, 0x46, 0x92, 0x27, 0x5e, 0x55, 0x2f, 0x8c, 0xa3, 0xa5, 0x7d, 0x69, 0xd5, 0x95, 0x3b,
+    0x07, 0x58, 0xb3, 0x40, 0x86, 0xac, 0x1d, 0xf7, 0x30, 0x37, 0x6b, 0xe4, 0x88, 0xd9, 0xe7, 0x89,
+    0xe1, 0x1b, 0x83, 0x49, 0x4c, 0x3f, 0xf8, 0xfe, 0x8d, 0x53, 0xaa, 0x90, 0xca, 0xd8, 0x85, 0x61,
+    0x20, 0x71, 0x67, 0xa4, 0x2d, 0x2b, 0x09, 0x5b, 0xcb, 0x9b, 0x25, 0xd0, 0xbe, 0xe5, 0x6c, 0x52,
+    0x59, 0xa6, 0x74, 0xd2, 0xe6, 0xf4, 0xb4, 0xc0, 0xd1, 0x66, 0xaf, 0xc2, 0x39, 0x4b, 0x63, 0xb6];
+
+const PI_INV: [u8; 256] = invert(&PI);
+
+// The coefficients of the linear function l, for a_15 down to a_0
+const LC: [u8; 16] = [148, 32, 133, 16, 194, 192, 1, 251, 1, 192, 194, 16, 133, 32, 148, 1];
+
+// The columns of L and of its inverse, so L(a) is the XOR of the columns of the set bits of a
+static L_COLS: [u128; 128] = columns(false);
+static L_INV_COLS: [u128; 128] = columns(true);
+
+const fn invert(sbox: &[u8; 256]) -> [u8; 256] {
+    let mut inv = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[sbox[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+}
+
+// Multiplication in GF(2^8) modulo x^8 + x^7 + x^6 + x + 1
+const fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut r = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            r ^= a;
+        }
+        a = (a << 1) ^ if a & 0x80 != 0 { 0xc3 } else { 0 };
+        b >>= 1;
+    }
+    r
+}
+
+const fn l(a: u128) -> u8 {
+    let mut r = 0;
+    let mut i = 0;
+    while i < 16 {
+        r ^= gf_mul(LC[i], (a >> (8 * (15 - i))) as u8);
+        i += 1;
+    }
+    r
+}
+
+const fn columns(inverse: bool) -> [u128; 128] {
+    let mut cols = [0u128; 128];
+    let mut i = 0;
+    while i < 128 {
+        let mut a = 1u128 << i;
+        let mut j = 0;
+        while j < 16 {
+            a = if inverse {
+                // R^-1(a_15 || ... || a_0) = a_14 || ... || a_0 || l(a_14, ..., a_0, a_15)
+                let b = a.rotate_left(8);
+                (b & !0xff) | l(b) as u128
+            } else {
+                // R(a_15 || ... || a_0) = l(a_15, ..., a_0) || a_15 || ... || a_1
+                ((l(a) as u128) << 120) | (a >> 8)
+            };
+            j += 1;
+        }
+        cols[i] = a;
+        i += 1;
+    }
+    cols
+}
+
+fn linear(cols: &[u128; 128], a: u128) -> u128 {
+    let mut r = 0;
+    for (i, &col) in cols.iter().enumerate() {
+        r ^= col & 0u128.wrapping_sub((a >> i) & 1);
+    }
+    r
+}
+
+fn substitute(sbox: &[u8; 256], a: u128) -> u128 {
+    let mut b = a.to_be_bytes();
+    for x in b.iter_mut() {
+        *x = sbox[*x as usize];
+    }
+    u128::from_be_bytes(b)
+}
+
+fn lsx(key: u128, a: u128) -> u128 {
+    linear(&L_COLS, substitute(&PI, a ^ key))
+}
+
+#[derive(Clone, Copy)]
+pub struct Kuznyechik {
+    keys: [u128; 10]
+}
+
+impl Kuznyechik {
+    /**
+     * Create a new Kuznyechik instance.
+     *
+     * # Arguments
+     * * key - The key, which must be 32 bytes long.
+     */
+    pub fn new(key: &[u8]) -> Kuznyechik {
+        assert!(key.len() == 32);
+        let mut keys = [0u128; 10];
+        let mut a1 = read_u128_be(&key[0..16]);
+        let mut a0 = read_u128_be(&key[16..32]);
+        keys[0] = a1;
+        keys[1] = a0;
+        for i in 0..4 {
+            for j in 0..8 {
+                let c = linear(&L_COLS, (8 * i + j + 1) as u128);
+                let t = lsx(c, a1) ^ a0;
+                a0 = a1;
+                a1 = t;
+            }
+            keys[2 * i + 2] = a1;
+            keys[2 * i + 3] = a0;
+        }
+        Kuznyechik {
+            keys
+        }
+    }
+}
+
+impl BlockEncryptor for Kuznyechik {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        let mut a = read_u128_be(input);
+        for &k in self.keys[..9].iter() {
+            a = lsx(k, a);
+        }
+        a ^= self.keys[9];
+        write_u128_be(output, a);
+    }
+}
+
+impl BlockDecryptor for Kuznyechik {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        let mut a = read_u128_be(input) ^ self.keys[9];
+        for &k in self.keys[..9].iter().rev() {
+            a = substitute(&PI_INV, linear(&L_INV_COLS, a)) ^ k;
+        }
+        write_u128_be(output, a);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blockmodes::CtrMode;
+    use crate::kuznyechik::{Kuznyechik, L_COLS, linear};
+    use crate::serialize::hex::FromHex;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor, SynchronousStreamCipher};
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    fn key() -> Vec<u8> {
+        hex_to_bytes("8899aabbccddeeff0011223344556677fedcba98765432100123456789abcdef")
+    }
+
+    // Examples from RFC 7801, Section 5
+    #[test]
+    fn test_linear() {
+        let tests = [
+            (0x64a59400000000000000000000000000, 0xd456584dd0e3e84cc3166e4b7fa2890d),
+            (0xd456584dd0e3e84cc3166e4b7fa2890d, 0x79d26221b87b584cd42fbc4ffea5de9a),
+            (0x79d26221b87b584cd42fbc4ffea5de9a, 0x0e93691a0cfc60408b7b68f66b513c13),
+            (0x0e93691a0cfc60408b7b68f66b513c13, 0xe6a8094fee0aa204fd97bcb0b44b8580),
+        ];
+        for &(input, output) in tests.iter() {
+            assert_eq!(linear(&L_COLS, input), output);
+        }
+    }
+
+    #[test]
+    fn test_key_schedule() {
+        let kuznyechik = Kuznyechik::new(&key());
+        assert_eq!(kuznyechik.keys[2], 0xdb31485315694343228d6aef8cc78c44);
+        assert_eq!(kuznyechik.keys[9], 0x72e9dd7416bcf45b755dbaa88e4a4043);
+    }
+
+    #[test]
+    fn test_kuznyechik() {
+        let kuznyechik = Kuznyechik::new(&key());
+        let plaintext = hex_to_bytes("1122334455667700ffeeddccbbaa9988");
+        let ciphertext = hex_to_bytes("7f679d90bebc24305a468d42b9d4edcd");
+        let mut output = [0u8; 16];
+        kuznyechik.encrypt_block(&plaintext, &mut output);
+        assert_eq!(&output[..], &ciphertext[..]);
+        kuznyechik.decrypt_block(&ciphertext, &mut output);
+        assert_eq!(&output[..], &plaintext[..]);
+    }
+
+    // Example from GOST R 34.13-2015, Section A.1.2
+    #[test]
+    fn test_kuznyechik_ctr() {
+        let iv = hex_to_bytes("1234567890abcef00000000000000000");
+        let plaintext = hex_to_bytes(
+            "1122334455667700ffeeddccbbaa998800112233445566778899aabbcceeff0a\
+             112233445566778899aabbcceeff0a002233445566778899aabbcceeff0a0011");
+        let expected = hex_to_bytes(
+            "f195d8bec10ed1dbd57b5fa240bda1b885eee733f6a13e5df33ce4b33c45dee4\
+             a5eae88be6356ed3d5e877f13564a3a5cb91fab1f20cbab6d1c6d15820bdba73");
+
+        let mut ctr = CtrMode::new(Kuznyechik::new(&key()), iv);
+        let mut output = vec![0u8; plaintext.len()];
+        ctr.process(&plaintext, &mut output);
+        assert_eq!(output, expected);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::kuznyechik::Kuznyechik;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+    #[bench]
+    pub fn kuznyechik_encrypt(bh: &mut Bencher) {
+        let kuznyechik = Kuznyechik::new(&[1u8; 32]);
+        let input = [1u8; 16];
+        let mut output = [0u8; 16];
+        bh.iter( || {
+            kuznyechik.encrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn kuznyechik_decrypt(bh: &mut Bencher) {
+        let kuznyechik = Kuznyechik::new(&[1u8; 32]);
+        let input = [1u8; 16];
+        let mut output = [0u8; 16];
+        bh.iter( || {
+            kuznyechik.decrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
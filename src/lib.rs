@@ -41,30 +41,56 @@ extern crate crypto;
 Rust-crypto-maintained supports the same algorithems as Rust-Crypto. Currently supported algorithms include:
 
 * AES
+* AES-CBC-HMAC-SHA2
+* AES Key Wrap (with and without padding)
+* ARIA
 * Bcrypt
 * BLAKE2b
+* BLAKE2bp
 * BLAKE2s
+* BLAKE2sp
+* BLAKE2Xb and BLAKE2Xs
+* BLAKE3
 * Blowfish
-* ChaCha20
+* Camellia
+* ChaCha20, ChaCha12 and ChaCha8
+* ChaChaRng (ChaCha20, ChaCha12 and ChaCha8 based)
+* CMAC and AES-CMAC-PRF-128
+* cSHAKE, KMAC, TupleHash and ParallelHash (NIST SP 800-185)
 * Curve25519
+* DES and Triple DES (TDEA)
 * ECB, CBC, and CTR block cipher modes
 * Ed25519
 * Fortuna
+* GCM for any 128 bit block cipher
 * Ghash
-* HC128
+* GMAC
+* HC128 and HC256
 * HMAC
+* KangarooTwelve (KT128 and KT256) and TurboSHAKE
+* Keccak-f[1600] permutation, sponge and duplex
+* Kuznyechik and Magma (GOST R 34.12-2015)
 * MD5
 * PBKDF2
 * PKCS padding for CBC block cipher mode
+* PMAC
 * Poly1305
+* Rabbit
 * RC4
 * RIPEMD-160
-* Salsa20 and XSalsa20
+* Salsa20, Salsa20/12, Salsa20/8 and XSalsa20
 * Scrypt
+* Serpent
 * Sha1
 * Sha2 (All fixed output size variants)
 * Sha3
+* Skein
+* SM3 and SM4
 * Sosemanuk
+* Streebog (GOST R 34.11-2012)
+* STROBE-128 (Merlin compatible)
+* Trivium
+* Twofish
 * Whirlpool
 */
 
@@ -86,43 +112,77 @@ extern crate test;
 
 pub mod aead;
 pub mod aes;
+pub mod aes_cbc_hmac_sha2;
 pub mod aes_gcm;
+pub mod aes_kw;
 pub mod aessafe;
+pub mod aria;
 pub mod bcrypt;
 pub mod bcrypt_pbkdf;
 pub mod blake2b;
+pub mod blake2bp;
 pub mod blake2s;
+pub mod blake2sp;
+pub mod blake2xb;
+pub mod blake2xs;
+pub mod blake3;
 pub mod blockmodes;
 pub mod blowfish;
 pub mod buffer;
+pub mod camellia;
+pub mod ccm;
 pub mod chacha20;
 pub mod chacha20poly1305;
+pub mod chacha_rng;
+pub mod cmac;
 #[allow(unsafe_code)]
 mod cryptoutil;
 pub mod curve25519;
+pub mod des;
 pub mod digest;
 pub mod ed25519;
 pub mod fortuna;
+pub mod gcm;
 #[allow(unsafe_code)]
 pub mod ghash;
+pub mod gmac;
 #[allow(unsafe_code)]
 pub mod hc128;
+pub mod hc256;
 pub mod hmac;
 pub mod hkdf;
+pub mod kangarootwelve;
+pub mod keccak;
+pub mod kuznyechik;
 pub mod mac;
+pub mod magma;
 pub mod md5;
 pub mod pbkdf2;
+pub mod pmac;
 pub mod poly1305;
+pub mod rabbit;
 pub mod rc4;
 pub mod ripemd160;
 pub mod salsa20;
 pub mod scrypt;
+pub mod serpent;
 pub mod sha1;
 pub mod sha2;
 pub mod sha3;
 mod simd;
+pub mod skein;
+pub mod sm3;
+pub mod sm4;
 pub mod sosemanuk;
+pub mod sp800_185;
+pub mod streebog;
+pub mod strobe;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[allow(unsafe_code)]
+mod stream_x86;
 pub mod threefish;
+pub mod trivium;
+pub mod twofish;
 pub mod symmetriccipher;
 #[allow(unsafe_code)]
 pub mod util;
@@ -0,0 +1,186 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the Magma block cipher, as specified in GOST R 34.12-2015 and
+ * [RFC 8891](https://tools.ietf.org/html/rfc8891). It has a 64 bit block and a 256 bit key, and
+ * uses the S-boxes fixed by the standard (id-tc26-gost-28147-param-Z).
+ *
+ * The implementation doesn't use any secret dependent table lookups or branches: each 4 bit S-box
+ * is packed into a 64 bit word and its output is selected with a shift.
+ */
+
+use crate::cryptoutil::{read_u32v_be, read_u32_be, write_u32_be};
+use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+// The substitutions Pi'_0 to Pi'_7, with Pi'_i(j) in bits 4 * j to 4 * j + 3 of SBOXES[i]
+static SBOXES: [u64; 8] = [
+    0x1f307d8e9b5a264c,
+    0xf0db74e1c5a93286,
+    0x069c471edaf2853b,
+    0xb9e35a076f4d128c,
+    0xc24be390d618a5f7,
+    0x0e34187bac296fd5,
+    0x73ad0b4fc19652e8,
+    0x2bc96af43850de71];
+
+// The round function g[k]
+fn g(key: u32, a: u32) -> u32 {
+    let x = a.wrapping_add(key);
+    let mut y = 0;
+    for (i, &sbox) in SBOXES.iter().enumerate() {
+        let nibble = (x >> (4 * i)) & 0xf;
+        y |= (((sbox >> (4 * nibble)) & 0xf) as u32) << (4 * i);
+    }
+    y.rotate_left(11)
+}
+
+#[derive(Clone, Copy)]
+pub struct Magma {
+    keys: [u32; 8]
+}
+
+impl Magma {
+    /**
+     * Create a new Magma instance.
+     *
+     * # Arguments
+     * * key - The key, which must be 32 bytes long.
+     */
+    pub fn new(key: &[u8]) -> Magma {
+        assert!(key.len() == 32);
+        let mut keys = [0u32; 8];
+        read_u32v_be(&mut keys, key);
+        Magma {
+            keys
+        }
+    }
+
+    // The 32 rounds use the round keys in the order given by `key_index`
+    fn crypt<F: Fn(usize) -> usize>(&self, key_index: F, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 8);
+        assert!(output.len() == 8);
+        let mut a1 = read_u32_be(&input[0..4]);
+        let mut a0 = read_u32_be(&input[4..8]);
+        for i in 0..31 {
+            let t = g(self.keys[key_index(i)], a0) ^ a1;
+            a1 = a0;
+            a0 = t;
+        }
+        a1 ^= g(self.keys[key_index(31)], a0);
+        write_u32_be(&mut output[0..4], a1);
+        write_u32_be(&mut output[4..8], a0);
+    }
+}
+
+impl BlockEncryptor for Magma {
+    fn block_size(&self) -> usize {
+        8
+    }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        // K_1, ..., K_8 three times, then K_8, ..., K_1
+        self.crypt(|i| if i < 24 { i % 8 } else { 7 - i % 8 }, input, output);
+    }
+}
+
+impl BlockDecryptor for Magma {
+    fn block_size(&self) -> usize {
+        8
+    }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        // K_1, ..., K_8, then K_8, ..., K_1 three times
+        self.crypt(|i| if i < 8 { i } else { 7 - i % 8 }, input, output);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::blockmodes::CtrMode;
+    use crate::magma::{Magma, g};
+    use crate::serialize::hex::FromHex;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor, SynchronousStreamCipher};
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    fn key() -> Vec<u8> {
+        hex_to_bytes("ffeeddccbbaa99887766554433221100f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff")
+    }
+
+    // Examples from RFC 8891, Section A.2
+    #[test]
+    fn test_g() {
+        let tests = [
+            (0x87654321, 0xfedcba98, 0xfdcbc20c),
+            (0xfdcbc20c, 0x87654321, 0x7e791a4b),
+            (0x7e791a4b, 0xfdcbc20c, 0xc76549ec),
+            (0xc76549ec, 0x7e791a4b, 0x9791c849),
+        ];
+        for &(key, input, output) in tests.iter() {
+            assert_eq!(g(key, input), output);
+        }
+    }
+
+    #[test]
+    fn test_magma() {
+        let magma = Magma::new(&key());
+        let plaintext = hex_to_bytes("fedcba9876543210");
+        let ciphertext = hex_to_bytes("4ee901e5c2d8ca3d");
+        let mut output = [0u8; 8];
+        magma.encrypt_block(&plaintext, &mut output);
+        assert_eq!(&output[..], &ciphertext[..]);
+        magma.decrypt_block(&ciphertext, &mut output);
+        assert_eq!(&output[..], &plaintext[..]);
+    }
+
+    // Example from GOST R 34.13-2015, Section A.2.2
+    #[test]
+    fn test_magma_ctr() {
+        let iv = hex_to_bytes("1234567800000000");
+        let plaintext = hex_to_bytes(
+            "92def06b3c130a59db54c704f8189d204a98fb2e67a8024c8912409b17b57e41");
+        let expected = hex_to_bytes(
+            "4e98110c97b7b93c3e250d93d6e85d69136d868807b2dbef568eb680ab52a12d");
+
+        let mut ctr = CtrMode::new(Magma::new(&key()), iv);
+        let mut output = vec![0u8; plaintext.len()];
+        ctr.process(&plaintext, &mut output);
+        assert_eq!(output, expected);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::magma::Magma;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+    #[bench]
+    pub fn magma_encrypt(bh: &mut Bencher) {
+        let magma = Magma::new(&[1u8; 32]);
+        let input = [1u8; 8];
+        let mut output = [0u8; 8];
+        bh.iter( || {
+            magma.encrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn magma_decrypt(bh: &mut Bencher) {
+        let magma = Magma::new(&[1u8; 32]);
+        let input = [1u8; 8];
+        let mut output = [0u8; 8];
+        bh.iter( || {
+            magma.decrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
@@ -0,0 +1,305 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the Rabbit stream cipher as specified in RFC 4503 [1]. It takes a 128 bit
+ * key and an optional 64 bit IV.
+ *
+ * Keys, IVs and keystream are byte strings in the order used by the eSTREAM reference code: the
+ * test vectors in the RFC write each of them as a single big endian number, which is the reverse.
+ *
+ * # References
+ * [1] - M. Boesgaard, M. Vesterager, E. Zenner. The Rabbit Stream Cipher Algorithm. RFC 4503.
+ *       <https://tools.ietf.org/html/rfc4503>
+ */
+
+use crate::buffer::{BufferResult, RefReadBuffer, RefWriteBuffer};
+use crate::symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher, SymmetricCipherError};
+use crate::cryptoutil::{read_u32v_le, symm_enc_or_dec, write_u32v_le};
+
+
+const A: [u32; 8] = [
+    0x4D34D34D, 0xD34D34D3, 0x34D34D34, 0x4D34D34D,
+    0xD34D34D3, 0x34D34D34, 0x4D34D34D, 0xD34D34D3
+];
+
+#[derive(Copy, Clone)]
+pub struct Rabbit {
+    x: [u32; 8],
+    c: [u32; 8],
+    carry: u32,
+    output: [u8; 16],
+    output_index: usize
+}
+
+impl Rabbit {
+    /// Creates a Rabbit instance from a 128 bit key and a 64 bit IV.
+    pub fn new(key: &[u8], iv: &[u8]) -> Rabbit {
+        assert!(iv.len() == 8);
+        let mut rabbit = Rabbit::new_without_iv(key);
+        rabbit.iv_setup(iv);
+
+        rabbit
+    }
+
+    /// Creates a Rabbit instance from a 128 bit key alone, skipping the IV setup. Only use this if
+    /// every key encrypts a single message.
+    pub fn new_without_iv(key: &[u8]) -> Rabbit {
+        assert!(key.len() == 16);
+        let mut rabbit = Rabbit { x: [0; 8], c: [0; 8], carry: 0, output: [0; 16], output_index: 16 };
+        rabbit.key_setup(key);
+
+        rabbit
+    }
+
+    fn key_setup(&mut self, key: &[u8]) {
+        let mut k = [0u32; 4];
+        read_u32v_le(&mut k, key);
+
+        self.x = [
+            k[0], (k[3] << 16) | (k[2] >> 16), k[1], (k[0] << 16) | (k[3] >> 16),
+            k[2], (k[1] << 16) | (k[0] >> 16), k[3], (k[2] << 16) | (k[1] >> 16)
+        ];
+        self.c = [
+            k[2].rotate_left(16), (k[0] & 0xFFFF0000) | (k[1] & 0xFFFF),
+            k[3].rotate_left(16), (k[1] & 0xFFFF0000) | (k[2] & 0xFFFF),
+            k[0].rotate_left(16), (k[2] & 0xFFFF0000) | (k[3] & 0xFFFF),
+            k[1].rotate_left(16), (k[3] & 0xFFFF0000) | (k[0] & 0xFFFF)
+        ];
+        self.carry = 0;
+
+        for _ in 0..4 {
+            self.next_state();
+        }
+
+        for j in 0..8 {
+            self.c[j] ^= self.x[(j + 4) & 7];
+        }
+    }
+
+    fn iv_setup(&mut self, iv: &[u8]) {
+        let mut v = [0u32; 2];
+        read_u32v_le(&mut v, iv);
+
+        let i0 = v[0];
+        let i2 = v[1];
+        let i1 = (i0 >> 16) | (i2 & 0xFFFF0000);
+        let i3 = (i2 << 16) | (i0 & 0xFFFF);
+
+        for (j, c) in self.c.iter_mut().enumerate() {
+            *c ^= [i0, i1, i2, i3][j & 3];
+        }
+
+        for _ in 0..4 {
+            self.next_state();
+        }
+    }
+
+    fn next_state(&mut self) {
+        // Counter update
+        for (c, a) in self.c.iter_mut().zip(A.iter()) {
+            let t = (*c as u64) + (*a as u64) + (self.carry as u64);
+            self.carry = (t >> 32) as u32;
+            *c = t as u32;
+        }
+
+        // Next-state function
+        let mut g = [0u32; 8];
+        for (j, g) in g.iter_mut().enumerate() {
+            let u = self.x[j].wrapping_add(self.c[j]) as u64;
+            let square = u * u;
+            *g = (square ^ (square >> 32)) as u32;
+        }
+
+        self.x[0] = g[0].wrapping_add(g[7].rotate_left(16)).wrapping_add(g[6].rotate_left(16));
+        self.x[1] = g[1].wrapping_add(g[0].rotate_left(8)).wrapping_add(g[7]);
+        self.x[2] = g[2].wrapping_add(g[1].rotate_left(16)).wrapping_add(g[0].rotate_left(16));
+        self.x[3] = g[3].wrapping_add(g[2].rotate_left(8)).wrapping_add(g[1]);
+        self.x[4] = g[4].wrapping_add(g[3].rotate_left(16)).wrapping_add(g[2].rotate_left(16));
+        self.x[5] = g[5].wrapping_add(g[4].rotate_left(8)).wrapping_add(g[3]);
+        self.x[6] = g[6].wrapping_add(g[5].rotate_left(16)).wrapping_add(g[4].rotate_left(16));
+        self.x[7] = g[7].wrapping_add(g[6].rotate_left(8)).wrapping_add(g[5]);
+    }
+
+    // Advances the state and extracts the next 16 bytes of keystream into self.output
+    fn update(&mut self) {
+        self.next_state();
+
+        let x = &self.x;
+        let s = [
+            x[0] ^ (x[5] >> 16) ^ (x[3] << 16),
+            x[2] ^ (x[7] >> 16) ^ (x[5] << 16),
+            x[4] ^ (x[1] >> 16) ^ (x[7] << 16),
+            x[6] ^ (x[3] >> 16) ^ (x[1] << 16)
+        ];
+        write_u32v_le(&mut self.output, &s);
+        self.output_index = 0;
+    }
+}
+
+impl SynchronousStreamCipher for Rabbit {
+    fn process(&mut self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == output.len());
+
+        for (x, y) in input.iter().zip(output.iter_mut()) {
+            if self.output_index == 16 {
+                self.update();
+            }
+            *y = *x ^ self.output[self.output_index];
+            self.output_index += 1;
+        }
+    }
+}
+
+impl Encryptor for Rabbit {
+    fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        symm_enc_or_dec(self, input, output)
+    }
+}
+
+impl Decryptor for Rabbit {
+    fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        symm_enc_or_dec(self, input, output)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::rabbit::Rabbit;
+    use crate::symmetriccipher::SynchronousStreamCipher;
+    use crate::serialize::hex::{FromHex};
+
+    // RFC 4503 writes keys, IVs and every 16 byte block of keystream as big endian numbers
+    fn from_rfc_hex(hex: &str, block_size: usize) -> Vec<u8> {
+        let bytes = hex.from_hex().unwrap();
+        bytes.chunks(block_size).flat_map(|b| b.iter().rev().cloned()).collect()
+    }
+
+    fn check(mut rabbit: Rabbit, expected_output: &[u8]) {
+        let input = vec![0u8; expected_output.len()];
+
+        let mut output = vec![0u8; expected_output.len()];
+        let mut first = rabbit;
+        first.process(&input, &mut output);
+        assert_eq!(output, expected_output);
+
+        // The same keystream, processed in pieces that don't line up with the keystream blocks
+        let mut output = vec![0u8; expected_output.len()];
+        for (i, o) in input.chunks(7).zip(output.chunks_mut(7)) {
+            rabbit.process(i, o);
+        }
+        assert_eq!(output, expected_output);
+    }
+
+    fn check_without_iv(key_hex: &str, expected_output_hex: &str) {
+        let key = from_rfc_hex(key_hex, 16);
+        check(Rabbit::new_without_iv(&key), &from_rfc_hex(expected_output_hex, 16));
+    }
+
+    fn check_with_iv(key_hex: &str, iv_hex: &str, expected_output_hex: &str) {
+        let key = from_rfc_hex(key_hex, 16);
+        let iv = from_rfc_hex(iv_hex, 8);
+        check(Rabbit::new(&key, &iv), &from_rfc_hex(expected_output_hex, 16));
+    }
+
+    // RFC 4503, Appendix A.1: testing without IV setup
+
+    #[test]
+    fn test_rabbit_rfc4503_without_iv_1() {
+        check_without_iv("00000000000000000000000000000000",
+                         "B15754F036A5D6ECF56B45261C4AF702\
+                          88E8D815C59C0C397B696C4789C68AA7\
+                          F416A1C3700CD451DA68D1881673D696");
+    }
+
+    #[test]
+    fn test_rabbit_rfc4503_without_iv_2() {
+        check_without_iv("912813292E3D36FE3BFC62F1DC51C3AC",
+                         "3D2DF3C83EF627A1E97FC38487E2519C\
+                          F576CD61F4405B8896BF53AA8554FC19\
+                          E5547473FBDB43508AE53B20204D4C5E");
+    }
+
+    #[test]
+    fn test_rabbit_rfc4503_without_iv_3() {
+        check_without_iv("8395741587E0C733E9E9AB01C09B0043",
+                         "0CB10DCDA041CDAC32EB5CFD02D0609B\
+                          95FC9FCA0F17015A7B7092114CFF3EAD\
+                          9649E5DE8BFC7F3F924147AD3A947428");
+    }
+
+    // RFC 4503, Appendix A.2: testing with IV setup
+
+    #[test]
+    fn test_rabbit_rfc4503_with_iv_1() {
+        check_with_iv("00000000000000000000000000000000",
+                      "0000000000000000",
+                      "C6A7275EF85495D87CCD5D376705B7ED\
+                       5F29A6AC04F5EFD47B8F293270DC4A8D\
+                       2ADE822B29DE6C1EE52BDB8A47BF8F66");
+    }
+
+    #[test]
+    fn test_rabbit_rfc4503_with_iv_2() {
+        check_with_iv("00000000000000000000000000000000",
+                      "C373F575C1267E59",
+                      "1FCD4EB9580012E2E0DCCC9222017D6D\
+                       A75F4E10D12125017B2499FFED936F2E\
+                       EBC112C393E738392356BDD012029BA7");
+    }
+
+    #[test]
+    fn test_rabbit_rfc4503_with_iv_3() {
+        check_with_iv("00000000000000000000000000000000",
+                      "A6EB561AD2F41727",
+                      "445AD8C805858DBF70B6AF23A151104D\
+                       96C8F27947F42C5BAEAE67C6ACC35B03\
+                       9FCBFC895FA71C17313DF034F01551CB");
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+    use crate::symmetriccipher::SynchronousStreamCipher;
+    use crate::rabbit::Rabbit;
+
+    #[bench]
+    pub fn rabbit_10(bh: & mut Bencher) {
+        let mut rabbit = Rabbit::new(&[0; 16], &[0; 8]);
+        let input = [1u8; 10];
+        let mut output = [0u8; 10];
+        bh.iter( || {
+            rabbit.process(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn rabbit_1k(bh: & mut Bencher) {
+        let mut rabbit = Rabbit::new(&[0; 16], &[0; 8]);
+        let input = [1u8; 1024];
+        let mut output = [0u8; 1024];
+        bh.iter( || {
+            rabbit.process(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn rabbit_64k(bh: & mut Bencher) {
+        let mut rabbit = Rabbit::new(&[0; 16], &[0; 8]);
+        let input = [1u8; 65536];
+        let mut output = [0u8; 65536];
+        bh.iter( || {
+            rabbit.process(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
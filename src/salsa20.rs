@@ -5,9 +5,13 @@
 // except according to those terms.
 
 use crate::buffer::{BufferResult, RefReadBuffer, RefWriteBuffer};
-use crate::symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher, SymmetricCipherError};
-use crate::cryptoutil::{read_u32_le, symm_enc_or_dec, write_u32_le, xor_keystream};
+use crate::symmetriccipher::{Encryptor, Decryptor, SeekableStreamCipher, SynchronousStreamCipher,
+                             SymmetricCipherError};
+use crate::cryptoutil::{read_u32_le, read_u32v_le, symm_enc_or_dec, write_u32_le, write_u32v_le,
+                        xor_keystream};
 use crate::simd::u32x4;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::stream_x86::{self, Backend};
 
 use std::cmp;
 
@@ -19,14 +23,24 @@ struct SalsaState {
   d: u32x4
 }
 
+/// The Salsa20 stream cipher with `ROUNDS` rounds, which must be even
 #[derive(Copy)]
-pub struct Salsa20 {
+pub struct Salsa<const ROUNDS: usize> {
     state: SalsaState,
     output: [u8; 64],
     offset: usize,
 }
 
-impl Clone for Salsa20 { fn clone(&self) -> Salsa20 { *self } }
+/// Salsa20 with 20 rounds
+pub type Salsa20 = Salsa<20>;
+/// Salsa20/12, the eSTREAM profile 1 variant with 12 rounds
+#[allow(non_camel_case_types)]
+pub type Salsa20_12 = Salsa<12>;
+/// Salsa20/8, with 8 rounds
+#[allow(non_camel_case_types)]
+pub type Salsa20_8 = Salsa<8>;
+
+impl <const ROUNDS: usize> Clone for Salsa<ROUNDS> { fn clone(&self) -> Salsa<ROUNDS> { *self } }
 
 const S7:u32x4 = u32x4(7, 7, 7, 7);
 const S9:u32x4 = u32x4(9, 9, 9, 9);
@@ -79,21 +93,82 @@ fn rowround(state: &mut SalsaState) -> () {
     add_rotate_xor!(state.d, state.a, state.b, S18);
 }
 
-impl Salsa20 {
-    pub fn new(key: &[u8], nonce: &[u8]) -> Salsa20 {
+fn double_rounds(state: &mut SalsaState, rounds: usize) {
+    for _ in 0..rounds / 2 {
+        columnround(state);
+        prepare_rowround!(state.a, state.b, state.c);
+        rowround(state);
+        prepare_columnround!(state.a, state.b, state.c);
+    }
+}
+
+impl SalsaState {
+    // Load a block of 16 words in the natural matrix order
+    fn from_block(input: &[u8]) -> SalsaState {
+        let mut x = [0u32; 16];
+        read_u32v_le(&mut x, input);
+        SalsaState {
+            a: u32x4(x[4], x[9], x[14], x[3]),
+            b: u32x4(x[8], x[13], x[2], x[7]),
+            c: u32x4(x[12], x[1], x[6], x[11]),
+            d: u32x4(x[0], x[5], x[10], x[15])
+        }
+    }
+
+    // Store the state as a block of 16 words in the natural matrix order
+    fn store_block(&self, output: &mut [u8]) {
+        write_u32v_le(output, &self.words());
+    }
+
+    // The 16 words of the state in the natural matrix order
+    fn words(&self) -> [u32; 16] {
+        let u32x4(x4, x9, x14, x3) = self.a;
+        let u32x4(x8, x13, x2, x7) = self.b;
+        let u32x4(x12, x1, x6, x11) = self.c;
+        let u32x4(x0, x5, x10, x15) = self.d;
+        [
+             x0,  x1,  x2,  x3,
+             x4,  x5,  x6,  x7,
+             x8,  x9, x10, x11,
+            x12, x13, x14, x15
+        ]
+    }
+}
+
+/// The Salsa20 core with `rounds` rounds, which hashes a 64 byte block into a 64 byte block.
+/// Scrypt uses it with 8 rounds.
+pub(crate) fn salsa20_core(rounds: usize, input: &[u8], output: &mut [u8]) {
+    let initial = SalsaState::from_block(input);
+    let mut state = initial;
+    double_rounds(&mut state, rounds);
+    let sum = SalsaState {
+        a: state.a + initial.a,
+        b: state.b + initial.b,
+        c: state.c + initial.c,
+        d: state.d + initial.d
+    };
+    sum.store_block(output);
+}
+
+impl <const ROUNDS: usize> Salsa<ROUNDS> {
+    pub fn new(key: &[u8], nonce: &[u8]) -> Salsa<ROUNDS> {
+        assert!(ROUNDS > 0 && ROUNDS & 1 == 0);
         assert!(key.len() == 16 || key.len() == 32);
         assert!(nonce.len() == 8);
-        Salsa20 { state: Salsa20::expand(key, nonce), output: [0; 64], offset: 64 }
+        Salsa { state: Self::expand(key, nonce), output: [0; 64], offset: 64 }
     }
 
-    pub fn new_xsalsa20(key: &[u8], nonce: &[u8]) -> Salsa20 {
+    /// Create an XSalsa instance. The subkey is derived with HSalsa using the same number of
+    /// rounds.
+    pub fn new_xsalsa20(key: &[u8], nonce: &[u8]) -> Salsa<ROUNDS> {
+        assert!(ROUNDS > 0 && ROUNDS & 1 == 0);
         assert!(key.len() == 32);
         assert!(nonce.len() == 24);
-        let mut xsalsa20 = Salsa20 { state: Salsa20::expand(key, &nonce[0..16]), output: [0; 64], offset: 64 };
+        let mut xsalsa20 = Salsa { state: Self::expand(key, &nonce[0..16]), output: [0; 64], offset: 64 };
 
         let mut new_key = [0; 32];
         xsalsa20.hsalsa20_hash(&mut new_key);
-        xsalsa20.state = Salsa20::expand(&new_key, &nonce[16..24]);
+        xsalsa20.state = Self::expand(&new_key, &nonce[16..24]);
 
         xsalsa20
     }
@@ -154,28 +229,17 @@ impl Salsa20 {
 
     fn hash(&mut self) {
         let mut state = self.state;
-        for _ in 0..10 {
-            columnround(&mut state);
-            prepare_rowround!(state.a, state.b, state.c);
-            rowround(&mut state);
-            prepare_columnround!(state.a, state.b, state.c);
-        }
-        let u32x4(x4, x9, x14, x3) = self.state.a + state.a;
-        let u32x4(x8, x13, x2, x7) = self.state.b + state.b;
-        let u32x4(x12, x1, x6, x11) = self.state.c + state.c;
-        let u32x4(x0, x5, x10, x15) = self.state.d + state.d;
-        let lens = [
-             x0,  x1,  x2,  x3,
-             x4,  x5,  x6,  x7,
-             x8,  x9, x10, x11,
-            x12, x13, x14, x15
-        ];
-        for i in 0..lens.len() {
-            write_u32_le(&mut self.output[i*4..(i+1)*4], lens[i]);
-        }
+        double_rounds(&mut state, ROUNDS);
+        let sum = SalsaState {
+            a: self.state.a + state.a,
+            b: self.state.b + state.b,
+            c: self.state.c + state.c,
+            d: self.state.d + state.d
+        };
+        sum.store_block(&mut self.output);
 
         self.state.b = self.state.b + u32x4(1, 0, 0, 0);
-        let u32x4(_, _, _, ctr_lo) = self.state.b;
+        let u32x4(ctr_lo, _, _, _) = self.state.b;
         if ctr_lo == 0 {
             self.state.a = self.state.a + u32x4(0, 1, 0, 0);
         }
@@ -183,14 +247,39 @@ impl Salsa20 {
         self.offset = 0;
     }
 
+    // Process as many whole blocks as the SIMD backend generates at once, and return the number of
+    // bytes processed
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn process_blocks(&mut self, input: &[u8], output: &mut [u8]) -> usize {
+        let backend = match Backend::detect() {
+            Some(backend) => backend,
+            None => return 0,
+        };
+        let size = backend.blocks() * 64;
+        let mut keystream = [0u8; 512];
+        let mut done = 0;
+        while input.len() - done >= size {
+            stream_x86::salsa_blocks(backend, &self.state.words(), ROUNDS, &mut keystream[..size]);
+            xor_keystream(&mut output[done..done + size], &input[done..done + size], &keystream[..size]);
+            // Advance the 64 bit counter (x8, x9)
+            let u32x4(a0, x9, a2, a3) = self.state.a;
+            let u32x4(x8, b1, b2, b3) = self.state.b;
+            let counter = ((x9 as u64) << 32 | x8 as u64).wrapping_add(backend.blocks() as u64);
+            self.state.a = u32x4(a0, (counter >> 32) as u32, a2, a3);
+            self.state.b = u32x4(counter as u32, b1, b2, b3);
+            done += size;
+        }
+        done
+    }
+
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    fn process_blocks(&mut self, _: &[u8], _: &mut [u8]) -> usize {
+        0
+    }
+
     fn hsalsa20_hash(&mut self, out: &mut [u8]) {
         let mut state = self.state;
-        for _ in 0..10 {
-            columnround(&mut state);
-            prepare_rowround!(state.a, state.b, state.c);
-            rowround(&mut state);
-            prepare_columnround!(state.a, state.b, state.c);
-        }
+        double_rounds(&mut state, ROUNDS);
         let u32x4(_, x9, _, _) = state.a;
         let u32x4(x8, _, _, x7) = state.b;
         let u32x4(_, _, x6, _) = state.c;
@@ -205,7 +294,7 @@ impl Salsa20 {
     }
 }
 
-impl SynchronousStreamCipher for Salsa20 {
+impl <const ROUNDS: usize> SynchronousStreamCipher for Salsa<ROUNDS> {
     fn process(&mut self, input: &[u8], output: &mut [u8]) {
         assert!(input.len() == output.len());
         let len = input.len();
@@ -214,6 +303,11 @@ impl SynchronousStreamCipher for Salsa20 {
             // If there is no keystream available in the output buffer,
             // generate the next block.
             if self.offset == 64 {
+                // Whole blocks can be generated several at a time
+                i += self.process_blocks(&input[i..], &mut output[i..]);
+                if i == len {
+                    break;
+                }
                 self.hash();
             }
 
@@ -224,16 +318,56 @@ impl SynchronousStreamCipher for Salsa20 {
             self.offset += count;
         }
     }
+
+    fn try_process(&mut self, input: &[u8], output: &mut [u8]) -> Result<(), SymmetricCipherError> {
+        assert!(input.len() == output.len());
+        // As for ChaCha, the block with the last counter value isn't used, so that a counter which
+        // wrapped around can't be mistaken for a fresh one
+        let u32x4(_, x9, _, _) = self.state.a;
+        let u32x4(x8, _, _, _) = self.state.b;
+        let blocks_left = u64::MAX - ((x9 as u64) << 32 | x8 as u64);
+        if input.len() as u128 > blocks_left as u128 * 64 + (64 - self.offset) as u128 {
+            return Err(SymmetricCipherError::CounterOverflow);
+        }
+        self.process(input, output);
+        Ok(())
+    }
+}
+
+impl <const ROUNDS: usize> SeekableStreamCipher for Salsa<ROUNDS> {
+    fn seek(&mut self, byte_offset: u64) -> Result<(), SymmetricCipherError> {
+        // The 64 bit block counter (x8, x9) can't overflow for any u64 byte offset
+        let block = byte_offset / 64;
+        let u32x4(a0, _, a2, a3) = self.state.a;
+        let u32x4(_, b1, b2, b3) = self.state.b;
+        self.state.a = u32x4(a0, (block >> 32) as u32, a2, a3);
+        self.state.b = u32x4(block as u32, b1, b2, b3);
+        self.offset = 64;
+        let partial = (byte_offset % 64) as usize;
+        if partial != 0 {
+            self.hash();
+            self.offset = partial;
+        }
+        Ok(())
+    }
+
+    fn position(&self) -> u64 {
+        // The counter is the number of the next block to generate
+        let u32x4(_, x9, _, _) = self.state.a;
+        let u32x4(x8, _, _, _) = self.state.b;
+        let counter = (x9 as u64) << 32 | x8 as u64;
+        counter.wrapping_mul(64).wrapping_sub((64 - self.offset) as u64)
+    }
 }
 
-impl Encryptor for Salsa20 {
+impl <const ROUNDS: usize> Encryptor for Salsa<ROUNDS> {
     fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
             -> Result<BufferResult, SymmetricCipherError> {
         symm_enc_or_dec(self, input, output)
     }
 }
 
-impl Decryptor for Salsa20 {
+impl <const ROUNDS: usize> Decryptor for Salsa<ROUNDS> {
     fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
             -> Result<BufferResult, SymmetricCipherError> {
         symm_enc_or_dec(self, input, output)
@@ -251,8 +385,15 @@ pub fn hsalsa20(key: &[u8], nonce: &[u8], out: &mut [u8]) {
 mod test {
     use std::iter::repeat;
 
-    use crate::salsa20::Salsa20;
-    use crate::symmetriccipher::SynchronousStreamCipher;
+    use crate::salsa20::{salsa20_core, Salsa, Salsa20, Salsa20_8, Salsa20_12};
+    use crate::simd::u32x4;
+    use crate::symmetriccipher::{SeekableStreamCipher, SynchronousStreamCipher};
+    use crate::symmetriccipher::SymmetricCipherError::CounterOverflow;
+    use crate::serialize::hex::FromHex;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
 
     use crate::digest::Digest;
     use crate::sha2::Sha256;
@@ -362,6 +503,169 @@ mod test {
         xsalsa20.process(&input, &mut stream);
         assert!(stream[..] == result[..]);
     }
+
+    #[test]
+    fn test_salsa20_seek() {
+        let key: Vec<u8> = (0..32).collect();
+        let nonce = [0u8; 8];
+        let input = [0u8; 1000];
+        let mut expected = [0u8; 1000];
+        Salsa20::new(&key, &nonce).process(&input, &mut expected);
+
+        for &offset in [0usize, 1, 63, 64, 65, 500, 999].iter() {
+            let mut c = Salsa20::new(&key, &nonce);
+            c.process(&input[..7], &mut [0u8; 7]);
+            assert_eq!(c.position(), 7);
+            c.seek(offset as u64).unwrap();
+            assert_eq!(c.position(), offset as u64);
+            let mut output = vec![0u8; 1000 - offset];
+            c.process(&input[offset..], &mut output);
+            assert_eq!(&output[..], &expected[offset..]);
+            assert_eq!(c.position(), 1000);
+        }
+
+        // The last 32 bytes of block 2 of the all zero key and nonce
+        let expected = hex_to_bytes("5352e8dea006b448f418528667425d28a0e524fb582e34b900ace05836a5ba8a");
+        let mut c = Salsa20::new(&[0u8; 32], &[0u8; 8]);
+        c.seek(2 * 64 + 32).unwrap();
+        let mut output = [0u8; 32];
+        c.process(&[0u8; 32], &mut output);
+        assert_eq!(&output[..], &expected[..]);
+    }
+
+    // The block counter used to test the nonce word x7 instead of x8 for the carry, so with a
+    // nonzero x7 the block after 0xffffffff repeated the keystream of block 0
+    #[test]
+    fn test_salsa20_counter_carries_into_high_word() {
+        let key: Vec<u8> = (0..32).collect();
+        let nonce: Vec<u8> = (0..8).collect();
+        // Blocks 0xffffffff and 0x100000000, where the low counter word carries into the high one
+        let expected = hex_to_bytes("60d0f601a5a3aedec240597b0138bb8272eb17d524c523f5f514d83bd7217805\
+            17678be2a6578459b8325dbfbe8650d4ae3a739423bab1faf0b0347bdb8bb3f8\
+            e58a3ce12a19d89b151819eec0956ae8b8ba7df7d537480a39b6678cbbda10f3\
+            f095aa1bc8e860392de7b267fb1245d1ff12efd12887cd1c797ea18bb7261e74");
+        let mut c = Salsa20::new(&key, &nonce);
+        c.seek(0xffffffff * 64).unwrap();
+        let mut output = [0u8; 128];
+        c.process(&[0u8; 128], &mut output);
+        assert_eq!(&output[..], &expected[..]);
+        assert_eq!(c.position(), 0x100000001 * 64);
+
+        let mut block0 = [0u8; 64];
+        Salsa20::new(&key, &nonce).process(&[0u8; 64], &mut block0);
+        assert!(output[64..] != block0[..]);
+    }
+
+    #[test]
+    fn test_salsa20_try_process_end() {
+        // Move the 64 bit block counter (x8, x9) to the last usable block
+        let mut c = Salsa20::new(&[0u8; 32], &[0u8; 8]);
+        let u32x4(a0, _, a2, a3) = c.state.a;
+        let u32x4(_, b1, b2, b3) = c.state.b;
+        c.state.a = u32x4(a0, 0xffffffff, a2, a3);
+        c.state.b = u32x4(0xfffffffe, b1, b2, b3);
+
+        let mut output = [0u8; 65];
+        match c.try_process(&[0u8; 65], &mut output) {
+            Err(CounterOverflow) => {},
+            _ => panic!("processing past the end of the keystream must fail"),
+        }
+        assert_eq!(output[..], [0u8; 65][..]);
+        c.try_process(&[0u8; 64], &mut output[..64]).unwrap();
+        match c.try_process(&[0u8; 1], &mut output[..1]) {
+            Err(CounterOverflow) => {},
+            _ => panic!("processing past the end of the keystream must fail"),
+        }
+    }
+
+    #[test]
+    fn test_salsa20_reduced_rounds_ecrypt_set_1_vector_0() {
+        let key = [128u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let nonce = [0u8; 8];
+        let mut stream = [0u8; 64];
+
+        let expected = hex_to_bytes("fc207dbfc76c5e1774961e7a5aad09069b2225ac1ce0fe7a0ce77003e7e5bdf8\
+            b31af821000813e6c56b8c1771d6ee7039b2fbd0a68e8ad70a3944b677937897");
+        Salsa20_12::new(&key, &nonce).process(&[0u8; 64], &mut stream);
+        assert_eq!(&stream[..], &expected[..]);
+
+        let expected = hex_to_bytes("a9c9f888ab552a2d1bbff9f36bebeb337a8b4b107c75b63bae26cb9a235bba9d\
+            784f38befc3adf4cd3e266687ea7b9f09ba650ae81eac6063ae31ff12218ddc5");
+        Salsa20_8::new(&key, &nonce).process(&[0u8; 64], &mut stream);
+        assert_eq!(&stream[..], &expected[..]);
+    }
+
+    #[test]
+    fn test_salsa20_8_core_rfc7914() {
+        let input = hex_to_bytes("7e879a214f3ec9867ca940e641718f26baee555b8c61c1b50df846116dcd3b1d\
+            ee24f319df9b3d8514121e4b5ac5aa3276021d2909c74829edebc68db8b8c25e");
+        let expected = hex_to_bytes("a41f859c6608cc993b81cacb020cef05044b2181a2fd337dfd7b1c6396682f29\
+            b4393168e3c9e6bcfe6bc5b7a06d96bae424cc102c91745c24ad673dc7618f81");
+        let mut output = [0u8; 64];
+        salsa20_core(8, &input, &mut output);
+        assert_eq!(&output[..], &expected[..]);
+    }
+
+    // Compare the blocks of each SIMD backend with the portable implementation
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn check_simd_backends<const ROUNDS: usize>() {
+        use crate::stream_x86::{self, Backend};
+
+        let key: Vec<u8> = (0..32).collect();
+        let nonce: Vec<u8> = (0..8).collect();
+        // The last counters make the low word carry into the high word
+        for &counter in [0u64, 1, 0xfffffffd, 0x1fffffffffffffd].iter() {
+            let mut c = Salsa::<ROUNDS>::new(&key, &nonce);
+            c.seek(counter * 64).unwrap();
+            let mut portable = c;
+            let mut expected = [0u8; 512];
+            for block in expected.chunks_mut(64) {
+                portable.hash();
+                block.copy_from_slice(&portable.output);
+            }
+
+            for &backend in [Backend::Sse2, Backend::Ssse3, Backend::Avx2].iter() {
+                if !backend.supported() {
+                    continue;
+                }
+                let size = backend.blocks() * 64;
+                let mut output = [0u8; 512];
+                stream_x86::salsa_blocks(backend, &c.state.words(), ROUNDS, &mut output[..size]);
+                assert_eq!(&output[..size], &expected[..size]);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn test_salsa_simd_backends() {
+        check_simd_backends::<8>();
+        check_simd_backends::<12>();
+        check_simd_backends::<20>();
+    }
+
+    #[test]
+    fn test_salsa20_bulk_matches_blockwise() {
+        // Large inputs take the SIMD path where available, single blocks never do
+        let key: Vec<u8> = (0..32).collect();
+        let nonce = [7u8; 8];
+        for &start in [3u64, 0xfffffff0 * 64 + 3].iter() {
+            let input: Vec<u8> = (0..21 * 64 - 3).map(|i| i as u8).collect();
+            let mut c = Salsa20::new(&key, &nonce);
+            c.seek(start).unwrap();
+            let mut expected = vec![0u8; input.len()];
+            for (i, o) in input.chunks(64).zip(expected.chunks_mut(64)) {
+                c.process(i, o);
+            }
+
+            let mut c = Salsa20::new(&key, &nonce);
+            c.seek(start).unwrap();
+            let mut output = vec![0u8; input.len()];
+            c.process(&input, &mut output);
+            assert_eq!(output, expected);
+            assert_eq!(c.position(), start + input.len() as u64);
+        }
+    }
 }
 
 #[cfg(all(test, feature = "with-bench"))]
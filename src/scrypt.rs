@@ -25,67 +25,10 @@ use crate::serialize::base64::{FromBase64, ToBase64};
 use crate::cryptoutil::{read_u32_le, read_u32v_le, write_u32_le};
 use crate::hmac::Hmac;
 use crate::pbkdf2::pbkdf2;
+use crate::salsa20::salsa20_core;
 use crate::sha2::Sha256;
 use crate::util::fixed_time_eq;
 
-// The salsa20/8 core function.
-fn salsa20_8(input: &[u8], output: &mut [u8]) {
-
-    let mut x = [0u32; 16];
-    read_u32v_le(&mut x, input);
-
-    let rounds = 8;
-
-    macro_rules! run_round (
-        ($($set_idx:expr, $idx_a:expr, $idx_b:expr, $rot:expr);*) => { {
-            $( x[$set_idx] ^= x[$idx_a].wrapping_add(x[$idx_b]).rotate_left($rot); )*
-        } }
-    );
-
-    for _ in 0..rounds / 2 {
-        run_round!(
-            0x4, 0x0, 0xc, 7;
-            0x8, 0x4, 0x0, 9;
-            0xc, 0x8, 0x4, 13;
-            0x0, 0xc, 0x8, 18;
-            0x9, 0x5, 0x1, 7;
-            0xd, 0x9, 0x5, 9;
-            0x1, 0xd, 0x9, 13;
-            0x5, 0x1, 0xd, 18;
-            0xe, 0xa, 0x6, 7;
-            0x2, 0xe, 0xa, 9;
-            0x6, 0x2, 0xe, 13;
-            0xa, 0x6, 0x2, 18;
-            0x3, 0xf, 0xb, 7;
-            0x7, 0x3, 0xf, 9;
-            0xb, 0x7, 0x3, 13;
-            0xf, 0xb, 0x7, 18;
-            0x1, 0x0, 0x3, 7;
-            0x2, 0x1, 0x0, 9;
-            0x3, 0x2, 0x1, 13;
-            0x0, 0x3, 0x2, 18;
-            0x6, 0x5, 0x4, 7;
-            0x7, 0x6, 0x5, 9;
-            0x4, 0x7, 0x6, 13;
-            0x5, 0x4, 0x7, 18;
-            0xb, 0xa, 0x9, 7;
-            0x8, 0xb, 0xa, 9;
-            0x9, 0x8, 0xb, 13;
-            0xa, 0x9, 0x8, 18;
-            0xc, 0xf, 0xe, 7;
-            0xd, 0xc, 0xf, 9;
-            0xe, 0xd, 0xc, 13;
-            0xf, 0xe, 0xd, 18
-        )
-    }
-
-    for i in 0..16 {
-        write_u32_le(
-            &mut output[i * 4..(i + 1) * 4],
-            x[i].wrapping_add(read_u32_le(&input[i * 4..(i + 1) * 4])));
-    }
-}
-
 fn xor(x: &[u8], y: &[u8], output: &mut [u8]) {
     for ((out, &x_i), &y_i) in output.iter_mut().zip(x.iter()).zip(y.iter()) {
         *out = x_i ^ y_i;
@@ -103,7 +46,7 @@ fn scrypt_block_mix(input: &[u8], output: &mut [u8]) {
 
     for (i, chunk) in input.chunks(64).enumerate() {
         xor(&x, chunk, &mut t);
-        salsa20_8(&t, &mut x);
+        salsa20_core(8, &t, &mut x);
         let pos = if i % 2 == 0 { (i / 2) * 64 } else { (i / 2) * 64 + input.len() / 2 };
         copy_memory(&x, &mut output[pos..pos + 64]);
     }
@@ -0,0 +1,457 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the Serpent block cipher by Anderson, Biham and Knudsen, with keys of up
+ * to 256 bits. Byte strings are interpreted in the little endian convention used by the NESSIE
+ * test vectors and most implementations (e.g. VeraCrypt, Nettle and the Linux kernel).
+ *
+ * Serpent is implemented in its "bitslice mode": the four 32 bit words of a block are processed as
+ * 32 parallel 4 bit S-box lookups, with each S-box evaluated as a boolean function of the words
+ * (in algebraic normal form), so no secret dependent table lookups or branches are used. The
+ * `BlockEncryptorX8`/`BlockDecryptorX8` implementations run the same code on vectors of 8 words,
+ * processing 8 blocks at once.
+ */
+
+use crate::cryptoutil::{read_u32v_le, write_u32_le};
+use crate::symmetriccipher::{BlockEncryptor, BlockEncryptorX8, BlockDecryptor, BlockDecryptorX8};
+
+const PHI: u32 = 0x9e3779b9;
+const ROUNDS: usize = 32;
+
+// The algebraic normal forms of the output bits of the S-boxes S0 to S7: bit m of ANF[s][b] is set
+// if the monomial consisting of the input bits set in m appears in output bit b of S-box s.
+static ANF: [[u16; 4]; 8] = [
+    [0x61fb, 0x64e3, 0x45ac, 0x0316],
+    [0x7247, 0x6d3b, 0x011d, 0x6b25],
+    [0x0134, 0x3ad6, 0x3d46, 0x0497],
+    [0x7346, 0x3a26, 0x0d9a, 0x31be],
+    [0x071d, 0x7562, 0x5cda, 0x0e56],
+    [0x071d, 0x1d1b, 0x7925, 0x2397],
+    [0x49f7, 0x0215, 0x5cdb, 0x51bc],
+    [0x7619, 0x2b7c, 0x4f96, 0x02b6]];
+
+// The same for the inverse S-boxes
+static INV_ANF: [[u16; 4]; 8] = [
+    [0x7e59, 0x6436, 0x011f, 0x7943],
+    [0x648f, 0x6794, 0x21e7, 0x0512],
+    [0x0456, 0x3a1c, 0x2f1b, 0x21c9],
+    [0x4752, 0x63d4, 0x3e68, 0x1ab6],
+    [0x3b17, 0x2338, 0x0dbf, 0x1a1c],
+    [0x0942, 0x0be6, 0x2c1a, 0x029d],
+    [0x49eb, 0x0135, 0x5c47, 0x5bdd],
+    [0x5c47, 0x6753, 0x3924, 0x0e98]];
+
+// The operations needed to run Serpent on a single 32 bit word or on a vector of them.
+trait SerpentWord: Copy {
+    fn splat(x: u32) -> Self;
+    fn xor(self, rhs: Self) -> Self;
+    fn and(self, rhs: Self) -> Self;
+    fn shl(self, n: u32) -> Self;
+    fn rotl(self, n: u32) -> Self;
+}
+
+impl SerpentWord for u32 {
+    fn splat(x: u32) -> u32 { x }
+    fn xor(self, rhs: u32) -> u32 { self ^ rhs }
+    fn and(self, rhs: u32) -> u32 { self & rhs }
+    fn shl(self, n: u32) -> u32 { self << n }
+    fn rotl(self, n: u32) -> u32 { self.rotate_left(n) }
+}
+
+// Word i of 8 different blocks
+#[derive(Clone, Copy)]
+struct U32x8([u32; 8]);
+
+impl U32x8 {
+    fn map<F: Fn(u32) -> u32>(self, f: F) -> U32x8 {
+        let mut r = self.0;
+        for x in r.iter_mut() {
+            *x = f(*x);
+        }
+        U32x8(r)
+    }
+
+    fn zip<F: Fn(u32, u32) -> u32>(self, rhs: U32x8, f: F) -> U32x8 {
+        let mut r = self.0;
+        for (x, &y) in r.iter_mut().zip(rhs.0.iter()) {
+            *x = f(*x, y);
+        }
+        U32x8(r)
+    }
+}
+
+impl SerpentWord for U32x8 {
+    fn splat(x: u32) -> U32x8 { U32x8([x; 8]) }
+    fn xor(self, rhs: U32x8) -> U32x8 { self.zip(rhs, |x, y| x ^ y) }
+    fn and(self, rhs: U32x8) -> U32x8 { self.zip(rhs, |x, y| x & y) }
+    fn shl(self, n: u32) -> U32x8 { self.map(|x| x << n) }
+    fn rotl(self, n: u32) -> U32x8 { self.map(|x| x.rotate_left(n)) }
+}
+
+// Apply the S-box with the algebraic normal form `anf` to the 32 nibbles formed by the bits of x,
+// with x[0] holding the least significant bits. The branches only depend on the (public) S-box.
+fn sbox<W: SerpentWord>(anf: &[u16; 4], x: &mut [W; 4]) {
+    let mut monomials = [W::splat(0xffffffff); 16];
+    for m in 1..16 {
+        monomials[m] = monomials[m & (m - 1)].and(x[m.trailing_zeros() as usize]);
+    }
+    for (y, &bit_anf) in x.iter_mut().zip(anf.iter()) {
+        let mut acc = W::splat(0);
+        for (m, &monomial) in monomials.iter().enumerate() {
+            if (bit_anf >> m) & 1 == 1 {
+                acc = acc.xor(monomial);
+            }
+        }
+        *y = acc;
+    }
+}
+
+fn linear_transform<W: SerpentWord>(x: &mut [W; 4]) {
+    x[0] = x[0].rotl(13);
+    x[2] = x[2].rotl(3);
+    x[1] = x[1].xor(x[0]).xor(x[2]);
+    x[3] = x[3].xor(x[2]).xor(x[0].shl(3));
+    x[1] = x[1].rotl(1);
+    x[3] = x[3].rotl(7);
+    x[0] = x[0].xor(x[1]).xor(x[3]);
+    x[2] = x[2].xor(x[3]).xor(x[1].shl(7));
+    x[0] = x[0].rotl(5);
+    x[2] = x[2].rotl(22);
+}
+
+fn inv_linear_transform<W: SerpentWord>(x: &mut [W; 4]) {
+    x[2] = x[2].rotl(32 - 22);
+    x[0] = x[0].rotl(32 - 5);
+    x[2] = x[2].xor(x[3]).xor(x[1].shl(7));
+    x[0] = x[0].xor(x[1]).xor(x[3]);
+    x[3] = x[3].rotl(32 - 7);
+    x[1] = x[1].rotl(32 - 1);
+    x[3] = x[3].xor(x[2]).xor(x[0].shl(3));
+    x[1] = x[1].xor(x[0]).xor(x[2]);
+    x[2] = x[2].rotl(32 - 3);
+    x[0] = x[0].rotl(32 - 13);
+}
+
+fn add_round_key<W: SerpentWord>(x: &mut [W; 4], key: &[u32; 4]) {
+    for (w, &k) in x.iter_mut().zip(key.iter()) {
+        *w = w.xor(W::splat(k));
+    }
+}
+
+fn encrypt_core<W: SerpentWord>(keys: &[[u32; 4]; ROUNDS + 1], x: &mut [W; 4]) {
+    for round in 0..ROUNDS {
+        add_round_key(x, &keys[round]);
+        sbox(&ANF[round % 8], x);
+        if round == ROUNDS - 1 {
+            add_round_key(x, &keys[ROUNDS]);
+        } else {
+            linear_transform(x);
+        }
+    }
+}
+
+fn decrypt_core<W: SerpentWord>(keys: &[[u32; 4]; ROUNDS + 1], x: &mut [W; 4]) {
+    for round in (0..ROUNDS).rev() {
+        if round == ROUNDS - 1 {
+            add_round_key(x, &keys[ROUNDS]);
+        } else {
+            inv_linear_transform(x);
+        }
+        sbox(&INV_ANF[round % 8], x);
+        add_round_key(x, &keys[round]);
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Serpent {
+    keys: [[u32; 4]; ROUNDS + 1]
+}
+
+impl Serpent {
+    /**
+     * Create a new Serpent instance.
+     *
+     * # Arguments
+     * * key - The key, of up to 32 bytes. Keys shorter than 256 bits are padded as described in the
+     *   Serpent specification; the standard key sizes are 16, 24 and 32 bytes.
+     */
+    pub fn new(key: &[u8]) -> Serpent {
+        assert!(!key.is_empty() && key.len() <= 32);
+        let mut padded = [0u8; 32];
+        padded[..key.len()].copy_from_slice(key);
+        if key.len() < 32 {
+            padded[key.len()] = 1;
+        }
+
+        // The prekeys w_-8 to w_131
+        let mut w = [0u32; 8 + 4 * (ROUNDS + 1)];
+        read_u32v_le(&mut w[..8], &padded);
+        for i in 0..4 * (ROUNDS + 1) {
+            w[i + 8] = (w[i] ^ w[i + 3] ^ w[i + 5] ^ w[i + 7] ^ PHI ^ i as u32).rotate_left(11);
+        }
+
+        let mut keys = [[0u32; 4]; ROUNDS + 1];
+        for (i, k) in keys.iter_mut().enumerate() {
+            k.copy_from_slice(&w[8 + 4 * i..12 + 4 * i]);
+            sbox(&ANF[(ROUNDS + 3 - i) % 8], k);
+        }
+
+        Serpent {
+            keys
+        }
+    }
+}
+
+impl BlockEncryptor for Serpent {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        let mut x = [0u32; 4];
+        read_u32v_le(&mut x, input);
+        encrypt_core(&self.keys, &mut x);
+        for (chunk, &w) in output.chunks_mut(4).zip(x.iter()) {
+            write_u32_le(chunk, w);
+        }
+    }
+}
+
+impl BlockDecryptor for Serpent {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        let mut x = [0u32; 4];
+        read_u32v_le(&mut x, input);
+        decrypt_core(&self.keys, &mut x);
+        for (chunk, &w) in output.chunks_mut(4).zip(x.iter()) {
+            write_u32_le(chunk, w);
+        }
+    }
+}
+
+fn load_x8(input: &[u8]) -> [U32x8; 4] {
+    let mut words = [0u32; 32];
+    read_u32v_le(&mut words, input);
+    let mut x = [U32x8([0; 8]); 4];
+    for (block, w) in words.chunks(4).enumerate() {
+        for j in 0..4 {
+            x[j].0[block] = w[j];
+        }
+    }
+    x
+}
+
+fn store_x8(x: &[U32x8; 4], output: &mut [u8]) {
+    for (block, chunk) in output.chunks_mut(16).enumerate() {
+        for j in 0..4 {
+            write_u32_le(&mut chunk[4 * j..4 * j + 4], x[j].0[block]);
+        }
+    }
+}
+
+impl BlockEncryptorX8 for Serpent {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block_x8(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 128);
+        assert!(output.len() == 128);
+        let mut x = load_x8(input);
+        encrypt_core(&self.keys, &mut x);
+        store_x8(&x, output);
+    }
+}
+
+impl BlockDecryptorX8 for Serpent {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn decrypt_block_x8(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 128);
+        assert!(output.len() == 128);
+        let mut x = load_x8(input);
+        decrypt_core(&self.keys, &mut x);
+        store_x8(&x, output);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cryptoutil::test::test_aes_candidate_monte_carlo;
+    use crate::serialize::hex::FromHex;
+    use crate::serpent::{ANF, INV_ANF, Serpent, sbox};
+    use crate::symmetriccipher::{BlockEncryptor, BlockEncryptorX8, BlockDecryptor, BlockDecryptorX8};
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    // The S-boxes as specified
+    static SBOXES: [[u8; 16]; 8] = [
+        [3, 8, 15, 1, 10, 6, 5, 11, 14, 13, 4, 2, 7, 0, 9, 12],
+        [15, 12, 2, 7, 9, 0, 5, 10, 1, 11, 14, 8, 6, 13, 3, 4],
+        [8, 6, 7, 9, 3, 12, 10, 15, 13, 1, 14, 4, 0, 11, 5, 2],
+        [0, 15, 11, 8, 12, 9, 6, 3, 13, 1, 2, 4, 10, 7, 5, 14],
+        [1, 15, 8, 3, 12, 0, 11, 6, 2, 5, 4, 10, 9, 14, 7, 13],
+        [15, 5, 2, 11, 4, 10, 9, 12, 0, 3, 14, 8, 13, 6, 7, 1],
+        [7, 2, 12, 5, 8, 4, 6, 11, 14, 9, 1, 15, 13, 3, 10, 0],
+        [1, 13, 15, 0, 14, 8, 2, 11, 7, 4, 12, 10, 9, 3, 5, 6]];
+
+    fn eval_sbox(anf: &[u16; 4], nibble: u8) -> u8 {
+        let mut x = [0u32; 4];
+        for (j, w) in x.iter_mut().enumerate() {
+            *w = ((nibble >> j) & 1) as u32;
+        }
+        sbox(anf, &mut x);
+        x.iter().enumerate().fold(0, |acc, (j, &w)| acc | (((w & 1) as u8) << j))
+    }
+
+    #[test]
+    fn test_anf() {
+        for (s, table) in SBOXES.iter().enumerate() {
+            for (input, &output) in table.iter().enumerate() {
+                assert_eq!(eval_sbox(&ANF[s], input as u8), output);
+                assert_eq!(eval_sbox(&INV_ANF[s], output), input as u8);
+            }
+        }
+    }
+
+    struct Test {
+        key: Vec<u8>,
+        plaintext: Vec<u8>,
+        ciphertext: Vec<u8>
+    }
+
+    // Test vectors from the NESSIE Serpent test vectors
+    fn tests() -> Vec<Test> {
+        vec![
+            Test {
+                key: hex_to_bytes("80000000000000000000000000000000"),
+                plaintext: hex_to_bytes("00000000000000000000000000000000"),
+                ciphertext: hex_to_bytes("264e5481eff42a4606abda06c0bfda3d")
+            },
+            Test {
+                key: hex_to_bytes("00000000000000000000000000000000"),
+                plaintext: hex_to_bytes("80000000000000000000000000000000"),
+                ciphertext: hex_to_bytes("a3b35de7c358ddd82644678c64b8bcbb")
+            },
+            Test {
+                key: hex_to_bytes("000102030405060708090a0b0c0d0e0f"),
+                plaintext: hex_to_bytes("00112233445566778899aabbccddeeff"),
+                ciphertext: hex_to_bytes("563e2cf8740a27c164804560391e9b27")
+            },
+            Test {
+                key: hex_to_bytes("800000000000000000000000000000000000000000000000"),
+                plaintext: hex_to_bytes("00000000000000000000000000000000"),
+                ciphertext: hex_to_bytes("9e274ead9b737bb21efcfca548602689")
+            },
+            Test {
+                key: hex_to_bytes("000102030405060708090a0b0c0d0e0f1011121314151617"),
+                plaintext: hex_to_bytes("00112233445566778899aabbccddeeff"),
+                ciphertext: hex_to_bytes("6ab816c82de53b93005008afa2246a02")
+            },
+            Test {
+                key: hex_to_bytes("8000000000000000000000000000000000000000000000000000000000000000"),
+                plaintext: hex_to_bytes("00000000000000000000000000000000"),
+                ciphertext: hex_to_bytes("a223aa1288463c0e2be38ebd825616c0")
+            },
+            Test {
+                key: hex_to_bytes("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"),
+                plaintext: hex_to_bytes("00112233445566778899aabbccddeeff"),
+                ciphertext: hex_to_bytes("2868b7a2d28ecd5e4fdefac3c4330074")
+            },
+        ]
+    }
+
+    #[test]
+    fn test_serpent() {
+        for t in tests().iter() {
+            let serpent = Serpent::new(&t.key);
+            let mut output = [0u8; 16];
+            serpent.encrypt_block(&t.plaintext, &mut output);
+            assert_eq!(&output[..], &t.ciphertext[..]);
+            serpent.decrypt_block(&t.ciphertext, &mut output);
+            assert_eq!(&output[..], &t.plaintext[..]);
+        }
+    }
+
+    #[test]
+    fn test_serpent_x8() {
+        for t in tests().iter() {
+            let serpent = Serpent::new(&t.key);
+            let mut input = [0u8; 128];
+            let mut expected = [0u8; 128];
+            for (i, (block, expected_block)) in input.chunks_mut(16).zip(expected.chunks_mut(16)).enumerate() {
+                block.copy_from_slice(&t.plaintext);
+                block[15] ^= i as u8;
+                serpent.encrypt_block(block, expected_block);
+            }
+            let mut output = [0u8; 128];
+            serpent.encrypt_block_x8(&input, &mut output);
+            assert_eq!(&output[..], &expected[..]);
+            serpent.decrypt_block_x8(&expected, &mut output);
+            assert_eq!(&output[..], &input[..]);
+        }
+    }
+
+    // The Monte Carlo tests of the Serpent submission to AES, which writes keys and blocks in the
+    // reverse of the byte order of the NESSIE test vectors
+    #[test]
+    fn test_serpent_monte_carlo() {
+        test_aes_candidate_monte_carlo(Serpent::new, 16, false, true,
+            &["90e7a5ba9497fa1bfc00f7d1a3a86a1e", "5d0c5da998aaa940d493738892579447"]);
+        test_aes_candidate_monte_carlo(Serpent::new, 16, true, true,
+            &["47c6786045bb9d30f4029e7ccccd1cae", "003380e19f10065740394f48e2fe80b7"]);
+        test_aes_candidate_monte_carlo(Serpent::new, 24, false, true,
+            &["2d8af7b79eb7f21fdb394c77c3fb8c3a", "145a25a48329ea5d2d74a9b4131d5604"]);
+        test_aes_candidate_monte_carlo(Serpent::new, 24, true, true,
+            &["0fb9b00ae4e6e0f328ddc43cee462898", "4c934ebda169107cb5194221683e5ead"]);
+        test_aes_candidate_monte_carlo(Serpent::new, 32, false, true,
+            &["92efa3ca9477794d31f4df7bce23e60a", "1eaee9147d3844e65e3c7b333587e432"]);
+        test_aes_candidate_monte_carlo(Serpent::new, 32, true, true,
+            &["cff2f5875d0fb0d3217052fc9d7b94a3", "96d0752aa50b521aa681dd8950b20223"]);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::serpent::Serpent;
+    use crate::symmetriccipher::{BlockEncryptor, BlockEncryptorX8};
+
+    #[bench]
+    pub fn serpent(bh: &mut Bencher) {
+        let serpent = Serpent::new(&[1u8; 16]);
+        let input = [1u8; 16];
+        let mut output = [0u8; 16];
+        bh.iter( || {
+            serpent.encrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn serpent_x8(bh: &mut Bencher) {
+        let serpent = Serpent::new(&[1u8; 16]);
+        let input = [1u8; 128];
+        let mut output = [0u8; 128];
+        bh.iter( || {
+            serpent.encrypt_block_x8(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
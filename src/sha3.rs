@@ -43,106 +43,32 @@ let hex = hasher.result_str();
 assert_eq!(hex, "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532");
 ```
 
- */
-
-use std::cmp;
-
-use crate::digest::Digest;
-use crate::cryptoutil::{write_u64v_le, read_u64v_le, zero};
-
-const B: usize = 200;
-const NROUNDS: usize = 24;
-const RC: [u64; 24] = [
-    0x0000000000000001,
-    0x0000000000008082,
-    0x800000000000808a,
-    0x8000000080008000,
-    0x000000000000808b,
-    0x0000000080000001,
-    0x8000000080008081,
-    0x8000000000008009,
-    0x000000000000008a,
-    0x0000000000000088,
-    0x0000000080008009,
-    0x000000008000000a,
-    0x000000008000808b,
-    0x800000000000008b,
-    0x8000000000008089,
-    0x8000000000008003,
-    0x8000000000008002,
-    0x8000000000000080,
-    0x000000000000800a,
-    0x800000008000000a,
-    0x8000000080008081,
-    0x8000000000008080,
-    0x0000000080000001,
-    0x8000000080008008
-];
-const ROTC: [usize; 24] = [
-    1, 3, 6, 10, 15, 21, 28, 36,
-    45, 55, 2, 14, 27, 41, 56, 8,
-    25, 43, 62, 18, 39, 61, 20, 44
-];
-const PIL: [usize; 24] = [
-    10, 7, 11, 17, 18, 3, 5, 16,
-    8, 21, 24, 4, 15, 23, 19, 13,
-    12, 2, 20, 14, 22, 9, 6, 1
-];
-const M5: [usize; 10] = [
-    0, 1, 2, 3, 4, 0, 1, 2, 3, 4
-];
-
-#[inline]
-fn rotl64(v: u64, n: usize) -> u64 {
-    ((v << (n % 64)) & 0xffffffffffffffff) ^ (v >> (64 - (n % 64)))
-}
-
-// Code based on Keccak-compact64.c from ref implementation.
-fn keccak_f(state: &mut [u8]) {
-    assert!(state.len() == B);
+The SHAKE functions are best used through `Shake128` and `Shake256`, which implement
+`ExtendableOutput`: once the input is absorbed, `finalize_xof` returns a reader that outputs as
+many bytes as needed.
 
-    let mut s: [u64; 25] = [0; 25];
-    let mut t: [u64; 1] = [0; 1];
-    let mut c: [u64; 5] = [0; 5];
+```rust
+use self::crypto::digest::{ExtendableOutput, XofReader};
+use self::crypto::sha3::Shake128;
 
-    read_u64v_le(&mut s, state);
+let mut shake = Shake128::new();
+shake.input_str("");
 
-    for round in 0..NROUNDS {
-        // Theta
-        for x in 0..5 {
-            c[x] = s[x] ^ s[5 + x] ^ s[10 + x] ^ s[15 + x] ^ s[20 + x];
-        }
-        for x in 0..5 {
-            t[0] = c[M5[x + 4]] ^ rotl64(c[M5[x + 1]], 1);
-            for y in 0..5 {
-                s[y * 5 + x] = s[y * 5 + x] ^ t[0];
-            }
-        }
+let mut reader = shake.finalize_xof();
+let mut output = [0u8; 16];
+reader.read(&mut output);
+assert_eq!(output, [0x7f, 0x9c, 0x2b, 0xa4, 0xe8, 0x8f, 0x82, 0x7d,
+                    0x61, 0x60, 0x45, 0x50, 0x76, 0x05, 0x85, 0x3e]);
+```
 
-        // Rho Pi
-        t[0] = s[1];
-        for x in 0..24 {
-            c[0] = s[PIL[x]];
-            s[PIL[x]] = rotl64(t[0], ROTC[x]);
-            t[0] = c[0];
-        }
+ */
 
-        // Chi
-        for y in 0..5 {
-            for x in 0..5 {
-                c[x] = s[y * 5 + x];
-            }
-            for x in 0..5 {
-                s[y * 5 + x] = c[x] ^ (!c[M5[x + 1]] & c[M5[x + 2]]);
-            }
-        }
+use std::cmp;
 
-        // Iota
-        s[0] = s[0] ^ RC[round];
-    }
+use crate::digest::{Digest, ExtendableOutput, XofReader};
+use crate::cryptoutil::zero;
+use crate::keccak::{keccak_f, Sponge, B, NROUNDS};
 
-    write_u64v_le(state, &s);
-}
 
 
 /// SHA-3 Modes.
@@ -245,12 +171,16 @@ impl Sha3 {
         Sha3::new(Sha3Mode::Sha3_512)
     }
 
-    /// New SHAKE-128 instance.
+    /// New SHAKE-128 instance. As SHAKE128 has no fixed output length, `output_bits` returns 0
+    /// and `result_str` doesn't work; `Shake128` provides SHAKE128 with unlimited output.
+    #[deprecated(note = "use sha3::Shake128")]
     pub fn shake_128() -> Sha3 {
         Sha3::new(Sha3Mode::Shake128)
     }
 
-    /// New SHAKE-256 instance.
+    /// New SHAKE-256 instance. As SHAKE256 has no fixed output length, `output_bits` returns 0
+    /// and `result_str` doesn't work; `Shake256` provides SHAKE256 with unlimited output.
+    #[deprecated(note = "use sha3::Shake256")]
     pub fn shake_256() -> Sha3 {
         Sha3::new(Sha3Mode::Shake256)
     }
@@ -370,7 +300,7 @@ impl Digest for Sha3 {
             }
 
             self.offset = 0;
-            keccak_f(&mut self.state);
+            keccak_f(&mut self.state, NROUNDS);
         }
     }
 
@@ -418,7 +348,7 @@ impl Digest for Sha3 {
                 self.offset += nread;
             }
 
-            keccak_f(&mut self.state);
+            keccak_f(&mut self.state, NROUNDS);
         }
 
         if out_len != 0 && out_len == self.offset {
@@ -454,11 +384,106 @@ impl Clone for Sha3 {
 }
 
 
+// Domain separation bits of SHAKE, followed by the first padding bit
+pub(crate) const SHAKE_DS: u8 = 0x1f;
+
+/// The SHAKE128 extendable output function.
+#[derive(Copy, Clone)]
+pub struct Shake128 {
+    sponge: Sponge
+}
+
+/// The SHAKE256 extendable output function.
+#[derive(Copy, Clone)]
+pub struct Shake256 {
+    sponge: Sponge
+}
+
+/// Squeezes the output of SHAKE128, SHAKE256 and the cSHAKE, TurboSHAKE and KangarooTwelve based
+/// functions.
+#[derive(Copy, Clone)]
+pub struct ShakeReader {
+    sponge: Sponge
+}
+
+impl ShakeReader {
+    // Pads the message absorbed into `sponge` with the domain separation bits `ds`
+    pub(crate) fn new(mut sponge: Sponge, ds: u8) -> ShakeReader {
+        sponge.pad(ds);
+        ShakeReader { sponge }
+    }
+}
+
+impl Shake128 {
+    /// New SHAKE128 instance.
+    pub fn new() -> Shake128 {
+        Shake128 { sponge: Sponge::new(Sha3Mode::Shake128.capacity()) }
+    }
+}
+
+impl Shake256 {
+    /// New SHAKE256 instance.
+    pub fn new() -> Shake256 {
+        Shake256 { sponge: Sponge::new(Sha3Mode::Shake256.capacity()) }
+    }
+}
+
+impl Default for Shake128 {
+    fn default() -> Shake128 {
+        Shake128::new()
+    }
+}
+
+impl ExtendableOutput for Shake128 {
+    type Reader = ShakeReader;
+
+    fn input(&mut self, data: &[u8]) {
+        self.sponge.absorb(data);
+    }
+
+    fn finalize_xof(self) -> ShakeReader {
+        ShakeReader::new(self.sponge, SHAKE_DS)
+    }
+
+    fn reset(&mut self) {
+        *self = Shake128::new();
+    }
+}
+
+impl Default for Shake256 {
+    fn default() -> Shake256 {
+        Shake256::new()
+    }
+}
+
+impl ExtendableOutput for Shake256 {
+    type Reader = ShakeReader;
+
+    fn input(&mut self, data: &[u8]) {
+        self.sponge.absorb(data);
+    }
+
+    fn finalize_xof(self) -> ShakeReader {
+        ShakeReader::new(self.sponge, SHAKE_DS)
+    }
+
+    fn reset(&mut self) {
+        *self = Shake256::new();
+    }
+}
+
+impl XofReader for ShakeReader {
+    fn read(&mut self, out: &mut [u8]) {
+        self.sponge.squeeze(out);
+    }
+}
+
+
 
 #[cfg(test)]
 mod tests {
-    use crate::digest::Digest;
-    use crate::sha3::{Sha3, Sha3Mode};
+    use crate::digest::{Digest, ExtendableOutput, XofReader};
+    use crate::sha3::{Sha3, Sha3Mode, Shake128, Shake256};
     use crate::serialize::hex::{FromHex, ToHex};
 
     struct Test {
@@ -6767,4 +6792,78 @@ mod tests {
 
         test_hash(&mut *sh, &test_cases[..]);
     }
+
+    fn test_xof<X: ExtendableOutput + Clone>(xof: X, tests: &[Test]) {
+        for t in tests.iter() {
+            let input = t.input.from_hex().unwrap();
+            let expected = t.output_str.from_hex().unwrap();
+
+            // All at once
+            let mut x = xof.clone();
+            x.input(&input);
+            let mut out = vec![0u8; expected.len()];
+            x.finalize_xof().read(&mut out);
+            assert_eq!(out, expected);
+
+            // Absorbing and squeezing in pieces of various sizes
+            let mut x = xof.clone();
+            for chunk in input.chunks(17) {
+                x.input(chunk);
+            }
+            let mut reader = x.finalize_xof();
+            let mut out = vec![0u8; expected.len()];
+            let mut pos = 0;
+            let mut size = 0;
+            while pos < out.len() {
+                let end = ::std::cmp::min(pos + size, out.len());
+                reader.read(&mut out[pos..end]);
+                pos = end;
+                size += 13;
+            }
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn test_shake128_xof() {
+        let test_cases = [
+            Test {
+                input: "",
+                output_str: "7f9c2ba4e88f827d616045507605853ed73b8093f6efbc88eb1a6eacfa66ef263cb1eea988004b93103cfb0aeefd2a686e01fa4a58e8a3639ca8a1e3f9ae57e235b8cc873c23dc62b8d260169afa2f75ab916a58d974918835d25e6a435085b2badfd6dfaac359a5efbb7bcc4b59d538df9a04302e10c8bc1cbf1a0b3a5120ea17cda7cfad765f5623474d368ccca8af0007cd9f5e4c849f167a580b14aabdefaee7eef47cb0fca9767be1fda69419dfb927e9df07348b196691abaeb580b32def58538b8d23f87732ea63b02b4fa0f4873360e2841928cd60dd4cee8cc0d4c922a96188d032675c8ac850933c7aff1533b94c834adbb69c6115bad4692d8619f90b0cdf8a7b9c264029ac185b70b83f2801f2f4b3f70c593ea3aeeb613a7f1b1de33fd75081f592305f2e4526edc09631b10958f464d889f31ba010250fda7f1368ec2967fc84ef2ae9aff268e0b1700affc6820b523a3d917135f2dff2"
+            },
+            Test {
+                input: "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f404142434445464748494a4b4c4d4e4f505152535455565758595a5b5c5d5e5f606162636465666768696a6b6c6d6e6f707172737475767778797a7b7c7d7e7f808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9fa0a1a2a3a4a5a6a7a8a9aaabacadaeafb0b1b2b3b4b5b6b7b8b9babbbcbdbebfc0c1c2c3c4c5c6c7",
+                output_str: "0c4234ca1e31801ae606f8b8d8e0665c66f42a21d601c2681858a92c79ad5d69e143c3b1393dd894e7abd5621b0d877f3573a34245e6b911f671081664a5fa53f778886cb56bdba60b2e8d21bd5b68b2f03f7db45fab8bec05d586922735967393f6c99991150acb1dcbfe12e54793975742408b347feedeabfeb77f9bbc70f3b14024309f530cc8919ed69e58b9b8ece0cf40db1b7a33d1329885e9ca4004b1fba4bad349b3f98d635b9775fc9cb1027c1e431756302e109614ff269d8415f43b504fbdff98605f9bf8a5ac0120f6e2403cc38fc07c6dfe2575f52f208cdf030b9fbdc20ecf6cbff7ff8e22744c70b25e3fa55eca18d67f3767f095f03856264588cf1fd09f29da759c2e849b1f345feebde0f271a418c12e126fbe086095b9433e06a84f609a0c91793cc7379342c5822870da2c37ea464a0ad2d778678a33d40bc054dfe5f39fcf3dae74a1e11e5c62dfab35b73cd2ecf088cc55d972"
+            }
+        ];
+
+        test_xof(Shake128::new(), &test_cases[..]);
+    }
+
+    #[test]
+    fn test_shake256_xof() {
+        let test_cases = [
+            Test {
+                input: "",
+                output_str: "46b9dd2b0ba88d13233b3feb743eeb243fcd52ea62b81b82b50c27646ed5762fd75dc4ddd8c0f200cb05019d67b592f6fc821c49479ab48640292eacb3b7c4be141e96616fb13957692cc7edd0b45ae3dc07223c8e92937bef84bc0eab862853349ec75546f58fb7c2775c38462c5010d846c185c15111e595522a6bcd16cf86f3d122109e3b1fdd943b6aec468a2d621a7c06c6a957c62b54dafc3be87567d677231395f6147293b68ceab7a9e0c58d864e8efde4e1b9a46cbe854713672f5caaae314ed9083dab4b099f8e300f01b8650f1f4b1d8fcf3f3cb53fb8e9eb2ea203bdc970f50ae55428a91f7f53ac266b28419c3778a15fd248d339ede785fb7f5a1aaa96d313eacc890936c173cdcd0fab882c45755feb3aed96d477ff96390bf9a66d1368b208e21f7c10d04a3dbd4e360633e5db4b602601c14cea737db3dcf722632cc77851cbdde2aaf0a33a07b373445df490cc8fc1e4160ff11837"
+            },
+            Test {
+                input: "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f404142434445464748494a4b4c4d4e4f505152535455565758595a5b5c5d5e5f606162636465666768696a6b6c6d6e6f707172737475767778797a7b7c7d7e7f808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9fa0a1a2a3a4a5a6a7a8a9aaabacadaeafb0b1b2b3b4b5b6b7b8b9babbbcbdbebfc0c1c2c3c4c5c6c7",
+                output_str: "4ee1ca03272b05d3bfb1e1c79a967f823b9fc5e4bb3987b1ba9e9cb5afb07a5ee3a07fbd457a94364964a841e7f466e5a022e21ab7f673c18ba98cdb1d5aecfae62268b068f1e4bf9ee9853bcce08dcd491c629aa218b60d3d453e83a554eb176cfef9729e99ff3a8127c49e3c3cf19ad26018ed796fedce98c5f867ec2bacbdb8012cc52b76e6d24a80fa3692d02a03634b34b2fb336232e4c027dca0cc4bd03a01f1cec8c35ad0e51687fad4e18ebc23a75851d466979d59db7391b61702a7fc85a1162bdbaaeab699499162f551da8b0c839f88ff96b8dd79015606526ab78fd1c101660de85653340f3d1dac2a22bcf1a2bef88d742de9006c2d5b6d8acd586b6bee76f85cccbf94e387c53c23e716c670c4db23c67901358ae64f3f0ccedfa05b29e84e1a11a635bfe79e4bd653c28884ec4034ed5516947d4dc5449032d6091dfd6e5a573b323f2624ff12898bb7a12d8cdd489cd1f8965b6ca22d"
+            }
+        ];
+
+        test_xof(Shake256::new(), &test_cases[..]);
+    }
+
+    #[test]
+    fn test_shake_xof_reset() {
+        let mut shake = Shake128::new();
+        shake.input_str("abc");
+        shake.reset();
+        shake.input_str("");
+        let mut out = [0u8; 8];
+        shake.finalize_xof().read(&mut out);
+        assert_eq!(out.to_hex(), "7f9c2ba4e88f827d");
+    }
 }
@@ -0,0 +1,571 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+An implementation of the Skein family of hash functions, version 1.3, as described in
+[The Skein Hash Function Family](https://www.schneier.com/academic/skein/).
+
+Skein is built from the Threefish tweakable block cipher in the Unique Block Iteration (UBI)
+chaining mode. There are three variants, named after the size of their internal state: Skein-256,
+Skein-512 and Skein-1024. Each of them can produce an output of any length, given in bits when
+constructing the hasher.
+
+All three variants implement both the `Digest` and the `Mac` trait. When a key is passed to the
+constructor the result is Skein-MAC, which processes the key in its own UBI block instead of
+using the HMAC construction. A personalization string and a tree hashing configuration can be set
+with `new_with_params`.
+
+```rust
+use self::crypto::digest::Digest;
+use self::crypto::skein::Skein512;
+
+let mut hasher = Skein512::new(512);
+hasher.input_str("abc");
+assert_eq!(hasher.result_str(),
+           "8f5dd9ec798152668e35129496b029a960c9a9b88662f7f9482f110b31f9f938\
+            93ecfb25c009baad9e46737197d5630379816a886aa05526d3a70df272d96e75");
+```
+ */
+
+use std::cmp;
+
+use crate::cryptoutil::{copy_memory, write_u64_le};
+use crate::digest::Digest;
+use crate::mac::{Mac, MacResult};
+use crate::symmetriccipher::BlockEncryptor;
+use crate::threefish::{Threefish1024, Threefish256, Threefish512};
+
+// Type values of the UBI tweak
+const TYPE_KEY: u8 = 0;
+const TYPE_CFG: u8 = 4;
+const TYPE_PRS: u8 = 8;
+const TYPE_MSG: u8 = 48;
+const TYPE_OUT: u8 = 63;
+
+/// The tree hashing parameters of Skein.
+///
+/// Leaves contain `2^leaf` blocks of the message and every node above them combines `2^fan_out`
+/// child nodes, up to a maximum tree height of `max_height` levels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SkeinTree {
+    pub leaf: u8,
+    pub fan_out: u8,
+    pub max_height: u8,
+}
+
+impl SkeinTree {
+    pub fn new(leaf: u8, fan_out: u8, max_height: u8) -> SkeinTree {
+        assert!(leaf >= 1, "Skein tree leaf size should be at least 1");
+        assert!(fan_out >= 1, "Skein tree fan out should be at least 1");
+        assert!(max_height >= 2, "Skein tree maximum height should be at least 2");
+        SkeinTree {
+            leaf,
+            fan_out,
+            max_height,
+        }
+    }
+}
+
+fn threefish(key: &[u8], tweak: &[u8], input: &[u8], output: &mut [u8]) {
+    match key.len() {
+        32 => Threefish256::new(key, tweak).encrypt_block(input, output),
+        64 => Threefish512::new(key, tweak).encrypt_block(input, output),
+        128 => Threefish1024::new(key, tweak).encrypt_block(input, output),
+        _ => unreachable!(),
+    }
+}
+
+// A single invocation of UBI. The last block is only processed by finish(), since it is the only
+// one that carries the final flag.
+#[derive(Clone)]
+struct Ubi {
+    chain: Vec<u8>,
+    buffer: Vec<u8>,
+    buffer_len: usize,
+    position: u64,
+    level: u8,
+    block_type: u8,
+    first: bool,
+}
+
+impl Ubi {
+    fn new(chain: &[u8], block_type: u8, level: u8, position: u64) -> Ubi {
+        Ubi {
+            chain: chain.to_vec(),
+            buffer: vec![0; chain.len()],
+            buffer_len: 0,
+            position,
+            level,
+            block_type,
+            first: true,
+        }
+    }
+
+    fn process_block(&mut self, last: bool) {
+        let mut tweak = [0u8; 16];
+        write_u64_le(&mut tweak[..8], self.position);
+        tweak[14] = self.level;
+        tweak[15] = self.block_type | (self.first as u8) << 6 | (last as u8) << 7;
+
+        for b in self.buffer[self.buffer_len..].iter_mut() {
+            *b = 0;
+        }
+        let mut output = vec![0u8; self.chain.len()];
+        threefish(&self.chain, &tweak, &self.buffer, &mut output);
+        for ((c, &o), &m) in self.chain.iter_mut().zip(output.iter()).zip(self.buffer.iter()) {
+            *c = o ^ m;
+        }
+
+        self.buffer_len = 0;
+        self.first = false;
+    }
+
+    fn input(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.buffer_len == self.buffer.len() {
+                self.process_block(false);
+            }
+            let take = cmp::min(self.buffer.len() - self.buffer_len, data.len());
+            copy_memory(&data[..take], &mut self.buffer[self.buffer_len..self.buffer_len + take]);
+            self.buffer_len += take;
+            self.position += take as u64;
+            data = &data[take..];
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.process_block(true);
+        self.chain
+    }
+}
+
+fn ubi(chain: &[u8], block_type: u8, data: &[u8]) -> Vec<u8> {
+    let mut ubi = Ubi::new(chain, block_type, 0, 0);
+    ubi.input(data);
+    ubi.finish()
+}
+
+// The open node of one level of the hash tree. In sequential mode there is a single level
+// without a node size limit.
+#[derive(Clone)]
+struct Level {
+    node: Ubi,
+    node_bytes: u64,
+    nodes: u64,
+}
+
+#[derive(Clone)]
+struct Engine {
+    output_bits: usize,
+    tree: Option<SkeinTree>,
+    initial: Vec<u8>,
+    levels: Vec<Level>,
+    root: Option<Vec<u8>>,
+}
+
+impl Engine {
+    fn new(state_bytes: usize, output_bits: usize, key: &[u8], personalization: &[u8],
+           tree: Option<SkeinTree>) -> Engine {
+        assert!(output_bits > 0, "Skein output length should be at least 1 bit");
+
+        let mut chain = vec![0u8; state_bytes];
+        if !key.is_empty() {
+            chain = ubi(&chain, TYPE_KEY, key);
+        }
+
+        let mut config = [0u8; 32];
+        copy_memory(b"SHA3", &mut config[..4]);
+        config[4] = 1;
+        write_u64_le(&mut config[8..16], output_bits as u64);
+        if let Some(tree) = tree {
+            config[16] = tree.leaf;
+            config[17] = tree.fan_out;
+            config[18] = tree.max_height;
+        }
+        chain = ubi(&chain, TYPE_CFG, &config);
+
+        if !personalization.is_empty() {
+            chain = ubi(&chain, TYPE_PRS, personalization);
+        }
+
+        let mut engine = Engine {
+            output_bits,
+            tree,
+            initial: chain,
+            levels: Vec::new(),
+            root: None,
+        };
+        engine.reset();
+        engine
+    }
+
+    fn reset(&mut self) {
+        self.levels.clear();
+        self.push_level();
+        self.root = None;
+    }
+
+    fn push_level(&mut self) {
+        let level = match self.tree {
+            Some(_) => self.levels.len() as u8 + 1,
+            None => 0,
+        };
+        self.levels.push(Level {
+            node: Ubi::new(&self.initial, TYPE_MSG, level, 0),
+            node_bytes: 0,
+            nodes: 0,
+        });
+    }
+
+    // The maximum number of bytes in a node of the given level, None if it is unlimited. Sizes
+    // that do not fit in a u64 are never reached, so they are treated as unlimited as well.
+    fn node_size(&self, index: usize) -> Option<u64> {
+        let block = self.initial.len() as u64;
+        let shift = match self.tree {
+            None => return None,
+            Some(tree) if index + 1 >= tree.max_height as usize => return None,
+            Some(tree) if index == 0 => tree.leaf,
+            Some(tree) => tree.fan_out,
+        };
+        if shift < 57 { Some(block << shift) } else { None }
+    }
+
+    fn input_level(&mut self, index: usize, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.levels.len() == index {
+                self.push_level();
+            }
+            let node_size = self.node_size(index);
+
+            // A full node is only finished once more data arrives, since the last node of a
+            // level is handled differently.
+            if Some(self.levels[index].node_bytes) == node_size {
+                let size = node_size.unwrap();
+                let level = &mut self.levels[index];
+                level.nodes += 1;
+                let next = Ubi::new(&self.initial, TYPE_MSG, level.node.level, level.nodes * size);
+                let node = std::mem::replace(&mut level.node, next);
+                level.node_bytes = 0;
+                self.input_level(index + 1, &node.finish());
+            }
+
+            let level = &mut self.levels[index];
+            let take = match node_size {
+                Some(size) => cmp::min(size - level.node_bytes, data.len() as u64) as usize,
+                None => data.len(),
+            };
+            level.node.input(&data[..take]);
+            level.node_bytes += take as u64;
+            data = &data[take..];
+        }
+    }
+
+    fn input(&mut self, data: &[u8]) {
+        assert!(self.root.is_none());
+        self.input_level(0, data);
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        let mut index = 0;
+        loop {
+            let next = Ubi::new(&self.initial, TYPE_MSG, 0, 0);
+            let level = &mut self.levels[index];
+            let node = std::mem::replace(&mut level.node, next).finish();
+            if level.nodes == 0 {
+                return node;
+            }
+            self.input_level(index + 1, &node);
+            index += 1;
+        }
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        if self.root.is_none() {
+            self.root = Some(self.finish());
+        }
+        let root = self.root.as_ref().unwrap();
+
+        let out = &mut out[..self.output_bits.div_ceil(8)];
+        let mut counter = [0u8; 8];
+        for (i, chunk) in out.chunks_mut(root.len()).enumerate() {
+            write_u64_le(&mut counter, i as u64);
+            let output = ubi(root, TYPE_OUT, &counter);
+            copy_memory(&output[..chunk.len()], chunk);
+        }
+        let partial_bits = self.output_bits % 8;
+        if partial_bits != 0 {
+            let last = out.len() - 1;
+            out[last] &= 0xff << (8 - partial_bits);
+        }
+    }
+}
+
+macro_rules! skein_impl(
+    ($name:ident, $state_bytes:expr, $doc:expr) => (
+        #[doc = $doc]
+        #[derive(Clone)]
+        pub struct $name {
+            engine: Engine,
+        }
+
+        impl $name {
+            /// Construct a new hasher producing an output of `output_bits` bits.
+            pub fn new(output_bits: usize) -> $name {
+                $name::new_with_params(output_bits, &[], &[], None)
+            }
+
+            /// Construct a new Skein-MAC instance using the given key.
+            pub fn new_keyed(output_bits: usize, key: &[u8]) -> $name {
+                $name::new_with_params(output_bits, key, &[], None)
+            }
+
+            /// Construct a new instance with all optional parameters. An empty key or
+            /// personalization string means the corresponding UBI block is skipped; without a
+            /// tree configuration the message is hashed sequentially.
+            pub fn new_with_params(output_bits: usize, key: &[u8], personalization: &[u8],
+                                   tree: Option<SkeinTree>) -> $name {
+                $name {
+                    engine: Engine::new($state_bytes, output_bits, key, personalization, tree),
+                }
+            }
+        }
+
+        impl Digest for $name {
+            fn input(&mut self, data: &[u8]) { self.engine.input(data); }
+            fn result(&mut self, out: &mut [u8]) { self.engine.result(out); }
+            fn reset(&mut self) { self.engine.reset(); }
+            fn output_bits(&self) -> usize { self.engine.output_bits }
+            fn block_size(&self) -> usize { $state_bytes }
+        }
+
+        impl Mac for $name {
+            /**
+             * Process input data.
+             *
+             * # Arguments
+             * * data - The input data to process.
+             *
+             */
+            fn input(&mut self, data: &[u8]) {
+                self.engine.input(data);
+            }
+
+            /**
+             * Reset the Mac state to begin processing another input stream.
+             */
+            fn reset(&mut self) {
+                self.engine.reset();
+            }
+
+            /**
+             * Obtain the result of a Mac computation as a MacResult.
+             */
+            fn result(&mut self) -> MacResult {
+                let mut mac = vec![0u8; Mac::output_bytes(self)];
+                self.raw_result(&mut mac);
+                MacResult::new_from_owned(mac)
+            }
+
+            /**
+             * Obtain the result of a Mac computation as [u8]. This method should be used very
+             * carefully since incorrect use of the Mac code could result in permitting a timing
+             * attack which defeats the security provided by a Mac function.
+             */
+            fn raw_result(&mut self, output: &mut [u8]) {
+                self.engine.result(output);
+            }
+
+            /**
+             * Get the size of the Mac code, in bytes.
+             */
+            fn output_bytes(&self) -> usize { self.engine.output_bits.div_ceil(8) }
+        }
+    )
+);
+
+skein_impl!(Skein256, 32, "The Skein-256 hash function, with a 256 bit internal state.");
+skein_impl!(Skein512, 64, "The Skein-512 hash function, with a 512 bit internal state.");
+skein_impl!(Skein1024, 128, "The Skein-1024 hash function, with a 1024 bit internal state.");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serialize::hex::FromHex;
+
+    fn hex_to_bytes(s: &str) -> Vec<u8> {
+        s.from_hex().unwrap()
+    }
+
+    // The message of the Skein 1.3 known answer tests: 0xff, 0xfe, 0xfd, ...
+    fn kat_message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| 0xff - i as u8).collect()
+    }
+
+    fn check<D: Digest>(mut hasher: D, msg: &[u8], expected: &str) {
+        let expected = hex_to_bytes(expected);
+        assert_eq!(hasher.output_bytes(), expected.len());
+        let mut out = vec![0u8; expected.len()];
+
+        hasher.input(msg);
+        hasher.result(&mut out);
+        assert_eq!(out, expected);
+
+        // Feed the message in pieces of all sizes, starting over with reset()
+        for step in &[1, 3, 31, 64, 127, 200] {
+            hasher.reset();
+            for chunk in msg.chunks(*step) {
+                hasher.input(chunk);
+            }
+            hasher.result(&mut out);
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn test_skein256_kat() {
+        check(Skein256::new(256), &kat_message(1),
+              "0b98dcd198ea0e50a7a244c444e25c23da30c10fc9a1f270a6637f1f34e67ed2");
+        check(Skein256::new(256), &kat_message(32),
+              "8d0fa4ef777fd759dfd4044e6f6a5ac3c774aec943dcfc07927b723b5dbf408b");
+        check(Skein256::new(256), &kat_message(64),
+              "df28e916630d0b44c4a849dc9a02f07a07cb30f732318256b15d865ac4ae162f");
+    }
+
+    #[test]
+    fn test_skein512_kat() {
+        check(Skein512::new(512), &kat_message(1),
+              "71b7bce6fe6452227b9ced6014249e5bf9a9754c3ad618ccc4e0aae16b316cc8\
+               ca698d864307ed3e80b6ef1570812ac5272dc409b5a012df2a579102f340617a");
+        check(Skein512::new(512), &kat_message(64),
+              "45863ba3be0c4dfc27e75d358496f4ac9a736a505d9313b42b2f5eada79fc17f\
+               63861e947afb1d056aa199575ad3f8c9a3cc1780b5e5fa4cae050e989876625b");
+        check(Skein512::new(512), &kat_message(128),
+              "91cca510c263c4ddd010530a33073309628631f308747e1bcbaa90e451cab92e\
+               5188087af4188773a332303e6667a7a210856f742139000071f48e8ba2a5adb7");
+    }
+
+    #[test]
+    fn test_skein1024_kat() {
+        check(Skein1024::new(1024), &kat_message(1),
+              "e62c05802ea0152407cdd8787fda9e35703de862a4fbc119cff8590afe79250b\
+               ccc8b3faf1bd2422ab5c0d263fb2f8afb3f796f048000381531b6f00d85161bc\
+               0fff4bef2486b1ebcd3773fabf50ad4ad5639af9040e3f29c6c931301bf79832\
+               e9da09857e831e82ef8b4691c235656515d437d2bda33bcec001c67ffde15ba8");
+        check(Skein1024::new(1024), &kat_message(128),
+              "1f3e02c46fb80a3fcd2dfbbc7c173800b40c60c2354af551189ebf433c3d85f9\
+               ff1803e6d920493179ed7ae7fce69c3581a5a2f82d3e0c7a295574d0cd7d217c\
+               484d2f6313d59a7718ead07d0729c24851d7e7d2491b902d489194e6b7d369db\
+               0ab7aa106f0ee0a39a42efc54f18d93776080985f907574f995ec6a37153a578");
+        check(Skein1024::new(1024), &kat_message(256),
+              "842a53c99c12b0cf80cf69491be5e2f7515de8733b6ea9422dfd676665b5fa42\
+               ffb3a9c48c217777950848cecdb48f640f81fb92bef6f88f7a85c1f7cd1446c9\
+               161c0afe8f25ae444f40d3680081c35aa43f640fd5fa3c3c030bcc06abac01d0\
+               98bcc984ebd8322712921e00b1ba07d6d01f26907050255ef2c8e24f716c52a5");
+    }
+
+    #[test]
+    fn test_output_lengths() {
+        check(Skein512::new(224), b"", "1541ae9fc3ebe24eb758ccb1fd60c2c31a9ebfe65b220086e7819e25");
+        check(Skein512::new(224), &[0xcc],
+              "23f031a6a4378039b66a5a178bad217eaec094b7fcba663a47ddcf33");
+        check(Skein256::new(1032), b"abc",
+              "ad5c53fac56b642cc05b389e9e3c82fda7fd1df8899bd6cc0ff4483f650c641d\
+               1bf169cd70bd8cb591e345fa0b1b2e33e87b1827277dcae6d3d4c9ede32d6389\
+               51b634a94024bb09ae271f8143722046090c7e96dfb7e5f1a1b19709103aebd2\
+               c976dcd46114ca185d0fb2a122805d9a42c14c28ffb45e9758354ff356e07254\
+               6a");
+        check(Skein512::new(12), b"abc", "50e0");
+    }
+
+    #[test]
+    fn test_personalization() {
+        check(Skein512::new_with_params(512, &[], b"Test", None), b"ABC",
+              "3d2aea7c3724684cd95fa246d6d1b951d82939400bfadf5e10aa86978ba9d07f\
+               2de2323774369f178d4d8224f5c3374b68c0a99a115b639c129b9f98e05012e9");
+    }
+
+    fn test_message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_tree() {
+        let msg = test_message(1000);
+        check(Skein512::new_with_params(512, &[], &[], Some(SkeinTree::new(1, 1, 2))), &msg,
+              "ab7d90dfdfe67d0ef002c7d3a2ad9712be98d8e4e29f0cb521ee36d2917e145f\
+               4f43923943570d1ecdc86d70fd8506274386c310a0c2882bdd3c5143b8e211fe");
+        check(Skein256::new_with_params(256, &[], &[], Some(SkeinTree::new(1, 1, 255))), &msg,
+              "f86747dc06e4c93f21308ac42f76c0190ab607ab0b1a36089e481beca88c0faf");
+        check(Skein1024::new_with_params(512, b"key", &[], Some(SkeinTree::new(2, 1, 3))), &msg,
+              "977238fa0f295f029d4776b8a0b395563f3f5811632902571be12f4d784847f5\
+               5662ae37b591638169259ea98ec26bc27a16a2d2b225be9c45ec1172e1280d21");
+    }
+
+    fn check_mac<M: Mac>(mut mac: M, msg: &[u8], expected: &str) {
+        let expected = hex_to_bytes(expected);
+        mac.input(msg);
+        assert!(mac.result() == MacResult::new(&expected));
+        mac.reset();
+        mac.input(msg);
+        assert!(mac.result() == MacResult::new(&expected));
+    }
+
+    #[test]
+    fn test_mac() {
+        let msg = test_message(300);
+        let key: Vec<u8> = (0..200).collect();
+        check_mac(Skein256::new_keyed(256, &key[..32]), &msg[..100],
+                  "0f04381097f505f946252e5ca4923e192512d7064f068db7802c5e9f957db3ce");
+        check_mac(Skein512::new_keyed(512, &key[..16]), &msg[..100],
+                  "d83b0c3f351bd5a1a0472d09a309b286506fff47eb2c8143f3150415c5ee820b\
+                   486eb6969b5577e7a8d2ce735157db26a45be233c7677366db946e77e7e9797d");
+        check_mac(Skein512::new_keyed(160, &key[..64]),
+                  b"The quick brown fox jumps over the lazy dog",
+                  "96658cdc81bf221d65379ecee2a5705376f27678");
+        check_mac(Skein1024::new_keyed(1024, &key), &msg,
+                  "43a3340caf0dc6b073878ea2b14321d341ad5c6b56ce0b8fb9d3312b1eeccb72\
+                   25ab9195d959735be82db92ecc033f491cb570e2d2f479a7a90ebc5ce903cd01\
+                   2a47f3a73cbe202248e6d65bfa99749115821474294a7900068d84722b1656a5\
+                   7063a580351d1bc12a8ac09722f56c836ad82541d25b099b671982ee3d293afb");
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use super::*;
+
+    #[bench]
+    pub fn skein512_10(bh: &mut Bencher) {
+        let mut sh = Skein512::new(512);
+        let bytes = [1u8; 10];
+        bh.iter(|| {
+            Digest::input(&mut sh, &bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn skein512_1k(bh: &mut Bencher) {
+        let mut sh = Skein512::new(512);
+        let bytes = [1u8; 1024];
+        bh.iter(|| {
+            Digest::input(&mut sh, &bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn skein1024_64k(bh: &mut Bencher) {
+        let mut sh = Skein1024::new(1024);
+        let bytes = [1u8; 65536];
+        bh.iter(|| {
+            Digest::input(&mut sh, &bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+}
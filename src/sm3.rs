@@ -0,0 +1,277 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+An implementation of the SM3 cryptographic hash algorithm, as specified in GB/T 32905-2016 (and in
+[draft-sca-cfrg-sm3](https://tools.ietf.org/html/draft-sca-cfrg-sm3-02)).
+
+SM3 has the same structure as SHA-256: a 256 bit digest of a message padded into 512 bit blocks.
+It implements the `Digest` trait, so it can be used with `Hmac`, `hkdf` and `pbkdf2`. An example of
+use is:
+
+```rust
+use self::crypto::digest::Digest;
+use self::crypto::sm3::Sm3;
+
+let mut hasher = Sm3::new();
+hasher.input_str("abc");
+assert_eq!(hasher.result_str(),
+           "66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0");
+```
+ */
+
+use crate::cryptoutil::{write_u32_be, read_u32v_be, add_bytes_to_bits, FixedBuffer, FixedBuffer64,
+                        StandardPadding};
+use crate::digest::Digest;
+
+const STATE_LEN: usize = 8;
+
+static IV: [u32; STATE_LEN] = [
+    0x7380166f, 0x4914b2b9, 0x172442d7, 0xda8a0600, 0xa96f30bc, 0x163138aa, 0xe38dee4d, 0xb0fb0e4e];
+
+fn p0(x: u32) -> u32 {
+    x ^ x.rotate_left(9) ^ x.rotate_left(17)
+}
+
+fn p1(x: u32) -> u32 {
+    x ^ x.rotate_left(15) ^ x.rotate_left(23)
+}
+
+/// Process a block with the SM3 compression function.
+pub fn sm3_digest_block(state: &mut [u32; STATE_LEN], block: &[u8]) {
+    assert_eq!(block.len(), 64);
+
+    let mut w = [0u32; 68];
+    read_u32v_be(&mut w[0..16], block);
+    for j in 16..68 {
+        w[j] = p1(w[j - 16] ^ w[j - 9] ^ w[j - 3].rotate_left(15)) ^ w[j - 13].rotate_left(7) ^
+            w[j - 6];
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for j in 0..64 {
+        let (t, ff, gg) = if j < 16 {
+            (0x79cc4519u32, a ^ b ^ c, e ^ f ^ g)
+        } else {
+            (0x7a879d8au32, (a & b) | (a & c) | (b & c), (e & f) | (!e & g))
+        };
+        let a12 = a.rotate_left(12);
+        let ss1 = a12.wrapping_add(e).wrapping_add(t.rotate_left(j as u32 % 32)).rotate_left(7);
+        let ss2 = ss1 ^ a12;
+        let tt1 = ff.wrapping_add(d).wrapping_add(ss2).wrapping_add(w[j] ^ w[j + 4]);
+        let tt2 = gg.wrapping_add(h).wrapping_add(ss1).wrapping_add(w[j]);
+        d = c;
+        c = b.rotate_left(9);
+        b = a;
+        a = tt1;
+        h = g;
+        g = f.rotate_left(19);
+        f = e;
+        e = p0(tt2);
+    }
+
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+        *s ^= v;
+    }
+}
+
+/// Structure representing the state of an Sm3 computation
+#[derive(Clone, Copy)]
+pub struct Sm3 {
+    state: [u32; STATE_LEN],
+    length_bits: u64,
+    buffer: FixedBuffer64,
+    computed: bool
+}
+
+impl Sm3 {
+    /// Construct an `Sm3` object
+    pub fn new() -> Sm3 {
+        Sm3 {
+            state: IV,
+            length_bits: 0,
+            buffer: FixedBuffer64::new(),
+            computed: false
+        }
+    }
+}
+
+impl Default for Sm3 {
+    fn default() -> Sm3 {
+        Sm3::new()
+    }
+}
+
+impl Digest for Sm3 {
+    fn reset(&mut self) {
+        self.state = IV;
+        self.length_bits = 0;
+        self.buffer.reset();
+        self.computed = false;
+    }
+
+    fn input(&mut self, msg: &[u8]) {
+        assert!(!self.computed);
+        // Assumes that msg.len() can be converted to u64 without overflow
+        self.length_bits = add_bytes_to_bits(self.length_bits, msg.len() as u64);
+        let state = &mut self.state;
+        self.buffer.input(msg, |d: &[u8]| { sm3_digest_block(state, d); });
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        if !self.computed {
+            let state = &mut self.state;
+            self.buffer.standard_padding(8, |d: &[u8]| { sm3_digest_block(state, d); });
+            write_u32_be(self.buffer.next(4), (self.length_bits >> 32) as u32);
+            write_u32_be(self.buffer.next(4), self.length_bits as u32);
+            sm3_digest_block(state, self.buffer.full_buffer());
+            self.computed = true;
+        }
+
+        for (chunk, &s) in out[..32].chunks_mut(4).zip(self.state.iter()) {
+            write_u32_be(chunk, s);
+        }
+    }
+
+    fn output_bits(&self) -> usize { 256 }
+
+    fn block_size(&self) -> usize { 64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cryptoutil::test::test_digest_1million_random;
+    use crate::digest::Digest;
+    use crate::hkdf::{hkdf_extract, hkdf_expand};
+    use crate::hmac::Hmac;
+    use crate::mac::Mac;
+    use crate::pbkdf2::pbkdf2;
+    use crate::serialize::hex::FromHex;
+    use crate::sm3::Sm3;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    struct Test {
+        input: &'static str,
+        output_str: &'static str
+    }
+
+    #[test]
+    fn test() {
+        // Examples 1 and 2 from GB/T 32905-2016, Appendix A
+        let tests = [
+            Test {
+                input: "abc",
+                output_str: "66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0"
+            },
+            Test {
+                input: "abcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcdabcd",
+                output_str: "debe9ff92275b8a138604889c18e5a4d6fdb70e5387e5765293dcba39c0c5732"
+            },
+        ];
+
+        let mut sh = Sm3::new();
+        for t in tests.iter() {
+            sh.input_str(t.input);
+            assert_eq!(sh.result_str(), t.output_str);
+            sh.reset();
+        }
+
+        // Test that it works when accepting the message in pieces
+        for t in tests.iter() {
+            let len = t.input.len();
+            let mut left = len;
+            while left > 0 {
+                let take = left - left / 2;
+                sh.input_str(&t.input[len - left..take + len - left]);
+                left -= take;
+            }
+            assert_eq!(sh.result_str(), t.output_str);
+            sh.reset();
+        }
+    }
+
+    #[test]
+    fn test_1million_random_sm3() {
+        let mut sh = Sm3::new();
+        test_digest_1million_random(
+            &mut sh,
+            64,
+            "c8aaf89429554029e231941a2acc0ad61ff2a5acd8fadd25847a3a732b3b02c3");
+    }
+
+    #[test]
+    fn test_hmac_sm3() {
+        let mut hmac = Hmac::new(Sm3::new(), b"key");
+        hmac.input(b"The quick brown fox jumps over the lazy dog");
+        let expected = hex_to_bytes(
+            "bd4a34077888162b210645b8ebf74b9af357303789357a27c7fc457244ebd398");
+        assert_eq!(hmac.result().code(), &expected[..]);
+    }
+
+    #[test]
+    fn test_hkdf_sm3() {
+        let ikm = [0x0bu8; 22];
+        let salt = hex_to_bytes("000102030405060708090a0b0c");
+        let info = hex_to_bytes("f0f1f2f3f4f5f6f7f8f9");
+        let mut prk = [0u8; 32];
+        hkdf_extract(Sm3::new(), &salt, &ikm, &mut prk);
+        let mut okm = [0u8; 42];
+        hkdf_expand(Sm3::new(), &prk, &info, &mut okm);
+        let expected = hex_to_bytes(
+            "c69fe91b7aaee2dd5718d72dcaee0cce93f1b8e41f792da51261b6a517e68b36ed2c595572b01dfa359b");
+        assert_eq!(&okm[..], &expected[..]);
+    }
+
+    #[test]
+    fn test_pbkdf2_sm3() {
+        let mut hmac = Hmac::new(Sm3::new(), b"password");
+        let mut output = [0u8; 32];
+        pbkdf2(&mut hmac, b"salt", 4096, &mut output);
+        let expected = hex_to_bytes(
+            "b6e8f2074c87432b78f62e5ced980fdff89e86af2f693dab1638e2b3683045dd");
+        assert_eq!(&output[..], &expected[..]);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+    use crate::digest::Digest;
+    use crate::sm3::Sm3;
+
+    #[bench]
+    pub fn sm3_10(bh: & mut Bencher) {
+        let mut sh = Sm3::new();
+        let bytes = [1u8; 10];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn sm3_1k(bh: & mut Bencher) {
+        let mut sh = Sm3::new();
+        let bytes = [1u8; 1024];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn sm3_64k(bh: & mut Bencher) {
+        let mut sh = Sm3::new();
+        let bytes = [1u8; 65536];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+}
@@ -0,0 +1,306 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the SM4 block cipher, as specified in GB/T 32907-2016 (and in
+ * [draft-ribose-cfrg-sm4](https://tools.ietf.org/html/draft-ribose-cfrg-sm4-10)).
+ *
+ * The implementation doesn't use any secret dependent table lookups or branches: the S-box is
+ * packed into 64 bit words, the word is selected with masks and the byte with a shift.
+ *
+ * SM4 can be used with the generic modes: `blockmodes` for CBC and CTR, `gcm::Gcm` and `ccm::Ccm`.
+ */
+
+use crate::cryptoutil::{read_u32v_be, write_u32_be};
+use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+// The S-box, with S(8 * i + j) in byte j (counting from the least significant byte) of SBOX[i]
+static SBOX: [u64; 32] = [
+    0xb73de1ccfee990d6, 0x052cfb28c214b616, 0xc304be2a769a672b, 0x99068649261344aa,
+    0x7a98ef91f450429c, 0x62accfed430b5433, 0x95e808c9a91cb3e4, 0xa63f8f75fa94df80,
+    0xba1773f3fca70747, 0xa84f85e6193c5983, 0x8bda6471b2816b68, 0x359d56704b0febf8,
+    0xa2d158635e0e241e, 0x877821013b7c2225, 0x5227d39f574600d4, 0x9ec8c4a0e702364c,
+    0xb538c740d28abfea, 0xa11561f9cef2f7a3, 0x551a349ba45daee0, 0xe3b18cf5303293ad,
+    0x60ca66822ee2f61d, 0x6f4e530dab2329c0, 0x2f8efdde4537dbd5, 0x515b6c6d726aff03,
+    0x7fbcddbb92af1b8d, 0xd85a101f415cd911, 0xbd7bcda58831c10a, 0xb0b4e5b812d0742d,
+    0x7e77960c4a976989, 0x84c66ec509f1b965, 0x204ddc3aec7df018, 0x4839cbd73e5fee79];
+
+static FK: [u32; 4] = [0xa3b1bac6, 0x56aa3350, 0x677d9197, 0xb27022dc];
+
+fn sbox(input: u32) -> u32 {
+    let index = input as u64;
+    let row = index >> 3;
+    let mut bits = 0;
+    for (r, &packed) in SBOX.iter().enumerate() {
+        // All ones if r == row, without branching
+        let mask = 0u64.wrapping_sub(((r as u64 ^ row).wrapping_sub(1)) >> 63);
+        bits |= packed & mask;
+    }
+    ((bits >> ((index & 7) * 8)) & 0xff) as u32
+}
+
+// The non-linear transformation tau, which applies the S-box to every byte
+fn tau(a: u32) -> u32 {
+    (sbox(a >> 24) << 24) | (sbox((a >> 16) & 0xff) << 16) | (sbox((a >> 8) & 0xff) << 8) |
+        sbox(a & 0xff)
+}
+
+// The round transformation T
+fn t(x: u32) -> u32 {
+    let b = tau(x);
+    b ^ b.rotate_left(2) ^ b.rotate_left(10) ^ b.rotate_left(18) ^ b.rotate_left(24)
+}
+
+// The key schedule transformation T'
+fn t_prime(x: u32) -> u32 {
+    let b = tau(x);
+    b ^ b.rotate_left(13) ^ b.rotate_left(23)
+}
+
+// The constant CK_i, whose j-th byte is (4 * i + j) * 7 mod 256
+fn ck(i: usize) -> u32 {
+    let mut c = 0;
+    for j in 0..4 {
+        c = (c << 8) | (((4 * i + j) * 7) & 0xff) as u32;
+    }
+    c
+}
+
+#[derive(Clone, Copy)]
+pub struct Sm4 {
+    rk: [u32; 32]
+}
+
+impl Sm4 {
+    /**
+     * Create a new Sm4 instance.
+     *
+     * # Arguments
+     * * key - The key, which must be 16 bytes long.
+     */
+    pub fn new(key: &[u8]) -> Sm4 {
+        assert!(key.len() == 16);
+        let mut k = [0u32; 4];
+        read_u32v_be(&mut k, key);
+        for (k, &fk) in k.iter_mut().zip(FK.iter()) {
+            *k ^= fk;
+        }
+        let mut rk = [0u32; 32];
+        for (i, r) in rk.iter_mut().enumerate() {
+            *r = k[0] ^ t_prime(k[1] ^ k[2] ^ k[3] ^ ck(i));
+            k = [k[1], k[2], k[3], *r];
+        }
+        Sm4 {
+            rk
+        }
+    }
+
+    fn crypt<'a, I: Iterator<Item = &'a u32>>(rk: I, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        let mut x = [0u32; 4];
+        read_u32v_be(&mut x, input);
+        for &r in rk {
+            x = [x[1], x[2], x[3], x[0] ^ t(x[1] ^ x[2] ^ x[3] ^ r)];
+        }
+        write_u32_be(&mut output[0..4], x[3]);
+        write_u32_be(&mut output[4..8], x[2]);
+        write_u32_be(&mut output[8..12], x[1]);
+        write_u32_be(&mut output[12..16], x[0]);
+    }
+}
+
+impl BlockEncryptor for Sm4 {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        Sm4::crypt(self.rk.iter(), input, output);
+    }
+}
+
+impl BlockDecryptor for Sm4 {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        Sm4::crypt(self.rk.iter().rev(), input, output);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::aead::{AeadEncryptor, AeadDecryptor};
+    use crate::blockmodes::{CbcEncryptor, CbcDecryptor, CtrMode, NoPadding};
+    use crate::buffer::{RefReadBuffer, RefWriteBuffer};
+    use crate::ccm::Ccm;
+    use crate::gcm::Gcm;
+    use crate::serialize::hex::FromHex;
+    use crate::sm4::{Sm4, sbox};
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor, Encryptor, Decryptor,
+                                 SynchronousStreamCipher};
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    #[test]
+    fn test_sbox() {
+        let first_row = hex_to_bytes("d690e9fecce13db716b614c228fb2c05");
+        for (i, &s) in first_row.iter().enumerate() {
+            assert_eq!(sbox(i as u32), s as u32);
+        }
+        assert_eq!(sbox(0xff), 0x48);
+
+        let mut seen = [false; 256];
+        for i in 0..256 {
+            seen[sbox(i) as usize] = true;
+        }
+        assert!(seen.iter().all(|&s| s));
+    }
+
+    // Examples 1 and 2 from GB/T 32907-2016, Appendix A
+    #[test]
+    fn test_sm4() {
+        let key = hex_to_bytes("0123456789abcdeffedcba9876543210");
+        let sm4 = Sm4::new(&key);
+        let expected = hex_to_bytes("681edf34d206965e86b3e94f536e4246");
+        let mut output = [0u8; 16];
+        sm4.encrypt_block(&key, &mut output);
+        assert_eq!(&output[..], &expected[..]);
+        sm4.decrypt_block(&expected, &mut output);
+        assert_eq!(&output[..], &key[..]);
+    }
+
+    fn sp800_38a_plaintext() -> Vec<u8> {
+        hex_to_bytes(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52ef")
+    }
+
+    #[test]
+    fn test_sm4_cbc() {
+        let key = hex_to_bytes("2b7e151628aed2a6abf7158809cf4f3c");
+        let iv = hex_to_bytes("000102030405060708090a0b0c0d0e0f");
+        let plaintext = sp800_38a_plaintext();
+        let expected = hex_to_bytes(
+            "ac529af989a62fce9cddc5ffb84125cab168dd69db3c0eea1ab16de6aea43c59\
+             2c15567bff8f707486c202c7be59101f");
+
+        let mut enc = CbcEncryptor::new(Sm4::new(&key), NoPadding, iv.clone());
+        let mut output = vec![0u8; plaintext.len()];
+        enc.encrypt(
+            &mut RefReadBuffer::new(&plaintext),
+            &mut RefWriteBuffer::new(&mut output),
+            true).unwrap();
+        assert_eq!(output, expected);
+
+        let mut dec = CbcDecryptor::new(Sm4::new(&key), NoPadding, iv);
+        let mut output = vec![0u8; expected.len()];
+        dec.decrypt(
+            &mut RefReadBuffer::new(&expected),
+            &mut RefWriteBuffer::new(&mut output),
+            true).unwrap();
+        assert_eq!(output, plaintext);
+    }
+
+    #[test]
+    fn test_sm4_ctr() {
+        let key = hex_to_bytes("2b7e151628aed2a6abf7158809cf4f3c");
+        let ctr = hex_to_bytes("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let plaintext = sp800_38a_plaintext();
+        let expected = hex_to_bytes(
+            "14ae4a72b97a93ce1216ccd998e371c160f7ef8b6344bd6da1992505e5fc219b\
+             0bf057f86c5d75103c0f46519c7fb2e7");
+
+        let mut ctr = CtrMode::new(Sm4::new(&key), ctr);
+        let mut output = vec![0u8; plaintext.len()];
+        ctr.process(&plaintext, &mut output);
+        assert_eq!(output, expected);
+    }
+
+    // Test vector from RFC 8998, Appendix A.1
+    #[test]
+    fn test_sm4_gcm() {
+        let key = hex_to_bytes("0123456789abcdeffedcba9876543210");
+        let nonce = hex_to_bytes("00001234567800000000abcd");
+        let aad = hex_to_bytes("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let plaintext = hex_to_bytes(
+            "aaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbccccccccccccccccdddddddddddddddd\
+             eeeeeeeeeeeeeeeeffffffffffffffffeeeeeeeeeeeeeeeeaaaaaaaaaaaaaaaa");
+        let expected = hex_to_bytes(
+            "17f399f08c67d5ee19d0dc9969c4bb7d5fd46fd3756489069157b282bb200735\
+             d82710ca5c22f0ccfa7cbf93d496ac15a56834cbcf98c397b4024a2691233b8d");
+        let expected_tag = hex_to_bytes("83de3541e4c2b58177e065a9bf7b62ec");
+
+        let mut ciphertext = vec![0u8; plaintext.len()];
+        let mut tag = [0u8; 16];
+        Gcm::new(Sm4::new(&key), &nonce, &aad).encrypt(&plaintext, &mut ciphertext, &mut tag);
+        assert_eq!(ciphertext, expected);
+        assert_eq!(&tag[..], &expected_tag[..]);
+
+        let mut output = vec![0u8; ciphertext.len()];
+        assert!(Gcm::new(Sm4::new(&key), &nonce, &aad).decrypt(&ciphertext, &mut output, &tag));
+        assert_eq!(output, plaintext);
+    }
+
+    // Test vector from RFC 8998, Appendix A.2
+    #[test]
+    fn test_sm4_ccm() {
+        let key = hex_to_bytes("0123456789abcdeffedcba9876543210");
+        let nonce = hex_to_bytes("00001234567800000000abcd");
+        let aad = hex_to_bytes("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let plaintext = hex_to_bytes(
+            "aaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbccccccccccccccccdddddddddddddddd\
+             eeeeeeeeeeeeeeeeffffffffffffffffeeeeeeeeeeeeeeeeaaaaaaaaaaaaaaaa");
+        let expected = hex_to_bytes(
+            "48af93501fa62adbcd414cce6034d895dda1bf8f132f042098661572e7483094\
+             fd12e518ce062c98acee28d95df4416bed31a2f04476c18bb40c84a74b97dc5b");
+        let expected_tag = hex_to_bytes("16842d4fa186f56ab33256971fa110f4");
+
+        let mut ciphertext = vec![0u8; plaintext.len()];
+        let mut tag = [0u8; 16];
+        Ccm::new(Sm4::new(&key), &nonce, &aad, 16).encrypt(&plaintext, &mut ciphertext, &mut tag);
+        assert_eq!(ciphertext, expected);
+        assert_eq!(&tag[..], &expected_tag[..]);
+
+        let mut output = vec![0u8; ciphertext.len()];
+        assert!(Ccm::new(Sm4::new(&key), &nonce, &aad, 16).decrypt(&ciphertext, &mut output, &tag));
+        assert_eq!(output, plaintext);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::sm4::Sm4;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+    #[bench]
+    pub fn sm4_encrypt(bh: &mut Bencher) {
+        let sm4 = Sm4::new(&[1u8; 16]);
+        let input = [1u8; 16];
+        let mut output = [0u8; 16];
+        bh.iter( || {
+            sm4.encrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn sm4_decrypt(bh: &mut Bencher) {
+        let sm4 = Sm4::new(&[1u8; 16]);
+        let input = [1u8; 16];
+        let mut output = [0u8; 16];
+        bh.iter( || {
+            sm4.decrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
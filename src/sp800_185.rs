@@ -0,0 +1,581 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the SHA-3 derived functions of NIST SP 800-185 [1]:
+ *
+ * * `CShake128` and `CShake256`, SHAKE customised with a function name and a customization
+ *   string, as extendable output functions
+ * * `Kmac128` and `Kmac256`, the Keccak message authentication code, as a `Mac`
+ * * `TupleHash128` and `TupleHash256`, which hash a sequence of byte strings such that the
+ *   boundaries between them matter
+ * * `ParallelHash128` and `ParallelHash256`, which hash long messages in independent blocks, as a
+ *   `Digest`
+ *
+ * The `SECURITY` parameter of the types is the security strength in bits, either 128 or 256. All of
+ * the functions are built on the Keccak sponge of the `keccak` module, with the input encodings
+ * `left_encode`, `right_encode`, `encode_string` and `bytepad` of the specification.
+ *
+ * # Usage
+ *
+ * ```rust
+ * use self::crypto::mac::Mac;
+ * use self::crypto::sp800_185::Kmac128;
+ *
+ * let key = [0x40u8; 32];
+ * let mut kmac = Kmac128::new(&key, b"My Tagged Application", 32);
+ * kmac.input(b"message");
+ * let tag = kmac.result();
+ * assert_eq!(tag.code().len(), 32);
+ * ```
+ *
+ * # References
+ * [1] - J. Kelsey, S. Chang, R. Perlner. SHA-3 Derived Functions: cSHAKE, KMAC, TupleHash, and
+ *       ParallelHash. NIST Special Publication 800-185, 2016.
+ *       <https://doi.org/10.6028/NIST.SP.800-185>
+ */
+
+use crate::digest::{Digest, ExtendableOutput, XofReader};
+use crate::mac::{Mac, MacResult};
+use crate::keccak::{capacity, Sponge};
+use crate::sha3::{ShakeReader, SHAKE_DS};
+
+// Domain separation bits of cSHAKE, followed by the first padding bit
+const CSHAKE_DS: u8 = 0x04;
+
+/// Encodes `x` as its big endian bytes without leading zeros, preceded by their number.
+pub fn left_encode(x: u64) -> Vec<u8> {
+    let bytes = x.to_be_bytes();
+    let skip = bytes.iter().take(7).take_while(|b| **b == 0).count();
+    let mut out = vec![(8 - skip) as u8];
+    out.extend_from_slice(&bytes[skip..]);
+    out
+}
+
+/// Encodes `x` as its big endian bytes without leading zeros, followed by their number.
+pub fn right_encode(x: u64) -> Vec<u8> {
+    let bytes = x.to_be_bytes();
+    let skip = bytes.iter().take(7).take_while(|b| **b == 0).count();
+    let mut out = bytes[skip..].to_vec();
+    out.push((8 - skip) as u8);
+    out
+}
+
+/// Encodes the byte string `s` such that it can be unambiguously parsed from the start: its
+/// length in bits with `left_encode`, followed by `s` itself.
+pub fn encode_string(s: &[u8]) -> Vec<u8> {
+    let mut out = left_encode(s.len() as u64 * 8);
+    out.extend_from_slice(s);
+    out
+}
+
+/// Prepends `left_encode(w)` to `x`, and pads the result with zeros to a multiple of `w` bytes.
+pub fn bytepad(x: &[u8], w: usize) -> Vec<u8> {
+    assert!(w > 0);
+    let mut out = left_encode(w as u64);
+    out.extend_from_slice(x);
+    let padded_len = out.len().div_ceil(w) * w;
+    out.resize(padded_len, 0);
+    out
+}
+
+// A cSHAKE instance, which can be reset to the state after absorbing a prefix
+#[derive(Copy, Clone)]
+struct CShakeCore {
+    sponge: Sponge,
+    initial: Sponge,
+    ds: u8
+}
+
+impl CShakeCore {
+    fn new(security: usize, function_name: &[u8], customization: &[u8]) -> CShakeCore {
+        let mut sponge = Sponge::new(capacity(security));
+        // Without function name and customization string, cSHAKE is SHAKE
+        let ds = if function_name.is_empty() && customization.is_empty() {
+            SHAKE_DS
+        } else {
+            let mut prefix = encode_string(function_name);
+            prefix.extend_from_slice(&encode_string(customization));
+            sponge.absorb(&bytepad(&prefix, sponge.rate()));
+            CSHAKE_DS
+        };
+        CShakeCore { sponge, initial: sponge, ds }
+    }
+
+    // Absorbs a prefix that reset() doesn't remove
+    fn absorb_prefix(&mut self, data: &[u8]) {
+        self.sponge.absorb(data);
+        self.initial = self.sponge;
+    }
+
+    fn input(&mut self, data: &[u8]) {
+        self.sponge.absorb(data);
+    }
+
+    fn finalize(self) -> ShakeReader {
+        ShakeReader::new(self.sponge, self.ds)
+    }
+
+    fn reset(&mut self) {
+        self.sponge = self.initial;
+    }
+
+    fn rate(&self) -> usize {
+        self.sponge.rate()
+    }
+}
+
+/// cSHAKE with `SECURITY` bits of security, 128 or 256
+#[derive(Copy, Clone)]
+pub struct CShake<const SECURITY: usize> {
+    core: CShakeCore
+}
+
+/// cSHAKE128
+pub type CShake128 = CShake<128>;
+/// cSHAKE256
+pub type CShake256 = CShake<256>;
+
+impl <const SECURITY: usize> CShake<SECURITY> {
+    /// Create a cSHAKE instance. The function name is reserved for functions defined by NIST, and
+    /// should be empty otherwise; the customization string is free to choose.
+    pub fn new(function_name: &[u8], customization: &[u8]) -> CShake<SECURITY> {
+        CShake { core: CShakeCore::new(SECURITY, function_name, customization) }
+    }
+}
+
+impl <const SECURITY: usize> ExtendableOutput for CShake<SECURITY> {
+    type Reader = ShakeReader;
+
+    fn input(&mut self, data: &[u8]) {
+        self.core.input(data);
+    }
+
+    fn finalize_xof(self) -> ShakeReader {
+        self.core.finalize()
+    }
+
+    fn reset(&mut self) {
+        self.core.reset();
+    }
+}
+
+/// KMAC with `SECURITY` bits of security, 128 or 256
+#[derive(Clone)]
+pub struct Kmac<const SECURITY: usize> {
+    core: CShakeCore,
+    output_bytes: usize
+}
+
+/// KMAC128
+pub type Kmac128 = Kmac<128>;
+/// KMAC256
+pub type Kmac256 = Kmac<256>;
+
+impl <const SECURITY: usize> Kmac<SECURITY> {
+    /// Create a KMAC instance producing `output_bytes` bytes of output. The key should be at least
+    /// `SECURITY` bits long.
+    pub fn new(key: &[u8], customization: &[u8], output_bytes: usize) -> Kmac<SECURITY> {
+        let mut core = CShakeCore::new(SECURITY, b"KMAC", customization);
+        let rate = core.rate();
+        core.absorb_prefix(&bytepad(&encode_string(key), rate));
+        Kmac { core, output_bytes }
+    }
+}
+
+impl <const SECURITY: usize> Mac for Kmac<SECURITY> {
+    fn input(&mut self, data: &[u8]) {
+        self.core.input(data);
+    }
+
+    fn reset(&mut self) {
+        self.core.reset();
+    }
+
+    fn result(&mut self) -> MacResult {
+        let mut code = vec![0u8; self.output_bytes];
+        self.raw_result(&mut code);
+        MacResult::new_from_owned(code)
+    }
+
+    fn raw_result(&mut self, output: &mut [u8]) {
+        assert!(output.len() == self.output_bytes);
+        let mut core = self.core;
+        core.input(&right_encode(self.output_bytes as u64 * 8));
+        core.finalize().read(output);
+    }
+
+    fn output_bytes(&self) -> usize { self.output_bytes }
+}
+
+/// TupleHash with `SECURITY` bits of security, 128 or 256
+#[derive(Clone)]
+pub struct TupleHash<const SECURITY: usize> {
+    core: CShakeCore,
+    output_bytes: usize
+}
+
+/// TupleHash128
+pub type TupleHash128 = TupleHash<128>;
+/// TupleHash256
+pub type TupleHash256 = TupleHash<256>;
+
+impl <const SECURITY: usize> TupleHash<SECURITY> {
+    /// Create a TupleHash instance producing `output_bytes` bytes of output.
+    pub fn new(customization: &[u8], output_bytes: usize) -> TupleHash<SECURITY> {
+        TupleHash { core: CShakeCore::new(SECURITY, b"TupleHash", customization), output_bytes }
+    }
+
+    /// Append `element` to the tuple being hashed. Unlike the input of a `Digest`, every call adds
+    /// a separate element, so hashing ("ab", "c") differs from hashing ("a", "bc").
+    pub fn input(&mut self, element: &[u8]) {
+        self.core.input(&left_encode(element.len() as u64 * 8));
+        self.core.input(element);
+    }
+
+    /// Retrieve the hash of the tuple. This method may be called multiple times.
+    pub fn result(&mut self, out: &mut [u8]) {
+        assert!(out.len() == self.output_bytes);
+        let mut core = self.core;
+        core.input(&right_encode(self.output_bytes as u64 * 8));
+        core.finalize().read(out);
+    }
+
+    /// Reset to the empty tuple.
+    pub fn reset(&mut self) {
+        self.core.reset();
+    }
+
+    /// Get the output size in bytes.
+    pub fn output_bytes(&self) -> usize {
+        self.output_bytes
+    }
+}
+
+/// ParallelHash with `SECURITY` bits of security, 128 or 256
+#[derive(Clone)]
+pub struct ParallelHash<const SECURITY: usize> {
+    core: CShakeCore,
+    // SHAKE over the current block of the message
+    block: Sponge,
+    block_size: usize,
+    block_offset: usize,
+    blocks: u64,
+    output_bytes: usize
+}
+
+/// ParallelHash128
+pub type ParallelHash128 = ParallelHash<128>;
+/// ParallelHash256
+pub type ParallelHash256 = ParallelHash<256>;
+
+impl <const SECURITY: usize> ParallelHash<SECURITY> {
+    /// Create a ParallelHash instance hashing the message in blocks of `block_size` bytes, and
+    /// producing `output_bytes` bytes of output.
+    pub fn new(block_size: usize, customization: &[u8], output_bytes: usize) -> ParallelHash<SECURITY> {
+        assert!(block_size > 0);
+        let mut core = CShakeCore::new(SECURITY, b"ParallelHash", customization);
+        core.absorb_prefix(&left_encode(block_size as u64));
+        ParallelHash {
+            core,
+            block: Sponge::new(capacity(SECURITY)),
+            block_size,
+            block_offset: 0,
+            blocks: 0,
+            output_bytes
+        }
+    }
+
+    // Absorbs the hash of the current block, and starts the next one
+    fn finish_block(&mut self) {
+        let mut block_hash = [0u8; 64];
+        let block_hash = &mut block_hash[..2 * SECURITY / 8];
+        ShakeReader::new(self.block, SHAKE_DS).read(block_hash);
+        self.core.input(block_hash);
+
+        self.block = Sponge::new(capacity(SECURITY));
+        self.block_offset = 0;
+        self.blocks += 1;
+    }
+}
+
+impl <const SECURITY: usize> Digest for ParallelHash<SECURITY> {
+    fn input(&mut self, data: &[u8]) {
+        let mut data = data;
+        while !data.is_empty() {
+            let n = ::std::cmp::min(self.block_size - self.block_offset, data.len());
+            self.block.absorb(&data[..n]);
+            self.block_offset += n;
+            data = &data[n..];
+
+            if self.block_offset == self.block_size {
+                self.finish_block();
+            }
+        }
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        let mut state = self.clone();
+        if state.block_offset > 0 {
+            state.finish_block();
+        }
+        state.core.input(&right_encode(state.blocks));
+        state.core.input(&right_encode(self.output_bytes as u64 * 8));
+        state.core.finalize().read(&mut out[..self.output_bytes]);
+    }
+
+    fn reset(&mut self) {
+        self.core.reset();
+        self.block = Sponge::new(capacity(SECURITY));
+        self.block_offset = 0;
+        self.blocks = 0;
+    }
+
+    fn output_bits(&self) -> usize {
+        self.output_bytes * 8
+    }
+
+    fn block_size(&self) -> usize {
+        self.core.rate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::digest::{Digest, ExtendableOutput, XofReader};
+    use crate::mac::Mac;
+    use crate::serialize::hex::FromHex;
+    use crate::sp800_185::{bytepad, encode_string, left_encode, right_encode, CShake128, CShake256,
+                           Kmac128, Kmac256, ParallelHash128, ParallelHash256, TupleHash128,
+                           TupleHash256};
+
+    // The test vectors are the samples published by NIST for SP 800-185
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    fn sample_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    fn kmac_key() -> Vec<u8> {
+        (0x40..0x60).collect()
+    }
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(left_encode(0), [1, 0]);
+        assert_eq!(left_encode(168), [1, 168]);
+        assert_eq!(left_encode(0x1234), [2, 0x12, 0x34]);
+        assert_eq!(left_encode(u64::MAX), [8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(right_encode(0), [0, 1]);
+        assert_eq!(right_encode(0x1234), [0x12, 0x34, 2]);
+        assert_eq!(encode_string(b""), [1, 0]);
+        assert_eq!(encode_string(b"ab"), [1, 16, b'a', b'b']);
+        assert_eq!(bytepad(&[1, 2], 4), [1, 4, 1, 2]);
+        assert_eq!(bytepad(&[1, 2, 3], 4), [1, 4, 1, 2, 3, 0, 0, 0]);
+    }
+
+    fn check_cshake<X: ExtendableOutput>(mut xof: X, data: &[u8], expected_hex: &str) {
+        let expected = hex_to_bytes(expected_hex);
+        xof.input(data);
+        let mut reader = xof.finalize_xof();
+        let mut out = vec![0u8; expected.len()];
+        let (first, second) = out.split_at_mut(5);
+        reader.read(first);
+        reader.read(second);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_cshake128() {
+        check_cshake(CShake128::new(b"", b"Email Signature"), &sample_data(4),
+                     "C1C36925B6409A04F1B504FCBCA9D82B4017277CB5ED2B2065FC1D3814D5AAF5");
+        check_cshake(CShake128::new(b"", b"Email Signature"), &sample_data(200),
+                     "C5221D50E4F822D96A2E8881A961420F294B7B24FE3D2094BAED2C6524CC166B");
+    }
+
+    #[test]
+    fn test_cshake256() {
+        check_cshake(CShake256::new(b"", b"Email Signature"), &sample_data(4),
+                     "D008828E2B80AC9D2218FFEE1D070C48B8E4C87BFF32C9699D5B6896EEE0EDD1\
+                      64020E2BE0560858D9C00C037E34A96937C561A74C412BB4C746469527281C8C");
+        check_cshake(CShake256::new(b"", b"Email Signature"), &sample_data(200),
+                     "07DC27B11E51FBAC75BC7B3C1D983E8B4B85FB1DEFAF218912AC864302730917\
+                      27F42B17ED1DF63E8EC118F04B23633C1DFB1574C8FB55CB45DA8E25AFB092BB");
+    }
+
+    #[test]
+    fn test_cshake_without_customization_is_shake() {
+        // SHAKE128 of the empty string
+        check_cshake(CShake128::new(b"", b""), b"",
+                     "7F9C2BA4E88F827D616045507605853ED73B8093F6EFBC88EB1A6EACFA66EF26");
+    }
+
+    #[test]
+    fn test_cshake_reset() {
+        let mut cshake = CShake128::new(b"", b"Email Signature");
+        cshake.input(b"garbage");
+        cshake.reset();
+        check_cshake(cshake, &sample_data(4),
+                     "C1C36925B6409A04F1B504FCBCA9D82B4017277CB5ED2B2065FC1D3814D5AAF5");
+    }
+
+    fn check_kmac<M: Mac>(mut mac: M, data: &[u8], expected_hex: &str) {
+        let expected = hex_to_bytes(expected_hex);
+        mac.input(data);
+        assert_eq!(mac.result().code(), &expected[..]);
+        // The result doesn't change the state
+        assert_eq!(mac.result().code(), &expected[..]);
+
+        // The key survives a reset
+        mac.reset();
+        let (first, second) = data.split_at(data.len() / 2);
+        mac.input(first);
+        mac.input(second);
+        assert_eq!(mac.result().code(), &expected[..]);
+    }
+
+    #[test]
+    fn test_kmac128() {
+        check_kmac(Kmac128::new(&kmac_key(), b"", 32), &sample_data(4),
+                   "E5780B0D3EA6F7D3A429C5706AA43A00FADBD7D49628839E3187243F456EE14E");
+        check_kmac(Kmac128::new(&kmac_key(), b"My Tagged Application", 32), &sample_data(4),
+                   "3B1FBA963CD8B0B59E8C1A6D71888B7143651AF8BA0A7070C0979E2811324AA5");
+        check_kmac(Kmac128::new(&kmac_key(), b"My Tagged Application", 32), &sample_data(200),
+                   "1F5B4E6CCA02209E0DCB5CA635B89A15E271ECC760071DFD805FAA38F9729230");
+    }
+
+    #[test]
+    fn test_kmac256() {
+        check_kmac(Kmac256::new(&kmac_key(), b"My Tagged Application", 64), &sample_data(4),
+                   "20C570C31346F703C9AC36C61C03CB64C3970D0CFC787E9B79599D273A68D2F7\
+                    F69D4CC3DE9D104A351689F27CF6F5951F0103F33F4F24871024D9C27773A8DD");
+        check_kmac(Kmac256::new(&kmac_key(), b"", 64), &sample_data(200),
+                   "75358CF39E41494E949707927CEE0AF20A3FF553904C86B08F21CC414BCFD691\
+                    589D27CF5E15369CBBFF8B9A4C2EB17800855D0235FF635DA82533EC6B759B69");
+        check_kmac(Kmac256::new(&kmac_key(), b"My Tagged Application", 64), &sample_data(200),
+                   "B58618F71F92E1D56C1B8C55DDD7CD188B97B4CA4D99831EB2699A837DA2E4D9\
+                    70FBACFDE50033AEA585F1A2708510C32D07880801BD182898FE476876FC8965");
+    }
+
+    fn tuple_elements(n: usize) -> Vec<Vec<u8>> {
+        vec![hex_to_bytes("000102"), hex_to_bytes("101112131415"),
+             hex_to_bytes("202122232425262728")].into_iter().take(n).collect()
+    }
+
+    #[test]
+    fn test_tuplehash128() {
+        let tests = [
+            (2, &b""[..], "C5D8786C1AFB9B82111AB34B65B2C0048FA64E6D48E263264CE1707D3FFC8ED1"),
+            (2, &b"My Tuple App"[..], "75CDB20FF4DB1154E841D758E24160C54BAE86EB8C13E7F5F40EB35588E96DFB"),
+            (3, &b"My Tuple App"[..], "E60F202C89A2631EDA8D4C588CA5FD07F39E5151998DECCF973ADB3804BB6E84"),
+        ];
+        for &(n, customization, expected_hex) in tests.iter() {
+            let mut tuplehash = TupleHash128::new(customization, 32);
+            for element in tuple_elements(n) {
+                tuplehash.input(&element);
+            }
+            let mut out = [0u8; 32];
+            tuplehash.result(&mut out);
+            assert_eq!(&out[..], &hex_to_bytes(expected_hex)[..]);
+        }
+    }
+
+    #[test]
+    fn test_tuplehash256() {
+        let tests = [
+            (2, &b""[..], "CFB7058CACA5E668F81A12A20A2195CE97A925F1DBA3E7449A56F82201EC6073\
+                           11AC2696B1AB5EA2352DF1423BDE7BD4BB78C9AED1A853C78672F9EB23BBE194"),
+            (2, &b"My Tuple App"[..], "147C2191D5ED7EFD98DBD96D7AB5A11692576F5FE2A5065F3E33DE6BBA9F3AA1\
+                                       C4E9A068A289C61C95AAB30AEE1E410B0B607DE3620E24A4E3BF9852A1D4367E"),
+            (3, &b"My Tuple App"[..], "45000BE63F9B6BFD89F54717670F69A9BC763591A4F05C50D68891A744BCC6E7\
+                                       D6D5B5E82C018DA999ED35B0BB49C9678E526ABD8E85C13ED254021DB9E790CE"),
+        ];
+        for &(n, customization, expected_hex) in tests.iter() {
+            let mut tuplehash = TupleHash256::new(customization, 64);
+            for element in tuple_elements(n) {
+                tuplehash.input(&element);
+            }
+            let mut out = [0u8; 64];
+            tuplehash.result(&mut out);
+            assert_eq!(&out[..], &hex_to_bytes(expected_hex)[..]);
+        }
+    }
+
+    #[test]
+    fn test_tuplehash_boundaries() {
+        let mut a = TupleHash128::new(b"", 32);
+        a.input(b"ab");
+        a.input(b"c");
+        let mut b = TupleHash128::new(b"", 32);
+        b.input(b"a");
+        b.input(b"bc");
+        let mut out_a = [0u8; 32];
+        let mut out_b = [0u8; 32];
+        a.result(&mut out_a);
+        b.result(&mut out_b);
+        assert!(out_a != out_b);
+
+        b.reset();
+        b.input(b"ab");
+        b.input(b"c");
+        b.result(&mut out_b);
+        assert_eq!(out_a, out_b);
+    }
+
+    fn parallel_data_24() -> Vec<u8> {
+        hex_to_bytes("000102030405060710111213141516172021222324252627")
+    }
+
+    fn parallel_data_72() -> Vec<u8> {
+        hex_to_bytes("000102030405060708090A0B101112131415161718191A1B202122232425262728292A2B\
+                      303132333435363738393A3B404142434445464748494A4B505152535455565758595A5B")
+    }
+
+    fn check_parallelhash<D: Digest>(mut digest: D, data: &[u8], expected_hex: &str) {
+        let expected = hex_to_bytes(expected_hex);
+        assert_eq!(digest.output_bytes(), expected.len());
+
+        digest.input(data);
+        let mut out = vec![0u8; expected.len()];
+        digest.result(&mut out);
+        assert_eq!(out, expected);
+        assert_eq!(digest.result_str(), expected_hex.to_lowercase());
+
+        // Input in pieces that don't line up with the blocks
+        digest.reset();
+        for chunk in data.chunks(5) {
+            digest.input(chunk);
+        }
+        digest.result(&mut out);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_parallelhash128() {
+        check_parallelhash(ParallelHash128::new(8, b"", 32), &parallel_data_24(),
+                           "BA8DC1D1D979331D3F813603C67F72609AB5E44B94A0B8F9AF46514454A2B4F5");
+        check_parallelhash(ParallelHash128::new(8, b"Parallel Data", 32), &parallel_data_24(),
+                           "FC484DCB3F84DCEEDC353438151BEE58157D6EFED0445A81F165E495795B7206");
+        check_parallelhash(ParallelHash128::new(12, b"Parallel Data", 32), &parallel_data_72(),
+                           "F7FD5312896C6685C828AF7E2ADB97E393E7F8D54E3C2EA4B95E5ACA3796E8FC");
+    }
+
+    #[test]
+    fn test_parallelhash256() {
+        check_parallelhash(ParallelHash256::new(8, b"", 64), &parallel_data_24(),
+                           "BC1EF124DA34495E948EAD207DD9842235DA432D2BBC54B4C110E64C45110553\
+                            1B7F2A3E0CE055C02805E7C2DE1FB746AF97A1DD01F43B824E31B87612410429");
+        check_parallelhash(ParallelHash256::new(8, b"Parallel Data", 64), &parallel_data_24(),
+                           "CDF15289B54F6212B4BC270528B49526006DD9B54E2B6ADD1EF6900DDA3963BB\
+                            33A72491F236969CA8AFAEA29C682D47A393C065B38E29FAE651A2091C833110");
+        check_parallelhash(ParallelHash256::new(12, b"Parallel Data", 64), &parallel_data_72(),
+                           "69D0FCB764EA055DD09334BC6021CB7E4B61348DFF375DA262671CDEC3EFFA8D\
+                            1B4568A6CCE16B1CAD946DDDE27F6CE2B8DEE4CD1B24851EBF00EB90D43813E9");
+    }
+}
@@ -0,0 +1,341 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+This private module generates several ChaCha or Salsa20 blocks in parallel with the SSE2, SSSE3 or
+AVX2 instructions of x86(_64) CPU's, which are detected at runtime. Each vector register holds the
+same state word of 4 (SSE2, SSSE3) or 8 (AVX2) consecutive blocks, so the rounds need no shuffling
+between the columns and the diagonals. Only the final transposition to the block layout does.
+
+When compiled for non-x86(_64) this module won't exist.
+*/
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// An instruction set extension that can be used to generate blocks in parallel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Sse2,
+    Ssse3,
+    Avx2
+}
+
+impl Backend {
+    /// The fastest backend supported by the CPU, if any
+    pub fn detect() -> Option<Backend> {
+        [Backend::Avx2, Backend::Ssse3, Backend::Sse2].iter().cloned().find(|b| b.supported())
+    }
+
+    pub fn supported(self) -> bool {
+        match self {
+            Backend::Sse2 => is_x86_feature_detected!("sse2"),
+            Backend::Ssse3 => is_x86_feature_detected!("ssse3"),
+            Backend::Avx2 => is_x86_feature_detected!("avx2")
+        }
+    }
+
+    /// The number of blocks generated at once
+    pub fn blocks(self) -> usize {
+        match self {
+            Backend::Sse2 | Backend::Ssse3 => 4,
+            Backend::Avx2 => 8
+        }
+    }
+}
+
+/// Write `backend.blocks()` ChaCha blocks into `out`, starting with the given state (in the natural
+/// order of the 16 words). Only the 32 bit counter in word 12 is incremented, the caller has to
+/// make sure that it doesn't wrap around.
+pub fn chacha_blocks(backend: Backend, state: &[u32; 16], rounds: usize, out: &mut [u8]) {
+    assert!(backend.supported());
+    assert!(out.len() == backend.blocks() * 64);
+    unsafe {
+        match backend {
+            Backend::Sse2 => sse2::chacha_blocks(state, rounds, out),
+            Backend::Ssse3 => ssse3::chacha_blocks(state, rounds, out),
+            Backend::Avx2 => avx2::chacha_blocks(state, rounds, out)
+        }
+    }
+}
+
+/// Write `backend.blocks()` Salsa20 blocks into `out`, starting with the given state (in the
+/// natural order of the 16 words). The 64 bit counter in words 8 and 9 wraps around.
+pub fn salsa_blocks(backend: Backend, state: &[u32; 16], rounds: usize, out: &mut [u8]) {
+    assert!(backend.supported());
+    assert!(out.len() == backend.blocks() * 64);
+    unsafe {
+        match backend {
+            // Salsa20 has no rotations by whole bytes, so SSSE3 doesn't help
+            Backend::Sse2 | Backend::Ssse3 => sse2::salsa_blocks(state, rounds, out),
+            Backend::Avx2 => avx2::salsa_blocks(state, rounds, out)
+        }
+    }
+}
+
+macro_rules! chacha_quarter_round {
+    ($x: ident, $a: expr, $b: expr, $c: expr, $d: expr) => {{
+        $x[$a] = add($x[$a], $x[$b]);
+        $x[$d] = rotl16(xor($x[$d], $x[$a]));
+        $x[$c] = add($x[$c], $x[$d]);
+        $x[$b] = rotl12(xor($x[$b], $x[$c]));
+        $x[$a] = add($x[$a], $x[$b]);
+        $x[$d] = rotl8(xor($x[$d], $x[$a]));
+        $x[$c] = add($x[$c], $x[$d]);
+        $x[$b] = rotl7(xor($x[$b], $x[$c]));
+    }}
+}
+
+macro_rules! salsa_quarter_round {
+    ($x: ident, $a: expr, $b: expr, $c: expr, $d: expr) => {{
+        $x[$b] = xor($x[$b], rotl7(add($x[$a], $x[$d])));
+        $x[$c] = xor($x[$c], rotl9(add($x[$b], $x[$a])));
+        $x[$d] = xor($x[$d], rotl13(add($x[$c], $x[$b])));
+        $x[$a] = xor($x[$a], rotl18(add($x[$d], $x[$c])));
+    }}
+}
+
+// Defines chacha_blocks() on top of the vector helpers of a backend module
+macro_rules! define_chacha_blocks {
+    ($feature: tt) => {
+        #[target_feature(enable = $feature)]
+        pub unsafe fn chacha_blocks(state: &[u32; 16], rounds: usize, out: &mut [u8]) {
+            let mut counters = [0u32; LANES];
+            for (i, c) in counters.iter_mut().enumerate() {
+                *c = state[12].wrapping_add(i as u32);
+            }
+            let mut x = [splat(0); 16];
+            for (xi, &s) in x.iter_mut().zip(state.iter()) {
+                *xi = splat(s);
+            }
+            x[12] = load(&counters);
+            let initial = x;
+
+            for _ in 0..rounds / 2 {
+                chacha_quarter_round!(x, 0, 4, 8, 12);
+                chacha_quarter_round!(x, 1, 5, 9, 13);
+                chacha_quarter_round!(x, 2, 6, 10, 14);
+                chacha_quarter_round!(x, 3, 7, 11, 15);
+                chacha_quarter_round!(x, 0, 5, 10, 15);
+                chacha_quarter_round!(x, 1, 6, 11, 12);
+                chacha_quarter_round!(x, 2, 7, 8, 13);
+                chacha_quarter_round!(x, 3, 4, 9, 14);
+            }
+            for (xi, &s) in x.iter_mut().zip(initial.iter()) {
+                *xi = add(*xi, s);
+            }
+            store(&x, out);
+        }
+    }
+}
+
+// Defines salsa_blocks() on top of the vector helpers of a backend module
+macro_rules! define_salsa_blocks {
+    ($feature: tt) => {
+        #[target_feature(enable = $feature)]
+        pub unsafe fn salsa_blocks(state: &[u32; 16], rounds: usize, out: &mut [u8]) {
+            let counter = (state[9] as u64) << 32 | state[8] as u64;
+            let mut low = [0u32; LANES];
+            let mut high = [0u32; LANES];
+            for i in 0..LANES {
+                let c = counter.wrapping_add(i as u64);
+                low[i] = c as u32;
+                high[i] = (c >> 32) as u32;
+            }
+            let mut x = [splat(0); 16];
+            for (xi, &s) in x.iter_mut().zip(state.iter()) {
+                *xi = splat(s);
+            }
+            x[8] = load(&low);
+            x[9] = load(&high);
+            let initial = x;
+
+            for _ in 0..rounds / 2 {
+                salsa_quarter_round!(x, 0, 4, 8, 12);
+                salsa_quarter_round!(x, 5, 9, 13, 1);
+                salsa_quarter_round!(x, 10, 14, 2, 6);
+                salsa_quarter_round!(x, 15, 3, 7, 11);
+                salsa_quarter_round!(x, 0, 1, 2, 3);
+                salsa_quarter_round!(x, 5, 6, 7, 4);
+                salsa_quarter_round!(x, 10, 11, 8, 9);
+                salsa_quarter_round!(x, 15, 12, 13, 14);
+            }
+            for (xi, &s) in x.iter_mut().zip(initial.iter()) {
+                *xi = add(*xi, s);
+            }
+            store(&x, out);
+        }
+    }
+}
+
+macro_rules! define_rotl {
+    ($name: ident, $feature: tt, $vec: ty, $or: ident, $shl: ident, $shr: ident, $n: expr) => {
+        #[inline]
+        #[target_feature(enable = $feature)]
+        pub unsafe fn $name(v: $vec) -> $vec {
+            $or($shl::<$n>(v), $shr::<{32 - $n}>(v))
+        }
+    }
+}
+
+mod sse2 {
+    use super::*;
+
+    pub const LANES: usize = 4;
+
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn splat(x: u32) -> __m128i { _mm_set1_epi32(x as i32) }
+
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn load(x: &[u32; LANES]) -> __m128i { _mm_loadu_si128(x.as_ptr() as *const __m128i) }
+
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn add(a: __m128i, b: __m128i) -> __m128i { _mm_add_epi32(a, b) }
+
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn xor(a: __m128i, b: __m128i) -> __m128i { _mm_xor_si128(a, b) }
+
+    define_rotl!(rotl7, "sse2", __m128i, _mm_or_si128, _mm_slli_epi32, _mm_srli_epi32, 7);
+    define_rotl!(rotl8, "sse2", __m128i, _mm_or_si128, _mm_slli_epi32, _mm_srli_epi32, 8);
+    define_rotl!(rotl9, "sse2", __m128i, _mm_or_si128, _mm_slli_epi32, _mm_srli_epi32, 9);
+    define_rotl!(rotl12, "sse2", __m128i, _mm_or_si128, _mm_slli_epi32, _mm_srli_epi32, 12);
+    define_rotl!(rotl13, "sse2", __m128i, _mm_or_si128, _mm_slli_epi32, _mm_srli_epi32, 13);
+    define_rotl!(rotl16, "sse2", __m128i, _mm_or_si128, _mm_slli_epi32, _mm_srli_epi32, 16);
+    define_rotl!(rotl18, "sse2", __m128i, _mm_or_si128, _mm_slli_epi32, _mm_srli_epi32, 18);
+
+    // Transpose 4 vectors of 4 words
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn transpose(a: __m128i, b: __m128i, c: __m128i, d: __m128i) -> [__m128i; 4] {
+        let ab_low = _mm_unpacklo_epi32(a, b);
+        let cd_low = _mm_unpacklo_epi32(c, d);
+        let ab_high = _mm_unpackhi_epi32(a, b);
+        let cd_high = _mm_unpackhi_epi32(c, d);
+        [
+            _mm_unpacklo_epi64(ab_low, cd_low),
+            _mm_unpackhi_epi64(ab_low, cd_low),
+            _mm_unpacklo_epi64(ab_high, cd_high),
+            _mm_unpackhi_epi64(ab_high, cd_high)
+        ]
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn store(x: &[__m128i; 16], out: &mut [u8]) {
+        for group in 0..4 {
+            let rows = transpose(x[4 * group], x[4 * group + 1], x[4 * group + 2], x[4 * group + 3]);
+            for (block, row) in rows.iter().enumerate() {
+                let p = out[block * 64 + group * 16..].as_mut_ptr() as *mut __m128i;
+                _mm_storeu_si128(p, *row);
+            }
+        }
+    }
+
+    define_chacha_blocks!("sse2");
+    define_salsa_blocks!("sse2");
+}
+
+mod ssse3 {
+    use super::*;
+    use super::sse2::{LANES, splat, load, add, xor, rotl7, rotl12, store};
+
+    // Rotations by whole bytes are a single byte shuffle
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub unsafe fn rotl8(v: __m128i) -> __m128i {
+        _mm_shuffle_epi8(v, _mm_setr_epi8(3, 0, 1, 2, 7, 4, 5, 6, 11, 8, 9, 10, 15, 12, 13, 14))
+    }
+
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    pub unsafe fn rotl16(v: __m128i) -> __m128i {
+        _mm_shuffle_epi8(v, _mm_setr_epi8(2, 3, 0, 1, 6, 7, 4, 5, 10, 11, 8, 9, 14, 15, 12, 13))
+    }
+
+    define_chacha_blocks!("ssse3");
+}
+
+mod avx2 {
+    use super::*;
+
+    pub const LANES: usize = 8;
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn splat(x: u32) -> __m256i { _mm256_set1_epi32(x as i32) }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn load(x: &[u32; LANES]) -> __m256i { _mm256_loadu_si256(x.as_ptr() as *const __m256i) }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn add(a: __m256i, b: __m256i) -> __m256i { _mm256_add_epi32(a, b) }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn xor(a: __m256i, b: __m256i) -> __m256i { _mm256_xor_si256(a, b) }
+
+    define_rotl!(rotl7, "avx2", __m256i, _mm256_or_si256, _mm256_slli_epi32, _mm256_srli_epi32, 7);
+    define_rotl!(rotl9, "avx2", __m256i, _mm256_or_si256, _mm256_slli_epi32, _mm256_srli_epi32, 9);
+    define_rotl!(rotl12, "avx2", __m256i, _mm256_or_si256, _mm256_slli_epi32, _mm256_srli_epi32, 12);
+    define_rotl!(rotl13, "avx2", __m256i, _mm256_or_si256, _mm256_slli_epi32, _mm256_srli_epi32, 13);
+    define_rotl!(rotl18, "avx2", __m256i, _mm256_or_si256, _mm256_slli_epi32, _mm256_srli_epi32, 18);
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn rotl8(v: __m256i) -> __m256i {
+        let mask = _mm_setr_epi8(3, 0, 1, 2, 7, 4, 5, 6, 11, 8, 9, 10, 15, 12, 13, 14);
+        _mm256_shuffle_epi8(v, _mm256_broadcastsi128_si256(mask))
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn rotl16(v: __m256i) -> __m256i {
+        let mask = _mm_setr_epi8(2, 3, 0, 1, 6, 7, 4, 5, 10, 11, 8, 9, 14, 15, 12, 13);
+        _mm256_shuffle_epi8(v, _mm256_broadcastsi128_si256(mask))
+    }
+
+    // Transpose 4 vectors of 4 words in both 128 bit halves
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn transpose(a: __m256i, b: __m256i, c: __m256i, d: __m256i) -> [__m256i; 4] {
+        let ab_low = _mm256_unpacklo_epi32(a, b);
+        let cd_low = _mm256_unpacklo_epi32(c, d);
+        let ab_high = _mm256_unpackhi_epi32(a, b);
+        let cd_high = _mm256_unpackhi_epi32(c, d);
+        [
+            _mm256_unpacklo_epi64(ab_low, cd_low),
+            _mm256_unpackhi_epi64(ab_low, cd_low),
+            _mm256_unpacklo_epi64(ab_high, cd_high),
+            _mm256_unpackhi_epi64(ab_high, cd_high)
+        ]
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn store(x: &[__m256i; 16], out: &mut [u8]) {
+        for group in 0..4 {
+            let rows = transpose(x[4 * group], x[4 * group + 1], x[4 * group + 2], x[4 * group + 3]);
+            // The low halves hold blocks 0 to 3, the high halves blocks 4 to 7
+            for (block, row) in rows.iter().enumerate() {
+                let p = out[block * 64 + group * 16..].as_mut_ptr() as *mut __m128i;
+                _mm_storeu_si128(p, _mm256_castsi256_si128(*row));
+                let p = out[(block + 4) * 64 + group * 16..].as_mut_ptr() as *mut __m128i;
+                _mm_storeu_si128(p, _mm256_extracti128_si256::<1>(*row));
+            }
+        }
+    }
+
+    define_chacha_blocks!("avx2");
+    define_salsa_blocks!("avx2");
+}
@@ -0,0 +1,436 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+An implementation of the Streebog hash functions, as specified in GOST R 34.11-2012 and
+[RFC 6986](https://tools.ietf.org/html/rfc6986).
+
+Streebog-512 and Streebog-256 only differ in their initial value and in the length of the output.
+Both implement the `Digest` trait, so they can be used with `Hmac`. Note that the standard writes
+messages and digests as little endian numbers, so the test vectors in RFC 6986 are the byte reversal
+of the input and output of this implementation. An example of use is:
+
+```rust
+use self::crypto::digest::Digest;
+use self::crypto::streebog::Streebog256;
+
+let mut hasher = Streebog256::new();
+hasher.input_str("abc");
+assert_eq!(hasher.result_str(),
+           "4e2919cf137ed41ec4fb6270c61826cc4fffb660341e0af3688cd0626d23b481");
+```
+ */
+
+use crate::cryptoutil::{read_u64v_le, write_u64_le, FixedBuffer, FixedBuffer64};
+use crate::digest::Digest;
+use crate::kuznyechik::PI;
+
+// The rows of the matrix of the linear transformation l, A_0 is used for the most significant bit
+const A: [u64; 64] = [
+    0x8e20faa72ba0b470, 0x47107ddd9b505a38, 0xad08b0e0c3282d1c, 0xd8045870ef14980e,
+    0x6c022c38f90a4c07, 0x3601161cf205268d, 0x1b8e0b0e798c13c8, 0x83478b07b2468764,
+    0xa011d380818e8f40, 0x5086e740ce47c920, 0x2843fd2067adea10, 0x14aff010bdd87508,
+    0x0ad97808d06cb404, 0x05e23c0468365a02, 0x8c711e02341b2d01, 0x46b60f011a83988e,
+    0x90dab52a387ae76f, 0x486dd4151c3dfdb9, 0x24b86a840e90f0d2, 0x125c354207487869,
+    0x092e94218d243cba, 0x8a174a9ec8121e5d, 0x4585254f64090fa0, 0xaccc9ca9328a8950,
+    0x9d4df05d5f661451, 0xc0a878a0a1330aa6, 0x60543c50de970553, 0x302a1e286fc58ca7,
+    0x18150f14b9ec46dd, 0x0c84890ad27623e0, 0x0642ca05693b9f70, 0x0321658cba93c138,
+    0x86275df09ce8aaa8, 0x439da0784e745554, 0xafc0503c273aa42a, 0xd960281e9d1d5215,
+    0xe230140fc0802984, 0x71180a8960409a42, 0xb60c05ca30204d21, 0x5b068c651810a89e,
+    0x456c34887a3805b9, 0xac361a443d1c8cd2, 0x561b0d22900e4669, 0x2b838811480723ba,
+    0x9bcf4486248d9f5d, 0xc3e9224312c8c1a0, 0xeffa11af0964ee50, 0xf97d86d98a327728,
+    0xe4fa2054a80b329c, 0x727d102a548b194e, 0x39b008152acb8227, 0x9258048415eb419d,
+    0x492c024284fbaec0, 0xaa16012142f35760, 0x550b8e9e21f7a530, 0xa48b474f9ef5dc18,
+    0x70a6a56e2440598e, 0x3853dc371220a247, 0x1ca76e95091051ad, 0x0edd37c48a08a6d8,
+    0x07e095624504536c, 0x8d70c431ac02a736, 0xc83862965601dd1b, 0x641c314b2b8ee083];
+
+// The iteration constants C_1 to C_12, as little endian 64 bit words
+static C: [[u64; 8]; 12] = [
+    [0xdd806559f2a64507, 0x05767436cc744d23, 0xa2422a08a460d315, 0x4b7ce09192676901,
+     0x714eb88d7585c4fc, 0x2f6a76432e45d016, 0xebcb2f81c0657c1f, 0xb1085bda1ecadae9],
+    [0xe679047021b19bb7, 0x55dda21bd7cbcd56, 0x5cb561c2db0aa7ca, 0x9ab5176b12d69958,
+     0x61d55e0f16b50131, 0xf3feea720a232b98, 0x4fe39d460f70b5d7, 0x6fa3b58aa99d2f1a],
+    [0x991e96f50aba0ab2, 0xc2b6f443867adb31, 0xc1c93a376062db09, 0xd3e20fe490359eb1,
+     0xf2ea7514b1297b7b, 0x06f15e5f529c1f8b, 0x0a39fc286a3d8435, 0xf574dcac2bce2fc7],
+    [0x220cbebc84e3d12e, 0x3453eaa193e837f1, 0xd8b71333935203be, 0xa9d72c82ed03d675,
+     0x9d721cad685e353f, 0x488e857e335c3c7d, 0xf948e1a05d71e4dd, 0xef1fdfb3e81566d2],
+    [0x601758fd7c6cfe57, 0x7a56a27ea9ea63f5, 0xdfff00b723271a16, 0xbfcd1747253af5a3,
+     0x359e35d7800fffbd, 0x7f151c1f1686104a, 0x9a3f410c6ca92363, 0x4bea6bacad474799],
+    [0xfa68407a46647d6e, 0xbf71c57236904f35, 0x0af21f66c2bec6b6, 0xcffaa6b71c9ab7b4,
+     0x187f9ab49af08ec6, 0x2d66c4f95142a46c, 0x6fa4c33b7a3039c0, 0xae4faeae1d3ad3d9],
+    [0x8886564d3a14d493, 0x3517454ca23c4af3, 0x06476983284a0504, 0x0992abc52d822c37,
+     0xd3473e33197a93c9, 0x399ec6c7e6bf87c9, 0x51ac86febf240954, 0xf4c70e16eeaac5ec],
+    [0xa47f0dd4bf02e71e, 0x36acc2355951a8d9, 0x69d18d2bd1a5c42f, 0xf4892bcb929b0690,
+     0x89b4443b4ddbc49a, 0x4eb7f8719c36de1e, 0x03e7aa020c6e4141, 0x9b1f5b424d93c9a7],
+    [0x7261445183235adb, 0x0e38dc92cb1f2a60, 0x7b2b8a9aa6079c54, 0x800a440bdbb2ceb1,
+     0x3cd955b7e00d0984, 0x3a7d3a1b25894224, 0x944c9ad8ec165fde, 0x378f5a541631229b],
+    [0x74b4c7fb98459ced, 0x3698fad1153bb6c3, 0x7a1e6c303b7652f4, 0x9fe76702af69334b,
+     0x1fffe18a1b336103, 0x8941e71cff8a78db, 0x382ae548b2e4f3f3, 0xabbedea680056f52],
+    [0x6bcaa4cd81f32d1b, 0xdea2594ac06fd85d, 0xefbacd1d7d476e98, 0x8a1d71efea48b9ca,
+     0x2001802114846679, 0xd8fa6bbbebab0761, 0x3002c6cd635afe94, 0x7bcd9ed0efc889fb],
+    [0x48bc924af11bd720, 0xfaf417d5d9b21b99, 0xe71da4aa88e12852, 0x5d80ef9d1891cc86,
+     0xf82012d430219f9b, 0xcda43c32bcdf1d77, 0xd21380b00449b17a, 0x378ee767f11631ba]];
+
+// The combined S, P and L transformations, for each byte of the 64 bit words
+static LPS_TABLE: [[u64; 256]; 8] = lps_table();
+
+const fn lps_table() -> [[u64; 256]; 8] {
+    let mut table = [[0u64; 256]; 8];
+    let mut i = 0;
+    while i < 8 {
+        let mut b = 0;
+        while b < 256 {
+            let s = PI[b];
+            let mut bit = 0;
+            while bit < 8 {
+                if (s >> bit) & 1 != 0 {
+                    table[i][b] ^= A[63 - 8 * i - bit];
+                }
+                bit += 1;
+            }
+            b += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
+fn lps(x: &[u64; 8]) -> [u64; 8] {
+    let mut out = [0u64; 8];
+    for (j, o) in out.iter_mut().enumerate() {
+        // The transposition P moves byte j of word i to byte i of word j
+        for (i, &w) in x.iter().enumerate() {
+            *o ^= LPS_TABLE[i][((w >> (8 * j)) & 0xff) as usize];
+        }
+    }
+    out
+}
+
+fn xor(a: &[u64; 8], b: &[u64; 8]) -> [u64; 8] {
+    let mut out = *a;
+    for (o, &b) in out.iter_mut().zip(b.iter()) {
+        *o ^= b;
+    }
+    out
+}
+
+// Addition modulo 2^512
+fn add(a: &mut [u64; 8], b: &[u64; 8]) {
+    let mut carry = false;
+    for (a, &b) in a.iter_mut().zip(b.iter()) {
+        let (sum, c1) = a.overflowing_add(b);
+        let (sum, c2) = sum.overflowing_add(carry as u64);
+        *a = sum;
+        carry = c1 || c2;
+    }
+}
+
+// The compression function g_N
+fn compress(h: &mut [u64; 8], n: &[u64; 8], m: &[u64; 8]) {
+    let mut k = lps(&xor(h, n));
+    let mut state = *m;
+    for c in C.iter() {
+        state = lps(&xor(&state, &k));
+        k = lps(&xor(&k, c));
+    }
+    *h = xor(&xor(h, &xor(&state, &k)), m);
+}
+
+#[derive(Clone, Copy)]
+struct EngineState {
+    h: [u64; 8],
+    n: [u64; 8],
+    sigma: [u64; 8]
+}
+
+impl EngineState {
+    fn process_block(&mut self, data: &[u8], bits: u64) {
+        let mut m = [0u64; 8];
+        read_u64v_le(&mut m, data);
+        compress(&mut self.h, &self.n, &m);
+        add(&mut self.n, &[bits, 0, 0, 0, 0, 0, 0, 0]);
+        add(&mut self.sigma, &m);
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Engine {
+    iv: u64,
+    buffer: FixedBuffer64,
+    state: EngineState,
+    finished: bool
+}
+
+impl Engine {
+    fn new(iv: u64) -> Engine {
+        Engine {
+            iv,
+            buffer: FixedBuffer64::new(),
+            state: EngineState {
+                h: [iv; 8],
+                n: [0; 8],
+                sigma: [0; 8]
+            },
+            finished: false
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Engine::new(self.iv);
+    }
+
+    fn input(&mut self, input: &[u8]) {
+        assert!(!self.finished);
+        let self_state = &mut self.state;
+        self.buffer.input(input, |input: &[u8]| { self_state.process_block(input, 512) });
+    }
+
+    fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        let bits = self.buffer.position() as u64 * 8;
+        self.buffer.next(1)[0] = 1;
+        self.buffer.zero_until(64);
+        self.state.process_block(self.buffer.full_buffer(), bits);
+        let state = &mut self.state;
+        compress(&mut state.h, &[0; 8], &state.n);
+        compress(&mut state.h, &[0; 8], &state.sigma);
+
+        self.finished = true;
+    }
+}
+
+/// The Streebog hash function with a 512 bit output
+#[derive(Clone, Copy)]
+pub struct Streebog512 {
+    engine: Engine
+}
+
+impl Streebog512 {
+    /**
+     * Construct a new instance of Streebog-512.
+     */
+    pub fn new() -> Streebog512 {
+        Streebog512 {
+            engine: Engine::new(0)
+        }
+    }
+}
+
+impl Default for Streebog512 {
+    fn default() -> Streebog512 {
+        Streebog512::new()
+    }
+}
+
+impl Digest for Streebog512 {
+    fn input(&mut self, d: &[u8]) {
+        self.engine.input(d);
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        self.engine.finish();
+        for (chunk, &h) in out[..64].chunks_mut(8).zip(self.engine.state.h.iter()) {
+            write_u64_le(chunk, h);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.engine.reset();
+    }
+
+    fn output_bits(&self) -> usize { 512 }
+
+    fn block_size(&self) -> usize { 64 }
+}
+
+/// The Streebog hash function with a 256 bit output
+#[derive(Clone, Copy)]
+pub struct Streebog256 {
+    engine: Engine
+}
+
+impl Streebog256 {
+    /**
+     * Construct a new instance of Streebog-256.
+     */
+    pub fn new() -> Streebog256 {
+        Streebog256 {
+            engine: Engine::new(0x0101010101010101)
+        }
+    }
+}
+
+impl Default for Streebog256 {
+    fn default() -> Streebog256 {
+        Streebog256::new()
+    }
+}
+
+impl Digest for Streebog256 {
+    fn input(&mut self, d: &[u8]) {
+        self.engine.input(d);
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        self.engine.finish();
+        // The most significant half of the 512 bit result
+        for (chunk, &h) in out[..32].chunks_mut(8).zip(self.engine.state.h[4..].iter()) {
+            write_u64_le(chunk, h);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.engine.reset();
+    }
+
+    fn output_bits(&self) -> usize { 256 }
+
+    fn block_size(&self) -> usize { 64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cryptoutil::test::test_digest_1million_random;
+    use crate::digest::Digest;
+    use crate::hmac::Hmac;
+    use crate::mac::Mac;
+    use crate::serialize::hex::{FromHex, ToHex};
+    use crate::streebog::{Streebog256, Streebog512};
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    // The standard writes messages and digests as numbers, least significant byte last
+    fn reversed(raw_hex: &str) -> Vec<u8> {
+        let mut bytes = hex_to_bytes(raw_hex);
+        bytes.reverse();
+        bytes
+    }
+
+    struct Test {
+        input: &'static str,
+        output_256: &'static str,
+        output_512: &'static str
+    }
+
+    fn check<D: Digest>(digest: &mut D, input: &[u8], expected: &[u8]) {
+        let mut out = vec![0u8; digest.output_bytes()];
+        digest.input(input);
+        digest.result(&mut out);
+        assert_eq!(out.to_hex(), expected.to_hex());
+        digest.reset();
+
+        // Test that it works when accepting the message in pieces
+        for chunk in input.chunks(7) {
+            digest.input(chunk);
+        }
+        digest.result(&mut out);
+        assert_eq!(out.to_hex(), expected.to_hex());
+        digest.reset();
+    }
+
+    #[test]
+    fn test() {
+        // Examples 1 and 2 from RFC 6986, Section 10
+        let tests = [
+            Test {
+                input:
+                    "3231303938373635343332313039383736353433323130393837363534333231\
+                     30393837363534333231303938373635343332313039383736353433323130",
+                output_256: "00557be5e584fd52a449b16b0251d05d27f94ab76cbaa6da890b59d8ef1e159d",
+                output_512:
+                    "486f64c1917879417fef082b3381a4e211c324f074654c38823a7b76f830ad00\
+                     fa1fbae42b1285c0352f227524bc9ab16254288dd6863dccd5b9f54a1ad0541b"
+            },
+            Test {
+                input:
+                    "fbe2e5f0eee3c820fbeafaebef20fffbf0e1e0f0f520e0ed20e8ece0ebe5f0f2\
+                     f120fff0eeec20f120faf2fee5e2202ce8f6f3ede220e8e6eee1e8f0f2d1202c\
+                     e8f0f2e5e220e5d1",
+                output_256: "508f7e553c06501d749a66fc28c6cac0b005746d97537fa85d9e40904efed29d",
+                output_512:
+                    "28fbc9bada033b1460642bdcddb90c3fb3e56c497ccd0f62b8a2ad4935e85f03\
+                     7613966de4ee00531ae60f3b5a47f8dae06915d5f2f194996fcabf2622e6881e"
+            },
+        ];
+
+        for t in tests.iter() {
+            let input = reversed(t.input);
+            check(&mut Streebog256::new(), &input, &reversed(t.output_256));
+            check(&mut Streebog512::new(), &input, &reversed(t.output_512));
+        }
+    }
+
+    #[test]
+    fn test_1million_random_streebog256() {
+        let mut sh = Streebog256::new();
+        test_digest_1million_random(
+            &mut sh,
+            64,
+            "841af1a0b2f92a800fb1b7e4aabc8e48763153c448a0fc57c90ba830e130f152");
+    }
+
+    #[test]
+    fn test_1million_random_streebog512() {
+        let mut sh = Streebog512::new();
+        test_digest_1million_random(
+            &mut sh,
+            64,
+            "d396a40b126b1f324465bfa7aa159859ab33fac02dcdd4515ad231206396a266\
+             d0102367e4c544ef47d2294064e1a25342d0cd25ae3d904b45abb1425ae41095");
+    }
+
+    // Examples from RFC 7836, Section A.1
+    #[test]
+    fn test_hmac_streebog() {
+        let key: Vec<u8> = (0..32).collect();
+        let data = hex_to_bytes("0126bdb87800af214341456563780100");
+
+        let mut hmac = Hmac::new(Streebog256::new(), &key);
+        hmac.input(&data);
+        let expected = hex_to_bytes(
+            "a1aa5f7de402d7b3d323f2991c8d4534013137010a83754fd0af6d7cd4922ed9");
+        assert_eq!(hmac.result().code(), &expected[..]);
+
+        let mut hmac = Hmac::new(Streebog512::new(), &key);
+        hmac.input(&data);
+        let expected = hex_to_bytes(
+            "a59bab22ecae19c65fbde6e5f4e9f5d8549d31f037f9df9b905500e171923a77\
+             3d5f1530f2ed7e964cb2eedc29e9ad2f3afe93b2814f79f5000ffc0366c251e6");
+        assert_eq!(hmac.result().code(), &expected[..]);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+    use crate::digest::Digest;
+    use crate::streebog::{Streebog256, Streebog512};
+
+    #[bench]
+    pub fn streebog256_1k(bh: & mut Bencher) {
+        let mut sh = Streebog256::new();
+        let bytes = [1u8; 1024];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+
+    #[bench]
+    pub fn streebog512_1k(bh: & mut Bencher) {
+        let mut sh = Streebog512::new();
+        let bytes = [1u8; 1024];
+        bh.iter( || {
+            sh.input(&bytes);
+        });
+        bh.bytes = bytes.len() as u64;
+    }
+}
@@ -0,0 +1,259 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the STROBE-128 protocol framework [1], version 1.0.2, on the Keccak
+ * duplex object of the `keccak` module.
+ *
+ * Only the operations that don't involve the transport are implemented, which is the subset used
+ * by Merlin transcripts [2]: a transcript built on `Strobe128` produces the same challenges as
+ * the Merlin library.
+ *
+ * # Usage
+ *
+ * ```rust
+ * use self::crypto::strobe::Strobe128;
+ *
+ * let mut strobe = Strobe128::new(b"My Protocol");
+ * strobe.meta_ad(b"message", false);
+ * strobe.ad(b"hello world", false);
+ *
+ * let mut challenge = [0u8; 32];
+ * strobe.meta_ad(b"challenge", false);
+ * strobe.prf(&mut challenge, false);
+ * ```
+ *
+ * # References
+ * [1] - M. Hamburg. The STROBE protocol framework. <https://strobe.sourceforge.io/specs/>
+ * [2] - H. de Valence, I. Lovecruft, O. Andreeva. Merlin transcripts.
+ *       <https://merlin.cool/>
+ */
+
+use std::cmp;
+use std::ops::Range;
+
+use crate::cryptoutil::read_u64v_le;
+use crate::keccak::{keccak_f1600, Duplex, NROUNDS};
+
+// The number of bytes per block: the rate of the sponge, less the two bytes of padding
+const STROBE_R: usize = 166;
+
+// Domain separation bits of cSHAKE, followed by the first padding bit
+const STROBE_DS: u8 = 0x04;
+
+const FLAG_I: u8 = 1;
+const FLAG_A: u8 = 1 << 1;
+const FLAG_C: u8 = 1 << 2;
+const FLAG_T: u8 = 1 << 3;
+const FLAG_M: u8 = 1 << 4;
+const FLAG_K: u8 = 1 << 5;
+
+/// A STROBE-128 protocol object
+#[derive(Copy, Clone)]
+pub struct Strobe128 {
+    duplex: Duplex,
+    // The position in the block where the current operation began
+    pos_begin: u8,
+    cur_flags: u8
+}
+
+impl Strobe128 {
+    /// Create a STROBE-128 object, and absorb `protocol_label` as metadata.
+    pub fn new(protocol_label: &[u8]) -> Strobe128 {
+        let mut initial_state = [0u8; 200];
+        initial_state[0..6].copy_from_slice(&[1, STROBE_R as u8 + 2, 1, 0, 1, 96]);
+        initial_state[6..18].copy_from_slice(b"STROBEv1.0.2");
+        let mut state = [0u64; 25];
+        read_u64v_le(&mut state, &initial_state);
+        keccak_f1600(&mut state);
+
+        let mut strobe = Strobe128 {
+            duplex: Duplex::from_state(state, 200 - STROBE_R - 2, STROBE_DS, NROUNDS),
+            pos_begin: 0,
+            cur_flags: 0
+        };
+        strobe.meta_ad(protocol_label, false);
+
+        strobe
+    }
+
+    /// Absorb `data` as metadata. If `more` is set, `data` continues the previous operation.
+    pub fn meta_ad(&mut self, data: &[u8], more: bool) {
+        self.begin_op(FLAG_M | FLAG_A, more);
+        self.for_each_block(data.len(), |duplex, range| duplex.absorb(&data[range]));
+    }
+
+    /// Absorb `data` as associated data. If `more` is set, `data` continues the previous
+    /// operation.
+    pub fn ad(&mut self, data: &[u8], more: bool) {
+        self.begin_op(FLAG_A, more);
+        self.for_each_block(data.len(), |duplex, range| duplex.absorb(&data[range]));
+    }
+
+    /// Fill `data` with pseudorandom output. If `more` is set, `data` continues the previous
+    /// operation.
+    pub fn prf(&mut self, data: &mut [u8], more: bool) {
+        self.begin_op(FLAG_I | FLAG_A | FLAG_C, more);
+        // Squeezes the state while overwriting it with zeros
+        for byte in data.iter_mut() {
+            *byte = 0;
+        }
+        self.for_each_block(data.len(), |duplex, range| duplex.decrypt(&mut data[range]));
+    }
+
+    /// Key the state with `data`. If `more` is set, `data` continues the previous operation.
+    pub fn key(&mut self, data: &[u8], more: bool) {
+        self.begin_op(FLAG_A | FLAG_C, more);
+        self.for_each_block(data.len(), |duplex, range| duplex.overwrite(&data[range]));
+    }
+
+    /// Forget `len` bytes of the state, so that it can't be rolled back. If `more` is set, this
+    /// continues the previous operation.
+    pub fn ratchet(&mut self, len: usize, more: bool) {
+        self.begin_op(FLAG_C, more);
+        self.for_each_block(len, |duplex, range| duplex.ratchet(range.len()));
+    }
+
+    fn begin_op(&mut self, flags: u8, more: bool) {
+        if more {
+            assert!(self.cur_flags == flags, "continued STROBE operation with different flags");
+            return;
+        }
+        assert!(flags & FLAG_T == 0, "STROBE operations using the transport are not supported");
+
+        let old_begin = self.pos_begin;
+        self.pos_begin = self.duplex.position() as u8 + 1;
+        self.cur_flags = flags;
+        self.for_each_block(2, |duplex, range| duplex.absorb(&[old_begin, flags][range]));
+
+        // Operations using the cipher start on a fresh block
+        if flags & (FLAG_C | FLAG_K) != 0 && self.duplex.position() != 0 {
+            self.run_f();
+        }
+    }
+
+    // Splits an operation on `len` bytes at the ends of the blocks
+    fn for_each_block<F: FnMut(&mut Duplex, Range<usize>)>(&mut self, len: usize, mut f: F) {
+        let mut start = 0;
+        while start < len {
+            let end = cmp::min(start + STROBE_R - self.duplex.position(), len);
+            f(&mut self.duplex, start..end);
+            start = end;
+
+            if self.duplex.position() == STROBE_R {
+                self.run_f();
+            }
+        }
+    }
+
+    fn run_f(&mut self) {
+        // STROBE pads the block with pos_begin followed by the cSHAKE padding. A full block and
+        // pos_begin fill the block of the duplex, which then applies the permutation by itself.
+        self.duplex.absorb(&[self.pos_begin]);
+        if self.duplex.position() != 0 {
+            self.duplex.permute();
+        }
+        self.pos_begin = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::serialize::hex::FromHex;
+    use crate::strobe::Strobe128;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    // The conformance test of the Merlin library
+    #[test]
+    fn test_conformance() {
+        let mut s1 = Strobe128::new(b"Conformance Test Protocol");
+
+        let msg = [99u8; 1024];
+
+        s1.meta_ad(b"ms", false);
+        s1.meta_ad(b"g", true);
+        s1.ad(&msg, false);
+
+        let mut prf1 = [0u8; 32];
+        s1.meta_ad(b"prf", false);
+        s1.prf(&mut prf1, false);
+
+        assert_eq!(prf1[..], hex_to_bytes(
+            "b48e645ca17c667fd5206ba57a6a228d72d8e1903814d3f17f622996d7cfefb0")[..]);
+
+        s1.meta_ad(b"key", false);
+        s1.key(&prf1, false);
+
+        let mut prf2 = [0u8; 32];
+        s1.meta_ad(b"prf", false);
+        s1.prf(&mut prf2, false);
+
+        assert_eq!(prf2[..], hex_to_bytes(
+            "07e45cce8078cee259e3e375bb85d75610e2d1e1201c5f645045a194edd49ff8")[..]);
+    }
+
+    // A Merlin transcript, as defined by the Merlin library on top of STROBE-128
+    struct Transcript {
+        strobe: Strobe128
+    }
+
+    impl Transcript {
+        fn new(label: &[u8]) -> Transcript {
+            let mut transcript = Transcript { strobe: Strobe128::new(b"Merlin v1.0") };
+            transcript.append_message(b"dom-sep", label);
+            transcript
+        }
+
+        fn append_message(&mut self, label: &[u8], message: &[u8]) {
+            self.strobe.meta_ad(label, false);
+            self.strobe.meta_ad(&(message.len() as u32).to_le_bytes(), true);
+            self.strobe.ad(message, false);
+        }
+
+        fn challenge_bytes(&mut self, label: &[u8], dest: &mut [u8]) {
+            self.strobe.meta_ad(label, false);
+            self.strobe.meta_ad(&(dest.len() as u32).to_le_bytes(), true);
+            self.strobe.prf(dest, false);
+        }
+    }
+
+    #[test]
+    fn test_merlin_transcript() {
+        let mut transcript = Transcript::new(b"test protocol");
+        transcript.append_message(b"some label", b"some data");
+
+        let mut challenge = [0u8; 32];
+        transcript.challenge_bytes(b"challenge", &mut challenge);
+
+        assert_eq!(challenge[..], hex_to_bytes(
+            "d5a21972d0d5fe320c0d263fac7fffb8145aa640af6e9bca177c03c7efcf0615")[..]);
+    }
+
+    #[test]
+    fn test_ratchet() {
+        let mut s1 = Strobe128::new(b"Ratchet Test");
+        s1.key(&[0x42; 32], false);
+        let mut s2 = s1;
+        s2.ratchet(32, false);
+
+        let mut prf1 = [0u8; 32];
+        let mut prf2 = [0u8; 32];
+        s1.prf(&mut prf1, false);
+        s2.prf(&mut prf2, false);
+        assert!(prf1 != prf2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_more_with_different_flags() {
+        let mut s1 = Strobe128::new(b"Flags Test");
+        s1.ad(b"data", false);
+        s1.meta_ad(b"more", true);
+    }
+}
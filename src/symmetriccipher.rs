@@ -30,10 +30,26 @@ pub trait BlockDecryptorX8 {
     fn decrypt_block_x8(&self, input: &[u8], output: &mut [u8]);
 }
 
+/// A block cipher that takes a tweak in addition to the key. The tweak can be different for every
+/// block, without the cost of a new key schedule.
+pub trait TweakableBlockEncryptor {
+    fn block_size(&self) -> usize;
+    fn tweak_size(&self) -> usize;
+    fn encrypt_block_with_tweak(&self, tweak: &[u8], input: &[u8], output: &mut [u8]);
+}
+
+pub trait TweakableBlockDecryptor {
+    fn block_size(&self) -> usize;
+    fn tweak_size(&self) -> usize;
+    fn decrypt_block_with_tweak(&self, tweak: &[u8], input: &[u8], output: &mut [u8]);
+}
+
 #[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
 pub enum SymmetricCipherError {
     InvalidLength,
-    InvalidPadding
+    InvalidPadding,
+    CounterOverflow
 }
 
 impl Display for SymmetricCipherError {
@@ -50,7 +66,8 @@ impl Error for SymmetricCipherError {
     fn description(&self) -> &str {
         match *self {
             SymmetricCipherError::InvalidLength => "padding output is not a multiple of blocksize",
-            SymmetricCipherError::InvalidPadding => "stripping the padding wasn't succesfull"
+            SymmetricCipherError::InvalidPadding => "stripping the padding wasn't succesfull",
+            SymmetricCipherError::CounterOverflow => "the counter of the keystream is exhausted"
         }
     }
 }
@@ -67,6 +84,25 @@ pub trait Decryptor {
 
 pub trait SynchronousStreamCipher {
     fn process(&mut self, input: &[u8], output: &mut [u8]);
+    /// Like `process`, but fails with `CounterOverflow` if the keystream is too short for the
+    /// input, in which case nothing is processed. The default implementation just calls `process`
+    /// and never fails: ciphers with a keystream that can run out, such as the CTR modes, ChaCha
+    /// and Salsa20, override it.
+    fn try_process(&mut self, input: &[u8], output: &mut [u8]) -> Result<(), SymmetricCipherError> {
+        self.process(input, output);
+        Ok(())
+    }
+}
+
+/// A stream cipher that can jump to any byte offset of its keystream, for random access to the
+/// encrypted data.
+pub trait SeekableStreamCipher: SynchronousStreamCipher {
+    /// Continue processing at keystream byte `byte_offset`. Fails with `CounterOverflow` if the
+    /// offset lies beyond the end of the keystream, in which case the cipher is left unchanged.
+    fn seek(&mut self, byte_offset: u64) -> Result<(), SymmetricCipherError>;
+
+    /// The keystream byte offset at which the next byte will be processed.
+    fn position(&self) -> u64;
 }
 
 // TODO - Its a bit unclear to me why this is necessary
@@ -75,6 +111,10 @@ impl SynchronousStreamCipher for Box<dyn SynchronousStreamCipher + 'static> {
         let me = &mut **self;
         me.process(input, output);
     }
+    fn try_process(&mut self, input: &[u8], output: &mut [u8]) -> Result<(), SymmetricCipherError> {
+        let me = &mut **self;
+        me.try_process(input, output)
+    }
 }
 
 impl Encryptor for Box<dyn SynchronousStreamCipher + 'static> {
@@ -98,8 +138,10 @@ mod test {
     fn test_symcipher_error() {
         let error1 = SymmetricCipherError::InvalidLength;
         let error2 = SymmetricCipherError::InvalidPadding;
-        println!("Testing {} and {}.", error1, error2);
+        let error3 = SymmetricCipherError::CounterOverflow;
+        println!("Testing {}, {} and {}.", error1, error2, error3);
         println!("Some error occured: {}", error1.description());
         println!("Another error occured: {}", error2.description());
+        println!("Yet another error occured: {}", error3.description());
     }
 }
@@ -7,7 +7,8 @@
 use std::ops::BitXor;
 
 use crate::cryptoutil::{read_u64v_le, write_u64v_le};
-use crate::symmetriccipher::{BlockDecryptor, BlockEncryptor};
+use crate::symmetriccipher::{BlockDecryptor, BlockEncryptor, TweakableBlockDecryptor,
+                              TweakableBlockEncryptor};
 
 // Magic constant for key schedule
 const C240: u64 = 0x1BD11BDAA9FC1A22;
@@ -37,12 +38,12 @@ const P_1024: [usize; 16] = [ 0, 15, 2, 11,  6, 13,  4, 9,
 macro_rules! define_threefish_struct(
     (
         $name:ident,
-        $rounds:expr,
         $key_size:expr
     ) => (
         #[derive(Clone, Copy)]
         pub struct $name {
-            sk: [[u64; $key_size / 8]; $rounds / 4 + 1]
+            k: [u64; $key_size / 8 + 1],
+            t: [u64; 3]
         }
     )
 );
@@ -51,14 +52,14 @@ macro_rules! define_threefish_impl(
     (
         $name:ident,
         $rounds:expr,
-        $key_size:expr
+        $key_size:expr,
+        $rot_table:expr,
+        $perm_table:expr
     ) => (
         impl $name {
             pub fn new(key: &[u8], tweak: &[u8]) -> $name {
                 assert!(key.len() == $key_size, "{:?} key length should be {}",
                         stringify!($name), $key_size);
-                assert!(tweak.len() == 16, "{:?} tweak length should be 16",
-                        stringify!($name));
 
                 const N_W: usize = $key_size / 8;
 
@@ -66,41 +67,35 @@ macro_rules! define_threefish_impl(
                 read_u64v_le(&mut k[..N_W], key);
                 k[N_W] = k[..N_W].iter().fold(C240, BitXor::bitxor);
 
+                $name { k, t: $name::tweak_words(tweak) }
+            }
+
+            fn tweak_words(tweak: &[u8]) -> [u64; 3] {
+                assert!(tweak.len() == 16, "{:?} tweak length should be 16",
+                        stringify!($name));
+
                 let mut t = [0u64; 3];
                 read_u64v_le(&mut t[..2], tweak);
                 t[2] = t[0] ^ t[1];
+                t
+            }
 
-                let mut sk = [[0u64; N_W]; $rounds / 4 + 1];
-                for s in 0..($rounds / 4 + 1) {
-                    for i in 0..N_W {
-                        sk[s][i] = k[(s + i) % (N_W + 1)];
-                        if i == N_W - 3 {
-                            sk[s][i] = sk[s][i].wrapping_add(t[s % 3]);
-                        } else if i == N_W - 2 {
-                            sk[s][i] = sk[s][i].wrapping_add(t[(s + 1) % 3]);
-                        } else if i == N_W - 1 {
-                            sk[s][i] = sk[s][i].wrapping_add(s as u64);
-                        }
-                    }
-                }
+            // The subkey of injection s. Only the key words are stored, the tweak words are
+            // added here, so that the tweak can change from one block to the next for free.
+            fn subkey(&self, t: &[u64; 3], s: usize) -> [u64; $key_size / 8] {
+                const N_W: usize = $key_size / 8;
 
-                $name { sk: sk }
+                let mut sk = [0u64; N_W];
+                for i in 0..N_W {
+                    sk[i] = self.k[(s + i) % (N_W + 1)];
+                }
+                sk[N_W - 3] = sk[N_W - 3].wrapping_add(t[s % 3]);
+                sk[N_W - 2] = sk[N_W - 2].wrapping_add(t[(s + 1) % 3]);
+                sk[N_W - 1] = sk[N_W - 1].wrapping_add(s as u64);
+                sk
             }
-        }
-    )
-);
 
-macro_rules! define_threefish_enc(
-    (
-        $name:ident,
-        $rounds:expr,
-        $key_size:expr,
-        $rot_table:expr,
-        $perm_table:expr
-    ) => (
-        impl BlockEncryptor for $name {
-            fn block_size(&self) -> usize { $key_size }
-            fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+            fn encrypt(&self, t: &[u64; 3], input: &[u8], output: &mut [u8]) {
                 assert!(input.len() == $key_size,
                         "{:?} input length should be {} bytes",
                         stringify!($name), $key_size);
@@ -114,18 +109,16 @@ macro_rules! define_threefish_enc(
                 read_u64v_le(&mut v, input);
 
                 for d in 0..$rounds {
+                    if d % 4 == 0 {
+                        let sk = self.subkey(t, d / 4);
+                        for i in 0..N_W {
+                            v[i] = v[i].wrapping_add(sk[i]);
+                        }
+                    }
                     let v_tmp = v.clone();
                     for j in 0..(N_W / 2) {
-                        let (v0, v1) = (v_tmp[2 * j], v_tmp[2 * j + 1]);
-                        let (e0, e1) =
-                            if d % 4 == 0 {
-                                (v0.wrapping_add(self.sk[d / 4][2 * j]),
-                                 v1.wrapping_add(self.sk[d / 4][2 * j + 1]))
-                            } else {
-                                (v0, v1)
-                            };
                         let r = $rot_table[d % 8][j];
-                        let (f0, f1) = mix(r, (e0, e1));
+                        let (f0, f1) = mix(r, (v_tmp[2 * j], v_tmp[2 * j + 1]));
                         let (pi0, pi1) =
                             ($perm_table[2 * j], $perm_table[2 * j + 1]);
                         v[pi0] = f0;
@@ -133,27 +126,15 @@ macro_rules! define_threefish_enc(
                     }
                 }
 
+                let sk = self.subkey(t, $rounds / 4);
                 for i in 0..N_W {
-                    v[i] = v[i].wrapping_add(self.sk[$rounds / 4][i]);
+                    v[i] = v[i].wrapping_add(sk[i]);
                 }
 
                 write_u64v_le(output, &v);
             }
-        }
-    )
-);
 
-macro_rules! define_threefish_dec(
-    (
-        $name:ident,
-        $rounds:expr,
-        $key_size:expr,
-        $rot_table:expr,
-        $perm_table:expr
-    ) => (
-        impl BlockDecryptor for $name {
-            fn block_size(&self) -> usize { $key_size }
-            fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+            fn decrypt(&self, t: &[u64; 3], input: &[u8], output: &mut [u8]) {
                 assert!(input.len() == $key_size,
                         "{:?} input length should be {} bytes",
                         stringify!($name), $key_size);
@@ -166,8 +147,9 @@ macro_rules! define_threefish_dec(
                 let mut v = [0u64; N_W];
                 read_u64v_le(&mut v, input);
 
+                let sk = self.subkey(t, $rounds / 4);
                 for i in 0..N_W {
-                    v[i] = v[i].wrapping_sub(self.sk[$rounds / 4][i]);
+                    v[i] = v[i].wrapping_sub(sk[i]);
                 }
 
                 for d in (0..$rounds).rev() {
@@ -175,18 +157,16 @@ macro_rules! define_threefish_dec(
                     for j in 0..(N_W / 2) {
                         let (inv_pi0, inv_pi1) =
                             ($perm_table[2 * j], $perm_table[2 * j + 1]);
-                        let (f0, f1) = (v_tmp[inv_pi0], v_tmp[inv_pi1]);
                         let r = $rot_table[d % 8][j];
-                        let (e0, e1) = inv_mix(r, (f0, f1));
-                        let (v0, v1) =
-                            if d % 4 == 0 {
-                                (e0.wrapping_sub(self.sk[d / 4][2 * j]),
-                                 e1.wrapping_sub(self.sk[d / 4][2 * j + 1]))
-                             } else {
-                                 (e0, e1)
-                             };
-                        v[2 * j] = v0;
-                        v[2 * j + 1] = v1;
+                        let (e0, e1) = inv_mix(r, (v_tmp[inv_pi0], v_tmp[inv_pi1]));
+                        v[2 * j] = e0;
+                        v[2 * j + 1] = e1;
+                    }
+                    if d % 4 == 0 {
+                        let sk = self.subkey(t, d / 4);
+                        for i in 0..N_W {
+                            v[i] = v[i].wrapping_sub(sk[i]);
+                        }
                     }
                 }
 
@@ -196,20 +176,64 @@ macro_rules! define_threefish_dec(
     )
 );
 
-define_threefish_struct!(Threefish256, 72, 32);
-define_threefish_impl!(Threefish256, 72, 32);
-define_threefish_enc!(Threefish256, 72, 32, R_256, P_256);
-define_threefish_dec!(Threefish256, 72, 32, R_256, P_256);
+macro_rules! define_threefish_enc(
+    (
+        $name:ident,
+        $key_size:expr
+    ) => (
+        impl BlockEncryptor for $name {
+            fn block_size(&self) -> usize { $key_size }
+            fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+                self.encrypt(&self.t, input, output);
+            }
+        }
+
+        impl TweakableBlockEncryptor for $name {
+            fn block_size(&self) -> usize { $key_size }
+            fn tweak_size(&self) -> usize { 16 }
+            fn encrypt_block_with_tweak(&self, tweak: &[u8], input: &[u8], output: &mut [u8]) {
+                self.encrypt(&$name::tweak_words(tweak), input, output);
+            }
+        }
+    )
+);
+
+macro_rules! define_threefish_dec(
+    (
+        $name:ident,
+        $key_size:expr
+    ) => (
+        impl BlockDecryptor for $name {
+            fn block_size(&self) -> usize { $key_size }
+            fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+                self.decrypt(&self.t, input, output);
+            }
+        }
+
+        impl TweakableBlockDecryptor for $name {
+            fn block_size(&self) -> usize { $key_size }
+            fn tweak_size(&self) -> usize { 16 }
+            fn decrypt_block_with_tweak(&self, tweak: &[u8], input: &[u8], output: &mut [u8]) {
+                self.decrypt(&$name::tweak_words(tweak), input, output);
+            }
+        }
+    )
+);
+
+define_threefish_struct!(Threefish256, 32);
+define_threefish_impl!(Threefish256, 72, 32, R_256, P_256);
+define_threefish_enc!(Threefish256, 32);
+define_threefish_dec!(Threefish256, 32);
 
-define_threefish_struct!(Threefish512, 72, 64);
-define_threefish_impl!(Threefish512, 72, 64);
-define_threefish_enc!(Threefish512, 72, 64, R_512, P_512);
-define_threefish_dec!(Threefish512, 72, 64, R_512, P_512);
+define_threefish_struct!(Threefish512, 64);
+define_threefish_impl!(Threefish512, 72, 64, R_512, P_512);
+define_threefish_enc!(Threefish512, 64);
+define_threefish_dec!(Threefish512, 64);
 
-define_threefish_struct!(Threefish1024, 80, 128);
-define_threefish_impl!(Threefish1024, 80, 128);
-define_threefish_enc!(Threefish1024, 80, 128, R_1024, P_1024);
-define_threefish_dec!(Threefish1024, 80, 128, R_1024, P_1024);
+define_threefish_struct!(Threefish1024, 128);
+define_threefish_impl!(Threefish1024, 80, 128, R_1024, P_1024);
+define_threefish_enc!(Threefish1024, 128);
+define_threefish_dec!(Threefish1024, 128);
 
 fn mix(r: u32, x: (u64, u64)) -> (u64, u64) {
     let y0 = x.0.wrapping_add(x.1);
@@ -226,7 +250,8 @@ fn inv_mix(r: u32, y: (u64, u64)) -> (u64, u64) {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::symmetriccipher::{BlockDecryptor, BlockEncryptor};
+    use crate::symmetriccipher::{BlockDecryptor, BlockEncryptor, TweakableBlockDecryptor,
+                                  TweakableBlockEncryptor};
 
     struct TestCase {
         tweak: [u8; 16],
@@ -440,6 +465,33 @@ mod test {
             test_decryptor(&threefish, &test_case);
         }
     }
+
+    fn test_tweakable<T: TweakableBlockEncryptor + TweakableBlockDecryptor>(
+            threefish: &T, test_case: &TestCase) {
+        assert_eq!(TweakableBlockEncryptor::block_size(threefish), test_case.key.len());
+        assert_eq!(TweakableBlockEncryptor::tweak_size(threefish), 16);
+
+        let mut output = vec![0u8; test_case.ciphertext.len()];
+        threefish.encrypt_block_with_tweak(&test_case.tweak, &test_case.plaintext, &mut output);
+        assert_eq!(output, test_case.ciphertext);
+        threefish.decrypt_block_with_tweak(&test_case.tweak, &test_case.ciphertext, &mut output);
+        assert_eq!(output, test_case.plaintext);
+    }
+
+    #[test]
+    fn test_threefish_with_tweak() {
+        // The tweak given to the constructor must not influence the per block tweak
+        let other_tweak = [0xa5; 16];
+        for test_case in tests256() {
+            test_tweakable(&Threefish256::new(&test_case.key, &other_tweak), &test_case);
+        }
+        for test_case in tests512() {
+            test_tweakable(&Threefish512::new(&test_case.key, &other_tweak), &test_case);
+        }
+        for test_case in tests1024() {
+            test_tweakable(&Threefish1024::new(&test_case.key, &other_tweak), &test_case);
+        }
+    }
 }
 
 // TODO: Benchmark tests
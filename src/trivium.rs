@@ -0,0 +1,231 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the Trivium stream cipher, as specified in [1]. It takes an 80 bit key
+ * and an 80 bit IV. Bits are numbered as in the eSTREAM reference code, so the test vectors
+ * published there apply directly.
+ *
+ * The three shift registers are kept in 128 bit integers, with the most recently shifted in bit
+ * at the highest position. None of the taps are within 64 bits of the input end of its register,
+ * so 64 steps of the cipher are computed at once.
+ *
+ * # References
+ * [1] - C. De Cannière, B. Preneel. Trivium Specifications. eSTREAM, ECRYPT Stream Cipher Project.
+ *       <https://www.ecrypt.eu.org/stream/p3ciphers/trivium/trivium_p3.pdf>
+ */
+
+use crate::buffer::{BufferResult, RefReadBuffer, RefWriteBuffer};
+use crate::symmetriccipher::{Encryptor, Decryptor, SynchronousStreamCipher, SymmetricCipherError};
+use crate::cryptoutil::{read_u64_le, symm_enc_or_dec, write_u64_le};
+
+
+#[derive(Copy, Clone)]
+pub struct Trivium {
+    // (s1, ..., s93), with s_i at bit 93 - i
+    a: u128,
+    // (s94, ..., s177), with s_i at bit 177 - i
+    b: u128,
+    // (s178, ..., s288), with s_i at bit 288 - i
+    c: u128,
+    output: [u8; 8],
+    output_index: usize
+}
+
+impl Trivium {
+    pub fn new(key: &[u8], iv: &[u8]) -> Trivium {
+        assert!(key.len() == 10);
+        assert!(iv.len() == 10);
+
+        let mut trivium = Trivium {
+            a: read_u80_le(key) << 13,
+            b: read_u80_le(iv) << 4,
+            c: 0x7,
+            output: [0; 8],
+            output_index: 8
+        };
+
+        // 4 full cycles of the state without generating output
+        for _ in 0..18 {
+            trivium.step64();
+        }
+
+        trivium
+    }
+
+    // Runs 64 steps of the cipher and returns the keystream bits, the first one in the lowest bit
+    fn step64(&mut self) -> u64 {
+        let a = |i: u32| (self.a >> (93 - i)) as u64;
+        let b = |i: u32| (self.b >> (84 - i)) as u64;
+        let c = |i: u32| (self.c >> (111 - i)) as u64;
+
+        let mut t1 = a(66) ^ a(93);
+        let mut t2 = b(69) ^ b(84);
+        let mut t3 = c(66) ^ c(111);
+        let z = t1 ^ t2 ^ t3;
+
+        t1 ^= (a(91) & a(92)) ^ b(78);
+        t2 ^= (b(82) & b(83)) ^ c(87);
+        t3 ^= (c(109) & c(110)) ^ a(69);
+
+        self.a = (self.a >> 64) | ((t3 as u128) << 29);
+        self.b = (self.b >> 64) | ((t1 as u128) << 20);
+        self.c = (self.c >> 64) | ((t2 as u128) << 47);
+
+        z
+    }
+}
+
+fn read_u80_le(input: &[u8]) -> u128 {
+    let mut buf = [0u8; 16];
+    buf[..10].copy_from_slice(input);
+    u128::from_le_bytes(buf)
+}
+
+impl SynchronousStreamCipher for Trivium {
+    fn process(&mut self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == output.len());
+
+        let mut data_index = 0;
+        let data_index_end = input.len();
+
+        // Process any unused keystream remaining from previous operations
+        while self.output_index < 8 && data_index < data_index_end {
+            output[data_index] = input[data_index] ^ self.output[self.output_index];
+            self.output_index += 1;
+            data_index += 1;
+        }
+
+        // Process whole keystream words
+        while data_index + 8 <= data_index_end {
+            let data_index_inc = data_index + 8;
+            let xored = read_u64_le(&input[data_index..data_index_inc]) ^ self.step64();
+            write_u64_le(&mut output[data_index..data_index_inc], xored);
+            data_index = data_index_inc;
+        }
+
+        // Process the remaining data, if any
+        if data_index < data_index_end {
+            let step = self.step64();
+            write_u64_le(&mut self.output, step);
+            self.output_index = 0;
+            while data_index < data_index_end {
+                output[data_index] = input[data_index] ^ self.output[self.output_index];
+                self.output_index += 1;
+                data_index += 1;
+            }
+        }
+    }
+}
+
+impl Encryptor for Trivium {
+    fn encrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        symm_enc_or_dec(self, input, output)
+    }
+}
+
+impl Decryptor for Trivium {
+    fn decrypt(&mut self, input: &mut RefReadBuffer, output: &mut RefWriteBuffer, _: bool)
+            -> Result<BufferResult, SymmetricCipherError> {
+        symm_enc_or_dec(self, input, output)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::trivium::Trivium;
+    use crate::symmetriccipher::SynchronousStreamCipher;
+    use crate::serialize::hex::{FromHex};
+
+    fn check(key_hex: &str, iv_hex: &str, expected_output_hex: &str) {
+        let key = key_hex.from_hex().unwrap();
+        let iv = iv_hex.from_hex().unwrap();
+        let expected_output = expected_output_hex.from_hex().unwrap();
+
+        let input = [0u8; 64];
+        let mut output = [0u8; 64];
+        let mut trivium = Trivium::new(&key, &iv);
+        trivium.process(&input, &mut output);
+        assert_eq!(output[..], expected_output[..]);
+
+        // The same keystream, processed in pieces that don't line up with the keystream words
+        let mut output = [0u8; 64];
+        let mut trivium = Trivium::new(&key, &iv);
+        for (i, o) in input.chunks(7).zip(output.chunks_mut(7)) {
+            trivium.process(i, o);
+        }
+        assert_eq!(output[..], expected_output[..]);
+    }
+
+    // eSTREAM Set 1, vector 0
+    #[test]
+    fn test_trivium_ecrypt_set_1_vector_0() {
+        check("80000000000000000000",
+              "00000000000000000000",
+              "38EB86FF730D7A9CAF8DF13A4420540DBB7B651464C87501552041C249F29A64\
+               D2FBF515610921EBE06C8F92CECF7F8098FF20CCCC6A62B97BE8EF7454FC80F9");
+    }
+
+    // eSTREAM Set 2, vector 0
+    #[test]
+    fn test_trivium_ecrypt_set_2_vector_0() {
+        check("00000000000000000000",
+              "00000000000000000000",
+              "FBE0BF265859051B517A2E4E239FC97F563203161907CF2DE7A8790FA1B2E9CD\
+               F75292030268B7382B4C1A759AA2599A285549986E74805903801A4CB5A5D4F2");
+    }
+
+    // eSTREAM Set 3, vector 0
+    #[test]
+    fn test_trivium_ecrypt_set_3_vector_0() {
+        check("00000000000000000000",
+              "80000000000000000000",
+              "F8901736640549E3BA7D42EA2D07B9F49233C18D773008BD755585B1A8CBAB86\
+               C1E9A9B91F1AD33483FD6EE3696D659C9374260456A36AAE11F033A519CBD5D7");
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+    use crate::symmetriccipher::SynchronousStreamCipher;
+    use crate::trivium::Trivium;
+
+    #[bench]
+    pub fn trivium_10(bh: & mut Bencher) {
+        let mut trivium = Trivium::new(&[0; 10], &[0; 10]);
+        let input = [1u8; 10];
+        let mut output = [0u8; 10];
+        bh.iter( || {
+            trivium.process(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn trivium_1k(bh: & mut Bencher) {
+        let mut trivium = Trivium::new(&[0; 10], &[0; 10]);
+        let input = [1u8; 1024];
+        let mut output = [0u8; 1024];
+        bh.iter( || {
+            trivium.process(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+
+    #[bench]
+    pub fn trivium_64k(bh: & mut Bencher) {
+        let mut trivium = Trivium::new(&[0; 10], &[0; 10]);
+        let input = [1u8; 65536];
+        let mut output = [0u8; 65536];
+        bh.iter( || {
+            trivium.process(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}
@@ -0,0 +1,374 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*!
+ * This module implements the Twofish block cipher by Schneier, Kelsey, Whiting, Wagner, Hall and
+ * Ferguson, with 128, 192 and 256 bit keys.
+ *
+ * The key dependent S-boxes are fully precomputed when the key is set (the "full keying" option
+ * of the Twofish paper), so, like Blowfish, this implementation uses secret dependent table
+ * lookups.
+ */
+
+use crate::cryptoutil::{read_u32v_le, write_u32_le};
+use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+
+// The 4 bit permutations t0 to t3 that define q0 and q1
+static Q0_T: [[u8; 16]; 4] = [
+    [0x8, 0x1, 0x7, 0xd, 0x6, 0xf, 0x3, 0x2, 0x0, 0xb, 0x5, 0x9, 0xe, 0xc, 0xa, 0x4],
+    [0xe, 0xc, 0xb, 0x8, 0x1, 0x2, 0x3, 0x5, 0xf, 0x4, 0xa, 0x6, 0x7, 0x0, 0x9, 0xd],
+    [0xb, 0xa, 0x5, 0xe, 0x6, 0xd, 0x9, 0x0, 0xc, 0x8, 0xf, 0x3, 0x2, 0x4, 0x7, 0x1],
+    [0xd, 0x7, 0xf, 0x4, 0x1, 0x2, 0x6, 0xe, 0x9, 0xb, 0x3, 0x0, 0x8, 0x5, 0xc, 0xa]];
+
+static Q1_T: [[u8; 16]; 4] = [
+    [0x2, 0x8, 0xb, 0xd, 0xf, 0x7, 0x6, 0xe, 0x3, 0x1, 0x9, 0x4, 0x0, 0xa, 0xc, 0x5],
+    [0x1, 0xe, 0x2, 0xb, 0x4, 0xc, 0x3, 0x7, 0x6, 0xd, 0xa, 0x5, 0xf, 0x9, 0x0, 0x8],
+    [0x4, 0xc, 0x7, 0x5, 0x1, 0x6, 0x9, 0xa, 0x0, 0xe, 0xd, 0x8, 0x2, 0xb, 0x3, 0xf],
+    [0xb, 0x9, 0x5, 0x1, 0xc, 0x3, 0xd, 0xe, 0x6, 0x4, 0x7, 0xf, 0x2, 0x0, 0x8, 0xa]];
+
+// The Reed-Solomon code used to derive the S-box key
+static RS: [[u8; 8]; 4] = [
+    [0x01, 0xa4, 0x55, 0x87, 0x5a, 0x58, 0xdb, 0x9e],
+    [0xa4, 0x56, 0x82, 0xf3, 0x1e, 0xc6, 0x68, 0xe5],
+    [0x02, 0xa1, 0xfc, 0xc1, 0x47, 0xae, 0x3d, 0x19],
+    [0xa4, 0x55, 0x87, 0x5a, 0x58, 0xdb, 0x9e, 0x03]];
+
+// x^8 + x^6 + x^3 + x^2 + 1
+const RS_POLY: u16 = 0x14d;
+// x^8 + x^6 + x^5 + x^3 + 1
+const MDS_POLY: u16 = 0x169;
+
+// Multiply a and b in GF(2^8) modulo poly. This is only used during key setup.
+fn gf_mul(a: u8, b: u8, poly: u16) -> u8 {
+    let mut a = a as u16;
+    let mut result = 0u16;
+    for i in 0..8 {
+        if (b >> i) & 1 == 1 {
+            result ^= a;
+        }
+        a <<= 1;
+        if a & 0x100 != 0 {
+            a ^= poly;
+        }
+    }
+    result as u8
+}
+
+fn q(t: &[[u8; 16]; 4], x: u8) -> u8 {
+    let ror4 = |b: u8| ((b >> 1) | (b << 3)) & 0xf;
+    let (a0, b0) = (x >> 4, x & 0xf);
+    let (a1, b1) = (a0 ^ b0, a0 ^ ror4(b0) ^ ((a0 << 3) & 0xf));
+    let (a2, b2) = (t[0][a1 as usize], t[1][b1 as usize]);
+    let (a3, b3) = (a2 ^ b2, a2 ^ ror4(b2) ^ ((a2 << 3) & 0xf));
+    let (a4, b4) = (t[2][a3 as usize], t[3][b3 as usize]);
+    (b4 << 4) | a4
+}
+
+// Multiply column `col` of the MDS matrix by `x`
+fn mds_column(col: usize, x: u8) -> u32 {
+    let x5b = gf_mul(x, 0x5b, MDS_POLY);
+    let xef = gf_mul(x, 0xef, MDS_POLY);
+    let column = match col {
+        0 => [x, x5b, xef, xef],
+        1 => [xef, xef, x5b, x],
+        2 => [x5b, xef, x, xef],
+        _ => [x5b, x, xef, x5b]
+    };
+    u32::from_le_bytes(column)
+}
+
+// The byte permutations of the function h for byte position `pos`, keyed with the list of words
+// `l`, but without the final MDS multiplication.
+fn h_byte(q0: &[u8; 256], q1: &[u8; 256], pos: usize, x: u8, l: &[u32]) -> u8 {
+    let lb = |i: usize| (l[i] >> (8 * pos)) as u8;
+    let mut y = x;
+    if l.len() == 4 {
+        y = (if pos == 0 || pos == 3 { q1 } else { q0 })[y as usize] ^ lb(3);
+    }
+    if l.len() >= 3 {
+        y = (if pos < 2 { q1 } else { q0 })[y as usize] ^ lb(2);
+    }
+    let (first, second, third) = match pos {
+        0 => (q0, q0, q1),
+        1 => (q1, q0, q0),
+        2 => (q0, q1, q1),
+        _ => (q1, q1, q0)
+    };
+    third[(second[(first[y as usize] ^ lb(1)) as usize] ^ lb(0)) as usize]
+}
+
+fn h(q0: &[u8; 256], q1: &[u8; 256], x: u32, l: &[u32]) -> u32 {
+    let mut result = 0;
+    for pos in 0..4 {
+        result ^= mds_column(pos, h_byte(q0, q1, pos, (x >> (8 * pos)) as u8, l));
+    }
+    result
+}
+
+#[derive(Clone, Copy)]
+pub struct Twofish {
+    k: [u32; 40],
+    s: [[u32; 256]; 4]
+}
+
+impl Twofish {
+    /**
+     * Create a new Twofish instance.
+     *
+     * # Arguments
+     * * key - The 16, 24 or 32 byte key.
+     */
+    pub fn new(key: &[u8]) -> Twofish {
+        assert!(key.len() == 16 || key.len() == 24 || key.len() == 32);
+        let words = key.len() / 8;
+
+        let mut q0 = [0u8; 256];
+        let mut q1 = [0u8; 256];
+        for x in 0..256 {
+            q0[x] = q(&Q0_T, x as u8);
+            q1[x] = q(&Q1_T, x as u8);
+        }
+
+        let mut m = [0u32; 8];
+        read_u32v_le(&mut m[..2 * words], key);
+        let mut me = [0u32; 4];
+        let mut mo = [0u32; 4];
+        let mut s_key = [0u32; 4];
+        for i in 0..words {
+            me[i] = m[2 * i];
+            mo[i] = m[2 * i + 1];
+            let mut s = [0u8; 4];
+            for (s_byte, rs_row) in s.iter_mut().zip(RS.iter()) {
+                for (&k, &r) in key[8 * i..8 * i + 8].iter().zip(rs_row.iter()) {
+                    *s_byte ^= gf_mul(k, r, RS_POLY);
+                }
+            }
+            // The S-box key is used in reverse order
+            s_key[words - 1 - i] = u32::from_le_bytes(s);
+        }
+
+        let mut k = [0u32; 40];
+        let rho = 0x01010101u32;
+        for i in 0..20 {
+            let a = h(&q0, &q1, (2 * i as u32).wrapping_mul(rho), &me[..words]);
+            let b = h(&q0, &q1, (2 * i as u32 + 1).wrapping_mul(rho), &mo[..words]).rotate_left(8);
+            k[2 * i] = a.wrapping_add(b);
+            k[2 * i + 1] = a.wrapping_add(b.wrapping_mul(2)).rotate_left(9);
+        }
+
+        let mut s = [[0u32; 256]; 4];
+        for (pos, table) in s.iter_mut().enumerate() {
+            for (x, entry) in table.iter_mut().enumerate() {
+                *entry = mds_column(pos, h_byte(&q0, &q1, pos, x as u8, &s_key[..words]));
+            }
+        }
+
+        Twofish {
+            k,
+            s
+        }
+    }
+
+    fn g(&self, x: u32) -> u32 {
+        self.s[0][(x & 0xff) as usize] ^
+            self.s[1][((x >> 8) & 0xff) as usize] ^
+            self.s[2][((x >> 16) & 0xff) as usize] ^
+            self.s[3][(x >> 24) as usize]
+    }
+
+    fn encrypt(&self, input: &[u8], output: &mut [u8]) {
+        let mut r = [0u32; 4];
+        read_u32v_le(&mut r, input);
+        for (x, &k) in r.iter_mut().zip(self.k.iter()) {
+            *x ^= k;
+        }
+        // Two rounds per iteration, so that the halves don't have to be swapped
+        for round in (0..16).step_by(2) {
+            let k = &self.k[2 * round + 8..2 * round + 12];
+            let t0 = self.g(r[0]);
+            let t1 = self.g(r[1].rotate_left(8));
+            r[2] = (r[2] ^ t0.wrapping_add(t1).wrapping_add(k[0])).rotate_right(1);
+            r[3] = r[3].rotate_left(1) ^ t0.wrapping_add(t1.wrapping_mul(2)).wrapping_add(k[1]);
+
+            let t0 = self.g(r[2]);
+            let t1 = self.g(r[3].rotate_left(8));
+            r[0] = (r[0] ^ t0.wrapping_add(t1).wrapping_add(k[2])).rotate_right(1);
+            r[1] = r[1].rotate_left(1) ^ t0.wrapping_add(t1.wrapping_mul(2)).wrapping_add(k[3]);
+        }
+        // Undo the last swap and apply the output whitening
+        for i in 0..4 {
+            write_u32_le(&mut output[4 * i..4 * i + 4], r[(i + 2) % 4] ^ self.k[i + 4]);
+        }
+    }
+
+    fn decrypt(&self, input: &[u8], output: &mut [u8]) {
+        let mut c = [0u32; 4];
+        read_u32v_le(&mut c, input);
+        let mut r = [0u32; 4];
+        for i in 0..4 {
+            r[(i + 2) % 4] = c[i] ^ self.k[i + 4];
+        }
+        for round in (0..16).step_by(2).rev() {
+            let k = &self.k[2 * round + 8..2 * round + 12];
+            let t0 = self.g(r[2]);
+            let t1 = self.g(r[3].rotate_left(8));
+            r[0] = r[0].rotate_left(1) ^ t0.wrapping_add(t1).wrapping_add(k[2]);
+            r[1] = (r[1] ^ t0.wrapping_add(t1.wrapping_mul(2)).wrapping_add(k[3])).rotate_right(1);
+
+            let t0 = self.g(r[0]);
+            let t1 = self.g(r[1].rotate_left(8));
+            r[2] = r[2].rotate_left(1) ^ t0.wrapping_add(t1).wrapping_add(k[0]);
+            r[3] = (r[3] ^ t0.wrapping_add(t1.wrapping_mul(2)).wrapping_add(k[1])).rotate_right(1);
+        }
+        for i in 0..4 {
+            write_u32_le(&mut output[4 * i..4 * i + 4], r[i] ^ self.k[i]);
+        }
+    }
+}
+
+impl BlockEncryptor for Twofish {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        self.encrypt(input, output);
+    }
+}
+
+impl BlockDecryptor for Twofish {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn decrypt_block(&self, input: &[u8], output: &mut [u8]) {
+        assert!(input.len() == 16);
+        assert!(output.len() == 16);
+        self.decrypt(input, output);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cryptoutil::test::test_aes_candidate_monte_carlo;
+    use crate::serialize::hex::FromHex;
+    use crate::symmetriccipher::{BlockEncryptor, BlockDecryptor};
+    use crate::twofish::Twofish;
+
+    fn hex_to_bytes(raw_hex: &str) -> Vec<u8> {
+        raw_hex.from_hex().ok().unwrap()
+    }
+
+    // The intermediate values from the Twofish paper's reference implementation (ecb_ival.txt)
+    #[test]
+    fn test_subkeys() {
+        let twofish = Twofish::new(&[0u8; 16]);
+        assert_eq!(&twofish.k[..4], &[0x52c54dde, 0x11f0626d, 0x7cac9d4a, 0x4d1b4aaa]);
+        assert_eq!(&twofish.k[36..], &[0x1fe71844, 0x85c05c89, 0xf298311e, 0x696ea672]);
+
+        let twofish = Twofish::new(&hex_to_bytes(
+            "0123456789abcdeffedcba987654321000112233445566778899aabbccddeeff"));
+        assert_eq!(&twofish.k[..4], &[0x5ec769bf, 0x44d13c60, 0x76cd39b1, 0x16750474]);
+        assert_eq!(&twofish.k[36..], &[0x3a9247f7, 0x9a3331dd, 0xee7515e6, 0xf0d54dcd]);
+    }
+
+    // The chained known answer tests from the Twofish paper (ecb_tbl.txt): the key and plaintext
+    // start out as zero, and every step the key is shifted by 16 bytes, taking the previous
+    // plaintext as its first 16 bytes, and the ciphertext becomes the new plaintext.
+    fn run_ecb_tbl(key_size: usize, expected: &[(usize, &str)]) {
+        let mut key = vec![0u8; key_size];
+        let mut plaintext = [0u8; 16];
+        for i in 1..50 {
+            let twofish = Twofish::new(&key);
+            let mut ciphertext = [0u8; 16];
+            twofish.encrypt_block(&plaintext, &mut ciphertext);
+            let mut decrypted = [0u8; 16];
+            twofish.decrypt_block(&ciphertext, &mut decrypted);
+            assert_eq!(decrypted, plaintext);
+
+            for &(n, ct) in expected.iter() {
+                if n == i {
+                    assert_eq!(&ciphertext[..], &hex_to_bytes(ct)[..]);
+                }
+            }
+
+            key.copy_within(..key_size - 16, 16);
+            key[..16].copy_from_slice(&plaintext);
+            plaintext = ciphertext;
+        }
+    }
+
+    #[test]
+    fn test_ecb_tbl_128() {
+        run_ecb_tbl(16, &[
+            (1, "9f589f5cf6122c32b6bfec2f2ae8c35a"),
+            (2, "d491db16e7b1c39e86cb086b789f5419"),
+            (3, "019f9809de1711858faac3a3ba20fbc3"),
+            (4, "6363977de839486297e661c6c9d668eb"),
+            (5, "816d5bd0fae35342bf2a7412c246f752"),
+            (48, "6b459286f3ffd28d49f15b1581b08e42")]);
+    }
+
+    #[test]
+    fn test_ecb_tbl_192() {
+        run_ecb_tbl(24, &[
+            (1, "efa71f788965bd4453f860178fc19101"),
+            (2, "88b2b2706b105e36b446bb6d731a1e88"),
+            (3, "39da69d6ba4997d585b6dc073ca341b2"),
+            (4, "182b02d81497ea45f9daacdc29193a65"),
+            (5, "7aff7a70ca2ff28ac31dd8ae5daaab63"),
+            (48, "f0ab73301125fa21ef70be5385fb76b6")]);
+    }
+
+    #[test]
+    fn test_ecb_tbl_256() {
+        run_ecb_tbl(32, &[
+            (1, "57ff739d4dc92c1bd7fc01700cc8216f"),
+            (2, "d43bb7556ea32e46f2a282b7d45b4e0d"),
+            (3, "90afe91bb288544f2c32dc239b2635e6"),
+            (4, "6cb4561c40bf0a9705931cb6d408e7fa"),
+            (5, "3059d6d61753b958d92f4781c8640e58"),
+            (48, "431058f4dbc7f734da4f02f04cc4f459")]);
+    }
+
+    // The Monte Carlo tests of the Twofish submission
+    #[test]
+    fn test_monte_carlo() {
+        test_aes_candidate_monte_carlo(Twofish::new, 16, false, false,
+            &["282be7e4fa1fbdc29661286f1f310b7e", "c8e1d477621acc37742bd16032075654"]);
+        test_aes_candidate_monte_carlo(Twofish::new, 16, true, false,
+            &["21d3f7f6724513946b72cfae47da2eed", "dd7d3cbe24ec771704f531ed82ce8aee"]);
+        test_aes_candidate_monte_carlo(Twofish::new, 24, false, false,
+            &["9ab71d7f280ff79f0d135bbd5fab7e37", "a1a3c49fd659216172cde292ce5f5226"]);
+        test_aes_candidate_monte_carlo(Twofish::new, 24, true, false,
+            &["b4582fa55072fcfef538f39072f234a9", "6f168851543ea0adaf932cd68a3c2563"]);
+        test_aes_candidate_monte_carlo(Twofish::new, 32, false, false,
+            &["04f2f36ca927ae506931de8f78b2513c", "04ead2a58c67fefcd4485d231c7ae4d7"]);
+        test_aes_candidate_monte_carlo(Twofish::new, 32, true, false,
+            &["bc7d078c4872063869deab891fb42761", "ab2f67d3aa11747c96ca942ce40925e1"]);
+    }
+}
+
+#[cfg(all(test, feature = "with-bench"))]
+mod bench {
+    use test::Bencher;
+
+    use crate::symmetriccipher::BlockEncryptor;
+    use crate::twofish::Twofish;
+
+    #[bench]
+    pub fn twofish(bh: &mut Bencher) {
+        let twofish = Twofish::new(&[1u8; 16]);
+        let input = [1u8; 16];
+        let mut output = [0u8; 16];
+        bh.iter( || {
+            twofish.encrypt_block(&input, &mut output);
+        });
+        bh.bytes = input.len() as u64;
+    }
+}